@@ -6,20 +6,59 @@
 
 use super::db_handle::MsgDBHandle;
 use crate::db_handle::handle_type::{self, All};
+use attest_messages::{AttestEnvelopable, CanonicalEnvelopeHash, GenericEnvelope};
 use rusqlite::Connection;
 use sapio_bitcoin::secp256k1::rand::{seq::SliceRandom, thread_rng};
+use sapio_bitcoin::XOnlyPublicKey;
 use std::{marker::PhantomData, pin::Pin, sync::Arc};
-use tokio::sync::Mutex;
+use tokio::sync::{broadcast, Mutex};
+
+/// How many insertions a slow subscriber may fall behind by before it starts
+/// missing them
+const INSERTED_CAPACITY: usize = 1024;
+
+/// An envelope that was committed to the DB through this MsgDB
+#[derive(Clone, Copy, Debug)]
+pub struct EnvelopeInserted {
+    pub hash: CanonicalEnvelopeHash,
+    pub genesis: CanonicalEnvelopeHash,
+    pub key: XOnlyPublicKey,
+    pub height: i64,
+}
+
+impl EnvelopeInserted {
+    pub(crate) fn of<M: AttestEnvelopable>(e: &GenericEnvelope<M>) -> Self {
+        EnvelopeInserted {
+            hash: e.canonicalized_hash_ref(),
+            genesis: e.get_genesis_hash(),
+            key: e.header().key(),
+            height: e.header().height(),
+        }
+    }
+}
 
 #[derive(Clone)]
-pub struct MsgDB(Arc<Vec<Arc<Mutex<Connection>>>>);
+pub struct MsgDB(
+    Arc<Vec<Arc<Mutex<Connection>>>>,
+    broadcast::Sender<EnvelopeInserted>,
+);
 
 impl MsgDB {
     pub fn new(db: Vec<Arc<Mutex<Connection>>>) -> Self {
         if db.len() < 2 {
             panic!("Expected at least two connections, one read one write")
         }
-        MsgDB(Arc::new(db))
+        MsgDB(Arc::new(db), broadcast::channel(INSERTED_CAPACITY).0)
+    }
+
+    /// Subscribes to envelopes as they are inserted.
+    ///
+    /// Only insertions made through this MsgDB (or a clone of it) are seen,
+    /// not ones made by another process sharing the database file. A
+    /// subscriber that falls too far behind gets a `Lagged` error and should
+    /// rescan the DB.
+    pub fn subscribe_inserted(&self) -> broadcast::Receiver<EnvelopeInserted> {
+        self.1.subscribe()
     }
 
     pub async fn map_all_sequential<F>(&self, f: F)
@@ -27,7 +66,7 @@ impl MsgDB {
         F: Fn(MsgDBHandle<All>) -> Pin<Box<dyn std::future::Future<Output = ()> + Send>>,
     {
         for conn in self.0.iter() {
-            let h = MsgDBHandle(conn.clone().lock_owned().await, PhantomData::default());
+            let h = MsgDBHandle(conn.clone().lock_owned().await, PhantomData, self.1.clone());
             f(h).await;
         }
     }
@@ -37,7 +76,7 @@ impl MsgDB {
         tracing::trace!("Getting Write Handle to DB...");
         let first = conns[0].clone().lock_owned().await;
         tracing::trace!("Write Handle Acquired");
-        MsgDBHandle(first, PhantomData::default())
+        MsgDBHandle(first, PhantomData, self.1.clone())
     }
 
     pub async fn get_handle_read(&self) -> MsgDBHandle<handle_type::ReadOnly> {
@@ -49,7 +88,7 @@ impl MsgDB {
                 .expect("conns known to be >= 2 in length");
             if let Ok(l) = lock.clone().try_lock_owned() {
                 tracing::trace!("Read Handle Acquired");
-                return MsgDBHandle(l, PhantomData::default());
+                return MsgDBHandle(l, PhantomData, self.1.clone());
             }
         }
         // pick a random lock to sleep on
@@ -58,6 +97,6 @@ impl MsgDB {
             .clone();
         let l = l.lock_owned().await;
         tracing::trace!("Read Handle Acquired");
-        MsgDBHandle(l, PhantomData::default())
+        MsgDBHandle(l, PhantomData, self.1.clone())
     }
 }
@@ -17,7 +17,6 @@ use attest_messages::Authenticated;
 use attest_messages::CanonicalEnvelopeHash;
 
 use attest_messages::GenericEnvelope;
-use fallible_iterator::FallibleIterator;
 use rusqlite::named_params;
 use rusqlite::types::FromSql;
 use sapio_bitcoin::XOnlyPublicKey;
@@ -113,6 +112,28 @@ where
         newer: &mut i64,
         map: &mut HashMap<CanonicalEnvelopeHash, E>,
     ) -> Result<(), rusqlite::Error>
+    where
+        E: FromSql + AsRef<GenericEnvelope<M>>,
+        M: AttestEnvelopable,
+    {
+        self.get_all_chain_commit_group_members_new_envelopes_for_chain_into_inconsistent_skip_invalid(
+            key, newer, map, false,
+        )
+    }
+
+    /// Like
+    /// [`Self::get_all_chain_commit_group_members_new_envelopes_for_chain_into_inconsistent`],
+    /// but optionally skipping envelopes that are not an `E`
+    pub fn get_all_chain_commit_group_members_new_envelopes_for_chain_into_inconsistent_skip_invalid<
+        E,
+        M,
+    >(
+        &self,
+        key: XOnlyPublicKey,
+        newer: &mut i64,
+        map: &mut HashMap<CanonicalEnvelopeHash, E>,
+        skip_invalid: bool,
+    ) -> Result<(), rusqlite::Error>
     where
         E: FromSql + AsRef<GenericEnvelope<M>>,
         M: AttestEnvelopable,
@@ -120,14 +141,19 @@ where
         let mut stmt = self
             .0
             .prepare_cached(SQL_GET_ALL_CHAIN_COMMIT_GROUP_MEMBERS_NEW_ENVELOPES_FOR_CHAIN)?;
-        let rows = stmt.query(named_params! {":key": PK(key), ":after": *newer})?;
-
-        rows.map(|r| Ok((r.get::<_, E>(0)?, r.get::<_, i64>(1)?)))
-            .for_each(|(v, id)| {
-                map.insert(v.as_ref().canonicalized_hash_ref(), v);
-                *newer = (*newer).max(id);
-                Ok(())
-            })?;
+        let mut rows = stmt.query(named_params! {":key": PK(key), ":after": *newer})?;
+        while let Some(row) = rows.next()? {
+            *newer = (*newer).max(row.get::<_, i64>(1)?);
+            match row.get::<_, E>(0) {
+                Ok(v) => {
+                    map.insert(v.as_ref().canonicalized_hash_ref(), v);
+                }
+                Err(e) if !skip_invalid => {
+                    return Err(e);
+                }
+                _ => {}
+            }
+        }
         Ok(())
     }
 }
@@ -8,6 +8,7 @@ use super::get::messages::message_exists_children;
 use super::handle_type;
 use super::ChainCommitGroupID;
 use super::MsgDBHandle;
+use crate::connection::EnvelopeInserted;
 use crate::db_handle::sql::insert::*;
 use crate::sql_error;
 use crate::sql_error::SqliteFail;
@@ -107,6 +108,7 @@ where
         M: AttestEnvelopable,
     {
         info!(genesis=?envelope.get_genesis_hash(), nickname, "Creating New Genesis");
+        let inserted = EnvelopeInserted::of(&envelope);
         let tx = self.0.transaction()?;
         let mut stmt = tx.prepare_cached(SQL_INSERT_USER)?;
         let hex_key = PK(envelope.header().key());
@@ -139,6 +141,9 @@ where
             .map(|t| t.and(Ok(hex_key.0.to_hex())));
         drop(stmt);
         tx.commit()?;
+        if let Ok(Ok(_)) = res {
+            self.notify_inserted(inserted);
+        }
         res
     }
     /// attempts to put an authenticated envelope in the DB
@@ -171,11 +176,21 @@ where
                 }
             }
         }
+        let inserted = EnvelopeInserted::of(&data);
         let res = try_insert_authenticated_envelope_with_txn(data, &tx);
         tx.commit()?;
+        if let Ok(Ok(())) = res {
+            self.notify_inserted(inserted);
+        }
         res
     }
 
+    /// Tells subscribers about a committed envelope
+    fn notify_inserted(&self, inserted: EnvelopeInserted) {
+        // no subscribers is not an error
+        let _ = self.2.send(inserted);
+    }
+
     /// Create a new Chain Commit Group
     pub fn new_chain_commit_group(
         &self,
@@ -6,10 +6,11 @@
 
 use std::marker::PhantomData;
 
+use super::connection::EnvelopeInserted;
 use super::sql_serializers::{self};
 use rusqlite::{types::FromSql, Connection, ToSql};
 use serde::{Deserialize, Serialize};
-use tokio::sync::OwnedMutexGuard;
+use tokio::sync::{broadcast, OwnedMutexGuard};
 
 pub mod create;
 pub mod get;
@@ -18,7 +19,11 @@ pub mod setup;
 pub mod sql;
 pub mod update;
 
-pub struct MsgDBHandle<T = handle_type::All>(pub OwnedMutexGuard<Connection>, pub PhantomData<T>);
+pub struct MsgDBHandle<T = handle_type::All>(
+    pub OwnedMutexGuard<Connection>,
+    pub PhantomData<T>,
+    pub(crate) broadcast::Sender<EnvelopeInserted>,
+);
 
 pub enum ConsistentMessages {
     AllMessagesNotReady,
@@ -116,6 +116,47 @@ async fn test_reused_nonce() {
     }
 }
 
+#[test(tokio::test)]
+async fn test_subscribe_inserted() {
+    let conn = setup_db().await;
+    let mut inserted = conn.subscribe_inserted();
+    let secp = Secp256k1::new();
+    let mut handle = conn.get_handle_all().await;
+    let kp = make_test_user(&secp, &mut handle, "TestUser".into());
+    let genesis = inserted.try_recv().unwrap();
+    assert_eq!(genesis.key, kp.x_only_public_key().0);
+    assert_eq!(genesis.height, 0);
+    assert_eq!(genesis.hash, genesis.genesis);
+
+    let envelope = handle
+        .wrap_message_in_envelope_for_user_by_key::<_, WrappedJson, _>(
+            CanonicalJsonValue::Null,
+            &kp,
+            &secp,
+            None,
+            None,
+            TipControl::AllTips,
+        )
+        .unwrap()
+        .unwrap();
+    let envelope = envelope.self_authenticate(&secp).unwrap();
+    handle
+        .try_insert_authenticated_envelope(envelope.clone(), false)
+        .unwrap()
+        .unwrap();
+    let next = inserted.try_recv().unwrap();
+    assert_eq!(next.hash, envelope.canonicalized_hash_ref());
+    assert_eq!(next.genesis, genesis.hash);
+    assert_eq!(next.height, 1);
+
+    // a failed insert is not announced
+    assert!(handle
+        .try_insert_authenticated_envelope(envelope, false)
+        .unwrap()
+        .is_err());
+    assert!(inserted.try_recv().is_err());
+}
+
 #[allow(unused)]
 fn print_db(handle: &MsgDBHandle) {
     let mut stm = handle
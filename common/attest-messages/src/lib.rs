@@ -294,6 +294,11 @@ impl std::fmt::Debug for CanonicalEnvelopeHash {
         f.write_str(&serde_json::to_string(self).unwrap())
     }
 }
+impl AsRef<[u8]> for CanonicalEnvelopeHash {
+    fn as_ref(&self) -> &[u8] {
+        &self.0[..]
+    }
+}
 impl ToHex for CanonicalEnvelopeHash {
     fn to_hex(&self) -> String {
         self.0.to_hex()
@@ -0,0 +1,163 @@
+// Copyright Judica, Inc 2022
+//
+// This Source Code Form is subject to the terms of the Mozilla Public
+//  License, v. 2.0. If a copy of the MPL was not distributed with this
+//  file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+//! Time for tasks that poll, back off, or retry.
+//!
+//! Such tasks wait through a [`Clock`] rather than on tokio's timer directly,
+//! so that a simulation can hand them a [`VirtualClock`], which only moves when
+//! it is advanced, and run the same schedule every time.
+
+use std::{sync::Arc, time::Duration};
+use tokio::{
+    sync::watch,
+    time::{Interval, MissedTickBehavior},
+};
+
+/// The clock tasks wait on
+#[derive(Clone, Default)]
+pub enum Clock {
+    /// Tokio's timer and the system time
+    #[default]
+    System,
+    /// Time that only passes when advanced
+    Virtual(Arc<VirtualClock>),
+}
+
+impl Clock {
+    /// the current time in milliseconds from UNIX_EPOCH
+    pub fn now_ms(&self) -> i64 {
+        match self {
+            Clock::System => crate::now(),
+            Clock::Virtual(v) => v.now_ms(),
+        }
+    }
+    pub async fn sleep(&self, d: Duration) {
+        match self {
+            Clock::System => tokio::time::sleep(d).await,
+            Clock::Virtual(v) => v.sleep(d).await,
+        }
+    }
+    /// Runs `f`, giving up after `d`
+    pub async fn timeout<F: std::future::Future>(&self, d: Duration, f: F) -> Option<F::Output> {
+        tokio::select! {
+            r = f => Some(r),
+            _ = self.sleep(d) => None,
+        }
+    }
+    /// Ticks every `period`, starting straight away. Ticks missed while the
+    /// task was busy are skipped.
+    pub fn interval(&self, period: Duration) -> ClockInterval {
+        match self {
+            Clock::System => {
+                let mut interval = tokio::time::interval(period);
+                interval.set_missed_tick_behavior(MissedTickBehavior::Skip);
+                ClockInterval::System(interval)
+            }
+            Clock::Virtual(v) => ClockInterval::Virtual {
+                next_ms: v.now_ms(),
+                period_ms: (period.as_millis() as i64).max(1),
+                clock: v.clone(),
+            },
+        }
+    }
+}
+
+/// An interval on a [`Clock`]
+pub enum ClockInterval {
+    System(Interval),
+    Virtual {
+        clock: Arc<VirtualClock>,
+        period_ms: i64,
+        next_ms: i64,
+    },
+}
+
+impl ClockInterval {
+    pub async fn tick(&mut self) {
+        match self {
+            ClockInterval::System(interval) => {
+                interval.tick().await;
+            }
+            ClockInterval::Virtual {
+                clock,
+                period_ms,
+                next_ms,
+            } => {
+                clock.sleep_until(*next_ms).await;
+                let late = clock.now_ms() - *next_ms;
+                *next_ms += *period_ms * (late / *period_ms + 1);
+            }
+        }
+    }
+}
+
+/// A clock that stands still until [`VirtualClock::advance`] is called
+pub struct VirtualClock {
+    now_ms: watch::Sender<i64>,
+}
+
+impl VirtualClock {
+    pub fn new(start_ms: i64) -> Self {
+        VirtualClock {
+            now_ms: watch::channel(start_ms).0,
+        }
+    }
+    pub fn now_ms(&self) -> i64 {
+        *self.now_ms.borrow()
+    }
+    /// Moves time forward by `d`, waking every task whose sleep has ended
+    pub fn advance(&self, d: Duration) {
+        let now = self.now_ms();
+        self.now_ms.send_replace(now + d.as_millis() as i64);
+    }
+    pub async fn sleep(&self, d: Duration) {
+        self.sleep_until(self.now_ms() + d.as_millis() as i64).await
+    }
+    pub async fn sleep_until(&self, deadline_ms: i64) {
+        let mut now = self.now_ms.subscribe();
+        while *now.borrow() < deadline_ms {
+            if now.changed().await.is_err() {
+                return;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_virtual_clock() {
+        let v = Arc::new(VirtualClock::new(1000));
+        let clock = Clock::Virtual(v.clone());
+        let mut interval = clock.interval(Duration::from_millis(10));
+        // the first tick is straight away
+        interval.tick().await;
+        let sleeper = tokio::spawn({
+            let clock = clock.clone();
+            async move { clock.sleep(Duration::from_millis(25)).await }
+        });
+        tokio::task::yield_now().await;
+        v.advance(Duration::from_millis(20));
+        tokio::task::yield_now().await;
+        assert!(!sleeper.is_finished());
+        v.advance(Duration::from_millis(5));
+        sleeper.await.unwrap();
+        assert_eq!(clock.now_ms(), 1025);
+        // the late tick fires at once, and the one missed at 1020 is skipped
+        interval.tick().await;
+        let next = clock.timeout(Duration::from_millis(10), interval.tick());
+        tokio::pin!(next);
+        tokio::select! {
+            biased;
+            _ = &mut next => panic!("Ticked Early"),
+            _ = tokio::task::yield_now() => {}
+        }
+        v.advance(Duration::from_millis(5));
+        assert_eq!(next.await, Some(()));
+    }
+}
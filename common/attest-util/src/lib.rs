@@ -6,6 +6,9 @@
 
 use std::{sync::Once, time::Instant};
 
+#[cfg(feature = "tokio")]
+pub mod clock;
+
 static START: Once = Once::new();
 
 static mut TIME: Option<Instant> = None;
@@ -18,8 +18,6 @@ use crate::attestations::client::PENDING_COOKIE;
 use crate::attestations::server::protocol::get_my_name;
 use crate::globals::Globals;
 use reqwest::Client;
-use sapio_bitcoin::secp256k1::rand::thread_rng;
-use sapio_bitcoin::secp256k1::rand::Rng;
 use std::sync::Arc;
 use std::time::Duration;
 use tokio::spawn;
@@ -129,8 +127,8 @@ impl AttestationClient {
         rec
     }
     pub async fn set_conn_pending(&self, svc: &ServiceUrl, force: bool) -> Option<u64> {
-        let d = { Duration::from_millis(thread_rng().gen_range(0, 1000)) };
-        tokio::time::sleep(d).await;
+        let timers = &self.g.config.peer_service.timer_override;
+        timers.sleep(timers.jitter(Duration::from_secs(1))).await;
         let mut ret_cookie = None;
         let mut f = self.connections.write().await;
         let r = f.entry(svc.clone()).or_insert_with(|| {
@@ -210,7 +208,12 @@ impl AttestationClient {
                         trace!(?svc, "Terminating Attempt Request");
                         self.set_conn_closed_from_pending(svc, current_cookie).await;
                     } else {
-                        tokio::time::sleep(Duration::from_secs(1)).await;
+                        self.g
+                            .config
+                            .peer_service
+                            .timer_override
+                            .sleep(Duration::from_secs(1))
+                            .await;
                     }
                 }
                 PeerState::Closed => {
@@ -218,7 +221,12 @@ impl AttestationClient {
                     cookie = self.set_conn_pending(svc, true).await;
                     if cookie.is_none() {
                         trace!(?svc, "No Cookie Gotten");
-                        tokio::time::sleep(Duration::from_secs(1)).await;
+                        self.g
+                            .config
+                            .peer_service
+                            .timer_override
+                            .sleep(Duration::from_secs(1))
+                            .await;
                         continue;
                     };
                     // Otherwise, we are supposed to set up a connection...
@@ -229,6 +237,30 @@ impl AttestationClient {
                     let db = self.db.clone();
                     let svc = svc.clone();
                     ojh = Some(spawn(async move {
+                        // a simulated node has no sockets, only links to the
+                        // other nodes on its network
+                        if let Some(network) = g.sim.clone() {
+                            let socket = loop {
+                                match network.connect(&g, &svc).await {
+                                    Ok(socket) => break socket,
+                                    Err(e) => tracing::debug!(
+                                        ?svc_url,
+                                        error = ?e,
+                                        "Retrying Opening Simulated Link To"
+                                    ),
+                                }
+                                g.config
+                                    .peer_service
+                                    .timer_override
+                                    .sleep(Duration::from_secs(1))
+                                    .await;
+                            };
+                            let res =
+                                protocol::run_protocol(g, socket, gss, db, Role::Client, Some(svc))
+                                    .await;
+                            trace!(?res, role=?Role::Client,"simulated link terminated");
+                            return;
+                        }
                         let socket = loop {
                             if let Ok(socket) =
                                 tungstenite_client_adaptor::ClientWebSocket::connect(
@@ -249,7 +281,11 @@ impl AttestationClient {
                                 role = ?Role::Client,
                                 "Retrying Opening Socket To"
                             );
-                            tokio::time::sleep(Duration::from_secs(1)).await;
+                            g.config
+                                .peer_service
+                                .timer_override
+                                .sleep(Duration::from_secs(1))
+                                .await;
                         };
                         let res =
                             protocol::run_protocol(g, socket, gss, db, Role::Client, Some(svc))
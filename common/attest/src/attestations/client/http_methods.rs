@@ -5,6 +5,8 @@
 //  file, You can obtain one at https://mozilla.org/MPL/2.0/.
 
 use super::AttestationClient;
+use super::ServiceUrl;
+use std::sync::Arc;
 
 impl AttestationClient {
     pub async fn authenticate(
@@ -13,6 +15,11 @@ impl AttestationClient {
         url: &String,
         port: u16,
     ) -> Result<(), reqwest::Error> {
+        if let Some(network) = &self.g.sim {
+            let to = ServiceUrl(Arc::new(url.clone()), port);
+            network.authenticate(&self.g, &to, *secret).await;
+            return Ok(());
+        }
         self.client
             .post(format!("http://{}:{}/authenticate", url, port))
             .json(secret)
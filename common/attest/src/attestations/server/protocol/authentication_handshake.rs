@@ -95,9 +95,13 @@ pub async fn handshake_protocol_server<W: WebSocketFunctionality>(
             .authenticate(&challenge_secret, &s.0, s.1)
             .await
             .map_err(|_| AttestProtocolError::FailedToAuthenticate)?;
-        tokio::time::timeout(Duration::from_secs(60), socket.t_recv())
+        g.config
+            .peer_service
+            .timer_override
+            .clock
+            .timeout(Duration::from_secs(60), socket.t_recv())
             .await
-            .map_err(|_| AttestProtocolError::TimedOut)?
+            .ok_or(AttestProtocolError::TimedOut)?
             .ok_or(AttestProtocolError::SocketClosed)??
             .only_text("for challenge_response")
             .and_then(|c| {
@@ -145,9 +149,14 @@ pub async fn handshake_protocol_client<W: WebSocketFunctionality>(
     })?;
     trace!(protocol, role=?Role::Client, ?me, "Confirmed Receipt of Challenge");
     trace!(protocol, role=?Role::Client, ?me, "Waiting to Learn Secret");
-    let cookie = tokio::time::timeout(Duration::from_secs(60), expect)
+    let cookie = g
+        .config
+        .peer_service
+        .timer_override
+        .clock
+        .timeout(Duration::from_secs(60), expect)
         .await
-        .map_err(|_| {
+        .ok_or_else(|| {
             trace!(protocol, role=?Role::Client, ?me, "Timed Out Learning Cookie");
             AttestProtocolError::TimedOut
         })?
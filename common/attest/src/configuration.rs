@@ -8,18 +8,20 @@ use attest_database::connection::MsgDB;
 use attest_database::setup_db;
 use attest_database::setup_test_db;
 use attest_util::bitcoin::BitcoinConfig;
+use attest_util::clock::{Clock, ClockInterval};
 
 use sapio_bitcoin::secp256k1::rand;
+use sapio_bitcoin::secp256k1::rand::rngs::StdRng;
 use sapio_bitcoin::secp256k1::rand::Rng;
+use sapio_bitcoin::secp256k1::rand::SeedableRng;
 use serde::Deserialize;
 use serde::Serialize;
 use std::error::Error;
 use std::path::PathBuf;
 use std::sync::Arc;
+use std::sync::Mutex;
 use std::time::Duration;
 
-use tokio::time::{Interval, MissedTickBehavior};
-
 pub(crate) const fn default_port() -> u16 {
     46789
 }
@@ -58,6 +60,13 @@ pub struct PeerServicesTimers {
     pub attach_tip_while_busy_rate: Duration,
     pub tip_fetch_rate: Duration,
     pub entropy_range: Duration,
+    /// What the peer services wait on
+    #[serde(skip)]
+    pub clock: Clock,
+    /// Where the jitter added to waits comes from, when it should be the same
+    /// every run
+    #[serde(skip)]
+    pub(crate) entropy: Option<Mutex<StdRng>>,
 }
 
 impl PeerServicesTimers {
@@ -68,6 +77,16 @@ impl PeerServicesTimers {
             attach_tip_while_busy_rate: Duration::from_millis((30000_f64 * scale) as u64),
             tip_fetch_rate: Duration::from_millis((15000_f64 * scale) as u64),
             entropy_range: Duration::from_millis((1000_f64 * scale) as u64),
+            clock: Clock::System,
+            entropy: None,
+        }
+    }
+    /// Waits on `clock` instead, with jitter drawn from `seed`
+    pub(crate) fn simulated(self, clock: Clock, seed: u64) -> Self {
+        Self {
+            clock,
+            entropy: Some(Mutex::new(StdRng::seed_from_u64(seed))),
+            ..self
         }
     }
 }
@@ -80,26 +99,35 @@ impl Default for PeerServicesTimers {
 
 impl PeerServicesTimers {
     pub(crate) fn rand(&self) -> Duration {
-        rand::thread_rng().gen_range(Duration::ZERO, self.entropy_range)
+        self.jitter(self.entropy_range)
+    }
+    /// A random wait shorter than `range`
+    pub(crate) fn jitter(&self, range: Duration) -> Duration {
+        match &self.entropy {
+            Some(rng) => rng
+                .lock()
+                .expect("Entropy Lock Poisoned")
+                .gen_range(Duration::ZERO, range),
+            None => rand::thread_rng().gen_range(Duration::ZERO, range),
+        }
+    }
+    pub(crate) async fn sleep(&self, d: Duration) {
+        self.clock.sleep(d).await
     }
-    pub(crate) fn reconnect_interval(&self) -> Interval {
-        let mut interval = tokio::time::interval(self.reconnect_rate);
-        interval.set_missed_tick_behavior(MissedTickBehavior::Skip);
-        interval
+    pub(crate) fn reconnect_interval(&self) -> ClockInterval {
+        self.clock.interval(self.reconnect_rate)
     }
     pub(crate) async fn scan_for_unsent_tips_delay(&self) {
         let d = self.scan_for_unsent_tips_rate + self.rand();
-        tokio::time::sleep(d).await
+        self.sleep(d).await
     }
     pub(crate) async fn tip_fetch_delay(&self) {
         let d = self.tip_fetch_rate + self.rand();
-        tokio::time::sleep(d).await
+        self.sleep(d).await
     }
     // todo: add randomization
-    pub(crate) fn attach_tip_while_busy_interval(&self) -> Interval {
-        let mut interval = tokio::time::interval(self.attach_tip_while_busy_rate);
-        interval.set_missed_tick_behavior(MissedTickBehavior::Skip);
-        interval
+    pub(crate) fn attach_tip_while_busy_interval(&self) -> ClockInterval {
+        self.clock.interval(self.attach_tip_while_busy_rate)
     }
 }
 
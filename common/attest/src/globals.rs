@@ -7,6 +7,7 @@
 use crate::{
     attestations::{client::AttestationClient, server::protocol::GlobalSocketState},
     configuration::Config,
+    sim::SimNetwork,
 };
 use attest_database::connection::MsgDB;
 use sapio_bitcoin::secp256k1::{All, Secp256k1};
@@ -24,6 +25,8 @@ pub struct Globals {
     pub client: OnceCell<AttestationClient>,
     pub socket_state: GlobalSocketState,
     pub msg_db: MsgDB,
+    /// The simulated network the node is on, if it is not on a real one
+    pub sim: Option<Arc<SimNetwork>>,
 }
 impl Globals {
     pub async fn get_client(self: &Arc<Self>) -> Result<AttestationClient, reqwest::Error> {
@@ -22,6 +22,8 @@ mod configuration;
 mod control;
 mod globals;
 mod peer_services;
+#[cfg_attr(not(test), allow(dead_code))]
+mod sim;
 mod tor;
 
 #[tokio::main]
@@ -51,6 +53,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
         client: Default::default(),
         msg_db,
         socket_state: GlobalSocketState::default(),
+        sim: None,
     });
     init_main(g).await
 }
@@ -0,0 +1,335 @@
+// Copyright Judica, Inc 2022
+//
+// This Source Code Form is subject to the terms of the Mozilla Public
+//  License, v. 2.0. If a copy of the MPL was not distributed with this
+//  file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+//! Module Sim runs several nodes in one process, the same way every time.
+//!
+//! Nodes that join a [`SimNetwork`] talk over in-memory links instead of
+//! websockets, and their peer services wait on the network's
+//! [`VirtualClock`], so time only passes when the test advances it. Faults
+//! are injected on the links: a message can be held back, or lost, which
+//! takes its link down as a TCP connection would go down, and the network can
+//! be partitioned so that some nodes cannot reach each other.
+//!
+//! Each link draws its faults from a generator seeded by the network's seed
+//! and the nodes on either end, so a link sees the same faults in the same
+//! order on every run with the same seed, however the tasks using it are
+//! scheduled. [`SimNetwork::trace`] records what each link drew, so two runs
+//! can be checked against each other.
+
+use crate::attestations::client::ServiceUrl;
+use crate::attestations::server::generic_websocket::WebSocketFunctionality;
+use crate::attestations::server::protocol::{self, get_my_name, AttestProtocolError};
+use crate::configuration::PeerServicesTimers;
+use crate::globals::Globals;
+use attest_util::clock::{Clock, VirtualClock};
+use axum::extract::ws::Message;
+use futures::{Future, Sink, Stream};
+use sapio_bitcoin::hashes::{sha256, Hash};
+use sapio_bitcoin::secp256k1::rand::rngs::StdRng;
+use sapio_bitcoin::secp256k1::rand::{Rng, SeedableRng};
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    error::Error,
+    fmt::Display,
+    pin::Pin,
+    sync::{Arc, Mutex, Weak},
+    task::{Context, Poll},
+    time::Duration,
+};
+use tokio::spawn;
+use tokio::sync::mpsc::{unbounded_channel, UnboundedReceiver, UnboundedSender};
+use tokio_tungstenite::tungstenite::protocol::Role;
+use tracing::{debug, trace};
+
+/// The faults injected into a [`SimNetwork`]
+#[derive(Clone, Default, Debug)]
+pub struct Faults {
+    /// The chance that any one message is lost, from 0 to 1
+    pub drop_rate: f64,
+    /// The longest a message may be held back
+    pub max_delay: Duration,
+    /// Groups of nodes that can only reach the nodes in the same group. When
+    /// empty, every node can reach every other.
+    pub partitions: Vec<BTreeSet<ServiceUrl>>,
+}
+
+impl Faults {
+    fn reachable(&self, a: &ServiceUrl, b: &ServiceUrl) -> bool {
+        self.partitions.is_empty()
+            || self
+                .partitions
+                .iter()
+                .any(|group| group.contains(a) && group.contains(b))
+    }
+}
+
+#[derive(Debug)]
+pub enum SimError {
+    NoSuchNode(ServiceUrl),
+    Unreachable(ServiceUrl),
+    NodeName(AttestProtocolError),
+}
+impl Display for SimError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:?}", self)
+    }
+}
+impl Error for SimError {}
+
+/// The faults each link drew, in order, as whether the message was lost and
+/// how long it was held back in milliseconds, keyed by the link's label
+pub type SimTrace = BTreeMap<String, Vec<(bool, u64)>>;
+
+/// Nodes connected in memory, on a virtual clock, with seeded faults
+pub struct SimNetwork {
+    seed: u64,
+    clock: Arc<VirtualClock>,
+    faults: Mutex<Faults>,
+    nodes: Mutex<BTreeMap<ServiceUrl, Weak<Globals>>>,
+    /// How many links each node has opened to each other node, so that each
+    /// link gets its own generator
+    opened: Mutex<BTreeMap<(ServiceUrl, ServiceUrl), u64>>,
+    trace: Mutex<SimTrace>,
+}
+
+impl SimNetwork {
+    pub fn new(seed: u64, start_ms: i64) -> Arc<Self> {
+        Arc::new(SimNetwork {
+            seed,
+            clock: Arc::new(VirtualClock::new(start_ms)),
+            faults: Default::default(),
+            nodes: Default::default(),
+            opened: Default::default(),
+            trace: Default::default(),
+        })
+    }
+    pub fn clock(&self) -> Clock {
+        Clock::Virtual(self.clock.clone())
+    }
+    /// Moves the network's clock forward by `d`
+    pub fn advance(&self, d: Duration) {
+        self.clock.advance(d)
+    }
+    /// Peer service timers for the node at `name`, on the network's clock
+    /// and with jitter drawn from the network's seed
+    pub(crate) fn timers(&self, name: &ServiceUrl, scale: f64) -> PeerServicesTimers {
+        PeerServicesTimers::scaled_default(scale)
+            .simulated(self.clock(), self.derive_seed(&[name.to_string()]))
+    }
+    /// Lets the other nodes connect to `g`
+    pub async fn join(&self, g: &Arc<Globals>) -> Result<ServiceUrl, SimError> {
+        let name = get_my_name(g).await.map_err(SimError::NodeName)?;
+        self.lock_nodes().insert(name.clone(), Arc::downgrade(g));
+        Ok(name)
+    }
+    pub fn set_faults(&self, faults: Faults) {
+        *self.faults.lock().expect("Faults Lock Poisoned") = faults;
+    }
+    /// Splits the network so that nodes only reach those in the same group
+    pub fn partition(&self, groups: Vec<Vec<ServiceUrl>>) {
+        let mut faults = self.faults.lock().expect("Faults Lock Poisoned");
+        faults.partitions = groups
+            .into_iter()
+            .map(|group| group.into_iter().collect())
+            .collect();
+    }
+    /// Lets every node reach every other again
+    pub fn heal(&self) {
+        self.faults
+            .lock()
+            .expect("Faults Lock Poisoned")
+            .partitions
+            .clear();
+    }
+    /// The faults drawn so far on every link
+    pub fn trace(&self) -> SimTrace {
+        self.trace.lock().expect("Trace Lock Poisoned").clone()
+    }
+    fn faults(&self) -> Faults {
+        self.faults.lock().expect("Faults Lock Poisoned").clone()
+    }
+    fn lock_nodes(&self) -> std::sync::MutexGuard<'_, BTreeMap<ServiceUrl, Weak<Globals>>> {
+        self.nodes.lock().expect("Nodes Lock Poisoned")
+    }
+    fn derive_seed(&self, parts: &[String]) -> u64 {
+        let mut preimage = self.seed.to_be_bytes().to_vec();
+        for part in parts {
+            preimage.extend(part.as_bytes());
+            preimage.push(0);
+        }
+        let h = sha256::Hash::hash(&preimage);
+        let mut seed = [0u8; 8];
+        seed.copy_from_slice(&h[..8]);
+        u64::from_be_bytes(seed)
+    }
+    /// Opens a link from `from` to the node at `to`, and runs the server side
+    /// of the protocol on it for `to`
+    pub(crate) async fn connect(
+        self: &Arc<Self>,
+        from: &Arc<Globals>,
+        to: &ServiceUrl,
+    ) -> Result<MemorySocket, SimError> {
+        let from = get_my_name(from).await.map_err(SimError::NodeName)?;
+        let node = self
+            .lock_nodes()
+            .get(to)
+            .and_then(Weak::upgrade)
+            .ok_or_else(|| SimError::NoSuchNode(to.clone()))?;
+        if !self.faults().reachable(&from, to) {
+            return Err(SimError::Unreachable(to.clone()));
+        }
+        let n = {
+            let mut opened = self.opened.lock().expect("Opened Lock Poisoned");
+            let n = opened.entry((from.clone(), to.clone())).or_default();
+            *n += 1;
+            *n
+        };
+        let (client_tx, client_sent) = unbounded_channel();
+        let (server_tx, server_sent) = unbounded_channel();
+        let (to_server, server_rx) = unbounded_channel();
+        let (to_client, client_rx) = unbounded_channel();
+        let parts = |direction: &str| {
+            [
+                from.to_string(),
+                to.to_string(),
+                n.to_string(),
+                direction.into(),
+            ]
+        };
+        self.link(&from, to, &parts("out"), client_sent, to_server);
+        self.link(to, &from, &parts("back"), server_sent, to_client);
+        let server = MemorySocket {
+            tx: server_tx,
+            rx: server_rx,
+        };
+        spawn(async move {
+            let res = protocol::run_protocol(
+                node.clone(),
+                server,
+                node.socket_state.clone(),
+                node.msg_db.clone(),
+                Role::Server,
+                None,
+            )
+            .await;
+            trace!(?res, role=?Role::Server, "simulated socket quit");
+        });
+        Ok(MemorySocket {
+            tx: client_tx,
+            rx: client_rx,
+        })
+    }
+    /// Hands the handshake secret to the node at `to`, which a real node
+    /// would send over HTTP. Nothing is sent if `to` can't be reached.
+    pub(crate) async fn authenticate(
+        &self,
+        from: &Arc<Globals>,
+        to: &ServiceUrl,
+        secret: [u8; 32],
+    ) {
+        let node = self.lock_nodes().get(to).and_then(Weak::upgrade);
+        if let (Ok(from), Some(node)) = (get_my_name(from).await, node) {
+            if self.faults().reachable(&from, to) {
+                node.socket_state.add_a_cookie(secret).await;
+            }
+        }
+    }
+    /// Forwards the messages `from` sends to `to`, with faults drawn from a
+    /// seed derived from `parts`, which also label the link in the trace.
+    /// Once a message is lost the link closes, and so does the link back once
+    /// `to` sees it close.
+    fn link(
+        self: &Arc<Self>,
+        from: &ServiceUrl,
+        to: &ServiceUrl,
+        parts: &[String],
+        mut sent: UnboundedReceiver<Message>,
+        deliver: UnboundedSender<Message>,
+    ) {
+        let mut rng = StdRng::seed_from_u64(self.derive_seed(parts));
+        let label = parts.join("/");
+        let net = self.clone();
+        let (from, to) = (from.clone(), to.clone());
+        spawn(async move {
+            while let Some(msg) = sent.recv().await {
+                let faults = net.faults();
+                // draw both every time, so one fault does not shift the others
+                let lost = rng.gen::<f64>() < faults.drop_rate;
+                let delay = rng.gen_range(0, faults.max_delay.as_millis() as u64 + 1);
+                net.trace
+                    .lock()
+                    .expect("Trace Lock Poisoned")
+                    .entry(label.clone())
+                    .or_default()
+                    .push((lost, delay));
+                if lost || !faults.reachable(&from, &to) {
+                    debug!(?from, ?to, "Simulated Link Lost a Message, Closing");
+                    break;
+                }
+                net.clock.sleep(Duration::from_millis(delay)).await;
+                if deliver.send(msg).is_err() {
+                    break;
+                }
+            }
+        });
+    }
+}
+
+/// One end of a simulated link
+pub struct MemorySocket {
+    tx: UnboundedSender<Message>,
+    rx: UnboundedReceiver<Message>,
+}
+
+impl Stream for MemorySocket {
+    type Item = Result<Message, axum::Error>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        self.rx.poll_recv(cx).map(|msg| msg.map(Ok))
+    }
+}
+
+impl Sink<Message> for MemorySocket {
+    type Error = axum::Error;
+
+    fn poll_ready(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn start_send(self: Pin<&mut Self>, item: Message) -> Result<(), Self::Error> {
+        self.tx.send(item).map_err(axum::Error::new)
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn poll_close(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl WebSocketFunctionality for MemorySocket {
+    fn t_recv<'a>(
+        &'a mut self,
+    ) -> Pin<Box<dyn Future<Output = Option<Result<Message, axum::Error>>> + Send + 'a>> {
+        Box::pin(async move { self.rx.recv().await.map(Ok) })
+    }
+
+    fn t_send<'a>(
+        &'a mut self,
+        msg: Message,
+    ) -> Pin<Box<dyn Future<Output = Result<(), axum::Error>> + Send + 'a>> {
+        Box::pin(async move { self.tx.send(msg).map_err(axum::Error::new) })
+    }
+
+    fn t_close(self) -> Pin<Box<dyn Future<Output = Result<(), axum::Error>> + Send>> {
+        Box::pin(async move {
+            drop(self);
+            Ok(())
+        })
+    }
+}
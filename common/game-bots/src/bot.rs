@@ -0,0 +1,151 @@
+// Copyright Judica, Inc 2022
+//
+// This Source Code Form is subject to the terms of the Mozilla Public
+//  License, v. 2.0. If a copy of the MPL was not distributed with this
+//  file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+use crate::strategy::Strategy;
+use crate::strategy::StrategyKind;
+use crate::GameHost;
+use attest_database::connection::MsgDB;
+use attest_database::db_handle::create::TipControl;
+use attest_database::generate_new_user;
+use attest_messages::Authenticated;
+use attest_messages::GenericEnvelope;
+use game_host_messages::JoinCode;
+use game_player_messages::ParticipantAction;
+use mine_with_friends_board::game::game_move::GameMove;
+use mine_with_friends_board::game::game_move::Heartbeat;
+use mine_with_friends_board::game::GameBoard;
+use mine_with_friends_board::sanitize::Unsanitized;
+use mine_with_friends_board::MoveEnvelope;
+use sapio_bitcoin::hashes::hex::ToHex;
+use sapio_bitcoin::secp256k1::rand::rngs::StdRng;
+use sapio_bitcoin::secp256k1::rand::SeedableRng;
+use sapio_bitcoin::secp256k1::All;
+use sapio_bitcoin::secp256k1::Secp256k1;
+use sapio_bitcoin::KeyPair;
+use sapio_bitcoin::XOnlyPublicKey;
+use std::error::Error;
+use std::sync::Arc;
+use tokio::sync::Mutex;
+use tokio::task::spawn_blocking;
+use tracing::debug;
+use tracing::info;
+
+/// A headless player, with its own chain in the attest database
+pub struct Bot {
+    pub nickname: String,
+    keypair: KeyPair,
+    /// The sequence number of the last move made
+    sequence: u64,
+    strategy: Box<dyn Strategy>,
+    rng: StdRng,
+}
+
+impl Bot {
+    /// Creates a new user chain for the bot, returning the bot and the genesis
+    /// envelope it must join a game with
+    pub async fn new(
+        nickname: String,
+        strategy: StrategyKind,
+        seed: u64,
+        secp: Arc<Secp256k1<All>>,
+        db: &MsgDB,
+    ) -> Result<
+        (Self, Authenticated<GenericEnvelope<ParticipantAction>>),
+        Box<dyn Error + Send + Sync>,
+    > {
+        let (keypair, next_nonce, genesis) = generate_new_user::<_, ParticipantAction, _>(
+            &secp,
+            MoveEnvelope {
+                d: Unsanitized(GameMove::Heartbeat(Heartbeat())),
+                sequence: 0,
+                time_millis: attest_util::now() as u64,
+            },
+        )
+        .map_err(|e| e.to_string())?;
+        let mut handle = db.get_handle_all().await;
+        let genesis = {
+            let nickname = nickname.clone();
+            spawn_blocking(move || {
+                handle.save_keypair(keypair)?;
+                let k = keypair.public_key().x_only_public_key().0;
+                handle.save_nonce_for_user_by_key(next_nonce, &secp, k)?;
+                let envelope = genesis.self_authenticate(&secp)?;
+                handle
+                    .insert_user_by_genesis_envelope(nickname, envelope.clone())?
+                    .map_err(|(e, _)| format!("{:?}", e))?;
+                Ok::<_, Box<dyn Error + Send + Sync>>(envelope)
+            })
+            .await??
+        };
+        info!(nickname, key = ?genesis.header().key(), "Created Bot");
+        Ok((
+            Bot {
+                nickname,
+                keypair,
+                sequence: 0,
+                strategy: strategy.build(),
+                rng: StdRng::seed_from_u64(seed),
+            },
+            genesis,
+        ))
+    }
+
+    pub fn key(&self) -> XOnlyPublicKey {
+        self.keypair.x_only_public_key().0
+    }
+
+    /// Asks the game host to add the bot to the game with the given join code
+    pub async fn join(
+        client: &reqwest::Client,
+        GameHost { url, port }: &GameHost,
+        code: JoinCode,
+        genesis: Authenticated<GenericEnvelope<ParticipantAction>>,
+    ) -> Result<(), reqwest::Error> {
+        client
+            .post(format!("http://{}:{}/game/player/new", url, port))
+            .json(&(code, genesis))
+            .send()
+            .await?
+            .json()
+            .await
+    }
+
+    /// Picks a move from the bot's current view of the game and signs it into
+    /// the bot's chain, where the attest node will pick it up
+    pub async fn take_turn(
+        &mut self,
+        game: &Mutex<GameBoard>,
+        secp: Arc<Secp256k1<All>>,
+        db: &MsgDB,
+    ) -> Result<(), Box<dyn Error + Send + Sync>> {
+        let key = self.key().to_hex();
+        let d = {
+            let mut game = game.lock().await;
+            let me = game.get_user_id(&key).ok_or("Bot is not in the Game")?;
+            self.strategy.next_move(&mut game, me, &key, &mut self.rng)
+        };
+        debug!(nickname = self.nickname, move_ = ?d, "Bot Making Move");
+        let mve = MoveEnvelope {
+            d: Unsanitized(d),
+            sequence: self.sequence + 1,
+            time_millis: attest_util::now() as u64,
+        };
+        let keypair = self.keypair;
+        let mut handle = db.get_handle_all().await;
+        spawn_blocking(move || {
+            handle.retry_insert_authenticated_envelope_atomic::<ParticipantAction, _, _>(
+                mve,
+                &keypair,
+                &secp,
+                None,
+                TipControl::AllTips,
+            )
+        })
+        .await??;
+        self.sequence += 1;
+        Ok(())
+    }
+}
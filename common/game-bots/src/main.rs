@@ -0,0 +1,199 @@
+// Copyright Judica, Inc 2022
+//
+// This Source Code Form is subject to the terms of the Mozilla Public
+//  License, v. 2.0. If a copy of the MPL was not distributed with this
+//  file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+//! Headless players, for load testing a game host and for filling out
+//! single-player games.
+//!
+//! Each bot gets its own chain in the attest database of a running attest node,
+//! joins a game through the game host, and then follows the game's sequencer
+//! to keep its own copy of the board, from which its strategy picks moves.
+use attest_database::connection::MsgDB;
+use attest_database::setup_db;
+use bot::Bot;
+use game_host_messages::{BroadcastByHost, Channelized, JoinCode};
+use game_sequencer::{attest_envelope, OnlineDBFetcher, Sequenced};
+use mine_with_friends_board::game::{GameBoard, GameSetup};
+use sapio_bitcoin::hashes::hex::ToHex;
+use sapio_bitcoin::secp256k1::Secp256k1;
+use sapio_bitcoin::XOnlyPublicKey;
+use serde::{Deserialize, Serialize};
+use std::error::Error;
+use std::path::PathBuf;
+use std::sync::atomic::AtomicBool;
+use std::sync::Arc;
+use std::time::Duration;
+use strategy::StrategyKind;
+use tokio::spawn;
+use tokio::sync::Mutex;
+use tokio::task::spawn_blocking;
+use tracing::{debug, info, warn};
+
+mod bot;
+mod strategy;
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct GameHost {
+    pub url: String,
+    pub port: u16,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct BotConfig {
+    nickname: String,
+    strategy: StrategyKind,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct Config {
+    /// The app instance of the attest node to share a database with
+    app_instance: String,
+    #[serde(default)]
+    prefix: Option<PathBuf>,
+    game_host: GameHost,
+    join_code: JoinCode,
+    /// If set, reach the game host through a Tor socks proxy on this port
+    #[serde(default)]
+    socks_port: Option<u16>,
+    bots: Vec<BotConfig>,
+    /// How often each bot moves
+    move_period_ms: u64,
+    /// Seeds each bot's strategy, for repeatable runs
+    #[serde(default)]
+    seed: u64,
+}
+
+fn get_config() -> Result<Config, Box<dyn Error + Send + Sync>> {
+    let config = std::env::var("GAME_BOTS_CONFIG_JSON").map(|s| serde_json::from_str(&s))??;
+    Ok(config)
+}
+
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn Error + Send + Sync>> {
+    tracing_subscriber::fmt::init();
+    let config = get_config()?;
+    let db = setup_db(
+        &format!("attestations.{}", config.app_instance),
+        config.prefix.clone(),
+    )
+    .await
+    .map_err(|e| format!("DB Setup Failed: {:?}", e))?;
+    let secp = Arc::new(Secp256k1::new());
+    let mut client = reqwest::Client::builder();
+    if let Some(port) = config.socks_port {
+        client = client.proxy(reqwest::Proxy::all(format!(
+            "socks5h://127.0.0.1:{}",
+            port
+        ))?);
+    }
+    let client = client.build()?;
+
+    let mut bots = vec![];
+    for (i, BotConfig { nickname, strategy }) in config.bots.into_iter().enumerate() {
+        let (bot, genesis) = Bot::new(
+            nickname,
+            strategy,
+            config.seed.wrapping_add(i as u64),
+            secp.clone(),
+            &db,
+        )
+        .await?;
+        Bot::join(&client, &config.game_host, config.join_code, genesis).await?;
+        info!(nickname = bot.nickname, "Bot Joined Game");
+        bots.push(bot);
+    }
+
+    let keys: Vec<String> = bots.iter().map(|b| b.key().to_hex()).collect();
+    let (host_key, setup) = wait_for_game(&db, &keys).await?;
+    info!(?host_key, "Game Started");
+    let game = Arc::new(Mutex::new(GameBoard::new(&setup)));
+
+    let shutdown: Arc<AtomicBool> = Default::default();
+    let db_fetcher = OnlineDBFetcher::new(
+        shutdown.clone(),
+        Duration::from_secs(1),
+        Duration::from_secs(1),
+        host_key,
+        db.clone(),
+    );
+    let sequencer = game_sequencer::Sequencer::new(shutdown.clone(), db_fetcher.clone(), None);
+    spawn(db_fetcher.run());
+    spawn({
+        let sequencer = sequencer.clone();
+        async move { sequencer.run().await }
+    });
+    // Keep every bot's view of the board up to date
+    spawn({
+        let game = game.clone();
+        async move {
+            while let Some(next) = sequencer.output_move().await {
+                match next {
+                    Sequenced::Attested(hash, header) => {
+                        if let Err(e) = attest_envelope(&mut *game.lock().await, &hash, &header) {
+                            warn!(error=?e, "Sequencer Attested to a Bad Time");
+                        }
+                    }
+                    Sequenced::Move((game_move, s)) => {
+                        if let Err(reason) = game.lock().await.play(game_move, s.to_hex()) {
+                            debug!(?reason, "Rejected Move");
+                        }
+                    }
+                }
+            }
+        }
+    });
+
+    let period = Duration::from_millis(config.move_period_ms);
+    let tasks: Vec<_> = bots
+        .into_iter()
+        .map(|mut bot| {
+            let game = game.clone();
+            let secp = secp.clone();
+            let db = db.clone();
+            spawn(async move {
+                let mut interval = tokio::time::interval(period);
+                loop {
+                    interval.tick().await;
+                    if let Some(reason) = game.lock().await.game_is_finished() {
+                        info!(nickname = bot.nickname, ?reason, "Game Over");
+                        break;
+                    }
+                    if let Err(e) = bot.take_turn(&game, secp.clone(), &db).await {
+                        warn!(nickname = bot.nickname, error = ?e, "Bot Failed to Move");
+                    }
+                }
+            })
+        })
+        .collect();
+    for t in tasks {
+        t.await?;
+    }
+    Ok(())
+}
+
+/// Waits for a game host to publish a GameSetup that all the bots are players
+/// in
+async fn wait_for_game(
+    db: &MsgDB,
+    keys: &[String],
+) -> Result<(XOnlyPublicKey, GameSetup), Box<dyn Error + Send + Sync>> {
+    loop {
+        let handle = db.get_handle_read().await;
+        let found =
+            spawn_blocking(move || handle.get_all_genesis::<Channelized<BroadcastByHost>>())
+                .await??
+                .iter()
+                .find_map(|e| match &e.msg().data {
+                    BroadcastByHost::GameSetup(g) if keys.iter().all(|k| g.players.contains(k)) => {
+                        Some((e.header().key(), g.clone()))
+                    }
+                    _ => None,
+                });
+        if let Some(found) = found {
+            return Ok(found);
+        }
+        tokio::time::sleep(Duration::from_secs(5)).await;
+    }
+}
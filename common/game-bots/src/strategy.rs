@@ -0,0 +1,223 @@
+// Copyright Judica, Inc 2022
+//
+// This Source Code Form is subject to the terms of the Mozilla Public
+//  License, v. 2.0. If a copy of the MPL was not distributed with this
+//  file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+//! Strategies decide what a bot does on each of its turns, given its own view
+//! of the game. They only ever read the board, so a rejected move costs the bot
+//! nothing but a turn.
+use mine_with_friends_board::{
+    entity::EntityID,
+    game::{
+        game_move::{GameMove, Heartbeat, ListNFTForSale, MintPowerPlant, PurchaseNFT, Trade},
+        GameBoard,
+    },
+    nfts::instances::powerplant::PlantType,
+    world::{MAX_LATITUDE, MAX_LONGITUDE},
+};
+use sapio_bitcoin::secp256k1::rand::{rngs::StdRng, seq::SliceRandom, Rng};
+use serde::{Deserialize, Serialize};
+
+pub trait Strategy: Send {
+    /// Pick the next move for the player `me`, who signs with `key`
+    fn next_move(
+        &mut self,
+        game: &mut GameBoard,
+        me: EntityID,
+        key: &str,
+        rng: &mut StdRng,
+    ) -> GameMove;
+}
+
+/// The strategies a bot can be configured with
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Eq, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum StrategyKind {
+    /// Only sends heartbeats, to keep the game clock moving
+    Idle,
+    RandomTrader,
+    PlantBuilder,
+    MarketMaker,
+}
+
+impl StrategyKind {
+    pub fn build(self) -> Box<dyn Strategy> {
+        match self {
+            StrategyKind::Idle => Box::new(Idle),
+            StrategyKind::RandomTrader => Box::new(RandomTrader),
+            StrategyKind::PlantBuilder => Box::new(PlantBuilder { max_plants: 5 }),
+            StrategyKind::MarketMaker => Box::new(MarketMaker { markup_percent: 10 }),
+        }
+    }
+}
+
+fn heartbeat() -> GameMove {
+    GameMove::Heartbeat(Heartbeat())
+}
+
+pub struct Idle;
+impl Strategy for Idle {
+    fn next_move(&mut self, _: &mut GameBoard, _: EntityID, _: &str, _: &mut StdRng) -> GameMove {
+        heartbeat()
+    }
+}
+
+/// Swaps a small random slice of its holdings on a random market each turn
+pub struct RandomTrader;
+impl Strategy for RandomTrader {
+    fn next_move(
+        &mut self,
+        game: &mut GameBoard,
+        me: EntityID,
+        _key: &str,
+        rng: &mut StdRng,
+    ) -> GameMove {
+        let markets = game.get_ux_materials_prices();
+        let market = match markets.choose(rng) {
+            Some(m) => m,
+            None => return heartbeat(),
+        };
+        let pair = market.trading_pair;
+        // sell up to 5% of whichever side we pick
+        let sell_a = rng.gen_bool(0.5);
+        let held = game.get_balance(if sell_a { pair.asset_a } else { pair.asset_b }, me);
+        let amount = rng.gen_range(0, held / 20 + 1);
+        if amount == 0 {
+            return heartbeat();
+        }
+        GameMove::Trade(Trade {
+            pair,
+            amount_a: if sell_a { amount } else { 0 },
+            amount_b: if sell_a { 0 } else { amount },
+            sell: true,
+            cap: None,
+        })
+    }
+}
+
+/// Builds power plants at random locations until it owns `max_plants`
+pub struct PlantBuilder {
+    pub max_plants: usize,
+}
+impl Strategy for PlantBuilder {
+    fn next_move(
+        &mut self,
+        game: &mut GameBoard,
+        me: EntityID,
+        _key: &str,
+        rng: &mut StdRng,
+    ) -> GameMove {
+        let owned = game
+            .get_user_power_plants(me)
+            .map(|p| p.power_plant_data.len())
+            .unwrap_or_default();
+        if owned >= self.max_plants {
+            return heartbeat();
+        }
+        let plant_type = *[PlantType::Solar, PlantType::Hydro, PlantType::Flare]
+            .choose(rng)
+            .expect("Not Empty");
+        GameMove::SuperMintPowerPlant(MintPowerPlant {
+            scale: 1,
+            location: (
+                rng.gen_range(-MAX_LATITUDE, MAX_LATITUDE + 1),
+                rng.gen_range(-MAX_LONGITUDE, MAX_LONGITUDE + 1),
+            ),
+            plant_type,
+        })
+    }
+}
+
+/// Buys the cheapest power plant listed by someone else whenever it can
+/// afford it, and relists everything it owns at a markup
+pub struct MarketMaker {
+    pub markup_percent: u128,
+}
+impl Strategy for MarketMaker {
+    fn next_move(
+        &mut self,
+        game: &mut GameBoard,
+        me: EntityID,
+        _key: &str,
+        _rng: &mut StdRng,
+    ) -> GameMove {
+        let listings = game
+            .get_ux_energy_market()
+            .map(|l| l.listings)
+            .unwrap_or_default();
+        let unlisted = game
+            .get_user_power_plants(me)
+            .ok()
+            .and_then(|p| p.power_plant_data.into_values().find(|p| !p.for_sale));
+        if let Some(plant) = unlisted {
+            // value a plant by its rating, then mark it up
+            let price = plant.watts * (100 + self.markup_percent) / 100;
+            return GameMove::ListNFTForSale(ListNFTForSale {
+                nft_id: plant.id,
+                price: price.max(1),
+                currency: game.bitcoin_token_id(),
+            });
+        }
+        let cheapest = listings
+            .into_iter()
+            .filter(|l| l.seller != me && l.price <= game.get_balance(l.currency, me))
+            .min_by_key(|l| l.price);
+        match cheapest {
+            Some(l) => GameMove::PurchaseNFT(PurchaseNFT {
+                nft_id: l.nft_id,
+                limit_price: l.price,
+                currency: l.currency,
+            }),
+            None => heartbeat(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use mine_with_friends_board::{game::GameSetup, sanitize::Unsanitized, MoveEnvelope};
+    use sapio_bitcoin::secp256k1::rand::SeedableRng;
+
+    #[test]
+    fn test_strategies_play_locally() {
+        let kinds = [
+            StrategyKind::Idle,
+            StrategyKind::RandomTrader,
+            StrategyKind::PlantBuilder,
+            StrategyKind::MarketMaker,
+        ];
+        let keys: Vec<String> = (0..kinds.len()).map(|i| format!("bot-{}", i)).collect();
+        let mut game = GameBoard::new(&GameSetup {
+            players: keys.clone(),
+            start_amount: 10_000_000,
+            finish_time: 1_000_000,
+            subsidy: Default::default(),
+            clock: Default::default(),
+            failover: Default::default(),
+        });
+        let mut bots: Vec<_> = kinds.iter().map(|k| k.build()).collect();
+        let mut rng = StdRng::seed_from_u64(0);
+        for turn in 0..50u64 {
+            for (bot, key) in bots.iter_mut().zip(keys.iter()) {
+                let me = game.get_user_id(key).unwrap();
+                let d = bot.next_move(&mut game, me, key, &mut rng);
+                let mv = MoveEnvelope {
+                    d: Unsanitized(d),
+                    sequence: turn,
+                    time_millis: turn * 1000,
+                };
+                // strategies may well make bad moves, they just must not
+                // break the game
+                let _ = game.play(mv, key.clone());
+            }
+        }
+        let builder = game.get_user_id(&keys[2]).unwrap();
+        assert!(!game
+            .get_user_power_plants(builder)
+            .unwrap()
+            .power_plant_data
+            .is_empty());
+    }
+}
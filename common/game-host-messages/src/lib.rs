@@ -4,12 +4,15 @@
 //  License, v. 2.0. If a copy of the MPL was not distributed with this
 //  file, You can obtain one at https://mozilla.org/MPL/2.0/.
 
-use attest_messages::{AttestEnvelopable, CanonicalEnvelopeHash};
-use mine_with_friends_board::game::{game_move::GameMove, GameSetup};
+use attest_messages::{AttestEnvelopable, CanonicalEnvelopeHash, Envelope};
+use mine_with_friends_board::game::{clock::ClockParams, game_move::GameMove, GameSetup};
 use ruma_serde::CanonicalJsonValue;
 use sapio_bitcoin::{
     hashes::hex::{FromHex, ToHex},
-    secp256k1::rand::{thread_rng, Rng},
+    secp256k1::{
+        rand::{thread_rng, Rng},
+        Secp256k1, Verification,
+    },
     XOnlyPublicKey,
 };
 use schemars::JsonSchema;
@@ -30,6 +33,13 @@ pub struct FinishArgs {
 #[derive(Serialize, Deserialize, Debug)]
 pub struct NewGameArgs {
     pub duration_minutes: u16,
+    /// How many players the lobby takes before it stops accepting joins,
+    /// defaults to the host's limit if not set
+    #[serde(default)]
+    pub max_players: Option<u16>,
+    /// How the game keeps time, defaults to the host's choice if not set
+    #[serde(default)]
+    pub clock: Option<ClockParams>,
 }
 
 #[derive(Serialize, Deserialize, Debug)]
@@ -37,7 +47,7 @@ pub struct NewGame {
     pub password: JoinCode,
     pub join: JoinCode,
 }
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Serialize, Deserialize, Clone)]
 pub enum AddPlayerError {
     AlreadySetup,
     NoMorePlayers,
@@ -51,6 +61,92 @@ impl Display for AddPlayerError {
 }
 impl Error for AddPlayerError {}
 
+/// Where a game hosted by a game host is in its life
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, JsonSchema)]
+pub enum GameLifecycle {
+    /// Players are joining, the game has no sequencer yet
+    Lobby,
+    /// The game contract is funded and moves are being sequenced
+    Running,
+    /// The game's duration has passed, moves needed to settle are still
+    /// sequenced
+    Finished,
+    /// The game contract's funds have been spent
+    Settled,
+    /// Closed by the admin, nothing more is sequenced
+    Closed,
+}
+
+/// What a game host reports about each game it hosts. Anyone can list these,
+/// so the join code is left out.
+#[derive(Serialize, Deserialize, Debug, Clone, JsonSchema)]
+pub struct GameSummary {
+    pub lifecycle: GameLifecycle,
+    pub paused: bool,
+    pub max_players: u16,
+    pub duration_minutes: u16,
+    pub players: usize,
+    #[schemars(with = "Option<String>")]
+    pub sequencer_key: Option<XOnlyPublicKey>,
+    pub created_time: i64,
+    pub started_time: Option<i64>,
+}
+
+/// Something a game's sequencing ran into that it could carry on past
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, JsonSchema)]
+pub enum SequencingIssue {
+    /// A move the host already sequenced is not in its database. Sequencing
+    /// carries on without it, and it is set aside as sequenced if it is
+    /// fetched from a peer later.
+    MissingSequenced(CanonicalEnvelopeHash),
+    /// One of the host's own envelopes was in a group it sequences. It is
+    /// never sequenced.
+    OwnEnvelope(CanonicalEnvelopeHash),
+    /// A chain has two envelopes at the same height. The chain is
+    /// quarantined: none of its moves are sequenced after that.
+    Equivocation {
+        genesis: CanonicalEnvelopeHash,
+        height: i64,
+    },
+}
+
+impl Display for SequencingIssue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        Debug::fmt(self, f)
+    }
+}
+impl Error for SequencingIssue {}
+
+/// How sequencing one of the host's games is going
+#[derive(Serialize, Deserialize, Debug, Clone, JsonSchema)]
+pub struct SequencingStatus {
+    #[schemars(with = "String")]
+    pub sequencer_key: XOnlyPublicKey,
+    /// Whether a task is sequencing the game right now
+    pub running: bool,
+    /// How many times the task stopped and had to be started again
+    pub restarts: u64,
+    /// Why the task last stopped, if it failed
+    pub last_error: Option<String>,
+    /// Height in the host's chain of its latest Sequence, if it sent one
+    pub sequenced_height: Option<i64>,
+    /// Issues the task is carrying on past
+    pub issues: Vec<SequencingIssue>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct PauseGameArgs {
+    pub passcode: JoinCode,
+    pub code: JoinCode,
+    pub paused: bool,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct CloseGameArgs {
+    pub passcode: JoinCode,
+    pub code: JoinCode,
+}
+
 #[derive(Serialize, Deserialize, Eq, Ord, PartialEq, PartialOrd, Hash, Clone, Copy, JsonSchema)]
 #[serde(into = "String")]
 #[serde(try_from = "String")]
@@ -92,12 +188,55 @@ pub struct Peer {
     pub service_url: String,
     pub port: u16,
 }
+/// How long the host goes without sending anything before it sends a
+/// [`BroadcastByHost::Heartbeat`]
+pub const HEARTBEAT_INTERVAL_MS: i64 = 30_000;
+
+/// Sent by the host when it has had nothing else to send for
+/// [`HEARTBEAT_INTERVAL_MS`], so players can tell a quiet game from a dead
+/// host. The Bitcoin checkpoint the host last saw is in the envelope's header,
+/// as with every envelope the host sends.
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone)]
+pub struct HostHeartbeat {
+    /// Height in the host's chain of its latest Sequence, if it sent one
+    pub sequenced_height: Option<i64>,
+}
+
 #[derive(Serialize, Deserialize, JsonSchema, Debug, Clone)]
 pub enum BroadcastByHost {
     GameSetup(GameSetup),
     Sequence(VecDeque<CanonicalEnvelopeHash>),
     NewPeer(Peer),
-    Heartbeat,
+    Heartbeat(HostHeartbeat),
+    /// Sent by a backup sequencer to take over from `from`, the sequencer
+    /// that had custody of the game, after `last`, its last envelope
+    TakeOver {
+        #[schemars(with = "String")]
+        from: XOnlyPublicKey,
+        last: CanonicalEnvelopeHash,
+    },
+    /// Sent by the host when it quarantines a player's chain, see
+    /// [`SequencingIssue::Equivocation`]
+    Quarantine(Box<EquivocationEvidence>),
+}
+
+/// Two different envelopes a player signed at the same height of their
+/// chain
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone)]
+pub struct EquivocationEvidence(pub Envelope, pub Envelope);
+
+impl EquivocationEvidence {
+    /// The key that equivocated, if both envelopes are validly signed by it
+    /// at the same height
+    pub fn equivocator<C: Verification>(&self, secp: &Secp256k1<C>) -> Option<XOnlyPublicKey> {
+        let (a, b) = (&self.0, &self.1);
+        let equivocated = a.header().key() == b.header().key()
+            && a.header().height() == b.header().height()
+            && a.canonicalized_hash_ref() != b.canonicalized_hash_ref()
+            && a.self_authenticate(secp).is_ok()
+            && b.self_authenticate(secp).is_ok();
+        equivocated.then(|| a.header().key())
+    }
 }
 
 impl BroadcastByHost {
@@ -5,8 +5,15 @@
 //  file, You can obtain one at https://mozilla.org/MPL/2.0/.
 
 use crate::{
-    app::routes::game_init::{add_player, create_new_game_instance, finish_setup, NewGameDB},
+    app::routes::{
+        admin::{close_game, list_games, pause_game, sequencing_status},
+        game_init::{add_player, create_new_game_instance, finish_setup},
+        rejections::list_rejections,
+        spectate::{spectate_game, SpectateCache},
+    },
     globals::Globals,
+    registry::Registry,
+    status::StatusBoard,
     Config,
 };
 use attest_database::{connection::MsgDB, db_handle::get::PeerInfo, generate_new_user};
@@ -248,6 +255,8 @@ pub fn run(
     config: Arc<Config>,
     db: MsgDB,
     globals: Globals,
+    registry: Registry,
+    status: StatusBoard,
 ) -> tokio::task::JoinHandle<Result<(), Box<dyn Error + Send + Sync + 'static>>> {
     let secp = Arc::new(Secp256k1::new());
     tokio::spawn(async move {
@@ -258,6 +267,12 @@ pub fn run(
             .route("/game/new", post(create_new_game_instance))
             .route("/game/player/new", post(add_player))
             .route("/game/finish", post(finish_setup))
+            .route("/game/spectate", post(spectate_game))
+            .route("/game/rejections", post(list_rejections))
+            .route("/game/admin/list", get(list_games))
+            .route("/game/admin/pause", post(pause_game))
+            .route("/game/admin/close", post(close_game))
+            .route("/game/admin/status", get(sequencing_status))
             .route("/peer", get(get_peers))
             .route("/ping", get(connected))
             .route("/attestation_chain/new", post(create_new_attestation_chain))
@@ -269,8 +284,10 @@ pub fn run(
             .layer(TraceLayer::new_for_http())
             .layer(Extension(db))
             .layer(Extension(secp))
-            .layer(Extension(Arc::new(Mutex::new(NewGameDB::new()))))
+            .layer(Extension(registry))
+            .layer(Extension(status))
             .layer(Extension(globals))
+            .layer(Extension(SpectateCache::default()))
             .layer(
                 CorsLayer::new()
                     .allow_methods([Method::GET, Method::OPTIONS, Method::POST])
@@ -0,0 +1,88 @@
+// Copyright Judica, Inc 2022
+//
+// This Source Code Form is subject to the terms of the Mozilla Public
+//  License, v. 2.0. If a copy of the MPL was not distributed with this
+//  file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+//! Module Admin defines HTTP Handlers for managing the games in the registry.
+//!
+//! Pausing or closing a game needs the admin passcode it was created with.
+
+use crate::{registry::Registry, status::StatusBoard};
+use axum::{
+    http::{Response, StatusCode},
+    Extension, Json,
+};
+use game_host_messages::{CloseGameArgs, GameSummary, PauseGameArgs, SequencingStatus};
+
+pub async fn list_games(
+    Extension(registry): Extension<Registry>,
+) -> Result<(Response<()>, Json<Vec<GameSummary>>), (StatusCode, String)> {
+    let games = registry.lock().await.list();
+    Ok((
+        Response::builder()
+            .status(200)
+            .header("Access-Control-Allow-Origin", "*")
+            .body(())
+            .expect("Response<()> should always be valid"),
+        Json(games),
+    ))
+}
+
+pub async fn pause_game(
+    Json(PauseGameArgs {
+        passcode,
+        code,
+        paused,
+    }): Json<PauseGameArgs>,
+    Extension(registry): Extension<Registry>,
+) -> Result<(Response<()>, Json<()>), (StatusCode, String)> {
+    registry
+        .lock()
+        .await
+        .pause(code, passcode, paused)
+        .await
+        .map_err(|e| (e.status(), e.to_string()))?;
+    Ok((
+        Response::builder()
+            .status(200)
+            .header("Access-Control-Allow-Origin", "*")
+            .body(())
+            .expect("Response<()> should always be valid"),
+        Json(()),
+    ))
+}
+
+pub async fn close_game(
+    Json(CloseGameArgs { passcode, code }): Json<CloseGameArgs>,
+    Extension(registry): Extension<Registry>,
+) -> Result<(Response<()>, Json<()>), (StatusCode, String)> {
+    registry
+        .lock()
+        .await
+        .close(code, passcode)
+        .await
+        .map_err(|e| (e.status(), e.to_string()))?;
+    Ok((
+        Response::builder()
+            .status(200)
+            .header("Access-Control-Allow-Origin", "*")
+            .body(())
+            .expect("Response<()> should always be valid"),
+        Json(()),
+    ))
+}
+
+pub async fn sequencing_status(
+    Extension(status): Extension<StatusBoard>,
+) -> Result<(Response<()>, Json<Vec<SequencingStatus>>), (StatusCode, String)> {
+    let games = status.lock().await.list();
+    Ok((
+        Response::builder()
+            .status(200)
+            .header("Access-Control-Allow-Origin", "*")
+            .body(())
+            .expect("Response<()> should always be valid"),
+        Json(games),
+    ))
+}
@@ -4,4 +4,7 @@
 //  License, v. 2.0. If a copy of the MPL was not distributed with this
 //  file, You can obtain one at https://mozilla.org/MPL/2.0/.
 
+pub mod admin;
 pub mod game_init;
+pub mod rejections;
+pub mod spectate;
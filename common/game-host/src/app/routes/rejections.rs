@@ -0,0 +1,51 @@
+// Copyright Judica, Inc 2022
+//
+// This Source Code Form is subject to the terms of the Mozilla Public
+//  License, v. 2.0. If a copy of the MPL was not distributed with this
+//  file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+//! Module Rejections defines an HTTP Handler for auditing which of the
+//! envelopes a host sequenced were not played, and why.
+
+use super::spectate::{game_setup, load_sequenced, SpectateArgs};
+use attest_database::connection::MsgDB;
+use axum::{
+    http::{Response, StatusCode},
+    Extension, Json,
+};
+use game_sequencer::{OfflineSequencer, RejectedEnvelope};
+use sapio_bitcoin::XOnlyPublicKey;
+use std::{collections::BTreeSet, str::FromStr};
+
+pub async fn list_rejections(
+    Json(SpectateArgs { sequencer_key }): Json<SpectateArgs>,
+    Extension(db): Extension<MsgDB>,
+) -> Result<(Response<()>, Json<Vec<RejectedEnvelope>>), (StatusCode, &'static str)> {
+    let raw = load_sequenced(&db, sequencer_key).await?;
+    let players = game_setup(&raw)?
+        .players
+        .iter()
+        .map(|p| XOnlyPublicKey::from_str(p))
+        .collect::<Result<BTreeSet<_>, _>>()
+        .map_err(|_| {
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "GameSetup has an Invalid Player Key",
+            )
+        })?;
+    let rejected = OfflineSequencer::try_from(raw)
+        .ok()
+        .and_then(|s| game_sequencer::rejected_envelopes(s, &players).ok())
+        .ok_or((
+            StatusCode::INTERNAL_SERVER_ERROR,
+            "Could not Sequence Moves",
+        ))?;
+    Ok((
+        Response::builder()
+            .status(200)
+            .header("Access-Control-Allow-Origin", "*")
+            .body(())
+            .expect("Response<()> should always be valid"),
+        Json(rejected),
+    ))
+}
@@ -0,0 +1,166 @@
+// Copyright Judica, Inc 2022
+//
+// This Source Code Form is subject to the terms of the Mozilla Public
+//  License, v. 2.0. If a copy of the MPL was not distributed with this
+//  file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+//! Module Spectate defines an HTTP Handler for following a game without
+//! playing in it.
+//!
+//! The host has every move it sequenced, so it can replay any of its games and
+//! serve the current state to anyone, no player key or attest node required.
+//! Anyone may ask, so a replay is kept for [`SPECTATE_CACHE_FOR`] and served to
+//! every spectator of that game in the meantime.
+
+use attest_database::connection::MsgDB;
+use axum::{
+    http::{Response, StatusCode},
+    Extension, Json,
+};
+use game_host_messages::BroadcastByHost;
+use game_player_messages::ParticipantAction;
+use game_sequencer::{custody, ExtractedMoveEnvelopes, OfflineSequencer, RawSequencer};
+use mine_with_friends_board::{
+    alliances::UXAlliance,
+    entity::EntityID,
+    game::{FinishReason, GameBoard, GameSetup, UXLeaderboardEntry},
+    nfts::{sale::UXNFTSale, UXPlantData},
+    tokens::token_swap::UXMaterialsPriceData,
+};
+use sapio_bitcoin::XOnlyPublicKey;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{BTreeMap, VecDeque},
+    sync::Arc,
+    time::{Duration, Instant},
+};
+use tokio::{sync::Mutex, task::spawn_blocking};
+
+/// How long a replayed game is served before it is replayed again
+pub const SPECTATE_CACHE_FOR: Duration = Duration::from_secs(5);
+
+/// The last view replayed for each game. Each game has its own lock, held
+/// while replaying, so spectators arriving mid-replay wait for it rather than
+/// starting their own.
+#[derive(Clone, Default)]
+pub struct SpectateCache(
+    Arc<Mutex<BTreeMap<XOnlyPublicKey, Arc<Mutex<Option<(Instant, SpectatorView)>>>>>>,
+);
+
+#[derive(Deserialize)]
+pub struct SpectateArgs {
+    pub(crate) sequencer_key: XOnlyPublicKey,
+}
+
+/// Everything a spectator is shown of a game
+#[derive(Serialize, Clone)]
+pub struct SpectatorView {
+    setup: GameSetup,
+    elapsed_time: u64,
+    finish_reason: Option<FinishReason>,
+    leaderboard: Vec<UXLeaderboardEntry>,
+    chat_log: VecDeque<(u64, EntityID, String)>,
+    materials_price_data: Vec<UXMaterialsPriceData>,
+    power_plants: Vec<UXPlantData>,
+    energy_exchange: Vec<UXNFTSale>,
+    alliances: Vec<UXAlliance>,
+}
+
+/// Loads a sequencer's chain, its backups' chains, and every move they
+/// sequenced
+pub(crate) async fn load_sequenced(
+    db: &MsgDB,
+    sequencer_key: XOnlyPublicKey,
+) -> Result<RawSequencer<ParticipantAction>, (StatusCode, &'static str)> {
+    let handle = db.get_handle_read().await;
+    spawn_blocking(move || custody::load_sequenced(&handle, sequencer_key))
+        .await
+        .map_err(|_| (StatusCode::INTERNAL_SERVER_ERROR, ""))?
+        .map_err(|_| (StatusCode::INTERNAL_SERVER_ERROR, ""))
+}
+
+/// The game a sequencer was set up for
+pub(crate) fn game_setup(
+    raw: &RawSequencer<ParticipantAction>,
+) -> Result<GameSetup, (StatusCode, &'static str)> {
+    match raw.sequencer_envelopes.first().map(|e| &e.msg().data) {
+        Some(BroadcastByHost::GameSetup(g)) => Ok(g.clone()),
+        _ => Err((StatusCode::NOT_FOUND, "No Game for Sequencer Key")),
+    }
+}
+
+/// Replays the game sequenced by `sequencer_key` from the start
+async fn replay(
+    db: &MsgDB,
+    sequencer_key: XOnlyPublicKey,
+) -> Result<SpectatorView, (StatusCode, &'static str)> {
+    let raw = load_sequenced(db, sequencer_key).await?;
+    let setup = game_setup(&raw)?;
+    spawn_blocking(move || {
+        let moves = OfflineSequencer::try_from(raw)
+            .ok()
+            .and_then(|s| ExtractedMoveEnvelopes::try_from(s).ok())
+            .ok_or((
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Could not Sequence Moves",
+            ))?;
+
+        let mut game = GameBoard::new(&setup);
+        let finish_reason = moves.replay(&mut game).or_else(|| game.game_is_finished());
+        Ok(SpectatorView {
+            elapsed_time: game.current_time(),
+            finish_reason,
+            leaderboard: game.get_ux_leaderboard(),
+            chat_log: game.get_ux_chat_log(),
+            materials_price_data: game.get_ux_materials_prices(),
+            power_plants: game.get_ux_power_plant_data(),
+            energy_exchange: game
+                .get_ux_energy_market()
+                .map(|l| l.listings)
+                .unwrap_or_default(),
+            alliances: game.get_ux_alliances(),
+            setup,
+        })
+    })
+    .await
+    .map_err(|_| (StatusCode::INTERNAL_SERVER_ERROR, ""))?
+}
+
+pub async fn spectate_game(
+    Json(SpectateArgs { sequencer_key }): Json<SpectateArgs>,
+    Extension(db): Extension<MsgDB>,
+    Extension(cache): Extension<SpectateCache>,
+) -> Result<(Response<()>, Json<SpectatorView>), (StatusCode, &'static str)> {
+    let slot = cache
+        .0
+        .lock()
+        .await
+        .entry(sequencer_key)
+        .or_default()
+        .clone();
+    let mut cached = slot.lock().await;
+    let view = match cached.as_ref() {
+        Some((at, view)) if at.elapsed() < SPECTATE_CACHE_FOR => view.clone(),
+        _ => match replay(&db, sequencer_key).await {
+            Ok(view) => {
+                *cached = Some((Instant::now(), view.clone()));
+                view
+            }
+            Err(e) => {
+                // only games that could be replayed keep a place in the cache
+                if cached.is_none() {
+                    cache.0.lock().await.remove(&sequencer_key);
+                }
+                return Err(e);
+            }
+        },
+    };
+    Ok((
+        Response::builder()
+            .status(200)
+            .header("Access-Control-Allow-Origin", "*")
+            .body(())
+            .expect("Response<()> should always be valid"),
+        Json(view),
+    ))
+}
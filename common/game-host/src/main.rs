@@ -7,20 +7,24 @@
 use app::CompilerModule;
 use attest_database::setup_db;
 use attest_database::{connection::MsgDB, db_handle::create::TipControl};
-use attest_messages::{
-    Authenticated, CanonicalEnvelopeHash, Envelope, GenericEnvelope, WrappedJson,
-};
+use attest_messages::{Authenticated, CanonicalEnvelopeHash, Envelope, WrappedJson};
 use attest_util::bitcoin::BitcoinConfig;
+use bitcoin_header_checkpoints::BitcoinCheckPointCache;
 use emulator_connect::{CTVAvailable, CTVEmulator};
 
 use event_log::db_handle::accessors::occurrence::sql::Idempotent;
 use event_log::db_handle::accessors::occurrence::ToOccurrence;
-use game_host_messages::{BroadcastByHost, Channelized};
+use game_host_messages::{
+    BroadcastByHost, Channelized, EquivocationEvidence, HostHeartbeat, SequencingIssue,
+    HEARTBEAT_INTERVAL_MS,
+};
+use game_sequencer::batching::BatchSelector;
+use game_sequencer::custody::{self, Custody, HostEnvelope, Standing};
+use game_sequencer::fair_order::FairOrder;
 use sapio::contract::Compiled;
-use sapio_bitcoin::secp256k1::rand;
-use sapio_bitcoin::secp256k1::rand::seq::SliceRandom;
 use sapio_bitcoin::secp256k1::All;
 use sapio_bitcoin::Network;
+use sapio_bitcoin::XOnlyPublicKey;
 use sapio_bitcoin::{secp256k1::Secp256k1, KeyPair};
 use sapio_litigator_events::ModuleRepo;
 use sapio_wasm_plugin::host::plugin_handle::ModuleLocator;
@@ -28,9 +32,10 @@ use sapio_wasm_plugin::host::WasmPluginHandle;
 
 use serde::{Deserialize, Serialize};
 
+use std::str::FromStr;
 use std::time::Duration;
 use std::{
-    collections::{BTreeMap, HashMap, VecDeque},
+    collections::{BTreeMap, HashMap, HashSet},
     error::Error,
     path::PathBuf,
     sync::Arc,
@@ -42,8 +47,12 @@ use tor::TorConfig;
 use tracing::{debug, info, warn};
 
 use crate::globals::GlobalsInner;
+use crate::registry::{GameRegistry, Registry};
+use crate::status::StatusBoard;
 mod app;
 mod globals;
+mod registry;
+mod status;
 mod tor;
 #[derive(Serialize, Deserialize)]
 pub struct Config {
@@ -132,6 +141,7 @@ async fn main() -> Result<(), Box<dyn Error + Send + Sync>> {
         .await
         .map_err(|e| e.to_string())?,
     ));
+    let registry: Registry = Arc::new(Mutex::new(GameRegistry::load(evlog.clone()).await?));
     let globals = Arc::new(GlobalsInner {
         module_repo_id,
         module_tag,
@@ -148,16 +158,41 @@ async fn main() -> Result<(), Box<dyn Error + Send + Sync>> {
     .await
     .map_err(|e| format!("DB Setup Failed: {:?}", e))?;
     let tor_server = tor::start(config.clone()).await;
+    let checkpoints = Arc::new(
+        BitcoinCheckPointCache::new(
+            config.bitcoin.get_new_client().await?,
+            None,
+            Default::default(),
+        )
+        .await,
+    );
+    let checkpoint_service = checkpoints
+        .run_cache_service()
+        .ok_or("Checkpoint service already started")?;
 
     let host = config.tor.get_hostname().await?;
     info!("Hosting Onion Service At: {}", host);
 
-    let app_instance = app::run(config.clone(), db.clone(), globals);
-    let game_instance = game_server(config, db.clone());
+    let status = StatusBoard::default();
+    let registry_instance = registry::monitor(registry.clone(), globals.clone());
+    let app_instance = app::run(
+        config.clone(),
+        db.clone(),
+        globals,
+        registry.clone(),
+        status.clone(),
+    );
+    let game_instance = game_server(config, db.clone(), registry, checkpoints, status);
     tokio::select! {
         a =  game_instance =>{
             a?;
         },
+        c = checkpoint_service => {
+            c??;
+        },
+        r = registry_instance => {
+            r?;
+        },
         b = app_instance => {
             b?.map_err(|e| format!("{}", e))?;
         }
@@ -168,7 +203,13 @@ async fn main() -> Result<(), Box<dyn Error + Send + Sync>> {
     Ok(())
 }
 
-async fn game_server(config: Arc<Config>, db: MsgDB) -> Result<(), Box<dyn Error + Send + Sync>> {
+async fn game_server(
+    config: Arc<Config>,
+    db: MsgDB,
+    registry: Registry,
+    checkpoints: Arc<BitcoinCheckPointCache>,
+    status: StatusBoard,
+) -> Result<(), Box<dyn Error + Send + Sync>> {
     let mut task_set = BTreeMap::<_, JoinHandle<_>>::new();
     let secp = Arc::new(Secp256k1::new());
     loop {
@@ -178,29 +219,52 @@ async fn game_server(config: Arc<Config>, db: MsgDB) -> Result<(), Box<dyn Error
             spawn_blocking(move || handle.get_keymap()).await??
         };
         for (key, value) in keymap {
+            if !registry.lock().await.should_sequence(&key) {
+                if let Some(task) = task_set.remove(&key) {
+                    info!(?key, "Game Paused or Closed, stopping game task...");
+                    task.abort();
+                    status.lock().await.halted(key);
+                }
+                continue;
+            }
             match task_set.entry(key) {
                 std::collections::btree_map::Entry::Vacant(e) => {
                     info!(?key, "No Task Found, starting new game task...");
                     let keypair = KeyPair::from_secret_key(&secp, &value);
+                    status.lock().await.started(key);
                     e.insert(spawn(game(
                         config.clone(),
                         db.clone(),
                         keypair,
                         secp.clone(),
+                        checkpoints.clone(),
+                        status.clone(),
                     )));
                 }
                 std::collections::btree_map::Entry::Occupied(ref mut x) => {
                     if x.get().is_finished() {
                         info!(?key, "Task Quit, rebooting...");
+                        let res = x.get_mut().await;
+                        debug!(?res, ?key, "Game Task Quit with");
+                        let error = match res {
+                            Ok(Ok(())) => None,
+                            Ok(Err(e)) => Some(e.to_string()),
+                            Err(e) => Some(e.to_string()),
+                        };
                         let keypair = KeyPair::from_secret_key(&secp, &value);
-                        let old = x.insert(spawn(game(
+                        {
+                            let mut status = status.lock().await;
+                            status.stopped(key, error);
+                            status.started(key);
+                        }
+                        x.insert(spawn(game(
                             config.clone(),
                             db.clone(),
                             keypair,
                             secp.clone(),
+                            checkpoints.clone(),
+                            status.clone(),
                         )));
-                        let res = old.await;
-                        debug!(?res, ?key, "Game Task Quit with");
                     } else {
                         info!(?key, "Task Healthy");
                     }
@@ -217,36 +281,80 @@ async fn game(
     db: MsgDB,
     keypair: KeyPair,
     secp: Arc<Secp256k1<All>>,
+    checkpoints: Arc<BitcoinCheckPointCache>,
+    status: StatusBoard,
 ) -> Result<(), Box<dyn Error + Send + Sync>> {
     let oracle_publickey = keypair.public_key().x_only_public_key().0;
-    let mut already_sequenced: Vec<CanonicalEnvelopeHash> = vec![];
-    // First we get all of the old messages for the Oracle itself, so that we
-    // can know which messages we've sequenced previously.
-    {
-        let v: Vec<Authenticated<GenericEnvelope<Channelized<BroadcastByHost>>>> = {
-            let handle = db.get_handle_read().await;
-            spawn_blocking(move || {
-                handle.load_all_messages_for_user_by_key_connected(&oracle_publickey)
-            })
-            .await??
-        };
-        let sequencer = game_sequencer::RawSequencer {
-            sequencer_envelopes: v,
-            msg_cache: Default::default(),
+    // Custody of the game can pass from its host to its backup sequencers
+    // (see [`custody`]), so a backup only sequences once it has taken over,
+    // and whoever has custody carries on from what was sent before it.
+    let (custody, position, followed) =
+        match await_custody(&db, keypair, &secp, &checkpoints).await? {
+            Some(c) => c,
+            None => {
+                info!(key=?oracle_publickey, "Custody Passed Us By, Not Sequencing");
+                return std::future::pending().await;
+            }
         };
-        let sequencer: game_sequencer::OfflineSequencer<game_player_messages::ParticipantAction> =
-            sequencer.try_into()?;
-        for batch in sequencer.batches_to_sequence {
-            already_sequenced.extend(batch.iter());
+    if position > 0 {
+        subscribe_to_players(&db, oracle_publickey, &followed).await?;
+    }
+    let mut already_sequenced: Vec<CanonicalEnvelopeHash> = vec![];
+    let mut fair_order = FairOrder::default();
+    let mut batch_selector = BatchSelector::default();
+    // First we go through everything sent with custody of the game, by us or
+    // by whoever had it before us, so that we can know which messages were
+    // sequenced previously, which chains were quarantined, and what the next
+    // heartbeat should report and when it is due.
+    //
+    // Chains that sent two messages at the same height are quarantined: we no
+    // longer sequence them since players could disagree on which one was
+    // played
+    let mut quarantined = HashSet::<CanonicalEnvelopeHash>::new();
+    let mut sequenced_height = followed
+        .iter()
+        .rev()
+        .filter(|e| e.header().key() == oracle_publickey)
+        .find(|e| e.msg().data.is_sequence())
+        .map(|e| e.header().height());
+    let mut last_sent_ms = followed
+        .last()
+        .filter(|e| e.header().key() == oracle_publickey)
+        .map(|e| e.header().sent_time_ms())
+        .unwrap_or_default();
+    for e in &followed {
+        match &e.msg().data {
+            BroadcastByHost::Sequence(batch) => {
+                fair_order.advance(batch);
+                already_sequenced.extend(batch.iter());
+            }
+            BroadcastByHost::Quarantine(evidence) => {
+                let genesis = evidence.0.get_genesis_hash();
+                let height = evidence.0.header().height();
+                quarantined.insert(genesis);
+                status.lock().await.issue(
+                    oracle_publickey,
+                    SequencingIssue::Equivocation { genesis, height },
+                );
+            }
+            _ => {}
         }
     }
+    // TakeOvers naming us seen so far, see [`lost_custody`]
+    let mut takeovers_seen = 0;
     info!(
         n = already_sequenced.len(),
         "Loaded Previous Sequence Messages"
     );
+    if let Some(height) = sequenced_height {
+        status.lock().await.sequenced(oracle_publickey, height);
+    }
     let mut all_unprocessed_messages = HashMap::new();
     let mut message_by_genesis = HashMap::<CanonicalEnvelopeHash, BTreeMap<i64, Envelope>>::new();
     let mut next_height_to_sequence: HashMap<CanonicalEnvelopeHash, i64> = Default::default();
+    // Messages we sequenced before but do not have. Our peers still have them,
+    // so we keep fetching, and until one arrives its chain stops at the gap.
+    let mut missing_sequenced = HashSet::<CanonicalEnvelopeHash>::new();
     let mut seq = 0;
 
     // Get All the messages that we've not yet seen, but incosistently
@@ -290,8 +398,12 @@ async fn game(
                 .or_default();
             *r = std::cmp::max(msg.header().height() + 1, *r);
         } else {
-            debug!(message_hash=?m, "Missing Message!");
-            panic!("Message was sequenced, but was not contained in database");
+            warn!(message_hash=?m, "Message was sequenced, but was not contained in database");
+            status
+                .lock()
+                .await
+                .issue(oracle_publickey, SequencingIssue::MissingSequenced(m));
+            missing_sequenced.insert(m);
         }
     }
     info!(
@@ -299,6 +411,14 @@ async fn game(
         "Found Existing Comitted Chains"
     );
     loop {
+        // A backup that stopped hearing from us may have taken over, after
+        // which nothing we send is followed
+        let (seen, lost) = lost_custody(&db, &custody, position, takeovers_seen).await?;
+        takeovers_seen = seen;
+        if lost {
+            warn!(key=?oracle_publickey, "Backup Took Over, No Longer Sequencing");
+            return std::future::pending().await;
+        }
         // Get All the messages that we've not yet seen, but incosistently
         // Incosistency means that we may still be fetching priors tips in our
         // network stack.
@@ -339,114 +459,301 @@ async fn game(
                 all_unprocessed_messages.keys().cloned().collect::<Vec<_>>();
             for value in &unprocessed_message_keys {
                 // we can remove it now because the only reason we will drop it is if it is not to be sequenced
-                if let Some((_k, e)) = all_unprocessed_messages.remove_entry(value) {
+                if let Some((k, e)) = all_unprocessed_messages.remove_entry(value) {
                     if e.header().key() == oracle_publickey {
-                        panic!("Chain Cannot Listen to itself, logic error");
+                        warn!(message_hash=?k, "Chain Cannot Listen to itself, skipping");
+                        status
+                            .lock()
+                            .await
+                            .issue(oracle_publickey, SequencingIssue::OwnEnvelope(k));
+                        continue;
+                    }
+                    let genesis = e.get_genesis_hash();
+                    let height = e.header().height();
+                    if missing_sequenced.remove(&k) {
+                        info!(message_hash=?k, "Found Missing Sequenced Message");
+                        let r = next_height_to_sequence.entry(genesis).or_default();
+                        *r = std::cmp::max(height + 1, *r);
+                        status
+                            .lock()
+                            .await
+                            .resolved(oracle_publickey, &SequencingIssue::MissingSequenced(k));
+                        continue;
                     }
-                    if message_by_genesis
-                        .entry(e.get_genesis_hash())
-                        .or_default()
-                        .insert(e.header().height(), e.inner())
-                        .is_some()
-                    {
-                        warn!("Should Never Be Possible to Have a Duplicate Message Here");
-                        // TODO: Panic Always?
-                        panic!("Test Invariant Failed");
+                    if quarantined.contains(&genesis) {
+                        continue;
+                    }
+                    let ms = message_by_genesis.entry(genesis).or_default();
+                    match ms.get(&height) {
+                        None => {
+                            ms.insert(height, e.inner());
+                        }
+                        // Seen twice, nothing to do
+                        Some(prior) if prior.canonicalized_hash_ref() == k => {}
+                        Some(prior) => {
+                            warn!(?genesis, height, "Chain Equivocated, quarantining it");
+                            let evidence = EquivocationEvidence(prior.clone(), e.inner());
+                            message_by_genesis.remove(&genesis);
+                            quarantined.insert(genesis);
+                            status.lock().await.issue(
+                                oracle_publickey,
+                                SequencingIssue::Equivocation { genesis, height },
+                            );
+                            // so players can tell why the chain's moves stopped
+                            // being sequenced, and can not claim censorship
+                            publish(
+                                &db,
+                                keypair,
+                                &secp,
+                                &checkpoints,
+                                BroadcastByHost::Quarantine(Box::new(evidence)),
+                            )
+                            .await?;
+                            last_sent_ms = attest_util::now();
+                        }
                     }
                 }
             }
         }
-        // Sort the new entries
-        let mut to_sequence = VecDeque::new();
-        for (genesis, ms) in message_by_genesis.iter_mut() {
-            let next_height = next_height_to_sequence.entry(*genesis).or_default();
-            let first = *next_height;
-
-            if ms.keys().next() != Some(&first) {
-                continue;
-            }
-            // iterate over the keys, checking for contiguity and breaking at any gap
-            'scan_keys: for (k1, k2) in ms
-                .keys()
-                .zip(ms.keys().skip(1).chain(std::iter::once(&i64::MAX)))
-            {
-                if k2 - k1 == 1 {
-                } else {
-                    // minimally, the first (contained) +1. or the last next-element
-                    *next_height = k1 + 1;
-                    break 'scan_keys;
-                }
+        // Collect the contiguous new entries for each chain
+        let mut waiting = vec![];
+        for (genesis, ms) in message_by_genesis.iter() {
+            let mut next_height = next_height_to_sequence
+                .get(genesis)
+                .cloned()
+                .unwrap_or_default();
+            // stop at any gap
+            while let Some(e) = ms.get(&next_height) {
+                waiting.push((*genesis, next_height, e.canonicalized_hash_ref()));
+                next_height += 1;
             }
-            // go from the first (e.g. 0)  to the
-            for k in first..*next_height {
-                let e = ms.remove(&k).expect("Must be present");
-                to_sequence.push_back((
-                    e.header().height(),
-                    e.canonicalized_hash_ref(),
-                    e.get_genesis_hash(),
-                ));
+        }
+        // Take at most a batch's worth, with chains taking turns, and leave
+        // the rest for the next batch
+        let to_sequence = batch_selector.select(waiting.iter().cloned());
+        for (genesis, height, _) in &to_sequence {
+            if let Some(ms) = message_by_genesis.get_mut(genesis) {
+                ms.remove(height);
             }
+            next_height_to_sequence.insert(*genesis, height + 1);
         }
 
-        info!(key=?keypair.x_only_public_key().0, n=to_sequence.len(), "Messages to Sequence");
+        info!(
+            key=?keypair.x_only_public_key().0,
+            n=to_sequence.len(),
+            carried_over=waiting.len() - to_sequence.len(),
+            "Messages to Sequence"
+        );
         if !to_sequence.is_empty() {
-            // to_sequence is naturally sorted by height here, which is a safe option.
-            // However, for more fairness, we should randomize and ensure height sortedness later
+            // schedules one message per chain in a round-robin, in an order
+            // players can check we didn't choose
+            let ordered = fair_order.order(to_sequence);
 
-            // ensure sorted by height
-            let mut group_min_height = to_sequence[0].0;
-            let mut g = to_sequence[0].2;
-            // To sequence is grouped by genesis. It will look like:
-            // g1@n g1@n+1 g2@m g3@n g3@n+1 g3@n+2
-            // therefore we can detect groups by where the discontinuities are.
-            // normalizes all the height groups...
-            // g1@0 g1@1 g2@0 g3@0 g3@1 g3@2
-            for s in to_sequence.iter_mut() {
-                if s.2 == g {
-                    s.0 -= group_min_height;
-                } else {
-                    group_min_height = s.0;
-                    s.0 -= group_min_height;
-                    g = s.2;
+            let height = publish(
+                &db,
+                keypair,
+                &secp,
+                &checkpoints,
+                BroadcastByHost::Sequence(ordered),
+            )
+            .await?;
+            sequenced_height = Some(height);
+            last_sent_ms = attest_util::now();
+            status.lock().await.sequenced(oracle_publickey, height);
+        } else if attest_util::now() - last_sent_ms >= HEARTBEAT_INTERVAL_MS {
+            // let players know we're still here, even with nothing to sequence
+            publish(
+                &db,
+                keypair,
+                &secp,
+                &checkpoints,
+                BroadcastByHost::Heartbeat(HostHeartbeat { sequenced_height }),
+            )
+            .await?;
+            last_sent_ms = attest_util::now();
+        }
+        tokio::time::sleep(Duration::from_secs(1)).await;
+    }
+}
+
+/// Waits until the key has custody of its game, taking over from the
+/// custodian ahead of it in line once the custodian has been quiet for long
+/// enough. Returns the game's custody, where the key is in it, and everything
+/// sent with custody so far, or None if custody passed the key by.
+async fn await_custody(
+    db: &MsgDB,
+    keypair: KeyPair,
+    secp: &Arc<Secp256k1<All>>,
+    checkpoints: &BitcoinCheckPointCache,
+) -> Result<Option<(Custody, usize, Vec<HostEnvelope>)>, Box<dyn Error + Send + Sync>> {
+    let key = keypair.x_only_public_key().0;
+    // our own game if we set one up, otherwise the game we are a backup for
+    let host = {
+        let handle = db.get_handle_read().await;
+        spawn_blocking(move || -> Result<_, Box<dyn Error + Send + Sync>> {
+            let genesis: Option<HostEnvelope> = handle.get_message_at_height_for_user(key, 0)?;
+            match genesis {
+                Some(g) if matches!(g.msg().data, BroadcastByHost::GameSetup(_)) => Ok(key),
+                _ => Ok(custody::find_host_for_backup(&handle, key)?.unwrap_or(key)),
+            }
+        })
+        .await??
+    };
+    loop {
+        let (custody, host_chain, backups) = {
+            let handle = db.get_handle_read().await;
+            spawn_blocking(move || custody::load_chains(&handle, host)).await??
+        };
+        let position = custody.position(&key).ok_or("Key Not in Custody of Game")?;
+        let followed = custody.follow(&host_chain, &backups);
+        let last = match followed.last() {
+            Some(last) => last,
+            None => return Ok(Some((custody, position, vec![]))),
+        };
+        match custody.standing(position, last) {
+            Standing::Custodian => {
+                let followed: Vec<HostEnvelope> = followed.into_iter().cloned().collect();
+                return Ok(Some((custody, position, followed)));
+            }
+            Standing::Passed => return Ok(None),
+            Standing::Behind { from, due_ms } => {
+                let now = attest_util::now();
+                if now < due_ms {
+                    // the custodian is still there as long as it keeps
+                    // sending, so check again once it might not be
+                    let wait = (due_ms - now).min(HEARTBEAT_INTERVAL_MS);
+                    tokio::time::sleep(Duration::from_millis(wait as u64)).await;
+                    continue;
                 }
+                info!(from=?custody.keys[from], to=?key, "Custodian Went Quiet, Taking Over");
+                publish(
+                    db,
+                    keypair,
+                    secp,
+                    checkpoints,
+                    BroadcastByHost::TakeOver {
+                        from: custody.keys[from],
+                        last: last.canonicalized_hash_ref(),
+                    },
+                )
+                .await?;
             }
+        }
+    }
+}
 
-            let slice = to_sequence.make_contiguous();
-            shuffle_slice(slice);
-
-            // schedules one message per chain in a randomized round-robin,
-            // balancing fairness and randomization.
-            to_sequence.as_mut_slices().0.sort_by_key(|k| k.0);
-
-            let msg = Channelized {
-                data: BroadcastByHost::Sequence(to_sequence.iter().map(|k| k.1).collect()),
-                channel: "default".into(),
-            };
-            {
-                let mut handle = db.get_handle_all().await;
-                // TODO: Run a tipcache
-
-                // try to insert and handle
-                let keypair = keypair;
-                let secp = secp.clone();
-                spawn_blocking(move || {
-                    handle
-                .retry_insert_authenticated_envelope_atomic::<Channelized<BroadcastByHost>, _, _>(
-                    msg,
-                    &keypair,
-                    &secp,
-                    None,
-                    TipControl::GroupsOnly,
-                )
+/// Whether a backup behind the key at `position` took over from it. `seen`
+/// is how many TakeOvers naming the key were found last time; custody is only
+/// followed again when a new one turns up. Returns how many there are now.
+async fn lost_custody(
+    db: &MsgDB,
+    custody: &Custody,
+    position: usize,
+    seen: usize,
+) -> Result<(usize, bool), Box<dyn Error + Send + Sync>> {
+    let key = custody.keys[position];
+    let host = custody.keys[0];
+    let behind = custody.keys[position + 1..].to_vec();
+    if behind.is_empty() {
+        return Ok((seen, false));
+    }
+    let handle = db.get_handle_read().await;
+    spawn_blocking(move || -> Result<_, Box<dyn Error + Send + Sync>> {
+        let mut takeovers = 0;
+        for k in &behind {
+            let chain: Vec<HostEnvelope> = handle.load_all_messages_for_user_by_key_connected(k)?;
+            takeovers += chain
+                .iter()
+                .filter(|e| match e.msg().data {
+                    BroadcastByHost::TakeOver { from, .. } => from == key,
+                    _ => false,
                 })
-                .await??;
+                .count();
+        }
+        if takeovers == seen {
+            return Ok((takeovers, false));
+        }
+        let (custody, host_chain, backups) = custody::load_chains(&handle, host)?;
+        let lost = custody
+            .follow(&host_chain, &backups)
+            .last()
+            .map(|last| custody.standing(position, last) != Standing::Custodian)
+            .unwrap_or_default();
+        Ok((takeovers, lost))
+    })
+    .await?
+}
+
+/// Subscribes the key's chain to a group of the game's players, so that a
+/// backup that took over fetches their moves as the host did. Players whose
+/// chains are not here yet are left out.
+async fn subscribe_to_players(
+    db: &MsgDB,
+    key: XOnlyPublicKey,
+    followed: &[HostEnvelope],
+) -> Result<(), Box<dyn Error + Send + Sync>> {
+    let players = match followed.first().map(|e| &e.msg().data) {
+        Some(BroadcastByHost::GameSetup(setup)) => setup.players.clone(),
+        _ => return Ok(()),
+    };
+    let handle = db.get_handle_all().await;
+    spawn_blocking(move || -> Result<_, Box<dyn Error + Send + Sync>> {
+        let genesis = handle
+            .get_message_at_height_for_user::<Channelized<BroadcastByHost>>(key, 0)?
+            .ok_or("Backup Chain Missing Genesis")?
+            .canonicalized_hash_ref();
+        if !handle
+            .get_all_chain_commit_groups_for_chain(genesis)?
+            .is_empty()
+        {
+            return Ok(());
+        }
+        let (_, group_id) = handle.new_chain_commit_group(None)?;
+        handle.add_subscriber_to_chain_commit_group(group_id, genesis)?;
+        for player in players {
+            let player = XOnlyPublicKey::from_str(&player)?;
+            match handle.get_tip_for_user_by_key::<WrappedJson>(player) {
+                Ok(tip) => {
+                    handle.add_member_to_chain_commit_group(group_id, tip.get_genesis_hash())?
+                }
+                Err(e) => {
+                    warn!(?player, error=?e, "Player's Chain Not Found, Not Sequencing It")
+                }
             }
         }
-        tokio::time::sleep(Duration::from_secs(1)).await;
-    }
+        Ok(())
+    })
+    .await?
 }
 
-fn shuffle_slice<T>(to_sequence: &mut [T]) {
-    let mut rng = rand::thread_rng();
-    SliceRandom::shuffle(to_sequence, &mut rng);
+/// Signs and inserts a message on the host's chain, checkpointing the latest
+/// Bitcoin blocks the host has seen, and returns its height
+async fn publish(
+    db: &MsgDB,
+    keypair: KeyPair,
+    secp: &Arc<Secp256k1<All>>,
+    checkpoints: &BitcoinCheckPointCache,
+    data: BroadcastByHost,
+) -> Result<i64, Box<dyn Error + Send + Sync>> {
+    let msg = Channelized {
+        data,
+        channel: "default".into(),
+    };
+    let tips = checkpoints.read_cache().await;
+    let mut handle = db.get_handle_all().await;
+    let secp = secp.clone();
+    spawn_blocking(move || {
+        handle.retry_insert_authenticated_envelope_atomic::<Channelized<BroadcastByHost>, _, _>(
+            msg,
+            &keypair,
+            &secp,
+            Some(tips),
+            TipControl::GroupsOnly,
+        )?;
+        let tip = handle.get_tip_for_user_by_key::<Channelized<BroadcastByHost>>(
+            keypair.x_only_public_key().0,
+        )?;
+        Ok(tip.header().height())
+    })
+    .await?
 }
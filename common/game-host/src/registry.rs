@@ -0,0 +1,655 @@
+// Copyright Judica, Inc 2022
+//
+// This Source Code Form is subject to the terms of the Mozilla Public
+//  License, v. 2.0. If a copy of the MPL was not distributed with this
+//  file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+//! Module Registry tracks every game this host runs, from lobby to settlement.
+//!
+//! Every change to a game is appended to the event log as a [`RegistryEvent`]
+//! before it is applied, and the registry is rebuilt by replaying them on
+//! startup, so lobbies and paused or closed games survive restarts.
+
+use crate::globals::Globals;
+use attest_messages::GenericEnvelope;
+use axum::http::StatusCode;
+use bitcoincore_rpc_async::RpcApi;
+use event_log::{
+    connection::EventLog,
+    db_handle::accessors::{
+        occurrence::{ApplicationTypeID, ToOccurrence},
+        occurrence_group::OccurrenceGroupID,
+    },
+};
+use game_host_messages::{AddPlayerError, GameLifecycle, GameSummary, JoinCode};
+use game_player_messages::ParticipantAction;
+use mine_with_friends_board::{
+    game::{
+        clock::{ClockParams, ClockSource},
+        game_move::GameMove,
+        GameSetup,
+    },
+    sanitize::Unsanitized,
+    MoveEnvelope,
+};
+use ruma_serde::CanonicalJsonValue;
+use sapio_bitcoin::{OutPoint, XOnlyPublicKey};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::HashMap,
+    error::Error,
+    fmt::{Debug, Display},
+    sync::Arc,
+    time::Duration,
+};
+use tokio::sync::Mutex;
+use tracing::{info, warn};
+
+/// The most players a lobby can be configured to take
+pub const MAX_PLAYERS: u16 = 64;
+/// How many players a lobby takes if the creator did not say
+pub const DEFAULT_MAX_PLAYERS: u16 = 10;
+/// The longest a game can be configured to run for
+pub const MAX_DURATION_MINUTES: u16 = 300;
+
+/// How games keep time if the lobby's creator did not say. Live clients
+/// attest to the sequencer's time, so players can't fast-forward the game.
+fn default_clock() -> ClockParams {
+    ClockParams {
+        source: ClockSource::Sequencer,
+        ..Default::default()
+    }
+}
+
+/// The event log group the registry is persisted in
+const REGISTRY_GROUP_KEY: &str = "game_registry";
+
+pub type Registry = Arc<Mutex<GameRegistry>>;
+
+#[derive(Clone)]
+enum GameStartingState {
+    AddingPlayers(Vec<GenericEnvelope<ParticipantAction>>),
+    WaitingForSetup(Vec<GenericEnvelope<ParticipantAction>>),
+    Setup(Vec<GenericEnvelope<ParticipantAction>>, GameSetup),
+}
+
+impl GameStartingState {
+    fn new() -> GameStartingState {
+        GameStartingState::AddingPlayers(vec![])
+    }
+    fn players(&self) -> &Vec<GenericEnvelope<ParticipantAction>> {
+        match self {
+            GameStartingState::AddingPlayers(v)
+            | GameStartingState::WaitingForSetup(v)
+            | GameStartingState::Setup(v, _) => v,
+        }
+    }
+    fn add_player(
+        &mut self,
+        p: GenericEnvelope<ParticipantAction>,
+        max_players: u16,
+    ) -> Result<(), AddPlayerError> {
+        if p.get_genesis_hash() != p.canonicalized_hash_ref() {
+            return Err(AddPlayerError::NotGenesisEnvelope);
+        }
+        match p.msg() {
+            ParticipantAction::MoveEnvelope(MoveEnvelope {
+                d: Unsanitized(GameMove::Heartbeat(_)),
+                sequence: _,
+                time_millis: _,
+            }) => {}
+            _ => return Err(AddPlayerError::WrongFirstMessage),
+        };
+        match self {
+            GameStartingState::AddingPlayers(ref mut v) => {
+                v.push(p);
+                if v.len() >= max_players as usize {
+                    let mut clr = vec![];
+                    std::mem::swap(&mut clr, v);
+                    *self = GameStartingState::WaitingForSetup(clr);
+                }
+                Ok(())
+            }
+            GameStartingState::Setup(_, _) => Err(AddPlayerError::AlreadySetup),
+            GameStartingState::WaitingForSetup(_) => Err(AddPlayerError::NoMorePlayers),
+        }
+    }
+    fn finalize_setup(
+        &mut self,
+        finish_time: Duration,
+        start_amount: u64,
+        clock: ClockParams,
+    ) -> Result<(), AddPlayerError> {
+        match self {
+            GameStartingState::AddingPlayers(v) | GameStartingState::WaitingForSetup(v) => {
+                let players = v.iter().map(|i| i.header().key().to_string()).collect();
+                let game = GameSetup {
+                    players,
+                    start_amount,
+                    // bounds checked by MAX_DURATION_MINUTES
+                    finish_time: finish_time.as_millis() as u64,
+                    subsidy: Default::default(),
+                    clock,
+                    failover: Default::default(),
+                };
+
+                let mut clr = vec![];
+                std::mem::swap(&mut clr, v);
+                *self = GameStartingState::Setup(clr, game);
+                Ok(())
+            }
+            GameStartingState::Setup(_, _) => Err(AddPlayerError::AlreadySetup),
+        }
+    }
+    /// Undoes [`GameStartingState::finalize_setup`], keeping the players
+    fn reopen(&mut self, max_players: u16) {
+        if let GameStartingState::Setup(v, _) = self {
+            let v = std::mem::take(v);
+            *self = if v.len() >= max_players as usize {
+                GameStartingState::WaitingForSetup(v)
+            } else {
+                GameStartingState::AddingPlayers(v)
+            };
+        }
+    }
+}
+
+/// A change to one game in the registry, as persisted in the event log
+#[derive(Serialize, Deserialize, Debug)]
+pub enum RegistryEvent {
+    Created {
+        code: JoinCode,
+        admin: JoinCode,
+        max_players: u16,
+        duration_minutes: u16,
+        time: i64,
+        /// Missing for games created before the clock could be chosen
+        #[serde(default)]
+        clock: ClockParams,
+    },
+    PlayerAdded {
+        code: JoinCode,
+        envelope: GenericEnvelope<ParticipantAction>,
+    },
+    Started {
+        code: JoinCode,
+        setup: GameSetup,
+        sequencer_key: XOnlyPublicKey,
+        funding: OutPoint,
+        time: i64,
+    },
+    Finished {
+        code: JoinCode,
+    },
+    Settled {
+        code: JoinCode,
+    },
+    Paused {
+        code: JoinCode,
+        paused: bool,
+    },
+    Closed {
+        code: JoinCode,
+    },
+}
+
+impl RegistryEvent {
+    fn code(&self) -> JoinCode {
+        match self {
+            RegistryEvent::Created { code, .. }
+            | RegistryEvent::PlayerAdded { code, .. }
+            | RegistryEvent::Started { code, .. }
+            | RegistryEvent::Finished { code }
+            | RegistryEvent::Settled { code }
+            | RegistryEvent::Paused { code, .. }
+            | RegistryEvent::Closed { code } => *code,
+        }
+    }
+}
+
+impl ToOccurrence for RegistryEvent {
+    fn to_data(&self) -> CanonicalJsonValue {
+        ruma_serde::to_canonical_value(self).unwrap()
+    }
+    fn stable_typeid() -> ApplicationTypeID {
+        ApplicationTypeID::from_inner("GameRegistryEvent")
+    }
+    fn unique_tag(&self) -> Option<String> {
+        None
+    }
+}
+
+#[derive(Debug)]
+pub enum RegistryError {
+    NoSuchGame,
+    WrongPasscode,
+    Paused,
+    NotAllowed(GameLifecycle),
+    InvalidConfig(&'static str),
+    AddPlayer(AddPlayerError),
+    Database(String),
+}
+impl Display for RegistryError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        Debug::fmt(self, f)
+    }
+}
+impl Error for RegistryError {}
+
+impl RegistryError {
+    pub(crate) fn status(&self) -> StatusCode {
+        match self {
+            RegistryError::NoSuchGame => StatusCode::NOT_FOUND,
+            RegistryError::WrongPasscode => StatusCode::UNAUTHORIZED,
+            RegistryError::Paused | RegistryError::NotAllowed(_) => StatusCode::CONFLICT,
+            RegistryError::InvalidConfig(_) => StatusCode::BAD_REQUEST,
+            RegistryError::AddPlayer(_) | RegistryError::Database(_) => {
+                StatusCode::INTERNAL_SERVER_ERROR
+            }
+        }
+    }
+}
+
+impl From<AddPlayerError> for RegistryError {
+    fn from(e: AddPlayerError) -> Self {
+        RegistryError::AddPlayer(e)
+    }
+}
+
+/// Everything the registry knows about one game
+#[derive(Clone)]
+struct RegisteredGame {
+    admin: JoinCode,
+    max_players: u16,
+    duration_minutes: u16,
+    created_time: i64,
+    clock: ClockParams,
+    lobby: GameStartingState,
+    lifecycle: GameLifecycle,
+    paused: bool,
+    started: Option<(XOnlyPublicKey, OutPoint, i64)>,
+}
+
+impl RegisteredGame {
+    fn duration(&self) -> Duration {
+        Duration::from_secs(self.duration_minutes as u64 * 60)
+    }
+    fn check_not_paused(&self) -> Result<(), RegistryError> {
+        if self.paused {
+            Err(RegistryError::Paused)
+        } else {
+            Ok(())
+        }
+    }
+    fn check_lifecycle(&self, allowed: &[GameLifecycle]) -> Result<(), RegistryError> {
+        if allowed.contains(&self.lifecycle) {
+            Ok(())
+        } else {
+            Err(RegistryError::NotAllowed(self.lifecycle))
+        }
+    }
+    /// Checks an event against the game's current state and applies it.
+    ///
+    /// Used both for new events, before they are recorded, and when replaying
+    /// recorded events on startup.
+    fn apply(&mut self, event: &RegistryEvent) -> Result<(), RegistryError> {
+        match event {
+            RegistryEvent::Created { .. } => return Err(RegistryError::NotAllowed(self.lifecycle)),
+            RegistryEvent::PlayerAdded { envelope, .. } => {
+                self.check_lifecycle(&[GameLifecycle::Lobby])?;
+                self.check_not_paused()?;
+                self.lobby.add_player(envelope.clone(), self.max_players)?;
+            }
+            RegistryEvent::Started {
+                setup,
+                sequencer_key,
+                funding,
+                time,
+                ..
+            } => {
+                self.check_lifecycle(&[GameLifecycle::Lobby])?;
+                self.lobby = GameStartingState::Setup(self.lobby.players().clone(), setup.clone());
+                self.lifecycle = GameLifecycle::Running;
+                self.started = Some((*sequencer_key, *funding, *time));
+            }
+            RegistryEvent::Finished { .. } => {
+                self.check_lifecycle(&[GameLifecycle::Running])?;
+                self.lifecycle = GameLifecycle::Finished;
+            }
+            RegistryEvent::Settled { .. } => {
+                self.check_lifecycle(&[GameLifecycle::Finished])?;
+                self.lifecycle = GameLifecycle::Settled;
+            }
+            RegistryEvent::Paused { paused, .. } => {
+                self.check_lifecycle(&[
+                    GameLifecycle::Lobby,
+                    GameLifecycle::Running,
+                    GameLifecycle::Finished,
+                ])?;
+                self.paused = *paused;
+            }
+            RegistryEvent::Closed { .. } => {
+                self.check_lifecycle(&[
+                    GameLifecycle::Lobby,
+                    GameLifecycle::Running,
+                    GameLifecycle::Finished,
+                ])?;
+                self.lifecycle = GameLifecycle::Closed;
+            }
+        }
+        Ok(())
+    }
+    fn summary(&self) -> GameSummary {
+        GameSummary {
+            lifecycle: self.lifecycle,
+            paused: self.paused,
+            max_players: self.max_players,
+            duration_minutes: self.duration_minutes,
+            players: self.lobby.players().len(),
+            sequencer_key: self.started.map(|(k, _, _)| k),
+            created_time: self.created_time,
+            started_time: self.started.map(|(_, _, t)| t),
+        }
+    }
+}
+
+pub struct GameRegistry {
+    games: HashMap<JoinCode, RegisteredGame>,
+    evlog: EventLog,
+    group: OccurrenceGroupID,
+}
+
+impl GameRegistry {
+    /// Loads the registry from the event log, replaying every recorded event
+    pub async fn load(evlog: EventLog) -> Result<GameRegistry, Box<dyn Error + Send + Sync>> {
+        let (group, occurrences) = {
+            let accessor = evlog.get_accessor().await;
+            let key = REGISTRY_GROUP_KEY.to_owned();
+            let group = accessor
+                .get_occurrence_group_by_key(&key)
+                .or_else(|_| accessor.insert_new_occurrence_group(&key))
+                .or_else(|_| accessor.get_occurrence_group_by_key(&key))?;
+            (group, accessor.get_occurrences_for_group(group)?)
+        };
+        let mut registry = GameRegistry {
+            games: Default::default(),
+            evlog,
+            group,
+        };
+        for (id, occurrence) in occurrences {
+            let event = RegistryEvent::from_occurrence(occurrence)?;
+            if let Err(e) = registry.apply(&event) {
+                // only possible if the log was written by a different version
+                warn!(?id, ?e, "Skipping Registry Event that does not Apply");
+            }
+        }
+        info!(n = registry.games.len(), "Loaded Game Registry");
+        Ok(registry)
+    }
+
+    fn apply(&mut self, event: &RegistryEvent) -> Result<(), RegistryError> {
+        match event {
+            RegistryEvent::Created {
+                code,
+                admin,
+                max_players,
+                duration_minutes,
+                time,
+                clock,
+            } => {
+                if self.games.contains_key(code) {
+                    return Err(RegistryError::NotAllowed(GameLifecycle::Lobby));
+                }
+                self.games.insert(
+                    *code,
+                    RegisteredGame {
+                        admin: *admin,
+                        max_players: *max_players,
+                        duration_minutes: *duration_minutes,
+                        created_time: *time,
+                        clock: clock.clone(),
+                        lobby: GameStartingState::new(),
+                        lifecycle: GameLifecycle::Lobby,
+                        paused: false,
+                        started: None,
+                    },
+                );
+            }
+            _ => self
+                .games
+                .get_mut(&event.code())
+                .ok_or(RegistryError::NoSuchGame)?
+                .apply(event)?,
+        }
+        Ok(())
+    }
+
+    /// Checks an event applies, records it in the event log, and only then
+    /// applies it, so the registry never runs ahead of what was persisted.
+    async fn record(&mut self, event: RegistryEvent) -> Result<(), RegistryError> {
+        match &event {
+            RegistryEvent::Created { code, .. } => {
+                if self.games.contains_key(code) {
+                    return Err(RegistryError::NotAllowed(GameLifecycle::Lobby));
+                }
+            }
+            _ => {
+                self.games
+                    .get(&event.code())
+                    .ok_or(RegistryError::NoSuchGame)?
+                    .clone()
+                    .apply(&event)?;
+            }
+        }
+        self.evlog
+            .get_accessor()
+            .await
+            .insert_new_occurrence_now_from(self.group, &event)
+            .map_err(|e| RegistryError::Database(e.to_string()))?
+            .map_err(|e| RegistryError::Database(format!("{:?}", e)))?;
+        self.apply(&event)
+    }
+
+    fn get_admin(
+        &self,
+        code: &JoinCode,
+        passcode: &JoinCode,
+    ) -> Result<&RegisteredGame, RegistryError> {
+        let game = self.games.get(code).ok_or(RegistryError::NoSuchGame)?;
+        if game.admin != *passcode {
+            return Err(RegistryError::WrongPasscode);
+        }
+        Ok(game)
+    }
+
+    /// Opens a new lobby, returning the admin passcode and the join code
+    pub async fn add_new_game(
+        &mut self,
+        duration_minutes: u16,
+        max_players: Option<u16>,
+        clock: Option<ClockParams>,
+    ) -> Result<(JoinCode, JoinCode), RegistryError> {
+        if duration_minutes > MAX_DURATION_MINUTES {
+            return Err(RegistryError::InvalidConfig(
+                "Duration too long, must be <= 300 minutes",
+            ));
+        }
+        let max_players = max_players.unwrap_or(DEFAULT_MAX_PLAYERS);
+        if max_players == 0 || max_players > MAX_PLAYERS {
+            return Err(RegistryError::InvalidConfig(
+                "Player limit must be between 1 and 64",
+            ));
+        }
+        let code = JoinCode::default();
+        let admin = JoinCode::default();
+        self.record(RegistryEvent::Created {
+            code,
+            admin,
+            max_players,
+            duration_minutes,
+            time: attest_util::now(),
+            clock: clock.unwrap_or_else(default_clock),
+        })
+        .await?;
+        Ok((admin, code))
+    }
+
+    pub async fn add_player(
+        &mut self,
+        code: JoinCode,
+        envelope: GenericEnvelope<ParticipantAction>,
+    ) -> Result<(), RegistryError> {
+        self.record(RegistryEvent::PlayerAdded { code, envelope })
+            .await
+    }
+
+    /// Closes the lobby to new players and fixes the game's setup.
+    ///
+    /// This is not persisted: until [`GameRegistry::start`] records the game
+    /// as running, a restart puts the game back in its lobby.
+    pub fn finalize_setup(
+        &mut self,
+        code: JoinCode,
+        passcode: JoinCode,
+        start_amount: u64,
+    ) -> Result<(Vec<GenericEnvelope<ParticipantAction>>, GameSetup), RegistryError> {
+        self.get_admin(&code, &passcode)?;
+        let game = self.games.get_mut(&code).ok_or(RegistryError::NoSuchGame)?;
+        game.check_lifecycle(&[GameLifecycle::Lobby])?;
+        game.check_not_paused()?;
+        let duration = game.duration();
+        let clock = game.clock.clone();
+        game.lobby.finalize_setup(duration, start_amount, clock)?;
+        match &game.lobby {
+            GameStartingState::Setup(envelopes, setup) => Ok((envelopes.clone(), setup.clone())),
+            _ => Err(RegistryError::AddPlayer(AddPlayerError::AlreadySetup)),
+        }
+    }
+
+    /// Puts a game whose setup was finalized, but which was never started,
+    /// back in its lobby, as a restart would
+    pub fn reopen_setup(&mut self, code: JoinCode) {
+        if let Some(game) = self.games.get_mut(&code) {
+            if game.lifecycle == GameLifecycle::Lobby {
+                game.lobby.reopen(game.max_players);
+            }
+        }
+    }
+
+    /// Records that a game's sequencer is running and its contract was funded
+    pub async fn start(
+        &mut self,
+        code: JoinCode,
+        setup: GameSetup,
+        sequencer_key: XOnlyPublicKey,
+        funding: OutPoint,
+    ) -> Result<(), RegistryError> {
+        self.record(RegistryEvent::Started {
+            code,
+            setup,
+            sequencer_key,
+            funding,
+            time: attest_util::now(),
+        })
+        .await
+    }
+
+    pub async fn pause(
+        &mut self,
+        code: JoinCode,
+        passcode: JoinCode,
+        paused: bool,
+    ) -> Result<(), RegistryError> {
+        self.get_admin(&code, &passcode)?;
+        self.record(RegistryEvent::Paused { code, paused }).await
+    }
+
+    pub async fn close(&mut self, code: JoinCode, passcode: JoinCode) -> Result<(), RegistryError> {
+        self.get_admin(&code, &passcode)?;
+        self.record(RegistryEvent::Closed { code }).await
+    }
+
+    pub fn list(&self) -> Vec<GameSummary> {
+        self.games.values().map(|game| game.summary()).collect()
+    }
+
+    /// Whether the sequencer for `key` should be running.
+    ///
+    /// Keys the registry does not know of, e.g. chains made directly through
+    /// `/attestation_chain/new`, are always sequenced.
+    pub fn should_sequence(&self, key: &XOnlyPublicKey) -> bool {
+        self.games
+            .values()
+            .find(|g| g.started.map(|(k, _, _)| k).as_ref() == Some(key))
+            .map(|g| {
+                !g.paused
+                    && matches!(
+                        g.lifecycle,
+                        GameLifecycle::Running | GameLifecycle::Finished
+                    )
+            })
+            .unwrap_or(true)
+    }
+}
+
+/// Moves games along their lifecycle as time passes: running games finish once
+/// their duration is over, and finished games settle once the output funding
+/// their contract is spent.
+pub async fn monitor(
+    registry: Registry,
+    globals: Globals,
+) -> Result<(), Box<dyn Error + Send + Sync>> {
+    loop {
+        let (running, finished): (Vec<_>, Vec<_>) = {
+            let registry = registry.lock().await;
+            let now = attest_util::now();
+            registry
+                .games
+                .iter()
+                .filter_map(|(code, g)| {
+                    let (_, funding, started) = g.started?;
+                    match g.lifecycle {
+                        GameLifecycle::Running
+                            if started + g.duration().as_millis() as i64 <= now =>
+                        {
+                            Some((*code, funding, true))
+                        }
+                        GameLifecycle::Finished => Some((*code, funding, false)),
+                        _ => None,
+                    }
+                })
+                .partition(|(_, _, running)| *running)
+        };
+        for (code, _, _) in running {
+            if let Err(e) = registry
+                .lock()
+                .await
+                .record(RegistryEvent::Finished { code })
+                .await
+            {
+                warn!(?code, ?e, "Could not Mark Game Finished");
+            }
+        }
+        for (code, funding, _) in finished {
+            match globals
+                .bitcoin_rpc
+                .get_tx_out(&funding.txid, funding.vout, Some(true))
+                .await
+            {
+                Ok(None) => {
+                    if let Err(e) = registry
+                        .lock()
+                        .await
+                        .record(RegistryEvent::Settled { code })
+                        .await
+                    {
+                        warn!(?code, ?e, "Could not Mark Game Settled");
+                    }
+                }
+                Ok(Some(_)) => {}
+                Err(e) => warn!(?code, ?e, "Could not Check Funding Output"),
+            }
+        }
+        tokio::time::sleep(Duration::from_secs(60)).await;
+    }
+}
@@ -0,0 +1,72 @@
+// Copyright Judica, Inc 2022
+//
+// This Source Code Form is subject to the terms of the Mozilla Public
+//  License, v. 2.0. If a copy of the MPL was not distributed with this
+//  file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+//! Module Status keeps track of how sequencing each game is going.
+//!
+//! A game's sequencing task reports the issues it carries on past here rather
+//! than stopping, and the task creator records when a task stops and why, so
+//! an admin can see which games need attention without reading the logs.
+
+use game_host_messages::{SequencingIssue, SequencingStatus};
+use sapio_bitcoin::XOnlyPublicKey;
+use std::{collections::BTreeMap, sync::Arc};
+use tokio::sync::Mutex;
+
+pub type StatusBoard = Arc<Mutex<SequencingStatuses>>;
+
+#[derive(Default)]
+pub struct SequencingStatuses {
+    games: BTreeMap<XOnlyPublicKey, SequencingStatus>,
+}
+
+impl SequencingStatuses {
+    fn get_mut(&mut self, key: XOnlyPublicKey) -> &mut SequencingStatus {
+        self.games.entry(key).or_insert_with(|| SequencingStatus {
+            sequencer_key: key,
+            running: false,
+            restarts: 0,
+            last_error: None,
+            sequenced_height: None,
+            issues: vec![],
+        })
+    }
+    /// A task started sequencing the game. Its issues are found again as it
+    /// catches up, so the previous task's are cleared.
+    pub fn started(&mut self, key: XOnlyPublicKey) {
+        let status = self.get_mut(key);
+        status.running = true;
+        status.issues.clear();
+    }
+    /// The game's task stopped, and will be started again unless the game was
+    /// paused or closed
+    pub fn stopped(&mut self, key: XOnlyPublicKey, error: Option<String>) {
+        let status = self.get_mut(key);
+        status.running = false;
+        status.restarts += 1;
+        if error.is_some() {
+            status.last_error = error;
+        }
+    }
+    /// The game's task was stopped because the game was paused or closed
+    pub fn halted(&mut self, key: XOnlyPublicKey) {
+        self.get_mut(key).running = false;
+    }
+    pub fn sequenced(&mut self, key: XOnlyPublicKey, height: i64) {
+        self.get_mut(key).sequenced_height = Some(height);
+    }
+    pub fn issue(&mut self, key: XOnlyPublicKey, issue: SequencingIssue) {
+        let status = self.get_mut(key);
+        if !status.issues.contains(&issue) {
+            status.issues.push(issue);
+        }
+    }
+    pub fn resolved(&mut self, key: XOnlyPublicKey, issue: &SequencingIssue) {
+        self.get_mut(key).issues.retain(|i| i != issue);
+    }
+    pub fn list(&self) -> Vec<SequencingStatus> {
+        self.games.values().cloned().collect()
+    }
+}
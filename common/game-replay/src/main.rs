@@ -0,0 +1,119 @@
+// Copyright Judica, Inc 2022
+//
+// This Source Code Form is subject to the terms of the Mozilla Public
+//  License, v. 2.0. If a copy of the MPL was not distributed with this
+//  file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+//! Replays a finished game for post-game analysis.
+//!
+//! The game is read either from the attest database of a node that followed
+//! it, by the game host's sequencer key, or from an exported trace (the JSON a
+//! litigator snapshots, as accepted for an `ExtractedMoveEnvelopes`). Every
+//! move is played through a fresh GameBoard and the state after each one is
+//! written out as a timeline, in JSON and as CSV tables.
+//!
+//! ```text
+//! game-replay db <app instance> <sequencer key> [options]
+//! game-replay file <trace.json> [options]
+//!
+//! options:
+//!     --out <prefix>     where to write the output (default: replay)
+//!     --bounty <sats>    the pot to compute the close distribution for
+//!                        (default: 100000000)
+//!     --prefix <path>    the attest database's prefix, for db
+//! ```
+use attest_database::setup_db;
+use game_player_messages::ParticipantAction;
+use game_sequencer::{custody, UnauthenticatedRawSequencer};
+use sapio_bitcoin::XOnlyPublicKey;
+use std::error::Error;
+use std::path::PathBuf;
+use std::str::FromStr;
+use tokio::task::spawn_blocking;
+use tracing::info;
+
+mod timeline;
+
+const USAGE: &str = "usage: game-replay (db <app instance> <sequencer key> | file <trace.json>) \
+                     [--out <prefix>] [--bounty <sats>] [--prefix <path>]";
+
+enum Source {
+    Database {
+        app_instance: String,
+        sequencer_key: XOnlyPublicKey,
+    },
+    File(PathBuf),
+}
+
+struct Args {
+    source: Source,
+    out: PathBuf,
+    bounty: u64,
+    prefix: Option<PathBuf>,
+}
+
+fn parse_args() -> Result<Args, Box<dyn Error + Send + Sync>> {
+    let mut args = std::env::args().skip(1);
+    let source = match args.next().as_deref() {
+        Some("db") => Source::Database {
+            app_instance: args.next().ok_or(USAGE)?,
+            sequencer_key: XOnlyPublicKey::from_str(&args.next().ok_or(USAGE)?)?,
+        },
+        Some("file") => Source::File(args.next().ok_or(USAGE)?.into()),
+        _ => Err(USAGE)?,
+    };
+    let mut parsed = Args {
+        source,
+        out: "replay".into(),
+        bounty: 100_000_000,
+        prefix: None,
+    };
+    while let Some(flag) = args.next() {
+        let value = args.next().ok_or(USAGE)?;
+        match flag.as_str() {
+            "--out" => parsed.out = value.into(),
+            "--bounty" => parsed.bounty = value.parse()?,
+            "--prefix" => parsed.prefix = Some(value.into()),
+            _ => Err(USAGE)?,
+        }
+    }
+    Ok(parsed)
+}
+
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn Error + Send + Sync>> {
+    tracing_subscriber::fmt::init();
+    let args = parse_args()?;
+    let trace = match args.source {
+        Source::Database {
+            app_instance,
+            sequencer_key,
+        } => load_from_db(&app_instance, args.prefix, sequencer_key).await?,
+        Source::File(path) => serde_json::from_slice(&tokio::fs::read(path).await?)?,
+    };
+    let timeline = timeline::Timeline::replay(trace, args.bounty)?;
+    info!(
+        moves = timeline.steps.len(),
+        finish_reason = ?timeline.finish_reason,
+        "Replayed Game"
+    );
+    timeline.write(&args.out)?;
+    Ok(())
+}
+
+/// Collects the sequencer's chain and every move it sequenced from the
+/// database, in the same form a trace is exported in
+async fn load_from_db(
+    app_instance: &str,
+    prefix: Option<PathBuf>,
+    sequencer_key: XOnlyPublicKey,
+) -> Result<UnauthenticatedRawSequencer<ParticipantAction>, Box<dyn Error + Send + Sync>> {
+    let db = setup_db(&format!("attestations.{}", app_instance), prefix)
+        .await
+        .map_err(|e| format!("DB Setup Failed: {:?}", e))?;
+    let handle = db.get_handle_read().await;
+    let sequenced = spawn_blocking(move || custody::load_sequenced(&handle, sequencer_key))
+        .await?
+        .map_err(|e| format!("Database Fetch Error: {}", e))?;
+    Ok(sequenced.into())
+}
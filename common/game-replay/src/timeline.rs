@@ -0,0 +1,258 @@
+// Copyright Judica, Inc 2022
+//
+// This Source Code Form is subject to the terms of the Mozilla Public
+//  License, v. 2.0. If a copy of the MPL was not distributed with this
+//  file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+use game_host_messages::BroadcastByHost;
+use game_player_messages::ParticipantAction;
+use game_sequencer::{
+    ExtractedMoveEnvelopes, OfflineSequencer, RawSequencer, UnauthenticatedRawSequencer,
+};
+use mine_with_friends_board::{
+    entity::EntityID,
+    game::{FinishReason, GameBoard, GameSetup, MoveRejectReason},
+    tokens::token_swap::UXMaterialsPriceData,
+    MoveEnvelope,
+};
+use serde::Serialize;
+use std::borrow::Cow;
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::ffi::OsString;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+use tracing::warn;
+
+/// The state of the game just after one move was played
+#[derive(Serialize)]
+pub struct Step {
+    pub index: usize,
+    /// The key of the player who made the move
+    pub player: String,
+    pub envelope: MoveEnvelope,
+    pub elapsed_time: u64,
+    pub rejected: Option<MoveRejectReason>,
+    /// Every player's token balances, by player key
+    pub balances: BTreeMap<String, Vec<(String, u128)>>,
+    pub hashrate_share: BTreeMap<EntityID, (u128, u128)>,
+    pub prices: Vec<UXMaterialsPriceData>,
+}
+
+/// A game, replayed move by move
+#[derive(Serialize)]
+pub struct Timeline {
+    pub host_key: String,
+    pub setup: GameSetup,
+    pub steps: Vec<Step>,
+    /// None if the trace ends before the game does
+    pub finish_reason: Option<FinishReason>,
+    pub close_distribution: Option<Vec<(String, u64)>>,
+}
+
+impl Timeline {
+    /// Replays a trace, the same way ExtractedMoveEnvelopes::replay does, but
+    /// recording the state after every move
+    pub fn replay(
+        raw: UnauthenticatedRawSequencer<ParticipantAction>,
+        bounty: u64,
+    ) -> Result<Self, Box<dyn Error + Send + Sync>> {
+        let (host_key, setup) = raw
+            .sequencer_envelopes
+            .iter()
+            .find_map(|e| match &e.msg().data {
+                BroadcastByHost::GameSetup(g) => Some((e.header().key(), g.clone())),
+                _ => None,
+            })
+            .ok_or("Trace has no GameSetup")?;
+        let raw = RawSequencer::try_from(raw)
+            .map_err(|e| format!("Trace Failed to Authenticate: {:?}", e))?;
+        let ExtractedMoveEnvelopes(moves, attested, _) =
+            ExtractedMoveEnvelopes::try_from(OfflineSequencer::try_from(raw)?)?;
+
+        let mut game = GameBoard::new(&setup);
+        let mut steps = Vec::with_capacity(moves.len());
+        let mut attested = attested.into_iter().peekable();
+        for (index, (envelope, pk)) in moves.into_iter().enumerate() {
+            while let Some((_, time, batch)) = attested.next_if(|(at, _, _)| *at <= index) {
+                if let Err(e) = game.attest_time(time, batch.as_ref()) {
+                    warn!(error=?e, "Sequencer Attested to a Bad Time");
+                }
+            }
+            let player = pk.to_string();
+            let rejected = game.play(envelope.clone(), player.clone()).err();
+            let finished = matches!(rejected, Some(MoveRejectReason::GameIsFinished(_)));
+            steps.push(Step {
+                index,
+                player,
+                envelope,
+                elapsed_time: game.current_time(),
+                rejected,
+                balances: setup
+                    .players
+                    .iter()
+                    .filter_map(|k| {
+                        let id = game.get_user_id(k)?;
+                        Some((k.clone(), game.get_user_token_balances(id)))
+                    })
+                    .collect(),
+                hashrate_share: game.get_user_hashrate_share(),
+                prices: game.get_ux_materials_prices(),
+            });
+            if finished {
+                break;
+            }
+        }
+        let finish_reason = game.game_is_finished();
+        let close_distribution = game
+            .get_close_distribution(bounty, host_key.to_string())
+            .ok();
+        Ok(Timeline {
+            host_key: host_key.to_string(),
+            setup,
+            steps,
+            finish_reason,
+            close_distribution,
+        })
+    }
+
+    /// Writes `<out>.json`, with everything, and a CSV table per series:
+    /// `<out>.moves.csv`, `<out>.balances.csv`, `<out>.hashrate.csv`,
+    /// `<out>.prices.csv` and `<out>.distribution.csv`
+    pub fn write(&self, out: &Path) -> Result<(), Box<dyn Error + Send + Sync>> {
+        let path = |suffix: &str| {
+            let mut p = OsString::from(out.as_os_str());
+            p.push(suffix);
+            PathBuf::from(p)
+        };
+        serde_json::to_writer_pretty(BufWriter::new(File::create(path(".json"))?), self)?;
+
+        write_csv(
+            &path(".moves.csv"),
+            &[
+                "step",
+                "elapsed_time",
+                "player",
+                "sequence",
+                "move",
+                "rejected",
+            ],
+            self.steps.iter().map(|s| {
+                vec![
+                    s.index.to_string(),
+                    s.elapsed_time.to_string(),
+                    s.player.clone(),
+                    s.envelope.sequence.to_string(),
+                    serde_json::to_string(&s.envelope.d).unwrap_or_default(),
+                    s.rejected
+                        .as_ref()
+                        .and_then(|r| serde_json::to_string(r).ok())
+                        .unwrap_or_default(),
+                ]
+            }),
+        )?;
+        write_csv(
+            &path(".balances.csv"),
+            &["step", "elapsed_time", "player", "token", "balance"],
+            self.steps.iter().flat_map(|s| {
+                s.balances.iter().flat_map(move |(player, balances)| {
+                    balances.iter().map(move |(token, balance)| {
+                        vec![
+                            s.index.to_string(),
+                            s.elapsed_time.to_string(),
+                            player.clone(),
+                            token.clone(),
+                            balance.to_string(),
+                        ]
+                    })
+                })
+            }),
+        )?;
+        write_csv(
+            &path(".hashrate.csv"),
+            &["step", "elapsed_time", "owner", "share"],
+            self.steps.iter().flat_map(|s| {
+                s.hashrate_share.iter().map(move |(owner, (n, d))| {
+                    vec![
+                        s.index.to_string(),
+                        s.elapsed_time.to_string(),
+                        String::from(*owner),
+                        (*n as f64 / *d as f64).to_string(),
+                    ]
+                })
+            }),
+        )?;
+        write_csv(
+            &path(".prices.csv"),
+            &[
+                "step",
+                "elapsed_time",
+                "asset_a",
+                "asset_b",
+                "mkt_qty_a",
+                "mkt_qty_b",
+            ],
+            self.steps.iter().flat_map(|s| {
+                s.prices.iter().map(move |p| {
+                    vec![
+                        s.index.to_string(),
+                        s.elapsed_time.to_string(),
+                        p.asset_a.clone(),
+                        p.asset_b.clone(),
+                        p.mkt_qty_a.to_string(),
+                        p.mkt_qty_b.to_string(),
+                    ]
+                })
+            }),
+        )?;
+        write_csv(
+            &path(".distribution.csv"),
+            &["key", "amount"],
+            self.close_distribution
+                .iter()
+                .flatten()
+                .map(|(key, amount)| vec![key.clone(), amount.to_string()]),
+        )?;
+        Ok(())
+    }
+}
+
+fn write_csv<I>(path: &Path, header: &[&str], rows: I) -> std::io::Result<()>
+where
+    I: Iterator<Item = Vec<String>>,
+{
+    let mut f = BufWriter::new(File::create(path)?);
+    writeln!(f, "{}", header.join(","))?;
+    for row in rows {
+        let row: Vec<_> = row.iter().map(|s| csv_field(s)).collect();
+        writeln!(f, "{}", row.join(","))?;
+    }
+    f.flush()
+}
+
+/// Quotes a field if it has anything a CSV reader would split on
+fn csv_field(s: &str) -> Cow<'_, str> {
+    if s.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", s.replace('"', "\"\"")).into()
+    } else {
+        s.into()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_csv_field() {
+        assert_eq!(csv_field("1234"), "1234");
+        assert_eq!(csv_field(""), "");
+        assert_eq!(csv_field("a,b"), "\"a,b\"");
+        assert_eq!(
+            csv_field("{\"trade\":{\"sell\":true}}"),
+            "\"{\"\"trade\"\":{\"\"sell\"\":true}}\""
+        );
+        assert_eq!(csv_field("two\nlines"), "\"two\nlines\"");
+    }
+}
@@ -0,0 +1,132 @@
+// Copyright Judica, Inc 2022
+//
+// This Source Code Form is subject to the terms of the Mozilla Public
+//  License, v. 2.0. If a copy of the MPL was not distributed with this
+//  file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+//! Which moves go in a batch.
+//!
+//! A batch holds at most [`MAX_BATCH_SIZE`] moves, and at most
+//! [`MAX_MOVES_PER_CHAIN`] from any one chain. Moves past either limit wait
+//! for a later batch. When more moves are waiting than fit, they are taken a
+//! round at a time, one per chain per round, and in the round that fills the
+//! batch the chains that had the fewest moves sequenced recently go first. A
+//! player sending far more than everyone else only delays their own moves.
+//!
+//! Anyone can check a batch is within the limits, but not which moves were
+//! waiting when it was made, so the rest is up to the host.
+
+use crate::fair_order::Orderable;
+use attest_messages::CanonicalEnvelopeHash;
+use std::collections::{BTreeMap, VecDeque};
+
+/// The most moves in one batch
+pub const MAX_BATCH_SIZE: usize = 256;
+/// The most moves from one chain in one batch
+pub const MAX_MOVES_PER_CHAIN: usize = 16;
+
+/// Whether a batch is within [`MAX_BATCH_SIZE`] and [`MAX_MOVES_PER_CHAIN`]
+pub fn within_limits(batch: &[Orderable]) -> bool {
+    let mut per_chain = BTreeMap::<CanonicalEnvelopeHash, usize>::new();
+    batch.len() <= MAX_BATCH_SIZE
+        && batch.iter().all(|(genesis, _, _)| {
+            let n = per_chain.entry(*genesis).or_default();
+            *n += 1;
+            *n <= MAX_MOVES_PER_CHAIN
+        })
+}
+
+/// Picks the moves for each of a host's batches
+#[derive(Clone, Debug, Default)]
+pub struct BatchSelector {
+    /// How many moves each chain had sequenced recently, halved every batch
+    usage: BTreeMap<CanonicalEnvelopeHash, u64>,
+}
+
+impl BatchSelector {
+    /// Picks the moves for the next batch out of `waiting`, which must hold a
+    /// contiguous run of moves from each chain. The moves left out are the
+    /// last of their chains' runs, so they can go in a later batch.
+    pub fn select<I>(&mut self, waiting: I) -> Vec<Orderable>
+    where
+        I: IntoIterator<Item = Orderable>,
+    {
+        let mut waiting: Vec<Orderable> = waiting.into_iter().collect();
+        waiting.sort_by_key(|(genesis, height, _)| (*genesis, *height));
+        let mut chains = BTreeMap::<CanonicalEnvelopeHash, VecDeque<Orderable>>::new();
+        for o in waiting {
+            chains.entry(o.0).or_default().push_back(o);
+        }
+        let mut turns: Vec<CanonicalEnvelopeHash> = chains.keys().cloned().collect();
+        turns.sort_by_key(|genesis| {
+            (
+                self.usage.get(genesis).cloned().unwrap_or_default(),
+                *genesis,
+            )
+        });
+        let mut selected = vec![];
+        'rounds: for _ in 0..MAX_MOVES_PER_CHAIN {
+            let before = selected.len();
+            for genesis in &turns {
+                if selected.len() == MAX_BATCH_SIZE {
+                    break 'rounds;
+                }
+                if let Some(o) = chains.get_mut(genesis).and_then(VecDeque::pop_front) {
+                    selected.push(o);
+                }
+            }
+            if selected.len() == before {
+                break;
+            }
+        }
+        self.usage.values_mut().for_each(|n| *n /= 2);
+        self.usage.retain(|_, n| *n > 0);
+        for (genesis, _, _) in &selected {
+            *self.usage.entry(*genesis).or_default() += 1;
+        }
+        selected
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use sapio_bitcoin::hashes::{hex::ToHex, sha256, Hash};
+
+    fn hash(i: u32) -> CanonicalEnvelopeHash {
+        serde_json::from_value(sha256::Hash::hash(&i.to_be_bytes()).to_hex().into()).unwrap()
+    }
+
+    /// `n` contiguous moves from chain `chain`
+    fn run(chain: u32, n: usize) -> Vec<Orderable> {
+        (0..n as i64)
+            .map(|height| (hash(chain), height, hash(chain * 1000 + height as u32 + 1)))
+            .collect()
+    }
+
+    #[test]
+    fn test_batch_limits() {
+        let mut selector = BatchSelector::default();
+        let spam = run(1, 10 * MAX_MOVES_PER_CHAIN);
+        let quiet = run(2, 1);
+        let batch = selector.select(spam.iter().chain(quiet.iter()).cloned());
+        assert!(within_limits(&batch));
+        // the spammer is cut to its quota and the rest carries over in order
+        assert_eq!(batch.len(), MAX_MOVES_PER_CHAIN + 1);
+        assert!(batch.contains(&quiet[0]));
+        assert!(spam[..MAX_MOVES_PER_CHAIN]
+            .iter()
+            .all(|o| batch.contains(o)));
+        assert!(!within_limits(&spam));
+
+        // once the batch is full, the chains that had the least go first
+        let chains = MAX_BATCH_SIZE as u32 + 1;
+        let waiting: Vec<Orderable> = (10..10 + chains).flat_map(|c| run(c, 1)).collect();
+        let mut selector = BatchSelector::default();
+        selector.select(run(10, 1));
+        let batch = selector.select(waiting.iter().cloned());
+        assert_eq!(batch.len(), MAX_BATCH_SIZE);
+        assert!(within_limits(&batch));
+        assert!(!batch.contains(&waiting[0]));
+    }
+}
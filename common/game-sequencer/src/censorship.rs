@@ -0,0 +1,793 @@
+// Copyright Judica, Inc 2022
+//
+// This Source Code Form is subject to the terms of the Mozilla Public
+//  License, v. 2.0. If a copy of the MPL was not distributed with this
+//  file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+//! Proofs that the host censored a player.
+//!
+//! A player's move is censored when someone else in the game acknowledged it,
+//! by naming it (or a later move on the same chain) in the tips of one of
+//! their own envelopes, and the host then sequenced a batch more than
+//! [`CENSORSHIP_TIMEOUT_MS`] after the acknowledgement without it, even though
+//! the move before it had already been sequenced. The host's own envelopes
+//! carry the tips of the players in the game, so the host usually acknowledges
+//! moves itself.
+//!
+//! Times are only taken from envelopes the host signed, or bounded by them, so
+//! players can not backdate an acknowledgement to frame the host. Another
+//! player's acknowledgement counts from when the host first named it (or a
+//! later envelope on the same chain) in its tips, and no acknowledgement counts
+//! from before the host sequenced the move before the censored one.
+//!
+//! The host is not to blame for moves it was right to hold back. Only a batch
+//! with room to spare counts as sequenced after the deadline, since a full one
+//! may have left the move for the next (see [`crate::batching`]). A player the
+//! host caught equivocating, and published a [`BroadcastByHost::Quarantine`]
+//! for, can not be censored at all.
+//!
+//! A [`CensorshipProof`] carries everything needed to check this without a
+//! database: the host's chain, the censored moves, and the acknowledgements.
+
+use crate::batching::MAX_BATCH_SIZE;
+use attest_messages::AuthenticationError;
+use attest_messages::CanonicalEnvelopeHash;
+use attest_messages::GenericEnvelope;
+use game_host_messages::{BroadcastByHost, Channelized};
+use game_player_messages::ParticipantAction;
+use sapio_bitcoin::secp256k1::{Secp256k1, Verification};
+use sapio_bitcoin::XOnlyPublicKey;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::fmt::Display;
+
+/// How long the host has to sequence a move once it has been acknowledged
+pub const CENSORSHIP_TIMEOUT_MS: i64 = 5 * 60 * 1000;
+
+type HostEnvelope = GenericEnvelope<Channelized<BroadcastByHost>>;
+type ParticipantEnvelope = GenericEnvelope<ParticipantAction>;
+
+#[derive(Serialize, Deserialize, JsonSchema, Clone, Debug)]
+pub struct CensorshipProof {
+    /// The host's chain, from its genesis up to a batch sent after the deadline
+    pub sequencer_envelopes: Vec<HostEnvelope>,
+    /// The first move of the player's that was never sequenced, followed by
+    /// the player's later moves up to the one that was acknowledged
+    pub censored: Vec<ParticipantEnvelope>,
+    /// Acknowledgements of the last of `censored` by other players. Each is a
+    /// run of the acknowledging player's chain, from the envelope naming the
+    /// move up to one the host named in its tips. Acknowledgements by the host
+    /// are found in `sequencer_envelopes`.
+    pub acknowledgements: Vec<Vec<ParticipantEnvelope>>,
+}
+
+#[derive(Debug)]
+pub enum CensorshipProofError {
+    AuthenticationError(AuthenticationError),
+    MessageFromWrongEntity,
+    Gap,
+    NothingCensored,
+    /// The move before the censored one was not sequenced either, so the host
+    /// could not have sequenced it
+    PredecessorNotSequenced,
+    WasSequenced,
+    NotAcknowledged,
+    /// The host has not sequenced a batch with room for the move since the
+    /// deadline passed
+    DeadlineNotPassed,
+    /// The host quarantined the player's chain for equivocating
+    Quarantined,
+}
+
+impl Display for CensorshipProofError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:?}", self)
+    }
+}
+impl std::error::Error for CensorshipProofError {}
+
+impl From<AuthenticationError> for CensorshipProofError {
+    fn from(e: AuthenticationError) -> Self {
+        CensorshipProofError::AuthenticationError(e)
+    }
+}
+
+impl CensorshipProof {
+    /// Checks that `host` censored one of `players`.
+    pub fn verify<C: Verification>(
+        &self,
+        secp: &Secp256k1<C>,
+        host: XOnlyPublicKey,
+        players: &[XOnlyPublicKey],
+    ) -> Result<(), CensorshipProofError> {
+        for e in &self.sequencer_envelopes {
+            e.self_authenticate(secp)?;
+        }
+        for e in self
+            .censored
+            .iter()
+            .chain(self.acknowledgements.iter().flatten())
+        {
+            e.self_authenticate(secp)?;
+        }
+
+        // the host's chain must be complete, or it might hide a batch with
+        // the censored move
+        if self
+            .sequencer_envelopes
+            .iter()
+            .any(|e| e.header().key() != host)
+        {
+            return Err(CensorshipProofError::MessageFromWrongEntity);
+        }
+        match self.sequencer_envelopes.first() {
+            Some(e) if e.header().height() == 0 => {}
+            _ => return Err(CensorshipProofError::Gap),
+        }
+        if !self
+            .sequencer_envelopes
+            .windows(2)
+            .all(|w| follows(&w[0], &w[1]))
+        {
+            return Err(CensorshipProofError::Gap);
+        }
+
+        let first = self
+            .censored
+            .first()
+            .ok_or(CensorshipProofError::NothingCensored)?;
+        let player = first.header().key();
+        if !players.contains(&player) {
+            return Err(CensorshipProofError::MessageFromWrongEntity);
+        }
+        if equivocators(secp, &self.sequencer_envelopes).contains(&player) {
+            return Err(CensorshipProofError::Quarantined);
+        }
+        if !self.censored.windows(2).all(|w| follows(&w[0], &w[1])) {
+            return Err(CensorshipProofError::Gap);
+        }
+
+        let sequenced = sequenced(&self.sequencer_envelopes);
+        if sequenced.contains(&first.canonicalized_hash_ref()) {
+            return Err(CensorshipProofError::WasSequenced);
+        }
+        // the host can not sequence a move before the one before it
+        let earliest = match first.header().ancestors() {
+            Some(a) => sequenced_at(&self.sequencer_envelopes, &a.prev_msg())
+                .ok_or(CensorshipProofError::PredecessorNotSequenced)?,
+            None => i64::MIN,
+        };
+
+        let last = self.censored.last().expect("Checked Non-Empty");
+        let tip = (
+            player,
+            last.header().height(),
+            last.canonicalized_hash_ref(),
+        );
+        let host_acks = self
+            .sequencer_envelopes
+            .iter()
+            .filter(|e| e.header().tips().contains(&tip))
+            .map(|e| e.header().sent_time_ms());
+        let player_acks = self.acknowledgements.iter().filter_map(|run| {
+            let (ack, end) = (run.first()?, run.last()?);
+            let key = ack.header().key();
+            if key == player || !players.contains(&key) || !ack.header().tips().contains(&tip) {
+                return None;
+            }
+            if !run.windows(2).all(|w| follows(&w[0], &w[1])) {
+                return None;
+            }
+            let end = (key, end.header().height(), end.canonicalized_hash_ref());
+            let seen = host_tipped(&self.sequencer_envelopes, &end)?;
+            Some(ack.header().sent_time_ms().max(seen))
+        });
+        let acknowledged = host_acks
+            .chain(player_acks)
+            .min()
+            .ok_or(CensorshipProofError::NotAcknowledged)?
+            .max(earliest);
+
+        if self.sequencer_envelopes.iter().any(|e| {
+            has_room(e) && e.header().sent_time_ms() > acknowledged + CENSORSHIP_TIMEOUT_MS
+        }) {
+            Ok(())
+        } else {
+            Err(CensorshipProofError::DeadlineNotPassed)
+        }
+    }
+}
+
+fn follows<M: attest_messages::AttestEnvelopable>(
+    prev: &GenericEnvelope<M>,
+    next: &GenericEnvelope<M>,
+) -> bool {
+    prev.header().key() == next.header().key()
+        && prev.header().height() + 1 == next.header().height()
+        && next
+            .header()
+            .ancestors()
+            .map(|a| a.prev_msg() == prev.canonicalized_hash_ref())
+            .unwrap_or(false)
+}
+
+fn sequenced(sequencer_envelopes: &[HostEnvelope]) -> HashSet<CanonicalEnvelopeHash> {
+    sequencer_envelopes
+        .iter()
+        .flat_map(|e| match &e.msg().data {
+            BroadcastByHost::Sequence(batch) => batch.iter().cloned().collect(),
+            _ => vec![],
+        })
+        .collect()
+}
+
+/// Whether `e` is a batch with room to spare, so nothing waiting was left out
+/// of it for lack of space
+fn has_room(e: &HostEnvelope) -> bool {
+    matches!(&e.msg().data, BroadcastByHost::Sequence(batch) if batch.len() < MAX_BATCH_SIZE)
+}
+
+/// The players the host published valid evidence of equivocation against
+fn equivocators<C: Verification>(
+    secp: &Secp256k1<C>,
+    sequencer_envelopes: &[HostEnvelope],
+) -> HashSet<XOnlyPublicKey> {
+    sequencer_envelopes
+        .iter()
+        .filter_map(|e| match &e.msg().data {
+            BroadcastByHost::Quarantine(evidence) => evidence.equivocator(secp),
+            _ => None,
+        })
+        .collect()
+}
+
+/// When the host first sequenced `hash`
+fn sequenced_at(sequencer_envelopes: &[HostEnvelope], hash: &CanonicalEnvelopeHash) -> Option<i64> {
+    sequencer_envelopes
+        .iter()
+        .filter(|e| match &e.msg().data {
+            BroadcastByHost::Sequence(batch) => batch.contains(hash),
+            _ => false,
+        })
+        .map(|e| e.header().sent_time_ms())
+        .min()
+}
+
+/// When the host first named `tip` in the tips of one of its envelopes
+fn host_tipped(
+    sequencer_envelopes: &[HostEnvelope],
+    tip: &(XOnlyPublicKey, i64, CanonicalEnvelopeHash),
+) -> Option<i64> {
+    sequencer_envelopes
+        .iter()
+        .filter(|e| e.header().tips().contains(tip))
+        .map(|e| e.header().sent_time_ms())
+        .min()
+}
+
+/// Looks through the database for a player the host has censored, returning
+/// a proof for the first one found.
+#[cfg(feature = "database_access")]
+pub fn find_censorship<T: attest_database::db_handle::handle_type::Get>(
+    handle: &attest_database::db_handle::MsgDBHandle<T>,
+    host: XOnlyPublicKey,
+    players: &[XOnlyPublicKey],
+) -> Result<Option<CensorshipProof>, Box<dyn std::error::Error + Send + Sync>> {
+    let sequencer_envelopes =
+        handle.load_all_messages_for_user_by_key_connected::<_, HostEnvelope>(&host)?;
+    let last_batch = match sequencer_envelopes
+        .iter()
+        .filter(|e| has_room(e))
+        .map(|e| e.header().sent_time_ms())
+        .max()
+    {
+        Some(t) => t,
+        None => return Ok(None),
+    };
+    let sequenced = sequenced(&sequencer_envelopes);
+    let quarantined = equivocators(&Secp256k1::verification_only(), &sequencer_envelopes);
+    let chains = players
+        .iter()
+        .map(|p| {
+            handle
+                .load_all_messages_for_user_by_key_connected::<_, ParticipantEnvelope>(p)
+                .map(|chain| (*p, chain))
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    for (player, chain) in &chains {
+        if quarantined.contains(player) {
+            continue;
+        }
+        // the host sequences each chain in order, so only the first move it
+        // has not sequenced can have been censored
+        let start = match chain
+            .iter()
+            .position(|e| !sequenced.contains(&e.canonicalized_hash_ref()))
+        {
+            Some(start) => start,
+            None => continue,
+        };
+        // the host can not sequence a move before the one before it
+        let earliest = match start {
+            0 => i64::MIN,
+            _ => sequenced_at(
+                &sequencer_envelopes,
+                &chain[start - 1].canonicalized_hash_ref(),
+            )
+            .unwrap_or(i64::MIN),
+        };
+        let host_chain = &sequencer_envelopes;
+        let acknowledged_at = |tip: &(XOnlyPublicKey, i64, CanonicalEnvelopeHash)| {
+            let by_host = host_chain
+                .iter()
+                .filter(|e| e.header().tips().contains(tip))
+                .map(|e| (e.header().sent_time_ms(), None));
+            // another player's acknowledgement counts from when the host saw
+            // it, through the first envelope after it that the host tipped
+            let by_players =
+                chains
+                    .iter()
+                    .filter(|(other, _)| other != player)
+                    .flat_map(|(other, c)| {
+                        c.iter()
+                            .enumerate()
+                            .filter(|(_, e)| e.header().tips().contains(tip))
+                            .filter_map(move |(k, e)| {
+                                let (seen, end) = (k..c.len())
+                                    .filter_map(|j| {
+                                        let end = (
+                                            *other,
+                                            c[j].header().height(),
+                                            c[j].canonicalized_hash_ref(),
+                                        );
+                                        host_tipped(host_chain, &end).map(|t| (t, j))
+                                    })
+                                    .min()?;
+                                Some((e.header().sent_time_ms().max(seen), Some(&c[k..=end])))
+                            })
+                    });
+            by_host.chain(by_players).min_by_key(|(t, _)| *t)
+        };
+        // the earliest acknowledgement of any of the unsequenced moves
+        let found = (start..chain.len())
+            .filter_map(|end| {
+                let e = &chain[end];
+                let tip = (*player, e.header().height(), e.canonicalized_hash_ref());
+                acknowledged_at(&tip).map(|(t, ack)| (t, end, ack))
+            })
+            .min_by_key(|(t, _, _)| *t);
+        if let Some((t, end, ack)) = found {
+            if t.max(earliest) + CENSORSHIP_TIMEOUT_MS < last_batch {
+                return Ok(Some(CensorshipProof {
+                    sequencer_envelopes,
+                    censored: chain[start..=end].to_vec(),
+                    acknowledgements: ack.map(|run| run.to_vec()).into_iter().collect(),
+                }));
+            }
+        }
+    }
+    Ok(None)
+}
+
+#[cfg(test)]
+pub(crate) mod test {
+    use super::*;
+    use attest_messages::{nonce::PrecomittedNonce, Ancestors, Header, Unsigned};
+    use game_host_messages::EquivocationEvidence;
+    use mine_with_friends_board::{
+        game::game_move::{GameMove, Heartbeat},
+        sanitize::Unsanitized,
+    };
+    use sapio_bitcoin::secp256k1::{rand, All, SecretKey};
+    use sapio_bitcoin::KeyPair;
+    use std::collections::VecDeque;
+
+    #[derive(Clone)]
+    pub(crate) struct Chain {
+        keypair: KeyPair,
+        key: XOnlyPublicKey,
+        tip: Option<(i64, CanonicalEnvelopeHash, CanonicalEnvelopeHash)>,
+    }
+
+    impl Chain {
+        pub(crate) fn new(secp: &Secp256k1<All>) -> Self {
+            let sk = SecretKey::new(&mut rand::thread_rng());
+            Chain {
+                keypair: KeyPair::from_secret_key(secp, &sk),
+                key: sk.x_only_public_key(secp).0,
+                tip: None,
+            }
+        }
+        pub(crate) fn key(&self) -> XOnlyPublicKey {
+            self.key
+        }
+        fn next<M: attest_messages::AttestEnvelopable>(
+            &mut self,
+            secp: &Secp256k1<All>,
+            msg: M,
+            tips: Vec<(XOnlyPublicKey, i64, CanonicalEnvelopeHash)>,
+            sent_time_ms: i64,
+        ) -> GenericEnvelope<M> {
+            let (height, ancestors) = match self.tip {
+                Some((h, prev, genesis)) => (h + 1, Some(Ancestors::new(prev, genesis))),
+                None => (0, None),
+            };
+            let mut e = GenericEnvelope::new(
+                Header::new(
+                    self.key(),
+                    PrecomittedNonce::new(secp).get_public(secp),
+                    ancestors,
+                    tips,
+                    height,
+                    sent_time_ms,
+                    Unsigned::new(None),
+                    Default::default(),
+                ),
+                msg,
+            );
+            e.sign_with(&self.keypair, secp, PrecomittedNonce::new(secp))
+                .expect("Signature OK");
+            let hash = e.canonicalized_hash_ref();
+            let genesis = self.tip.map(|t| t.2).unwrap_or(hash);
+            self.tip = Some((height, hash, genesis));
+            e
+        }
+        pub(crate) fn player_move(
+            &mut self,
+            secp: &Secp256k1<All>,
+            tips: Vec<(XOnlyPublicKey, i64, CanonicalEnvelopeHash)>,
+            sent_time_ms: i64,
+        ) -> ParticipantEnvelope {
+            let msg = ParticipantAction::new(
+                Unsanitized(GameMove::Heartbeat(Heartbeat())),
+                self.tip.map(|t| t.0 as u64 + 1).unwrap_or(0),
+                sent_time_ms as u64,
+            );
+            self.next(secp, msg, tips, sent_time_ms)
+        }
+        pub(crate) fn host(
+            &mut self,
+            secp: &Secp256k1<All>,
+            data: BroadcastByHost,
+            tips: Vec<(XOnlyPublicKey, i64, CanonicalEnvelopeHash)>,
+            sent_time_ms: i64,
+        ) -> HostEnvelope {
+            let msg = Channelized {
+                data,
+                channel: "default".into(),
+            };
+            self.next(secp, msg, tips, sent_time_ms)
+        }
+    }
+
+    pub(crate) fn heartbeat() -> BroadcastByHost {
+        BroadcastByHost::Heartbeat(game_host_messages::HostHeartbeat {
+            sequenced_height: None,
+        })
+    }
+
+    fn tip(e: &ParticipantEnvelope) -> (XOnlyPublicKey, i64, CanonicalEnvelopeHash) {
+        (
+            e.header().key(),
+            e.header().height(),
+            e.canonicalized_hash_ref(),
+        )
+    }
+
+    #[test]
+    fn test_censorship_proof() {
+        let secp = Secp256k1::new();
+        let (mut host, mut alice, mut bob) =
+            (Chain::new(&secp), Chain::new(&secp), Chain::new(&secp));
+        let players = [alice.key(), bob.key()];
+        let t = 1_000_000;
+
+        let a0 = alice.player_move(&secp, vec![], t);
+        let b0 = bob.player_move(&secp, vec![], t);
+        let a1 = alice.player_move(&secp, vec![tip(&b0)], t + 10);
+        let b1 = bob.player_move(&secp, vec![tip(&a1)], t + 20);
+        let b2 = bob.player_move(&secp, vec![], t + 25);
+        let first_batch = VecDeque::from(vec![
+            a0.canonicalized_hash_ref(),
+            b0.canonicalized_hash_ref(),
+        ]);
+        let mut sequencer_envelopes = vec![
+            host.host(&secp, heartbeat(), vec![], t),
+            host.host(&secp, BroadcastByHost::Sequence(first_batch), vec![], t + 5),
+            // the host hears of bob's acknowledgement through a later move
+            host.host(&secp, heartbeat(), vec![tip(&b2)], t + 30),
+        ];
+        let before_late = host.clone();
+        // the host only sequences bob's move, long after it saw bob acknowledge
+        // alice's
+        let late = t + 30 + CENSORSHIP_TIMEOUT_MS + 1;
+        let second_batch = VecDeque::from(vec![b1.canonicalized_hash_ref()]);
+        sequencer_envelopes.push(host.host(
+            &secp,
+            BroadcastByHost::Sequence(second_batch.clone()),
+            vec![],
+            late,
+        ));
+
+        let proof = CensorshipProof {
+            sequencer_envelopes: sequencer_envelopes.clone(),
+            censored: vec![a1.clone()],
+            acknowledgements: vec![vec![b1.clone(), b2.clone()]],
+        };
+        proof
+            .verify(&secp, host.key(), &players)
+            .expect("Censorship Proven");
+
+        // an acknowledgement by the censored player does not count
+        let own_ack = CensorshipProof {
+            acknowledgements: vec![vec![a1.clone()]],
+            ..proof.clone()
+        };
+        assert!(matches!(
+            own_ack.verify(&secp, host.key(), &players),
+            Err(CensorshipProofError::NotAcknowledged)
+        ));
+
+        // nor does one the host never saw, whatever time it claims
+        let unseen = CensorshipProof {
+            acknowledgements: vec![vec![b1.clone()]],
+            ..proof.clone()
+        };
+        assert!(matches!(
+            unseen.verify(&secp, host.key(), &players),
+            Err(CensorshipProofError::NotAcknowledged)
+        ));
+        // or one that is not on the acknowledging player's chain
+        let off_chain = CensorshipProof {
+            acknowledgements: vec![vec![b1.clone(), a1.clone(), b2.clone()]],
+            ..proof.clone()
+        };
+        assert!(matches!(
+            off_chain.verify(&secp, host.key(), &players),
+            Err(CensorshipProofError::NotAcknowledged)
+        ));
+
+        // bob's acknowledgement counts from when the host saw it, not from
+        // when bob says he sent it
+        let mut fork = before_late;
+        let backdated = CensorshipProof {
+            sequencer_envelopes: sequencer_envelopes[..3]
+                .iter()
+                .cloned()
+                .chain(std::iter::once(fork.host(
+                    &secp,
+                    BroadcastByHost::Sequence(second_batch),
+                    vec![],
+                    t + 20 + CENSORSHIP_TIMEOUT_MS + 1,
+                )))
+                .collect(),
+            ..proof.clone()
+        };
+        assert!(matches!(
+            backdated.verify(&secp, host.key(), &players),
+            Err(CensorshipProofError::DeadlineNotPassed)
+        ));
+
+        // the host was still within the timeout
+        let early = CensorshipProof {
+            sequencer_envelopes: sequencer_envelopes[..3].to_vec(),
+            ..proof.clone()
+        };
+        assert!(matches!(
+            early.verify(&secp, host.key(), &players),
+            Err(CensorshipProofError::DeadlineNotPassed)
+        ));
+
+        // a hole in the host's chain could hide the move
+        let gap = CensorshipProof {
+            sequencer_envelopes: vec![
+                sequencer_envelopes[0].clone(),
+                sequencer_envelopes[3].clone(),
+            ],
+            ..proof.clone()
+        };
+        assert!(matches!(
+            gap.verify(&secp, host.key(), &players),
+            Err(CensorshipProofError::Gap)
+        ));
+
+        // the wrong host
+        assert!(matches!(
+            proof.verify(&secp, alice.key(), &players),
+            Err(CensorshipProofError::MessageFromWrongEntity)
+        ));
+
+        // bob's sequenced move was not censored
+        let sequenced = CensorshipProof {
+            censored: vec![b1.clone()],
+            acknowledgements: vec![],
+            ..proof
+        };
+        assert!(sequenced.verify(&secp, host.key(), &players).is_err());
+    }
+
+    #[test]
+    fn test_censorship_after_late_predecessor() {
+        let secp = Secp256k1::new();
+        let (mut host, mut alice, mut bob) =
+            (Chain::new(&secp), Chain::new(&secp), Chain::new(&secp));
+        let players = [alice.key(), bob.key()];
+        let t = 1_000_000;
+
+        let a0 = alice.player_move(&secp, vec![], t);
+        let a1 = alice.player_move(&secp, vec![], t + 10);
+        let b0 = bob.player_move(&secp, vec![tip(&a1)], t + 20);
+        // the host acknowledges alice's second move at once, but only
+        // sequences her first one much later
+        let sequenced_first = t + CENSORSHIP_TIMEOUT_MS;
+        let sequencer_envelopes = vec![
+            host.host(&secp, heartbeat(), vec![tip(&a1), tip(&b0)], t + 30),
+            host.host(
+                &secp,
+                BroadcastByHost::Sequence(VecDeque::from(vec![a0.canonicalized_hash_ref()])),
+                vec![],
+                sequenced_first,
+            ),
+            host.host(
+                &secp,
+                BroadcastByHost::Sequence(VecDeque::from(vec![b0.canonicalized_hash_ref()])),
+                vec![],
+                t + 30 + CENSORSHIP_TIMEOUT_MS + 1,
+            ),
+        ];
+        let proof = CensorshipProof {
+            sequencer_envelopes,
+            censored: vec![a1.clone()],
+            acknowledgements: vec![vec![b0.clone()]],
+        };
+        // the move could not be sequenced before the one before it was
+        assert!(matches!(
+            proof.verify(&secp, host.key(), &players),
+            Err(CensorshipProofError::DeadlineNotPassed)
+        ));
+
+        let mut sequencer_envelopes = proof.sequencer_envelopes.clone();
+        sequencer_envelopes.push(host.host(
+            &secp,
+            heartbeat(),
+            vec![],
+            sequenced_first + CENSORSHIP_TIMEOUT_MS,
+        ));
+        sequencer_envelopes.push(host.host(
+            &secp,
+            BroadcastByHost::Sequence(VecDeque::new()),
+            vec![],
+            sequenced_first + CENSORSHIP_TIMEOUT_MS + 1,
+        ));
+        CensorshipProof {
+            sequencer_envelopes,
+            ..proof
+        }
+        .verify(&secp, host.key(), &players)
+        .expect("Censorship Proven");
+    }
+
+    /// `e` as the host keeps envelopes from the chains it sequences
+    fn erased(e: &ParticipantEnvelope) -> attest_messages::Envelope {
+        serde_json::from_value(serde_json::to_value(e).expect("Serializable"))
+            .expect("Same Envelope")
+    }
+
+    #[test]
+    fn test_no_censorship_of_quarantined() {
+        let secp = Secp256k1::new();
+        let (mut host, mut alice) = (Chain::new(&secp), Chain::new(&secp));
+        let players = [alice.key()];
+        let t = 1_000_000;
+
+        let a0 = alice.player_move(&secp, vec![], t);
+        let mut fork = alice.clone();
+        let a1 = alice.player_move(&secp, vec![], t + 10);
+        // alice signs another move at the same height
+        let forked = fork.player_move(&secp, vec![], t + 10);
+        let sequencer_envelopes = vec![
+            host.host(&secp, heartbeat(), vec![tip(&a1)], t + 20),
+            host.host(
+                &secp,
+                BroadcastByHost::Sequence(VecDeque::from(vec![a0.canonicalized_hash_ref()])),
+                vec![],
+                t + 25,
+            ),
+        ];
+        let quarantine = |evidence: EquivocationEvidence| {
+            let mut host = host.clone();
+            let mut sequencer_envelopes = sequencer_envelopes.clone();
+            sequencer_envelopes.push(host.host(
+                &secp,
+                BroadcastByHost::Quarantine(Box::new(evidence)),
+                vec![],
+                t + 30,
+            ));
+            sequencer_envelopes.push(host.host(
+                &secp,
+                BroadcastByHost::Sequence(VecDeque::new()),
+                vec![],
+                t + 30 + CENSORSHIP_TIMEOUT_MS,
+            ));
+            CensorshipProof {
+                sequencer_envelopes,
+                censored: vec![a1.clone()],
+                acknowledgements: vec![],
+            }
+        };
+
+        assert!(matches!(
+            quarantine(EquivocationEvidence(erased(&a1), erased(&forked))).verify(
+                &secp,
+                host.key(),
+                &players
+            ),
+            Err(CensorshipProofError::Quarantined)
+        ));
+        // the same envelope twice is no evidence
+        quarantine(EquivocationEvidence(erased(&a1), erased(&a1)))
+            .verify(&secp, host.key(), &players)
+            .expect("Censorship Proven");
+        // nor are envelopes at different heights
+        quarantine(EquivocationEvidence(erased(&a0), erased(&a1)))
+            .verify(&secp, host.key(), &players)
+            .expect("Censorship Proven");
+    }
+
+    #[test]
+    fn test_no_censorship_behind_full_batch() {
+        let secp = Secp256k1::new();
+        let (mut host, mut alice, mut bob) =
+            (Chain::new(&secp), Chain::new(&secp), Chain::new(&secp));
+        let players = [alice.key(), bob.key()];
+        let t = 1_000_000;
+
+        let a0 = alice.player_move(&secp, vec![], t);
+        let a1 = alice.player_move(&secp, vec![], t + 10);
+        let b0 = bob.player_move(&secp, vec![], t + 10);
+        let mut sequencer_envelopes = vec![
+            host.host(&secp, heartbeat(), vec![tip(&a1)], t + 20),
+            host.host(
+                &secp,
+                BroadcastByHost::Sequence(VecDeque::from(vec![a0.canonicalized_hash_ref()])),
+                vec![],
+                t + 25,
+            ),
+            // the only batch after the deadline had no room for alice's move
+            host.host(
+                &secp,
+                BroadcastByHost::Sequence(VecDeque::from(vec![
+                    b0.canonicalized_hash_ref();
+                    MAX_BATCH_SIZE
+                ])),
+                vec![],
+                t + 30 + CENSORSHIP_TIMEOUT_MS,
+            ),
+        ];
+        let proof = CensorshipProof {
+            sequencer_envelopes: sequencer_envelopes.clone(),
+            censored: vec![a1.clone()],
+            acknowledgements: vec![],
+        };
+        assert!(matches!(
+            proof.verify(&secp, host.key(), &players),
+            Err(CensorshipProofError::DeadlineNotPassed)
+        ));
+
+        // the next batch had room, but left it out anyway
+        sequencer_envelopes.push(host.host(
+            &secp,
+            BroadcastByHost::Sequence(VecDeque::new()),
+            vec![],
+            t + 40 + CENSORSHIP_TIMEOUT_MS,
+        ));
+        CensorshipProof {
+            sequencer_envelopes,
+            ..proof
+        }
+        .verify(&secp, host.key(), &players)
+        .expect("Censorship Proven");
+    }
+}
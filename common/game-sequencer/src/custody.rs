@@ -0,0 +1,479 @@
+// Copyright Judica, Inc 2022
+//
+// This Source Code Form is subject to the terms of the Mozilla Public
+//  License, v. 2.0. If a copy of the MPL was not distributed with this
+//  file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+//! Following a game's sequencing across hosts.
+//!
+//! A game's [`FailoverParams`] name backup sequencers that may take over if
+//! the host goes quiet. Custody of the game starts with the host. A backup
+//! takes over by sending a [`BroadcastByHost::TakeOver`] naming the
+//! custodian's last envelope, at least `handoff_after` ms after that envelope
+//! was sent, doubled for each backup ahead of it in line that it skips. Its
+//! envelopes from the TakeOver on are then sequenced in place of the
+//! custodian's, and anything the custodian sent after the named envelope is
+//! dropped, even if it never went quiet.
+//!
+//! A custodian that comes back after going quiet carries on where it left
+//! off, unless a backup took over in the meantime. If several backups take
+//! over from the same envelope, the one earliest in line wins.
+//!
+//! Everything here is decided from the signed chains alone, so everyone with
+//! the same chains follows the same custody and sees the same move order. An
+//! envelope that takes longer than `handoff_after` to reach a player can still
+//! change what that player saw, which is why it should be minutes, not seconds.
+
+use crate::SequencerError;
+use attest_messages::{Authenticated, GenericEnvelope};
+use game_host_messages::{BroadcastByHost, Channelized};
+use mine_with_friends_board::game::FailoverParams;
+use sapio_bitcoin::XOnlyPublicKey;
+use std::collections::BTreeMap;
+use std::str::FromStr;
+
+pub type HostEnvelope = Authenticated<GenericEnvelope<Channelized<BroadcastByHost>>>;
+
+/// What follows an envelope sent with custody of the game, see
+/// [`Custody::after`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Step {
+    /// The custodian's next envelope is sequenced
+    Continue,
+    /// The backup at `to` took over, from the envelope at `at` in its chain
+    TakeOver { to: usize, at: usize },
+    /// The custodian has sent nothing since, and nobody has taken over
+    Stop,
+}
+
+/// Where a sequencer stands in the custody of a game, see
+/// [`Custody::standing`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Standing {
+    /// It has custody of the game
+    Custodian,
+    /// The custodian at `from` is ahead of it in line, and may be taken over
+    /// from at `due_ms` unless it sends something first
+    Behind { from: usize, due_ms: i64 },
+    /// Custody passed it by, so it can never have it
+    Passed,
+}
+
+/// The sequencers that may have custody of a game, in the order they take
+/// over
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Custody {
+    /// The host, then its backups
+    pub keys: Vec<XOnlyPublicKey>,
+    pub handoff_after: i64,
+}
+
+impl Custody {
+    /// Custody for a game with no backups
+    pub fn single(host: XOnlyPublicKey) -> Custody {
+        Custody {
+            keys: vec![host],
+            handoff_after: i64::MAX,
+        }
+    }
+    pub fn new(host: XOnlyPublicKey, params: &FailoverParams) -> Result<Custody, SequencerError> {
+        let mut keys = vec![host];
+        for backup in &params.backups {
+            let key =
+                XOnlyPublicKey::from_str(backup).map_err(|_| SequencerError::InvalidBackupKey)?;
+            if keys.contains(&key) {
+                return Err(SequencerError::InvalidBackupKey);
+            }
+            keys.push(key);
+        }
+        Ok(Custody {
+            keys,
+            handoff_after: params.handoff_after.min(i64::MAX as u64) as i64,
+        })
+    }
+    /// Custody for the game set up in the genesis of the host's chain
+    pub fn for_host_genesis(genesis: &HostEnvelope) -> Result<Custody, SequencerError> {
+        match &genesis.msg().data {
+            BroadcastByHost::GameSetup(setup) => {
+                Custody::new(genesis.header().key(), &setup.failover)
+            }
+            _ => Ok(Custody::single(genesis.header().key())),
+        }
+    }
+    pub fn has_backups(&self) -> bool {
+        self.keys.len() > 1
+    }
+    /// Where `key` is in line, if it may have custody of the game
+    pub fn position(&self, key: &XOnlyPublicKey) -> Option<usize> {
+        self.keys.iter().position(|k| k == key)
+    }
+    /// How long the custodian at `from` must be quiet before the backup at
+    /// `to` may take over from it
+    pub fn handoff_wait(&self, from: usize, to: usize) -> i64 {
+        2i64.checked_pow((to - from - 1) as u32)
+            .and_then(|m| self.handoff_after.checked_mul(m))
+            .unwrap_or(i64::MAX)
+    }
+    /// Where the sequencer at `position` stands once `last` was the latest
+    /// envelope sent with custody of the game, see [`Custody::follow`]
+    pub fn standing(&self, position: usize, last: &HostEnvelope) -> Standing {
+        let from = self.position(&last.header().key()).unwrap_or_default();
+        match from.cmp(&position) {
+            std::cmp::Ordering::Equal => Standing::Custodian,
+            std::cmp::Ordering::Less => Standing::Behind {
+                from,
+                due_ms: last
+                    .header()
+                    .sent_time_ms()
+                    .saturating_add(self.handoff_wait(from, position)),
+            },
+            std::cmp::Ordering::Greater => Standing::Passed,
+        }
+    }
+    /// Whether `takeover`, from the sequencer at `to`, validly takes over from
+    /// the custodian at `from` whose last envelope was `last`
+    pub fn takes_over(
+        &self,
+        from: usize,
+        last: &HostEnvelope,
+        to: usize,
+        takeover: &HostEnvelope,
+    ) -> bool {
+        if to <= from || to >= self.keys.len() || takeover.header().key() != self.keys[to] {
+            return false;
+        }
+        match &takeover.msg().data {
+            BroadcastByHost::TakeOver { from: f, last: l } => {
+                *f == self.keys[from]
+                    && *l == last.canonicalized_hash_ref()
+                    && takeover.header().sent_time_ms()
+                        >= last
+                            .header()
+                            .sent_time_ms()
+                            .saturating_add(self.handoff_wait(from, to))
+            }
+            _ => false,
+        }
+    }
+    /// Finds the takeover from the custodian at `from` after `last`, in the
+    /// chains of the backups behind it, returning the position of the backup
+    /// and the index of the TakeOver in its chain
+    pub fn find_takeover(
+        &self,
+        from: usize,
+        last: &HostEnvelope,
+        chains: &BTreeMap<XOnlyPublicKey, Vec<HostEnvelope>>,
+    ) -> Option<(usize, usize)> {
+        (from + 1..self.keys.len()).find_map(|to| {
+            chains
+                .get(&self.keys[to])?
+                .iter()
+                .position(|e| self.takes_over(from, last, to, e))
+                .map(|i| (to, i))
+        })
+    }
+    /// Decides what follows the custodian at `position` having sent `last`,
+    /// given its `next` envelope, if any, and the chains of its backups. A
+    /// valid TakeOver naming `last` ends its custody there, even if it kept
+    /// sending. Otherwise `next` continues it, however long after `last` it
+    /// was sent.
+    pub fn after(
+        &self,
+        position: usize,
+        last: &HostEnvelope,
+        next: Option<&HostEnvelope>,
+        backups: &BTreeMap<XOnlyPublicKey, Vec<HostEnvelope>>,
+    ) -> Step {
+        if let Some((to, at)) = self.find_takeover(position, last, backups) {
+            return Step::TakeOver { to, at };
+        }
+        match next {
+            Some(_) => Step::Continue,
+            None => Step::Stop,
+        }
+    }
+    /// Follows custody from the host's chain through its backups' chains,
+    /// returning every envelope sent with custody of the game, in order
+    pub fn follow<'a>(
+        &self,
+        host: &'a [HostEnvelope],
+        backups: &'a BTreeMap<XOnlyPublicKey, Vec<HostEnvelope>>,
+    ) -> Vec<&'a HostEnvelope> {
+        let mut followed = vec![];
+        let mut position = 0;
+        let mut chain = host;
+        let mut i = 0;
+        while let Some(last) = chain.get(i) {
+            followed.push(last);
+            match self.after(position, last, chain.get(i + 1), backups) {
+                Step::Continue => i += 1,
+                Step::TakeOver { to, at } => {
+                    position = to;
+                    chain = &backups[&self.keys[to]];
+                    i = at;
+                }
+                Step::Stop => break,
+            }
+        }
+        followed
+    }
+}
+
+/// The chains custody of a game can pass through, see [`load_chains`]
+pub type CustodyChains = (
+    Custody,
+    Vec<HostEnvelope>,
+    BTreeMap<XOnlyPublicKey, Vec<HostEnvelope>>,
+);
+
+/// Loads the custody of the host's game, the host's chain, and the chains of
+/// its backups.
+#[cfg(feature = "database_access")]
+pub fn load_chains<T>(
+    handle: &attest_database::db_handle::MsgDBHandle<T>,
+    host: XOnlyPublicKey,
+) -> Result<CustodyChains, Box<dyn std::error::Error + Send + Sync>>
+where
+    T: attest_database::db_handle::handle_type::Get,
+{
+    let host_envelopes: Vec<HostEnvelope> =
+        handle.load_all_messages_for_user_by_key_connected(&host)?;
+    let custody = match host_envelopes.first() {
+        Some(genesis) => Custody::for_host_genesis(genesis)?,
+        None => Custody::single(host),
+    };
+    let mut backup_envelopes = BTreeMap::new();
+    for k in &custody.keys[1..] {
+        let chain: Vec<HostEnvelope> = handle.load_all_messages_for_user_by_key_connected(k)?;
+        backup_envelopes.insert(*k, chain);
+    }
+    Ok((custody, host_envelopes, backup_envelopes))
+}
+
+/// Finds the host of a game that names `backup` as one of its backup
+/// sequencers. A backup key is meant for a single game; if several name it,
+/// the first found is returned.
+#[cfg(feature = "database_access")]
+pub fn find_host_for_backup<T>(
+    handle: &attest_database::db_handle::MsgDBHandle<T>,
+    backup: XOnlyPublicKey,
+) -> Result<Option<XOnlyPublicKey>, Box<dyn std::error::Error + Send + Sync>>
+where
+    T: attest_database::db_handle::handle_type::Get,
+{
+    Ok(handle
+        .get_all_genesis::<Channelized<BroadcastByHost>>()?
+        .iter()
+        .filter_map(|genesis| Custody::for_host_genesis(genesis).ok())
+        .find(|custody| matches!(custody.position(&backup), Some(p) if p > 0))
+        .map(|custody| custody.keys[0]))
+}
+
+/// Loads the host's chain, the chains of its backups, and every move any of
+/// them sequenced.
+#[cfg(feature = "database_access")]
+pub fn load_sequenced<T, M>(
+    handle: &attest_database::db_handle::MsgDBHandle<T>,
+    host: XOnlyPublicKey,
+) -> Result<crate::RawSequencer<M>, Box<dyn std::error::Error + Send + Sync>>
+where
+    T: attest_database::db_handle::handle_type::Get,
+    M: attest_messages::AttestEnvelopable,
+{
+    let (_, sequencer_envelopes, backup_envelopes) = load_chains(handle, host)?;
+    let def = Default::default();
+    let msg_cache = handle
+        .messages_by_hash::<_, Authenticated<GenericEnvelope<M>>, M>(
+            sequencer_envelopes
+                .iter()
+                .chain(backup_envelopes.values().flatten())
+                .flat_map(|m| match &m.msg().data {
+                    BroadcastByHost::Sequence(d) => d,
+                    _ => &def,
+                }),
+        )?
+        .into_iter()
+        .map(|v| (v.canonicalized_hash_ref(), v))
+        .collect();
+    Ok(crate::RawSequencer {
+        sequencer_envelopes,
+        backup_envelopes,
+        msg_cache,
+    })
+}
+
+#[cfg(test)]
+pub(crate) mod test {
+    use super::*;
+    use crate::censorship::test::{heartbeat, Chain};
+    use attest_messages::CanonicalEnvelopeHash;
+    use mine_with_friends_board::game::GameSetup;
+    use sapio_bitcoin::secp256k1::{All, Secp256k1};
+
+    pub(crate) fn auth(
+        secp: &Secp256k1<All>,
+        e: GenericEnvelope<Channelized<BroadcastByHost>>,
+    ) -> HostEnvelope {
+        e.self_authenticate(secp).expect("Valid Signature")
+    }
+
+    pub(crate) fn take_over(
+        secp: &Secp256k1<All>,
+        chain: &mut Chain,
+        from: &Chain,
+        last: &HostEnvelope,
+        t: i64,
+    ) -> HostEnvelope {
+        let data = BroadcastByHost::TakeOver {
+            from: from.key(),
+            last: last.canonicalized_hash_ref(),
+        };
+        auth(secp, chain.host(secp, data, vec![], t))
+    }
+
+    pub(crate) fn genesis(
+        secp: &Secp256k1<All>,
+        host: &mut Chain,
+        backups: &[&Chain],
+        handoff: i64,
+        t: i64,
+    ) -> HostEnvelope {
+        let setup = GameSetup {
+            players: vec![],
+            start_amount: 0,
+            finish_time: 0,
+            subsidy: Default::default(),
+            clock: Default::default(),
+            failover: FailoverParams {
+                backups: backups.iter().map(|b| b.key().to_string()).collect(),
+                handoff_after: handoff as u64,
+            },
+        };
+        auth(
+            secp,
+            host.host(secp, BroadcastByHost::GameSetup(setup), vec![], t),
+        )
+    }
+
+    fn hashes(followed: Vec<&HostEnvelope>) -> Vec<CanonicalEnvelopeHash> {
+        followed
+            .iter()
+            .map(|e| e.canonicalized_hash_ref())
+            .collect()
+    }
+
+    #[test]
+    fn test_follow_custody() {
+        let secp = Secp256k1::new();
+        let (mut host, mut first, mut second) =
+            (Chain::new(&secp), Chain::new(&secp), Chain::new(&secp));
+        let handoff = 1000;
+        let t = 1_000_000;
+        let h0 = genesis(&secp, &mut host, &[&first, &second], handoff, t);
+        let h1 = auth(&secp, host.host(&secp, heartbeat(), vec![], t + 10));
+        // the host comes back after it could have been replaced
+        let h2 = auth(
+            &secp,
+            host.host(&secp, heartbeat(), vec![], t + 10 + handoff),
+        );
+        let custody = Custody::for_host_genesis(&h0).expect("Valid Failover");
+        assert_eq!(custody.keys, vec![host.key(), first.key(), second.key()]);
+        let none = BTreeMap::new();
+        assert_eq!(custody.after(0, &h0, Some(&h1), &none), Step::Continue);
+        // nobody took over, so the host carries on
+        assert_eq!(custody.after(0, &h1, Some(&h2), &none), Step::Continue);
+        assert_eq!(custody.after(0, &h2, None, &none), Step::Stop);
+
+        let host_chain = vec![h0.clone(), h1.clone(), h2.clone()];
+        let mut backups = BTreeMap::new();
+        assert_eq!(
+            hashes(custody.follow(&host_chain, &backups)),
+            hashes(vec![&h0, &h1, &h2])
+        );
+        // each backup may take over once the host has been quiet long enough
+        assert_eq!(custody.standing(0, &h2), Standing::Custodian);
+        assert_eq!(
+            custody.standing(1, &h2),
+            Standing::Behind {
+                from: 0,
+                due_ms: t + 10 + 2 * handoff
+            }
+        );
+        assert_eq!(
+            custody.standing(2, &h2),
+            Standing::Behind {
+                from: 0,
+                due_ms: t + 10 + 3 * handoff
+            }
+        );
+
+        // the second in line must wait twice as long to skip the first
+        let early = take_over(&secp, &mut second, &host, &h1, t + 10 + handoff);
+        assert!(!custody.takes_over(0, &h1, 2, &early));
+        let late = take_over(&secp, &mut second, &host, &h1, t + 10 + 2 * handoff);
+        assert!(custody.takes_over(0, &h1, 2, &late));
+        // and must name the custodian's last envelope
+        assert!(!custody.takes_over(0, &h0, 2, &late));
+
+        let s1 = auth(
+            &secp,
+            second.host(&secp, heartbeat(), vec![], t + 3 * handoff),
+        );
+        backups.insert(second.key(), vec![early, late.clone(), s1.clone()]);
+        assert_eq!(
+            hashes(custody.follow(&host_chain, &backups)),
+            hashes(vec![&h0, &h1, &late, &s1])
+        );
+        // the TakeOver wins over h2, and custody passes the first by
+        assert_eq!(custody.standing(2, &s1), Standing::Custodian);
+        assert_eq!(custody.standing(1, &s1), Standing::Passed);
+        assert_eq!(custody.standing(0, &s1), Standing::Passed);
+
+        // the first in line wins when both take over
+        let f0 = take_over(&secp, &mut first, &host, &h1, t + 10 + 3 * handoff);
+        backups.insert(first.key(), vec![f0.clone()]);
+        assert_eq!(
+            hashes(custody.follow(&host_chain, &backups)),
+            hashes(vec![&h0, &h1, &f0])
+        );
+    }
+
+    #[test]
+    fn test_take_over_ends_custody() {
+        let secp = Secp256k1::new();
+        let (mut host, mut first) = (Chain::new(&secp), Chain::new(&secp));
+        let handoff = 1000;
+        let t = 1_000_000;
+        let h0 = genesis(&secp, &mut host, &[&first], handoff, t);
+        let h1 = auth(&secp, host.host(&secp, heartbeat(), vec![], t + 10));
+        // the host never goes quiet, but the backup only hears of h1
+        let h2 = auth(
+            &secp,
+            host.host(&secp, heartbeat(), vec![], t + 10 + handoff - 1),
+        );
+        let h3 = auth(
+            &secp,
+            host.host(&secp, heartbeat(), vec![], t + 10 + 2 * handoff - 2),
+        );
+        let f0 = take_over(&secp, &mut first, &host, &h1, t + 10 + handoff);
+        let f1 = auth(
+            &secp,
+            first.host(&secp, heartbeat(), vec![], t + 10 + handoff + 1),
+        );
+        let custody = Custody::for_host_genesis(&h0).expect("Valid Failover");
+        let host_chain = vec![h0.clone(), h1.clone(), h2.clone(), h3];
+        let mut backups = BTreeMap::new();
+        backups.insert(first.key(), vec![f0.clone(), f1.clone()]);
+
+        // the TakeOver wins over the host's next envelope
+        assert_eq!(
+            custody.after(0, &h1, Some(&h2), &backups),
+            Step::TakeOver { to: 1, at: 0 }
+        );
+        // so both keep sending, but only the backup is followed after h1
+        assert_eq!(
+            hashes(custody.follow(&host_chain, &backups)),
+            hashes(vec![&h0, &h1, &f0, &f1])
+        );
+    }
+}
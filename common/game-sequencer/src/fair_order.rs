@@ -0,0 +1,173 @@
+// Copyright Judica, Inc 2022
+//
+// This Source Code Form is subject to the terms of the Mozilla Public
+//  License, v. 2.0. If a copy of the MPL was not distributed with this
+//  file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+//! Verifiable ordering of the moves in a sequenced batch.
+//!
+//! The host does not get to pick the order of a batch. Every batch has a seed,
+//! chained from the previous batch's seed and the hashes of the moves in the
+//! batch, so the order is fixed as soon as the host decides what to include.
+//! Moves are scheduled in rounds, one per chain per round, and within a round
+//! by their hash keyed with the seed. Anyone holding the batches and the moves'
+//! envelopes can redo this and check the host's order.
+//!
+//! This does not stop a host from grinding over which moves to include in a
+//! batch, only from choosing the order of the ones it did include.
+
+use attest_messages::CanonicalEnvelopeHash;
+use sapio_bitcoin::hashes::{sha256, Hash, HashEngine};
+use std::collections::{BTreeMap, VecDeque};
+
+const TAG: &[u8] = b"mine-with-friends/fair-order";
+
+/// A move to be ordered: the genesis of its chain, its height on that chain,
+/// and its hash
+pub type Orderable = (CanonicalEnvelopeHash, i64, CanonicalEnvelopeHash);
+
+/// The seed chain over one sequencer's batches
+#[derive(Clone, Debug)]
+pub struct FairOrder {
+    seed: sha256::Hash,
+}
+
+impl Default for FairOrder {
+    fn default() -> Self {
+        FairOrder {
+            seed: sha256::Hash::hash(TAG),
+        }
+    }
+}
+
+impl FairOrder {
+    /// Moves the seed past a batch, returning the seed the batch is ordered by.
+    /// The seed depends only on which moves are in the batch, not their order.
+    pub fn advance<'a, I>(&mut self, batch: I) -> sha256::Hash
+    where
+        I: IntoIterator<Item = &'a CanonicalEnvelopeHash>,
+    {
+        let mut hashes: Vec<_> = batch.into_iter().collect();
+        hashes.sort();
+        let mut engine = sha256::Hash::engine();
+        engine.input(TAG);
+        engine.input(&self.seed[..]);
+        for h in hashes {
+            engine.input(h.as_ref());
+        }
+        self.seed = sha256::Hash::from_engine(engine);
+        self.seed
+    }
+
+    /// Orders a batch, moving the seed past it
+    pub fn order<I>(&mut self, batch: I) -> VecDeque<CanonicalEnvelopeHash>
+    where
+        I: IntoIterator<Item = Orderable>,
+    {
+        let mut batch: Vec<Orderable> = batch.into_iter().collect();
+        let seed = self.advance(batch.iter().map(|(_, _, h)| h));
+        batch.sort_by_key(|(genesis, height, _)| (*genesis, *height));
+        // a move's round is its place among the moves from its chain
+        let mut rounds = BTreeMap::<CanonicalEnvelopeHash, u64>::new();
+        let mut keyed: Vec<_> = batch
+            .into_iter()
+            .map(|(genesis, _, hash)| {
+                let round = rounds.entry(genesis).or_default();
+                *round += 1;
+                (*round, keyed_hash(&seed, &hash), hash)
+            })
+            .collect();
+        keyed.sort();
+        keyed.into_iter().map(|(_, _, h)| h).collect()
+    }
+
+    /// Checks that a batch, in the order it was sequenced in, is in the order
+    /// [`FairOrder::order`] puts it in. Moves the seed past it either way.
+    pub fn verify(&mut self, batch: &[Orderable]) -> bool {
+        let expected = self.order(batch.iter().cloned());
+        expected.iter().eq(batch.iter().map(|(_, _, h)| h))
+    }
+}
+
+fn keyed_hash(seed: &sha256::Hash, hash: &CanonicalEnvelopeHash) -> sha256::Hash {
+    let mut engine = sha256::Hash::engine();
+    engine.input(&seed[..]);
+    engine.input(hash.as_ref());
+    sha256::Hash::from_engine(engine)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use sapio_bitcoin::hashes::hex::ToHex;
+
+    fn hash(i: u8) -> CanonicalEnvelopeHash {
+        serde_json::from_value(sha256::Hash::hash(&[i]).to_hex().into()).unwrap()
+    }
+
+    fn batch() -> Vec<Orderable> {
+        let (a, b, c) = (hash(100), hash(101), hash(102));
+        vec![
+            (a, 4, hash(0)),
+            (a, 5, hash(1)),
+            (a, 6, hash(2)),
+            (b, 0, hash(3)),
+            (c, 9, hash(4)),
+            (c, 10, hash(5)),
+        ]
+    }
+
+    #[test]
+    fn test_round_robin() {
+        let batch = batch();
+        let ordered = FairOrder::default().order(batch.clone());
+        assert_eq!(ordered.len(), batch.len());
+        let position = |h| ordered.iter().position(|o| *o == h).unwrap();
+        // every chain stays in height order...
+        for w in batch.windows(2).filter(|w| w[0].0 == w[1].0) {
+            assert!(position(w[0].2) < position(w[1].2));
+        }
+        // ...and gets one move per round
+        let first_round: Vec<_> = ordered.iter().take(3).collect();
+        assert!(first_round.contains(&&batch[0].2));
+        assert!(first_round.contains(&&batch[3].2));
+        assert!(first_round.contains(&&batch[4].2));
+        assert_eq!(*ordered.back().unwrap(), batch[2].2);
+    }
+
+    #[test]
+    fn test_verify() {
+        let batch = batch();
+        let mut host = FairOrder::default();
+        let first = host.order(batch.clone());
+        let second = host.order(batch[2..].iter().cloned());
+        let lookup = |ordered: &VecDeque<CanonicalEnvelopeHash>| -> Vec<Orderable> {
+            ordered
+                .iter()
+                .map(|h| *batch.iter().find(|o| o.2 == *h).unwrap())
+                .collect()
+        };
+
+        // the order does not depend on the order moves were handed in
+        let mut reversed = batch.clone();
+        reversed.reverse();
+        assert_eq!(FairOrder::default().order(reversed), first);
+
+        let mut verifier = FairOrder::default();
+        assert!(verifier.verify(&lookup(&first)));
+        assert!(verifier.verify(&lookup(&second)));
+
+        // batches are ordered by the seed chain, not only their own moves
+        let mut chained = FairOrder::default();
+        chained.advance(first.iter());
+        assert_ne!(
+            chained.advance(second.iter()),
+            FairOrder::default().advance(second.iter())
+        );
+
+        // swapping any two moves is caught
+        let mut swapped = lookup(&first);
+        swapped.swap(0, 1);
+        assert!(!FairOrder::default().verify(&swapped));
+    }
+}
@@ -4,13 +4,20 @@
 //  License, v. 2.0. If a copy of the MPL was not distributed with this
 //  file, You can obtain one at https://mozilla.org/MPL/2.0/.
 
-use attest_messages::{Authenticated, GenericEnvelope};
+use attest_messages::{Authenticated, CanonicalEnvelopeHash, GenericEnvelope, Header};
 use game_player_messages::ParticipantAction;
-use mine_with_friends_board::MoveEnvelope;
+use mine_with_friends_board::{
+    game::{
+        clock::{ClockError, SequencerTime},
+        FinishReason, GameBoard, GameSetup, MoveRejectReason,
+    },
+    MoveEnvelope,
+};
 use ruma_serde::CanonicalJsonValue;
 use sapio_bitcoin::{psbt::PartiallySignedTransaction, XOnlyPublicKey};
 use schemars::JsonSchema;
 use serde::Deserialize;
+use std::collections::BTreeSet;
 use std::sync::{atomic::AtomicBool, Arc};
 #[cfg(feature = "has_async")]
 use tokio::{
@@ -27,11 +34,21 @@ use crate::DBFetcher;
 #[cfg(feature = "has_async")]
 use crate::GenericSequencer;
 use crate::OfflineSequencer;
+use crate::Sequenced;
 use crate::SequenceingError;
+use crate::Void;
+use crate::{RejectReason, RejectedEnvelope};
 
 #[derive(Deserialize)]
 #[serde(try_from = "OfflineSequencer<ParticipantAction>")]
-pub struct ExtractedMoveEnvelopes(pub Vec<(MoveEnvelope, XOnlyPublicKey)>);
+pub struct ExtractedMoveEnvelopes(
+    pub Vec<(MoveEnvelope, XOnlyPublicKey)>,
+    /// The time the sequencer attested to for each batch, and the hash of the
+    /// envelope attesting it, keyed by the index of the batch's first move
+    pub Vec<(usize, SequencerTime, CanonicalEnvelopeHash)>,
+    /// The GameSetup the sequencer's genesis envelope committed to, if known
+    pub Option<GameSetup>,
+);
 
 impl JsonSchema for ExtractedMoveEnvelopes {
     fn schema_name() -> String {
@@ -43,17 +60,104 @@ impl JsonSchema for ExtractedMoveEnvelopes {
     }
 }
 
-impl ExtractedMoveEnvelopes {}
+impl ExtractedMoveEnvelopes {
+    /// The setup to replay the trace onto. The players, starting amount and
+    /// finish time are the caller's, as agreed to in the contract; how the
+    /// game mines and keeps time come from the setup the sequencer's genesis
+    /// committed to, so the replay pays out the same as the host's board.
+    pub fn game_setup(
+        &self,
+        players: Vec<String>,
+        start_amount: u64,
+        finish_time: u64,
+    ) -> GameSetup {
+        let recorded = self.2.clone().unwrap_or_else(|| GameSetup {
+            players: vec![],
+            start_amount,
+            finish_time,
+            subsidy: Default::default(),
+            clock: Default::default(),
+            failover: Default::default(),
+        });
+        GameSetup {
+            players,
+            start_amount,
+            finish_time,
+            ..recorded
+        }
+    }
+    /// Play every move into the game, attesting to the sequencer's time for
+    /// each batch before its moves. Returns why the game finished, if it did.
+    pub fn replay(self, game: &mut GameBoard) -> Option<FinishReason> {
+        let mut attested = self.1.into_iter().peekable();
+        for (i, (mv, pk)) in self.0.into_iter().enumerate() {
+            while let Some((_, time, batch)) = attested.next_if(|(at, _, _)| *at <= i) {
+                if let Err(e) = game.attest_time(time, batch.as_ref()) {
+                    tracing::warn!(error=?e, "Sequencer Attested to a Bad Time");
+                }
+            }
+            if let Err(MoveRejectReason::GameIsFinished(g)) = game.play(mv, pk.to_string()) {
+                return Some(g);
+            }
+        }
+        // batches after the last move still move the clock
+        for (_, time, batch) in attested {
+            if let Err(e) = game.attest_time(time, batch.as_ref()) {
+                tracing::warn!(error=?e, "Sequencer Attested to a Bad Time");
+            }
+        }
+        None
+    }
+}
+
+/// Attests to the time of one of the sequencer's envelopes, as drivers must
+/// for each [`Sequenced::Attested`] before playing the moves that follow it
+pub fn attest_envelope(
+    game: &mut GameBoard,
+    hash: &CanonicalEnvelopeHash,
+    header: &Header,
+) -> Result<(), ClockError> {
+    game.attest_time(attested_time(header), hash.as_ref())
+}
+
+/// The time the sequencer attested to in one of its envelope headers
+pub fn attested_time(header: &Header) -> SequencerTime {
+    SequencerTime {
+        time_millis: u64::try_from(header.sent_time_ms()).unwrap_or_default(),
+        // unknown checkpoints are recorded as -1
+        bitcoin_height: u64::try_from(header.checkpoints().checkpoints[0].1).ok(),
+    }
+}
 
 impl TryFrom<OfflineSequencer<ParticipantAction>> for ExtractedMoveEnvelopes {
     type Error = SequenceingError<serde_json::Error>;
 
     fn try_from(mut value: OfflineSequencer<ParticipantAction>) -> Result<Self, Self::Error> {
-        let x = value.directly_sequence_map(read_move)?;
-        Ok(ExtractedMoveEnvelopes(x))
+        let batches = value.directly_sequence_batches_map(read_move)?;
+        let mut moves = vec![];
+        let mut attested = vec![];
+        for (batch, sequenced) in value.batches_to_sequence.iter().zip(batches) {
+            if let Some((hash, header)) = &batch.attestation {
+                attested.push((moves.len(), attested_time(header), *hash));
+            }
+            moves.extend(sequenced);
+        }
+        Ok(ExtractedMoveEnvelopes(moves, attested, value.game_setup))
     }
 }
 
+/// The envelopes in an offline sequencer that a [`Sequencer`] for `players`
+/// would not pass on
+pub fn rejected_envelopes(
+    mut value: OfflineSequencer<ParticipantAction>,
+    players: &BTreeSet<XOnlyPublicKey>,
+) -> Result<Vec<RejectedEnvelope>, SequenceingError<Void>> {
+    value.directly_sequence_map(|m| {
+        Ok((!players.contains(&m.header().key()))
+            .then(|| RejectedEnvelope::new(&m, RejectReason::NotAPlayer)))
+    })
+}
+
 type MoveReadFn = fn(
     Authenticated<GenericEnvelope<ParticipantAction>>,
 ) -> Result<Option<(MoveEnvelope, XOnlyPublicKey)>, serde_json::Error>;
@@ -85,27 +189,42 @@ impl Sequencer {
     pub fn new(
         shutdown: Arc<AtomicBool>,
         db_fetcher: Arc<dyn DBFetcher<ParticipantAction>>,
+        players: Option<BTreeSet<XOnlyPublicKey>>,
     ) -> Self {
-        Sequencer(GenericSequencer::new(shutdown, db_fetcher, read_move))
+        Sequencer(GenericSequencer::new(
+            shutdown, db_fetcher, players, read_move,
+        ))
     }
 
     pub async fn run(&self) -> Result<(), JoinError> {
         self.0.clone().run().await
     }
 
-    pub async fn output_move(&self) -> Option<(MoveEnvelope, XOnlyPublicKey)> {
-        self.0.output_move().await.flatten()
+    /// The next move, or attestation to attest before the moves after it
+    pub async fn output_move(&self) -> Option<Sequenced<(MoveEnvelope, XOnlyPublicKey)>> {
+        loop {
+            match self.0.output_move().await? {
+                Sequenced::Attested(hash, header) => {
+                    return Some(Sequenced::Attested(hash, header))
+                }
+                Sequenced::Move(Some(m)) => return Some(Sequenced::Move(m)),
+                // not a move
+                Sequenced::Move(None) => {}
+            }
+        }
     }
 }
 
 type AGP = Authenticated<GenericEnvelope<ParticipantAction>>;
 type EnvReadFn = fn(AGP) -> Result<AGP, serde_json::Error>;
+type SequencedMove = Sequenced<(MoveEnvelope, XOnlyPublicKey)>;
 #[cfg(feature = "has_async")]
 #[derive(Clone)]
 pub struct DemuxedSequencer {
     pub sequencer: Arc<GenericSequencer<EnvReadFn, AGP, serde_json::Error, ParticipantAction>>,
-    pub send_move: UnboundedSender<(MoveEnvelope, XOnlyPublicKey)>,
-    pub recieve_move: Arc<Mutex<UnboundedReceiver<(MoveEnvelope, XOnlyPublicKey)>>>,
+    /// Moves, along with the attestations to play them after
+    pub send_move: UnboundedSender<SequencedMove>,
+    pub recieve_move: Arc<Mutex<UnboundedReceiver<SequencedMove>>>,
     pub send_psbt: UnboundedSender<(PartiallySignedTransaction, String)>,
     pub recieve_psbt: Arc<Mutex<UnboundedReceiver<(PartiallySignedTransaction, String)>>>,
     pub send_custom: UnboundedSender<CanonicalJsonValue>,
@@ -117,12 +236,13 @@ impl DemuxedSequencer {
     pub fn new(
         shutdown: Arc<AtomicBool>,
         db_fetcher: Arc<dyn DBFetcher<ParticipantAction>>,
+        players: Option<BTreeSet<XOnlyPublicKey>>,
     ) -> Self {
         let (send_move, recieve_move) = unbounded_channel();
         let (send_psbt, recieve_psbt) = unbounded_channel();
         let (send_custom, recieve_custom) = unbounded_channel::<CanonicalJsonValue>();
         DemuxedSequencer {
-            sequencer: GenericSequencer::new(shutdown, db_fetcher, Ok),
+            sequencer: GenericSequencer::new(shutdown, db_fetcher, players, Ok),
             send_move,
             recieve_move: Arc::new(Mutex::new(recieve_move)),
             send_psbt,
@@ -144,11 +264,21 @@ impl DemuxedSequencer {
                 let mut listening_custom = true;
                 let mut listening_psbt = true;
                 loop {
-                    if let Some(e) = this.sequencer.output_move().await {
-                        match e.msg() {
+                    match this.sequencer.output_move().await {
+                        Some(Sequenced::Attested(hash, header)) if listening_moves => {
+                            listening_moves = this
+                                .send_move
+                                .send(Sequenced::Attested(hash, header))
+                                .is_ok();
+                        }
+                        // nothing plays the moves any more
+                        Some(Sequenced::Attested(..)) | None => {}
+                        Some(Sequenced::Move(e)) => match e.msg() {
                             ParticipantAction::MoveEnvelope(m) if listening_moves => {
-                                listening_moves =
-                                    this.send_move.send((m.clone(), e.header().key())).is_ok();
+                                listening_moves = this
+                                    .send_move
+                                    .send(Sequenced::Move((m.clone(), e.header().key())))
+                                    .is_ok();
                             }
                             ParticipantAction::Custom(c) if listening_custom => {
                                 listening_custom = this.send_custom.send(c.clone()).is_ok();
@@ -159,8 +289,11 @@ impl DemuxedSequencer {
                                     .send((c.data.0.clone(), c.channel.clone()))
                                     .is_ok();
                             }
-                            _ => {}
-                        }
+                            // the receiver for this kind of message was dropped
+                            _ => this
+                                .sequencer
+                                .reject(RejectedEnvelope::new(&e, RejectReason::WrongChannel)),
+                        },
                     }
                     if !(listening_custom || listening_moves || listening_psbt) {
                         break;
@@ -5,20 +5,26 @@
 //  file, You can obtain one at https://mozilla.org/MPL/2.0/.
 
 #[cfg(feature = "database_access")]
-use attest_database::connection::MsgDB;
+use attest_database::connection::{EnvelopeInserted, MsgDB};
 use attest_messages::AttestEnvelopable;
 use attest_messages::Authenticated;
 use attest_messages::AuthenticationError;
 use attest_messages::CanonicalEnvelopeHash;
 use attest_messages::GenericEnvelope;
+use attest_messages::Header;
+#[cfg(feature = "database_access")]
+use attest_util::clock::Clock;
 use game_host_messages::Peer;
 use game_host_messages::{BroadcastByHost, Channelized};
+use mine_with_friends_board::game::GameSetup;
 use sapio_bitcoin::secp256k1::Secp256k1;
 use sapio_bitcoin::XOnlyPublicKey;
 use schemars::JsonSchema;
 use serde::Deserialize;
 use serde::Serialize;
 use std::collections::hash_map::Entry::{Occupied, Vacant};
+use std::collections::BTreeMap;
+use std::collections::BTreeSet;
 use std::collections::HashMap;
 use std::collections::VecDeque;
 use std::convert::TryFrom;
@@ -28,23 +34,34 @@ use std::sync::atomic::AtomicBool;
 use std::sync::atomic::Ordering;
 use std::sync::Arc;
 use std::time::Duration;
+#[cfg(feature = "database_access")]
+use tokio::sync::broadcast::error::RecvError;
 #[cfg(feature = "has_async")]
 use tokio::{
     spawn,
     sync::{
+        broadcast,
         futures::Notified,
         mpsc::{unbounded_channel, UnboundedReceiver, UnboundedSender},
-        Mutex, Notify,
+        watch, Mutex, Notify,
     },
     task::{spawn_blocking, JoinError, JoinHandle},
-    time::sleep,
 };
 use tracing::debug;
 use tracing::info;
 use tracing::trace;
 use tracing::warn;
+pub mod batching;
+pub mod censorship;
+pub mod custody;
+pub mod fair_order;
 pub mod game_specific;
+pub mod liveness;
+use custody::{Custody, HostEnvelope, Step};
+use fair_order::{FairOrder, Orderable};
 pub use game_specific::*;
+#[cfg(feature = "database_access")]
+use liveness::HostLiveness;
 
 #[derive(Serialize, Deserialize, JsonSchema)]
 #[serde(bound = "M: AttestEnvelopable")]
@@ -53,6 +70,10 @@ where
     M: AttestEnvelopable,
 {
     pub sequencer_envelopes: Vec<GenericEnvelope<Channelized<BroadcastByHost>>>,
+    #[serde(default)]
+    #[schemars(with = "BTreeMap<String, Vec<GenericEnvelope<Channelized<BroadcastByHost>>>>")]
+    pub backup_envelopes:
+        BTreeMap<XOnlyPublicKey, Vec<GenericEnvelope<Channelized<BroadcastByHost>>>>,
     pub msg_cache: HashMap<CanonicalEnvelopeHash, GenericEnvelope<M>>,
 }
 impl<M: AttestEnvelopable> TryFrom<UnauthenticatedRawSequencer<M>> for RawSequencer<M> {
@@ -65,6 +86,19 @@ impl<M: AttestEnvelopable> TryFrom<UnauthenticatedRawSequencer<M>> for RawSequen
                 .iter()
                 .map(|v| v.solemnly_swear_self_authenticated())
                 .collect::<Result<Vec<_>, AuthenticationError>>()?,
+            backup_envelopes: value
+                .backup_envelopes
+                .iter()
+                .map(|(k, chain)| {
+                    Ok((
+                        *k,
+                        chain
+                            .iter()
+                            .map(|v| v.solemnly_swear_self_authenticated())
+                            .collect::<Result<Vec<_>, AuthenticationError>>()?,
+                    ))
+                })
+                .collect::<Result<BTreeMap<_, _>, AuthenticationError>>()?,
             msg_cache: value
                 .msg_cache
                 .iter()
@@ -82,9 +116,34 @@ where
     M: AttestEnvelopable,
 {
     pub sequencer_envelopes: Vec<Authenticated<GenericEnvelope<Channelized<BroadcastByHost>>>>,
+    /// The chains of the game's backup sequencers, see [`custody`]
+    pub backup_envelopes:
+        BTreeMap<XOnlyPublicKey, Vec<Authenticated<GenericEnvelope<Channelized<BroadcastByHost>>>>>,
     pub msg_cache: HashMap<CanonicalEnvelopeHash, Authenticated<GenericEnvelope<M>>>,
 }
 
+impl<M: AttestEnvelopable> From<RawSequencer<M>> for UnauthenticatedRawSequencer<M> {
+    fn from(value: RawSequencer<M>) -> Self {
+        Self {
+            sequencer_envelopes: value
+                .sequencer_envelopes
+                .into_iter()
+                .map(Authenticated::inner)
+                .collect(),
+            backup_envelopes: value
+                .backup_envelopes
+                .into_iter()
+                .map(|(k, chain)| (k, chain.into_iter().map(Authenticated::inner).collect()))
+                .collect(),
+            msg_cache: value
+                .msg_cache
+                .into_iter()
+                .map(|(h, e)| (h, e.inner()))
+                .collect(),
+        }
+    }
+}
+
 impl<M> JsonSchema for RawSequencer<M>
 where
     M: AttestEnvelopable,
@@ -105,6 +164,9 @@ pub enum SequencerError {
     MissingTip,
     Gap,
     AuthenticationError,
+    /// A backup sequencer in the GameSetup is not a valid key, or is listed
+    /// twice
+    InvalidBackupKey,
 }
 
 impl Display for SequencerError {
@@ -114,53 +176,178 @@ impl Display for SequencerError {
 }
 impl std::error::Error for SequencerError {}
 
+/// Why the sequencer did not pass on an envelope the host sequenced
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone, PartialEq, Eq)]
+pub enum RejectReason {
+    /// The envelope's message could not be read
+    Deserialization(String),
+    /// Nothing is reading the kind of message the envelope carries
+    WrongChannel,
+    /// The envelope was signed by a key that is not in the game
+    NotAPlayer,
+}
+
+/// An envelope the host sequenced that was not passed on
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone)]
+pub struct RejectedEnvelope {
+    pub hash: CanonicalEnvelopeHash,
+    #[schemars(with = "String")]
+    pub key: XOnlyPublicKey,
+    pub height: i64,
+    pub reason: RejectReason,
+}
+
+impl RejectedEnvelope {
+    pub fn new<M: AttestEnvelopable>(envelope: &GenericEnvelope<M>, reason: RejectReason) -> Self {
+        RejectedEnvelope {
+            hash: envelope.canonicalized_hash_ref(),
+            key: envelope.header().key(),
+            height: envelope.header().height(),
+            reason,
+        }
+    }
+}
+
+/// Checks a sequencer's chain is complete from its genesis and signed by one
+/// key
+fn check_sequencer_chain(
+    chain: &[Authenticated<GenericEnvelope<Channelized<BroadcastByHost>>>],
+) -> Result<(), SequencerError> {
+    if let Some(false) = chain.first().map(|v| v.header().height() == 0) {
+        return Err(SequencerError::MissingTip);
+    }
+    if chain
+        .windows(2)
+        .any(|s| s[0].header().height() + 1 != s[1].header().height())
+    {
+        return Err(SequencerError::Gap);
+    }
+    if chain
+        .windows(2)
+        .any(|s| s[0].header().key() != s[1].header().key())
+    {
+        return Err(SequencerError::MessageFromWrongEntity);
+    }
+    Ok(())
+}
+
+/// The moves of a batch the host sequenced that are played: all of them if
+/// the batch is within the [`batching`] limits and was sequenced in its fair
+/// order, none if not. Online and offline sequencing both check batches here,
+/// so a rejected batch is handled the same by both: it is still attested to,
+/// and the seed moves past it so the batches after it are checked the same
+/// way.
+fn checked_moves(
+    fair_order: &mut FairOrder,
+    batch: &VecDeque<CanonicalEnvelopeHash>,
+    orderable: &[Orderable],
+) -> Option<VecDeque<CanonicalEnvelopeHash>> {
+    if batch.len() > batching::MAX_BATCH_SIZE || !batching::within_limits(orderable) {
+        fair_order.advance(batch);
+        return None;
+    }
+    fair_order.verify(orderable).then(|| batch.clone())
+}
+
 impl<M: AttestEnvelopable> TryFrom<RawSequencer<M>> for OfflineSequencer<M> {
     type Error = SequencerError;
 
     fn try_from(value: RawSequencer<M>) -> Result<Self, Self::Error> {
-        if let Some(false) = value
-            .sequencer_envelopes
-            .first()
-            .map(|v| v.header().height() == 0)
-        {
-            return Err(SequencerError::MissingTip);
-        }
-        if value
-            .sequencer_envelopes
-            .windows(2)
-            .any(|s| s[0].header().height() + 1 != s[1].header().height())
-        {
-            return Err(SequencerError::Gap);
-        }
-        if value
-            .sequencer_envelopes
-            .windows(2)
-            .any(|s| s[0].header().key() != s[1].header().key())
-        {
-            return Err(SequencerError::MessageFromWrongEntity);
-        }
-        let mut batches_to_sequence: Vec<VecDeque<CanonicalEnvelopeHash>> = vec![];
-        for envelope in &value.sequencer_envelopes {
-            let v = envelope.msg();
-            match &v.data {
-                BroadcastByHost::Sequence(s) => batches_to_sequence.push(s.clone()),
-                BroadcastByHost::NewPeer(_) => {}
-                BroadcastByHost::Heartbeat => {}
-                BroadcastByHost::GameSetup(_) => {}
+        check_sequencer_chain(&value.sequencer_envelopes)?;
+        for (key, chain) in &value.backup_envelopes {
+            check_sequencer_chain(chain)?;
+            if chain.iter().any(|e| e.header().key() != *key) {
+                return Err(SequencerError::MessageFromWrongEntity);
             }
         }
+        let followed = match value.sequencer_envelopes.first() {
+            Some(genesis) => Custody::for_host_genesis(genesis)?
+                .follow(&value.sequencer_envelopes, &value.backup_envelopes),
+            None => vec![],
+        };
+        let game_setup = match value.sequencer_envelopes.first().map(|e| &e.msg().data) {
+            Some(BroadcastByHost::GameSetup(g)) => Some(g.clone()),
+            _ => None,
+        };
+        let mut batches_to_sequence: Vec<Batch> = vec![];
+        let mut fair_order = FairOrder::default();
+        for envelope in followed {
+            let attestation = Some((envelope.canonicalized_hash_ref(), envelope.header().clone()));
+            let batch = match &envelope.msg().data {
+                BroadcastByHost::Sequence(s) => s,
+                BroadcastByHost::Heartbeat(_) => {
+                    batches_to_sequence.push(Batch {
+                        attestation,
+                        moves: VecDeque::new(),
+                    });
+                    continue;
+                }
+                BroadcastByHost::NewPeer(_) => continue,
+                BroadcastByHost::GameSetup(_) => continue,
+                BroadcastByHost::TakeOver { .. } => continue,
+                BroadcastByHost::Quarantine(_) => continue,
+            };
+            let orderable: Option<Vec<Orderable>> = batch
+                .iter()
+                .map(|h| {
+                    let e = value.msg_cache.get(h)?;
+                    Some((e.get_genesis_hash(), e.header().height(), *h))
+                })
+                .collect();
+            let moves = match orderable {
+                Some(o) => checked_moves(&mut fair_order, batch, &o).unwrap_or_default(),
+                // Can't be checked without the moves, but then it can't be
+                // sequenced either
+                None => {
+                    fair_order.advance(batch);
+                    batch.clone()
+                }
+            };
+            batches_to_sequence.push(Batch { attestation, moves });
+        }
         Ok(OfflineSequencer {
             msg_cache: value.msg_cache,
             batches_to_sequence,
+            game_setup,
         })
     }
 }
 
+/// The moves one of the sequencer's envelopes sequenced, and the hash and
+/// header of that envelope, which the game attests to before playing them.
+/// Heartbeats are batches without any moves.
+#[derive(Clone, Debug)]
+pub struct Batch {
+    /// None for batches that did not come from a sequencer's chain
+    pub attestation: Option<(CanonicalEnvelopeHash, Header)>,
+    pub moves: VecDeque<CanonicalEnvelopeHash>,
+}
+
+impl From<VecDeque<CanonicalEnvelopeHash>> for Batch {
+    fn from(moves: VecDeque<CanonicalEnvelopeHash>) -> Self {
+        Batch {
+            attestation: None,
+            moves,
+        }
+    }
+}
+
+/// What a sequencer outputs, in order
+#[derive(Clone, Debug)]
+pub enum Sequenced<R> {
+    /// The hash and header of one of the sequencer's Sequence or Heartbeat
+    /// envelopes, whose time must be attested before playing what follows
+    Attested(CanonicalEnvelopeHash, Box<Header>),
+    Move(R),
+}
+
 #[derive(Deserialize)]
 #[serde(try_from = "RawSequencer<M>")]
 #[serde(bound = "M: AttestEnvelopable")]
 pub struct OfflineSequencer<M: AttestEnvelopable> {
-    pub batches_to_sequence: Vec<VecDeque<CanonicalEnvelopeHash>>,
+    pub batches_to_sequence: Vec<Batch>,
+    /// The GameSetup in the sequencer's genesis envelope, if known
+    pub game_setup: Option<GameSetup>,
     pub msg_cache: HashMap<CanonicalEnvelopeHash, Authenticated<GenericEnvelope<M>>>,
 }
 
@@ -205,9 +392,25 @@ impl<M: AttestEnvelopable> OfflineSequencer<M> {
     where
         F: Fn(Authenticated<GenericEnvelope<M>>) -> Result<Option<R>, E>,
     {
-        let mut v = vec![];
+        Ok(self
+            .directly_sequence_batches_map(f)?
+            .into_iter()
+            .flatten()
+            .collect())
+    }
+    /// Like directly_sequence_map, but keeps the results of each batch
+    /// separate so they can be matched up with its attestation
+    pub fn directly_sequence_batches_map<F, R, E>(
+        &mut self,
+        f: F,
+    ) -> Result<Vec<Vec<R>>, SequenceingError<E>>
+    where
+        F: Fn(Authenticated<GenericEnvelope<M>>) -> Result<Option<R>, E>,
+    {
+        let mut batches = vec![];
         for batch in &self.batches_to_sequence {
-            for h in batch {
+            let mut v = vec![];
+            for h in &batch.moves {
                 if let Some(e) = self.msg_cache.remove(h) {
                     if let Some(r) = f(e)? {
                         v.push(r);
@@ -216,9 +419,10 @@ impl<M: AttestEnvelopable> OfflineSequencer<M> {
                     return Err(SequenceingError::MissingEnvelope(*h));
                 }
             }
+            batches.push(v);
         }
 
-        Ok(v)
+        Ok(batches)
     }
 }
 
@@ -247,6 +451,7 @@ where
         }
         Ok(OfflineSequencer {
             batches_to_sequence,
+            game_setup: None,
             msg_cache: c,
         })
     }
@@ -254,7 +459,7 @@ where
 
 #[cfg(feature = "has_async")]
 pub struct OfflineDBFetcher<M: AttestEnvelopable> {
-    batches_to_sequence: Arc<Mutex<UnboundedReceiver<VecDeque<CanonicalEnvelopeHash>>>>,
+    batches_to_sequence: Arc<Mutex<UnboundedReceiver<Batch>>>,
     msg_cache: Arc<Mutex<HashMap<CanonicalEnvelopeHash, Authenticated<GenericEnvelope<M>>>>>,
     new_msgs_in_cache: Arc<Notify>,
 }
@@ -265,7 +470,7 @@ where
     M: AttestEnvelopable,
 {
     pub fn new(
-        batches_to_sequence: Vec<VecDeque<CanonicalEnvelopeHash>>,
+        batches_to_sequence: Vec<Batch>,
         msg_cache: HashMap<CanonicalEnvelopeHash, Authenticated<GenericEnvelope<M>>>,
     ) -> Self {
         let (tx, rx) = unbounded_channel();
@@ -287,9 +492,7 @@ where
 }
 #[cfg(feature = "has_async")]
 impl<M: AttestEnvelopable> DBFetcher<M> for OfflineDBFetcher<M> {
-    fn batches_to_sequence(
-        &self,
-    ) -> Arc<Mutex<UnboundedReceiver<VecDeque<CanonicalEnvelopeHash>>>> {
+    fn batches_to_sequence(&self) -> Arc<Mutex<UnboundedReceiver<Batch>>> {
         self.batches_to_sequence.clone()
     }
 
@@ -304,18 +507,23 @@ impl<M: AttestEnvelopable> DBFetcher<M> for OfflineDBFetcher<M> {
     }
 }
 
+/// Follows the DB as envelopes are inserted into it. Insertions made through
+/// the same MsgDB wake the fetcher straight away; the two periods only bound
+/// how long it waits for ones made by other processes sharing the database.
 #[cfg(feature = "database_access")]
 pub struct OnlineDBFetcher<M: AttestEnvelopable> {
     poll_sequencer_period: Duration,
     shutdown: Arc<AtomicBool>,
     db: MsgDB,
-    schedule_batches_to_sequence: UnboundedSender<VecDeque<CanonicalEnvelopeHash>>,
-    batches_to_sequence: Arc<Mutex<UnboundedReceiver<VecDeque<CanonicalEnvelopeHash>>>>,
+    schedule_batches_to_sequence: UnboundedSender<Batch>,
+    batches_to_sequence: Arc<Mutex<UnboundedReceiver<Batch>>>,
     oracle_key: XOnlyPublicKey,
     msg_cache: Arc<Mutex<HashMap<CanonicalEnvelopeHash, Authenticated<GenericEnvelope<M>>>>>,
     rebuild_db_period: Duration,
     is_running: AtomicBool,
     new_msgs_in_cache: Arc<Notify>,
+    liveness: watch::Sender<Option<HostLiveness>>,
+    clock: Clock,
 }
 #[cfg(feature = "database_access")]
 impl<M> OnlineDBFetcher<M>
@@ -328,6 +536,25 @@ where
         rebuild_db_period: Duration,
         oracle_key: XOnlyPublicKey,
         db: MsgDB,
+    ) -> Arc<Self> {
+        Self::new_with_clock(
+            shutdown,
+            poll_sequencer_period,
+            rebuild_db_period,
+            oracle_key,
+            db,
+            Clock::System,
+        )
+    }
+    /// Like [`OnlineDBFetcher::new`], but waits out the polling periods on
+    /// `clock`
+    pub fn new_with_clock(
+        shutdown: Arc<AtomicBool>,
+        poll_sequencer_period: Duration,
+        rebuild_db_period: Duration,
+        oracle_key: XOnlyPublicKey,
+        db: MsgDB,
+        clock: Clock,
     ) -> Arc<Self> {
         let (schedule_batches_to_sequence, batches_to_sequence) = unbounded_channel();
         let batches_to_sequence = Arc::new(Mutex::new(batches_to_sequence));
@@ -342,9 +569,21 @@ where
             new_msgs_in_cache: Default::default(),
             rebuild_db_period,
             is_running: Default::default(),
+            liveness: watch::channel(None).0,
+            clock,
         })
     }
 
+    /// Follows what was last heard from the sequencer with custody of the
+    /// game, updated as its envelopes are read
+    pub fn subscribe_liveness(&self) -> watch::Receiver<Option<HostLiveness>> {
+        self.liveness.subscribe()
+    }
+    fn heard(&self, envelope: &HostEnvelope) {
+        let next = HostLiveness::heard(self.liveness.borrow().as_ref(), envelope);
+        self.liveness.send_replace(Some(next));
+    }
+
     pub async fn run(self: Arc<Self>) {
         let last =
             self.is_running
@@ -360,38 +599,149 @@ where
     fn should_shutdown(&self) -> bool {
         self.shutdown.load(Ordering::Relaxed)
     }
+    /// Waits until an envelope matching `filter` is inserted, or at most
+    /// `fallback`
+    async fn wait_for_insert<F>(
+        &self,
+        inserted: &mut broadcast::Receiver<EnvelopeInserted>,
+        fallback: Duration,
+        filter: F,
+    ) where
+        F: Fn(&EnvelopeInserted) -> bool,
+    {
+        let wait = async {
+            loop {
+                match inserted.recv().await {
+                    Ok(e) if filter(&e) => return,
+                    Ok(_) => {}
+                    // we missed some, any of which might have matched
+                    Err(RecvError::Lagged(_)) => return,
+                    Err(RecvError::Closed) => std::future::pending().await,
+                }
+            }
+        };
+        self.clock.timeout(fallback, wait).await;
+    }
     /// Goes through the oracles commitments in order
     fn start_sequencer(self: Arc<Self>) -> JoinHandle<()> {
         spawn(async move {
+            // subscribe before the first read so no insert is missed
+            let mut inserted = self.db.subscribe_inserted();
+            // which sequencer has custody of the game (see [`custody`]), and
+            // how far its chain has been read
+            let mut custody = Custody::single(self.oracle_key);
+            let mut position = 0;
             let mut count = 0;
+            let mut last: Option<HostEnvelope> = None;
+            let mut fair_order = FairOrder::default();
             while !self.should_shutdown() {
                 'check: while !self.should_shutdown() {
-                    let msg: Result<
-                        Option<Authenticated<GenericEnvelope<Channelized<BroadcastByHost>>>>,
-                        _,
-                    > = {
+                    let key = custody.keys[position];
+                    let msg: Result<Option<HostEnvelope>, _> = {
                         let handle = self.db.get_handle_read().await;
-                        let oracle_key = self.oracle_key;
-                        spawn_blocking(move || {
-                            handle.get_message_at_height_for_user(oracle_key, count)
-                        })
-                        .await
-                        .expect("Panic Free")
+                        spawn_blocking(move || handle.get_message_at_height_for_user(key, count))
+                            .await
+                            .expect("Panic Free")
                     };
-                    match msg {
-                        Ok(None) => {
-                            debug!(key=?self.oracle_key, sleep_for = ?self.poll_sequencer_period, "No New Messages Sleeping...");
-                            sleep(self.poll_sequencer_period).await;
+                    let msg = match msg {
+                        Ok(msg) => msg,
+                        Err(e) => {
+                            warn!(error=?e, "Database Failure");
                             continue 'check;
                         }
-                        Ok(Some(envelope)) => {
+                    };
+                    // the same decision the offline sequencer makes in
+                    // [`Custody::follow`]
+                    let step = match &last {
+                        Some(prev) => {
+                            let backups = match self.load_backups(&custody, position).await {
+                                Some(backups) => backups,
+                                None => continue 'check,
+                            };
+                            match custody.after(position, prev, msg.as_ref(), &backups) {
+                                Step::TakeOver { to, at } => {
+                                    let takeover = backups[&custody.keys[to]][at].clone();
+                                    info!(from=?key, to=?custody.keys[to], "Backup Sequencer Took Over");
+                                    position = to;
+                                    count = takeover.header().height() as u64 + 1;
+                                    self.heard(&takeover);
+                                    last = Some(takeover);
+                                    continue 'check;
+                                }
+                                step => step,
+                            }
+                        }
+                        None => Step::Continue,
+                    };
+                    match msg {
+                        Some(envelope) if step == Step::Continue => {
+                            self.heard(&envelope);
                             match &envelope.msg().data {
-                                BroadcastByHost::Heartbeat => {}
-                                BroadcastByHost::Sequence(s) => {
-                                    info!(key=?self.oracle_key, n_msg = s.len(), "Got Batch to Sequence");
-                                    if self.schedule_batches_to_sequence.send(s.clone()).is_err() {
+                                BroadcastByHost::Heartbeat(_) => {
+                                    let batch = Batch {
+                                        attestation: Some((
+                                            envelope.canonicalized_hash_ref(),
+                                            envelope.header().clone(),
+                                        )),
+                                        moves: VecDeque::new(),
+                                    };
+                                    if self.schedule_batches_to_sequence.send(batch).is_err() {
                                         return;
+                                    }
+                                }
+                                BroadcastByHost::Sequence(s) => {
+                                    info!(?key, n_msg = s.len(), "Got Batch to Sequence");
+                                    // The order can only be checked once we
+                                    // have every move in the batch
+                                    let moves: Result<Vec<Authenticated<GenericEnvelope<M>>>, _> = {
+                                        let handle = self.db.get_handle_read().await;
+                                        let s = s.clone();
+                                        spawn_blocking(move || handle.messages_by_hash(s.iter()))
+                                            .await
+                                            .expect("Panic Free")
                                     };
+                                    let moves = match moves {
+                                        Ok(moves) => moves,
+                                        Err(e) => {
+                                            debug!(error=?e, "Batch Incomplete, Waiting...");
+                                            self.wait_for_insert(
+                                                &mut inserted,
+                                                self.poll_sequencer_period,
+                                                |e| s.contains(&e.hash),
+                                            )
+                                            .await;
+                                            continue 'check;
+                                        }
+                                    };
+                                    let orderable: Vec<Orderable> = moves
+                                        .iter()
+                                        .map(|e| {
+                                            (
+                                                e.get_genesis_hash(),
+                                                e.header().height(),
+                                                e.canonicalized_hash_ref(),
+                                            )
+                                        })
+                                        .collect();
+                                    let moves = checked_moves(&mut fair_order, s, &orderable)
+                                        .unwrap_or_else(|| {
+                                            warn!(
+                                                ?key,
+                                                height = count,
+                                                "Sequencer Batch Over Limits or Not in Fair Order, Skipping Its Moves"
+                                            );
+                                            VecDeque::new()
+                                        });
+                                    let batch = Batch {
+                                        attestation: Some((
+                                            envelope.canonicalized_hash_ref(),
+                                            envelope.header().clone(),
+                                        )),
+                                        moves,
+                                    };
+                                    if self.schedule_batches_to_sequence.send(batch).is_err() {
+                                        return;
+                                    }
                                 }
                                 BroadcastByHost::NewPeer(Peer { service_url, port }) => {
                                     let handle = self.db.get_handle_all().await;
@@ -411,44 +761,117 @@ where
                                     })
                                     .await;
                                 }
+                                BroadcastByHost::GameSetup(_) if position == 0 && count == 0 => {
+                                    custody = Custody::for_host_genesis(&envelope)
+                                        .unwrap_or_else(|e| {
+                                            warn!(error=?e, "Invalid Failover, Not Following Backups");
+                                            Custody::single(self.oracle_key)
+                                        });
+                                }
                                 BroadcastByHost::GameSetup(_) => {}
+                                BroadcastByHost::TakeOver { .. } => {}
+                                BroadcastByHost::Quarantine(_) => {}
                             }
                             count += 1;
+                            last = Some(envelope);
                         }
-                        Err(e) => {
-                            warn!(error=?e, "Database Failure")
+                        // Nothing new from the custodian
+                        _ => {
+                            debug!(?key, "No New Messages, Waiting...");
+                            self.wait_for_insert(&mut inserted, self.poll_sequencer_period, |e| {
+                                custody.keys.contains(&e.key)
+                            })
+                            .await;
+                            continue 'check;
                         }
                     }
                 }
             }
         })
     }
-    /// This task builds a HashMap of all unprocessed envelopes regularly
+    /// Loads the chains of the backups behind the custodian at `position`
+    async fn load_backups(
+        &self,
+        custody: &Custody,
+        position: usize,
+    ) -> Option<BTreeMap<XOnlyPublicKey, Vec<HostEnvelope>>> {
+        if !custody.has_backups() {
+            return Some(BTreeMap::new());
+        }
+        let handle = self.db.get_handle_read().await;
+        let keys = custody.keys[position + 1..].to_vec();
+        let chains = spawn_blocking(move || {
+            keys.iter()
+                .map(|k| {
+                    handle
+                        .load_all_messages_for_user_by_key_connected(k)
+                        .map(|chain| (*k, chain))
+                })
+                .collect::<Result<BTreeMap<_, Vec<HostEnvelope>>, _>>()
+        })
+        .await
+        .expect("Panic Free");
+        chains.map_err(|e| warn!(error=?e, "Database Failure")).ok()
+    }
+    /// Whether the oracle's chain subscribes to any chain commit group here
+    async fn oracle_has_groups(&self) -> bool {
+        let handle = self.db.get_handle_read().await;
+        let oracle_key = self.oracle_key;
+        let res = spawn_blocking(move || {
+            handle
+                .get_message_at_height_for_user::<Channelized<BroadcastByHost>>(oracle_key, 0)
+                .and_then(|genesis| match genesis {
+                    Some(g) => handle
+                        .get_all_chain_commit_groups_for_chain(g.canonicalized_hash_ref())
+                        .map(|groups| !groups.is_empty()),
+                    None => Ok(false),
+                })
+        })
+        .await
+        .expect("Panic Free");
+        res.unwrap_or_else(|e| {
+            warn!(error=?e, "Checking Chain Commit Groups Failed");
+            false
+        })
+    }
+    /// This task keeps a HashMap of all unprocessed envelopes, reading the new
+    /// ones in whenever any are inserted
     fn start_envelope_db_fetcher(self: Arc<Self>) -> JoinHandle<()> {
         spawn(async move {
-            let mut newer = None;
+            let mut inserted = self.db.subscribe_inserted();
+            let mut cursor = EnvelopeCursor::All(None);
             while !self.should_shutdown() {
-                let newer_before = newer;
+                // Until the oracle's chain commit group is known here we have
+                // to load every message. Once it is, only the group's messages
+                // are loaded, starting over since the group may have older
+                // messages we skipped as invalid.
+                if let EnvelopeCursor::All(_) = cursor {
+                    if self.oracle_has_groups().await {
+                        info!(key=?self.oracle_key, "Filtering Envelopes by Chain Commit Group");
+                        cursor = EnvelopeCursor::Groups(0);
+                    }
+                }
+                let cursor_before = cursor;
                 {
                     let mut env = self.msg_cache.clone().lock_owned().await;
                     let handle = self.db.get_handle_read().await;
-                    // it's fine for us to filter for *only* game moves in our
-                    // DB...  Ideally, we'd be able to use the group filter as
-                    // well, but currently we don't have that set up properly
-                    // for the host, so instead we load all moves.
-                    let mut newer_copy = newer;
-                    let (res, newer_overwrite) = spawn_blocking(move || {
-                        let c = handle.get_all_messages_collect_into_inconsistent_skip_invalid(
-                            &mut newer_copy,
-                            &mut env,
-                            true,
-                        );
-
-                        (c, newer_copy)
+                    let oracle_key = self.oracle_key;
+                    let (res, cursor_after) = spawn_blocking(move || {
+                        let res = match &mut cursor {
+                            EnvelopeCursor::All(newer) => handle
+                                .get_all_messages_collect_into_inconsistent_skip_invalid(
+                                    newer, &mut env, true,
+                                ),
+                            EnvelopeCursor::Groups(newer) => handle
+                                .get_all_chain_commit_group_members_new_envelopes_for_chain_into_inconsistent_skip_invalid(
+                                    oracle_key, newer, &mut env, true,
+                                ),
+                        };
+                        (res, cursor)
                     })
                     .await
                     .expect("Panic Free");
-                    newer = newer_overwrite;
+                    cursor = cursor_after;
 
                     if let Err(e) = res {
                         warn!(error=?e, "DB Fetching Failed");
@@ -456,23 +879,32 @@ where
                     }
                 }
 
-                if newer_before != newer {
-                    info!(key=?self.oracle_key, new=newer, before=newer_before, "Got New Messages");
+                if cursor_before != cursor {
+                    info!(key=?self.oracle_key, new=?cursor, before=?cursor_before, "Got New Messages");
                     self.new_msgs_in_cache.notify_waiters();
                 }
-                debug!(key=?self.oracle_key, wait_till=?self.rebuild_db_period, "Waiting to scan DB Again");
-                sleep(self.rebuild_db_period).await;
+                debug!(key=?self.oracle_key, "Waiting for New Messages");
+                self.wait_for_insert(&mut inserted, self.rebuild_db_period, |_| true)
+                    .await;
             }
             self.new_msgs_in_cache.notify_waiters();
         })
     }
 }
 
+/// How far the envelope fetcher has read the DB
+#[cfg(feature = "database_access")]
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+enum EnvelopeCursor {
+    /// Every message
+    All(Option<i64>),
+    /// Only messages of the oracle's chain commit groups
+    Groups(i64),
+}
+
 #[cfg(feature = "database_access")]
 impl<M: AttestEnvelopable> DBFetcher<M> for OnlineDBFetcher<M> {
-    fn batches_to_sequence(
-        &self,
-    ) -> Arc<Mutex<UnboundedReceiver<VecDeque<CanonicalEnvelopeHash>>>> {
+    fn batches_to_sequence(&self) -> Arc<Mutex<UnboundedReceiver<Batch>>> {
         self.batches_to_sequence.clone()
     }
     fn msg_cache(
@@ -491,8 +923,7 @@ pub trait DBFetcher<M>: Send + Sync
 where
     M: AttestEnvelopable,
 {
-    fn batches_to_sequence(&self)
-        -> Arc<Mutex<UnboundedReceiver<VecDeque<CanonicalEnvelopeHash>>>>;
+    fn batches_to_sequence(&self) -> Arc<Mutex<UnboundedReceiver<Batch>>>;
     fn msg_cache(
         &self,
     ) -> Arc<Mutex<HashMap<CanonicalEnvelopeHash, Authenticated<GenericEnvelope<M>>>>>;
@@ -502,15 +933,23 @@ where
 pub struct GenericSequencer<F, R, E, M: AttestEnvelopable> {
     db_fetcher: Arc<dyn DBFetcher<M>>,
     shutdown: Arc<AtomicBool>,
-    push_next_envelope: UnboundedSender<Authenticated<GenericEnvelope<M>>>,
-    output_envelope: Mutex<UnboundedReceiver<Authenticated<GenericEnvelope<M>>>>,
-    push_next_move: UnboundedSender<R>,
-    output_move: Mutex<UnboundedReceiver<R>>,
+    push_next_envelope: UnboundedSender<Sequenced<Authenticated<GenericEnvelope<M>>>>,
+    output_envelope: Mutex<UnboundedReceiver<Sequenced<Authenticated<GenericEnvelope<M>>>>>,
+    push_next_move: UnboundedSender<Sequenced<R>>,
+    output_move: Mutex<UnboundedReceiver<Sequenced<R>>>,
+    /// If set, envelopes from anyone else are rejected
+    players: Option<BTreeSet<XOnlyPublicKey>>,
+    rejected: broadcast::Sender<RejectedEnvelope>,
     is_running: AtomicBool,
     envelope_extractor: F,
     _pd: PhantomData<E>,
 }
 
+/// How many rejections a slow subscriber may fall behind by before it starts
+/// missing them
+#[cfg(feature = "has_async")]
+const REJECTED_CAPACITY: usize = 256;
+
 #[cfg(feature = "has_async")]
 impl<F, R, E, M> GenericSequencer<F, R, E, M>
 where
@@ -522,6 +961,7 @@ where
     pub fn new(
         shutdown: Arc<AtomicBool>,
         db_fetcher: Arc<dyn DBFetcher<M>>,
+        players: Option<BTreeSet<XOnlyPublicKey>>,
         envelope_extractor: F,
     ) -> Arc<Self> {
         let (push_next_envelope, output_envelope) = unbounded_channel();
@@ -535,6 +975,8 @@ where
             output_envelope,
             push_next_move,
             output_move,
+            players,
+            rejected: broadcast::channel(REJECTED_CAPACITY).0,
             is_running: Default::default(),
             envelope_extractor,
             _pd: Default::default(),
@@ -555,10 +997,22 @@ where
         Ok(())
     }
 
-    pub async fn output_move(self: &Arc<Self>) -> Option<R> {
+    pub async fn output_move(self: &Arc<Self>) -> Option<Sequenced<R>> {
         self.output_move.lock().await.recv().await
     }
 
+    /// Subscribes to the envelopes that are sequenced but not passed on.
+    /// Rejections from before subscribing are not seen.
+    pub fn subscribe_rejected(&self) -> broadcast::Receiver<RejectedEnvelope> {
+        self.rejected.subscribe()
+    }
+
+    pub(crate) fn reject(&self, rejected: RejectedEnvelope) {
+        warn!(msg_hash=?rejected.hash, reason=?rejected.reason, "Rejected Envelope");
+        // no subscribers is not an error
+        let _ = self.rejected.send(rejected);
+    }
+
     fn should_shutdown(&self) -> bool {
         self.shutdown.load(Ordering::Relaxed)
     }
@@ -569,8 +1023,21 @@ where
             let batches = self.db_fetcher.batches_to_sequence();
             let mut input_envelope_hashes = batches.lock().await;
             let msg_cache = self.db_fetcher.msg_cache();
-            while let Some(mut envelope_hashes) = input_envelope_hashes.recv().await {
+            while let Some(Batch {
+                attestation,
+                moves: mut envelope_hashes,
+            }) = input_envelope_hashes.recv().await
+            {
                 info!(n = envelope_hashes.len(), "Got New Batch to Sequence");
+                if let Some((hash, header)) = attestation {
+                    if self
+                        .push_next_envelope
+                        .send(Sequenced::Attested(hash, Box::new(header)))
+                        .is_err()
+                    {
+                        return;
+                    }
+                }
                 let mut should_wait = None;
                 'wait_for_new: while !envelope_hashes.is_empty() {
                     if let Some(n) = should_wait.take() {
@@ -586,9 +1053,14 @@ where
                     while let Some(envelope) = envelope_hashes.pop_front() {
                         match envs.entry(envelope) {
                             Occupied(e) => {
-                                // TODO: Batch size
+                                // batches are kept within batching's limits
+                                // by the DBFetcher
                                 let envelope = e.remove();
-                                if self.push_next_envelope.send(envelope).is_err() {
+                                if self
+                                    .push_next_envelope
+                                    .send(Sequenced::Move(envelope))
+                                    .is_err()
+                                {
                                     // quit if the channel is closed
                                     return;
                                 }
@@ -607,24 +1079,54 @@ where
         })
     }
     // Run the deserialization of the inner message type to move sets in it's own thread so that we can process
-    // moves in a pipeline as they get deserialized
-    // TODO: We skip invalid moves? Should do something else?
+    // moves in a pipeline as they get deserialized. Envelopes that are skipped
+    // are reported to subscribe_rejected.
     fn start_move_deserializer(self: Arc<Self>) -> JoinHandle<()> {
         spawn(async move {
             let mut next_envelope = self.output_envelope.lock().await;
-            while let Some(envelope) = next_envelope.recv().await {
+            while let Some(next) = next_envelope.recv().await {
+                let envelope = match next {
+                    Sequenced::Attested(hash, header) => {
+                        if self
+                            .push_next_move
+                            .send(Sequenced::Attested(hash, header))
+                            .is_err()
+                        {
+                            return;
+                        }
+                        continue;
+                    }
+                    Sequenced::Move(envelope) => envelope,
+                };
                 trace!(msg_hash=?envelope.canonicalized_hash_ref(), "Got Envelope");
-                let extracted = (self.envelope_extractor)(envelope);
-                match extracted {
+                if let Some(players) = &self.players {
+                    if !players.contains(&envelope.header().key()) {
+                        self.reject(RejectedEnvelope::new(&envelope, RejectReason::NotAPlayer));
+                        continue;
+                    }
+                }
+                // the extractor takes the envelope, so keep what a rejection needs
+                let (hash, key, height) = (
+                    envelope.canonicalized_hash_ref(),
+                    envelope.header().key(),
+                    envelope.header().height(),
+                );
+                match (self.envelope_extractor)(envelope) {
                     Ok(extracted) => {
-                        if self.push_next_move.send(extracted).is_err() {
+                        if self
+                            .push_next_move
+                            .send(Sequenced::Move(extracted))
+                            .is_err()
+                        {
                             return;
                         }
                     }
-                    Err(e) => {
-                        // Skip
-                        warn!(bad_move=?e,"Invalid Move Found");
-                    }
+                    Err(e) => self.reject(RejectedEnvelope {
+                        hash,
+                        key,
+                        height,
+                        reason: RejectReason::Deserialization(format!("{:?}", e)),
+                    }),
                 }
             }
         })
@@ -633,6 +1135,7 @@ where
 
 #[cfg(test)]
 mod test {
+    use crate::censorship::test::Chain;
     use crate::game_specific::Sequencer;
 
     use super::*;
@@ -643,7 +1146,7 @@ mod test {
         sanitize::Unsanitized,
     };
     use sapio_bitcoin::{
-        secp256k1::{rand, SecretKey},
+        secp256k1::{rand, All, SecretKey},
         KeyPair,
     };
     use std::collections::VecDeque;
@@ -706,13 +1209,18 @@ mod test {
             .collect::<HashMap<_, _>>();
         let hashes = envelopes
             .iter()
-            .map(|es| es.iter().map(|e| e.canonicalized_hash_ref()).collect())
-            .collect::<Vec<_>>();
+            .map(|es| {
+                es.iter()
+                    .map(|e| e.canonicalized_hash_ref())
+                    .collect::<VecDeque<_>>()
+                    .into()
+            })
+            .collect::<Vec<Batch>>();
         let db_fetcher_direct = OfflineDBFetcher::new(hashes.clone(), hmap.clone())
             .directly_sequence()
             .unwrap();
         let db_fetcher = Arc::new(OfflineDBFetcher::new(hashes, hmap));
-        let s = Sequencer::new(Default::default(), db_fetcher);
+        let s = Sequencer::new(Default::default(), db_fetcher, None);
         {
             let s = s.clone();
             spawn(async move { s.run().await });
@@ -722,7 +1230,7 @@ mod test {
 
         for batch in envelopes {
             for envelope in batch {
-                if let Some((m, x)) = s.output_move().await {
+                if let Some(Sequenced::Move((m, x))) = s.output_move().await {
                     let game_move = envelope.msg();
                     assert_eq!(ParticipantAction::MoveEnvelope(m), *game_move);
                     assert_eq!(x, envelope.header().key());
@@ -732,10 +1240,211 @@ mod test {
             }
         }
     }
+    #[tokio::test]
+    async fn test_attested_before_moves() {
+        let envelopes = make_random_moves();
+        let hmap = envelopes
+            .iter()
+            .flat_map(|e| e.iter())
+            .map(|e| (e.canonicalized_hash_ref(), e.clone()))
+            .collect::<HashMap<_, _>>();
+        // any envelope will do as the attesting one, and the last batch has
+        // no moves, like a heartbeat
+        let attesting = |i: usize| {
+            let e = &envelopes[i][0];
+            Some((e.canonicalized_hash_ref(), e.header().clone()))
+        };
+        let batches = vec![
+            Batch {
+                attestation: attesting(0),
+                moves: envelopes[0]
+                    .iter()
+                    .map(|e| e.canonicalized_hash_ref())
+                    .collect(),
+            },
+            Batch {
+                attestation: attesting(1),
+                moves: VecDeque::new(),
+            },
+        ];
+        let db_fetcher = Arc::new(OfflineDBFetcher::new(batches, hmap));
+        let s = Sequencer::new(Default::default(), db_fetcher, None);
+        {
+            let s = s.clone();
+            spawn(async move { s.run().await });
+        }
+        match s.output_move().await {
+            Some(Sequenced::Attested(hash, _)) => assert_eq!(Some(hash), attesting(0).map(|a| a.0)),
+            _ => unreachable!("Batch not attested before its moves"),
+        }
+        for envelope in &envelopes[0] {
+            match s.output_move().await {
+                Some(Sequenced::Move((_, x))) => assert_eq!(x, envelope.header().key()),
+                _ => unreachable!("Missing move"),
+            }
+        }
+        match s.output_move().await {
+            Some(Sequenced::Attested(hash, _)) => assert_eq!(Some(hash), attesting(1).map(|a| a.0)),
+            _ => unreachable!("Empty batch not attested"),
+        }
+    }
+    #[test]
+    fn test_unfair_batch_attested_only() {
+        let secp = sapio_bitcoin::secp256k1::Secp256k1::new();
+        let mut players = [Chain::new(&secp), Chain::new(&secp), Chain::new(&secp)];
+        let mut msg_cache = HashMap::new();
+        let (first, second) = (
+            play(&secp, &mut players, &mut msg_cache, 1000),
+            play(&secp, &mut players, &mut msg_cache, 2000),
+        );
+        let mut unfair = FairOrder::default().order(first);
+        unfair.swap(0, 1);
+        // the seed moves past the rejected batch all the same
+        let mut fair_order = FairOrder::default();
+        fair_order.advance(unfair.iter());
+        let fair = fair_order.order(second);
+        assert_rejected_then_accepted(&secp, unfair, fair, msg_cache);
+    }
+    #[test]
+    fn test_oversized_batch_attested_only() {
+        let secp = sapio_bitcoin::secp256k1::Secp256k1::new();
+        let mut players: Vec<Chain> = (0..=batching::MAX_BATCH_SIZE)
+            .map(|_| Chain::new(&secp))
+            .collect();
+        let mut msg_cache = HashMap::new();
+        let first = play(&secp, &mut players, &mut msg_cache, 1000);
+        let second = play(&secp, &mut players[..2], &mut msg_cache, 2000);
+        // in fair order, but one move too many
+        let mut fair_order = FairOrder::default();
+        let oversized = fair_order.order(first);
+        assert_eq!(oversized.len(), batching::MAX_BATCH_SIZE + 1);
+        let fair = fair_order.order(second);
+        assert_rejected_then_accepted(&secp, oversized, fair, msg_cache);
+    }
+    /// A move from each player at `t`
+    fn play(
+        secp: &Secp256k1<All>,
+        players: &mut [Chain],
+        msg_cache: &mut HashMap<
+            CanonicalEnvelopeHash,
+            Authenticated<GenericEnvelope<ParticipantAction>>,
+        >,
+        t: i64,
+    ) -> Vec<Orderable> {
+        players
+            .iter_mut()
+            .map(|p| {
+                let e = p
+                    .player_move(secp, vec![], t)
+                    .self_authenticate(secp)
+                    .expect("Valid Signature");
+                let o = (
+                    e.get_genesis_hash(),
+                    e.header().height(),
+                    e.canonicalized_hash_ref(),
+                );
+                msg_cache.insert(o.2, e);
+                o
+            })
+            .collect()
+    }
+    /// Sequences `rejected` then `accepted` offline, checking only the moves
+    /// of `accepted` are played, but both batches are attested to
+    fn assert_rejected_then_accepted(
+        secp: &Secp256k1<All>,
+        rejected: VecDeque<CanonicalEnvelopeHash>,
+        accepted: VecDeque<CanonicalEnvelopeHash>,
+        msg_cache: HashMap<
+            CanonicalEnvelopeHash,
+            Authenticated<GenericEnvelope<ParticipantAction>>,
+        >,
+    ) {
+        let mut host = Chain::new(secp);
+        let setup = GameSetup {
+            players: vec![],
+            start_amount: 0,
+            finish_time: 0,
+            subsidy: Default::default(),
+            clock: Default::default(),
+            failover: Default::default(),
+        };
+        let sequencer_envelopes: Vec<HostEnvelope> = [
+            BroadcastByHost::GameSetup(setup),
+            BroadcastByHost::Sequence(rejected),
+            BroadcastByHost::Sequence(accepted.clone()),
+        ]
+        .into_iter()
+        .enumerate()
+        .map(|(i, data)| {
+            host.host(secp, data, vec![], 1000 * i as i64)
+                .self_authenticate(secp)
+                .expect("Valid Signature")
+        })
+        .collect();
+        let attested = |i: usize| Some(sequencer_envelopes[i].canonicalized_hash_ref());
+        let (rejected, accepted_at) = (attested(1), attested(2));
+        let sequencer = OfflineSequencer::try_from(RawSequencer {
+            sequencer_envelopes,
+            backup_envelopes: Default::default(),
+            msg_cache,
+        })
+        .expect("Rejected Batches are Skipped");
+        let batches = &sequencer.batches_to_sequence;
+        assert_eq!(batches.len(), 2);
+        assert_eq!(batches[0].attestation.as_ref().map(|a| a.0), rejected);
+        assert!(batches[0].moves.is_empty());
+        assert_eq!(batches[1].attestation.as_ref().map(|a| a.0), accepted_at);
+        assert_eq!(batches[1].moves, accepted);
+    }
+    #[tokio::test]
+    async fn test_rejected_non_players() {
+        let mut envelopes = make_random_moves();
+        let outsiders = envelopes.pop().unwrap();
+        let players = envelopes
+            .iter()
+            .flatten()
+            .map(|e| e.header().key())
+            .collect::<BTreeSet<_>>();
+        let all = envelopes.iter().chain(std::iter::once(&outsiders));
+        let hmap = all
+            .clone()
+            .flatten()
+            .map(|e| (e.canonicalized_hash_ref(), e.clone()))
+            .collect::<HashMap<_, _>>();
+        let hashes = all
+            .map(|es| {
+                es.iter()
+                    .map(|e| e.canonicalized_hash_ref())
+                    .collect::<VecDeque<_>>()
+                    .into()
+            })
+            .collect::<Vec<Batch>>();
+        let db_fetcher = Arc::new(OfflineDBFetcher::new(hashes, hmap));
+        let s = Sequencer::new(Default::default(), db_fetcher, Some(players));
+        let mut rejected = s.0.subscribe_rejected();
+        {
+            let s = s.clone();
+            spawn(async move { s.run().await });
+        }
+
+        for envelope in envelopes.iter().flatten() {
+            let x = match s.output_move().await.unwrap() {
+                Sequenced::Move((_, x)) => x,
+                Sequenced::Attested(..) => unreachable!("Batches have no attestations"),
+            };
+            assert_eq!(x, envelope.header().key());
+        }
+        for envelope in outsiders {
+            let r = rejected.recv().await.unwrap();
+            assert_eq!(r.hash, envelope.canonicalized_hash_ref());
+            assert_eq!(r.key, envelope.header().key());
+            assert_eq!(r.reason, RejectReason::NotAPlayer);
+        }
+    }
     struct TestDBFetcher {
         to_seq: Vec<VecDeque<Authenticated<GenericEnvelope<ParticipantAction>>>>,
-        schedule_batches_to_sequence: UnboundedSender<VecDeque<CanonicalEnvelopeHash>>,
-        batches_to_sequence: Arc<Mutex<UnboundedReceiver<VecDeque<CanonicalEnvelopeHash>>>>,
+        schedule_batches_to_sequence: UnboundedSender<Batch>,
+        batches_to_sequence: Arc<Mutex<UnboundedReceiver<Batch>>>,
         msg_cache: Arc<
             Mutex<
                 HashMap<CanonicalEnvelopeHash, Authenticated<GenericEnvelope<ParticipantAction>>>,
@@ -764,7 +1473,13 @@ mod test {
                     for batch in &me.to_seq {
                         if me
                             .schedule_batches_to_sequence
-                            .send(batch.iter().map(|e| e.canonicalized_hash_ref()).collect())
+                            .send(
+                                batch
+                                    .iter()
+                                    .map(|e| e.canonicalized_hash_ref())
+                                    .collect::<VecDeque<_>>()
+                                    .into(),
+                            )
                             .is_err()
                         {
                             // quit on close
@@ -822,9 +1537,7 @@ mod test {
         }
     }
     impl DBFetcher<ParticipantAction> for TestDBFetcher {
-        fn batches_to_sequence(
-            &self,
-        ) -> Arc<Mutex<UnboundedReceiver<VecDeque<CanonicalEnvelopeHash>>>> {
+        fn batches_to_sequence(&self) -> Arc<Mutex<UnboundedReceiver<Batch>>> {
             self.batches_to_sequence.clone()
         }
 
@@ -847,14 +1560,14 @@ mod test {
         let envelopes = make_random_moves();
         let db_fetcher = TestDBFetcher::new(envelopes.clone());
         spawn(db_fetcher.clone().run());
-        let s = Sequencer::new(Default::default(), db_fetcher);
+        let s = Sequencer::new(Default::default(), db_fetcher, None);
         {
             let s = s.clone();
             spawn(async move { s.run().await });
         }
         for batch in envelopes {
             for envelope in batch {
-                if let Some((m, x)) = s.output_move().await {
+                if let Some(Sequenced::Move((m, x))) = s.output_move().await {
                     let game_move = envelope.msg();
                     assert_eq!(ParticipantAction::MoveEnvelope(m), *game_move);
                     assert_eq!(x, envelope.header().key());
@@ -864,4 +1577,126 @@ mod test {
             }
         }
     }
+    #[test]
+    fn test_replay_uses_recorded_subsidy() {
+        use mine_with_friends_board::{
+            game::{
+                clock::{ClockParams, ClockSource},
+                GameBoard,
+            },
+            nfts::instances::powerplant::subsidy::SubsidyParams,
+        };
+        let players = vec!["alice".to_string(), "bob".to_string()];
+        let recorded = GameSetup {
+            players: players.clone(),
+            start_amount: 100_000_000,
+            finish_time: 60 * 60 * 1000,
+            subsidy: SubsidyParams {
+                initial_subsidy: 7_000,
+                halving_interval: 5 * 60 * 1000,
+                payout_period: 30 * 1000,
+                retarget_every: 0,
+            },
+            clock: ClockParams {
+                source: ClockSource::Sequencer,
+                max_skew: 1000,
+            },
+            failover: Default::default(),
+        };
+        let trace = ExtractedMoveEnvelopes(vec![], vec![], Some(recorded.clone()));
+        let setup = trace.game_setup(players, 100_000_000, 60 * 60 * 1000);
+        assert_eq!(setup.subsidy, recorded.subsidy);
+        assert_eq!(setup.clock, recorded.clock);
+        let (replayed, host) = (GameBoard::new(&setup), GameBoard::new(&recorded));
+        let schedule = replayed.get_ux_mining_schedule();
+        assert_eq!(schedule.current_subsidy, 7_000);
+        assert_eq!(schedule.payout_period, 30 * 1000);
+        assert_eq!(schedule.halvings, host.get_ux_mining_schedule().halvings);
+    }
+    /// Inserts envelopes as the host or a backup would, registering each
+    /// chain from its genesis
+    #[cfg(feature = "database_access")]
+    async fn insert_host_envelopes(db: &MsgDB, envelopes: Vec<HostEnvelope>) {
+        let mut handle = db.get_handle_all().await;
+        spawn_blocking(move || {
+            for e in envelopes {
+                if e.header().height() == 0 {
+                    handle
+                        .insert_user_by_genesis_envelope(e.header().key().to_string(), e)
+                        .unwrap()
+                        .unwrap();
+                } else {
+                    handle
+                        .try_insert_authenticated_envelope(e, false)
+                        .unwrap()
+                        .unwrap();
+                }
+            }
+        })
+        .await
+        .unwrap();
+    }
+    #[cfg(feature = "database_access")]
+    async fn next_attested(fetcher: &OnlineDBFetcher<ParticipantAction>) -> CanonicalEnvelopeHash {
+        let batch = fetcher.batches_to_sequence().lock().await.recv().await;
+        batch
+            .and_then(|b| b.attestation)
+            .map(|(hash, _)| hash)
+            .expect("Fetcher Stopped")
+    }
+    #[cfg(feature = "database_access")]
+    #[tokio::test]
+    async fn test_simulated_online_takeover() {
+        use crate::censorship::test::heartbeat;
+        use crate::custody::test::{auth, genesis, take_over};
+        use attest_util::clock::VirtualClock;
+        let secp = Secp256k1::new();
+        let (mut host, mut backup) = (Chain::new(&secp), Chain::new(&secp));
+        let handoff = 1000;
+        let t = 1_000_000;
+        let poll = Duration::from_secs(1);
+        let h0 = genesis(&secp, &mut host, &[&backup], handoff, t);
+        let h1 = auth(&secp, host.host(&secp, heartbeat(), vec![], t + 10));
+        let b0 = auth(&secp, backup.host(&secp, heartbeat(), vec![], t));
+
+        // time only passes when advanced, so the fetcher's fallback polls
+        // never fire on their own and only inserts wake it
+        let clock = Arc::new(VirtualClock::new(t));
+        let db = attest_database::setup_test_db().await;
+        let shutdown = Arc::new(AtomicBool::new(false));
+        insert_host_envelopes(&db, vec![h0, h1.clone(), b0]).await;
+        let fetcher = OnlineDBFetcher::<ParticipantAction>::new_with_clock(
+            shutdown.clone(),
+            poll,
+            poll,
+            host.key(),
+            db.clone(),
+            Clock::Virtual(clock.clone()),
+        );
+        spawn(fetcher.clone().run());
+        assert_eq!(next_attested(&fetcher).await, h1.canonicalized_hash_ref());
+
+        // the backup takes over from h1, and the host keeps sending anyway
+        let b1 = take_over(&secp, &mut backup, &host, &h1, t + 10 + handoff);
+        let b2 = auth(
+            &secp,
+            backup.host(&secp, heartbeat(), vec![], t + 20 + handoff),
+        );
+        let h2 = auth(&secp, host.host(&secp, heartbeat(), vec![], t + 20));
+        insert_host_envelopes(&db, vec![b1, b2.clone(), h2]).await;
+        assert_eq!(next_attested(&fetcher).await, b2.canonicalized_hash_ref());
+        let liveness = fetcher.subscribe_liveness().borrow().clone();
+        assert_eq!(liveness.map(|l| l.key), Some(backup.key()));
+
+        // polling again changes nothing, custody stays with the backup
+        clock.advance(poll * 3);
+        let h3 = auth(&secp, host.host(&secp, heartbeat(), vec![], t + 30));
+        let b3 = auth(
+            &secp,
+            backup.host(&secp, heartbeat(), vec![], t + 30 + handoff),
+        );
+        insert_host_envelopes(&db, vec![h3, b3.clone()]).await;
+        assert_eq!(next_attested(&fetcher).await, b3.canonicalized_hash_ref());
+        shutdown.store(true, Ordering::Relaxed);
+    }
 }
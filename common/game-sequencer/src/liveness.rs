@@ -0,0 +1,106 @@
+// Copyright Judica, Inc 2022
+//
+// This Source Code Form is subject to the terms of the Mozilla Public
+//  License, v. 2.0. If a copy of the MPL was not distributed with this
+//  file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+//! Whether the host is still there.
+//!
+//! A host with nothing to sequence sends a [`BroadcastByHost::Heartbeat`]
+//! every [`HEARTBEAT_INTERVAL_MS`], so one that has sent nothing for a few
+//! intervals has most likely gone down, even though from the moves alone it
+//! would look like nobody is playing.
+
+use crate::custody::HostEnvelope;
+use game_host_messages::{BroadcastByHost, HEARTBEAT_INTERVAL_MS};
+use sapio_bitcoin::XOnlyPublicKey;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+/// How long the host may send nothing before it is considered unresponsive
+pub const HOST_UNRESPONSIVE_AFTER_MS: i64 = 3 * HEARTBEAT_INTERVAL_MS;
+
+/// What was last heard from the sequencer with custody of a game
+#[derive(Serialize, Deserialize, JsonSchema, Clone, Debug, PartialEq, Eq)]
+pub struct HostLiveness {
+    #[schemars(with = "String")]
+    pub key: XOnlyPublicKey,
+    /// When it sent its latest envelope, by its own clock
+    pub last_heard_ms: i64,
+    /// Height in its chain of its latest Sequence, if it sent one
+    pub sequenced_height: Option<i64>,
+    /// The latest Bitcoin block height it checkpointed, if it knew one
+    pub checkpoint_height: Option<i64>,
+}
+
+impl HostLiveness {
+    /// Liveness once `envelope` is heard from the custodian, given what was
+    /// heard before it
+    pub fn heard(prev: Option<&HostLiveness>, envelope: &HostEnvelope) -> HostLiveness {
+        let header = envelope.header();
+        let prev = prev.filter(|p| p.key == header.key());
+        let sequenced_height = match &envelope.msg().data {
+            BroadcastByHost::Sequence(_) => Some(header.height()),
+            BroadcastByHost::Heartbeat(h) => h.sequenced_height,
+            _ => prev.and_then(|p| p.sequenced_height),
+        };
+        // unknown checkpoints are recorded as -1
+        let checkpoint_height = Some(header.checkpoints().checkpoints[0].1)
+            .filter(|h| *h >= 0)
+            .or_else(|| prev.and_then(|p| p.checkpoint_height));
+        HostLiveness {
+            key: header.key(),
+            last_heard_ms: header.sent_time_ms(),
+            sequenced_height,
+            checkpoint_height,
+        }
+    }
+    /// When the custodian went quiet, if as of `now_ms` it has been quiet for
+    /// longer than [`HOST_UNRESPONSIVE_AFTER_MS`]
+    pub fn unresponsive_since(&self, now_ms: i64) -> Option<i64> {
+        (now_ms.saturating_sub(self.last_heard_ms) > HOST_UNRESPONSIVE_AFTER_MS)
+            .then_some(self.last_heard_ms)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::censorship::test::Chain;
+    use game_host_messages::HostHeartbeat;
+    use sapio_bitcoin::secp256k1::Secp256k1;
+    use std::collections::VecDeque;
+
+    #[test]
+    fn test_host_liveness() {
+        let secp = Secp256k1::new();
+        let mut host = Chain::new(&secp);
+        let t = 1_000_000;
+        let mut heard = |data, t| {
+            host.host(&secp, data, vec![], t)
+                .self_authenticate(&secp)
+                .expect("Valid Signature")
+        };
+        let batch = heard(BroadcastByHost::Sequence(VecDeque::new()), t);
+        let liveness = HostLiveness::heard(None, &batch);
+        assert_eq!(liveness.sequenced_height, Some(0));
+        assert_eq!(liveness.checkpoint_height, None);
+
+        let beat = heard(
+            BroadcastByHost::Heartbeat(HostHeartbeat {
+                sequenced_height: Some(0),
+            }),
+            t + HEARTBEAT_INTERVAL_MS,
+        );
+        let liveness = HostLiveness::heard(Some(&liveness), &beat);
+        assert_eq!(liveness.last_heard_ms, t + HEARTBEAT_INTERVAL_MS);
+        assert_eq!(liveness.sequenced_height, Some(0));
+
+        let quiet_until = liveness.last_heard_ms + HOST_UNRESPONSIVE_AFTER_MS;
+        assert_eq!(liveness.unresponsive_since(quiet_until), None);
+        assert_eq!(
+            liveness.unresponsive_since(quiet_until + 1),
+            Some(liveness.last_heard_ms)
+        );
+    }
+}
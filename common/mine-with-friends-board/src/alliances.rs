@@ -0,0 +1,434 @@
+// Copyright Judica, Inc 2022
+//
+// This Source Code Form is subject to the terms of the Mozilla Public
+//  License, v. 2.0. If a copy of the MPL was not distributed with this
+//  file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+//! Alliances of players with a shared treasury.
+//!
+//! An alliance's id is also its treasury: tokens sent to it and power plants
+//! deposited with it are owned by the alliance, and plants it owns mine into
+//! it. Nothing leaves the treasury, and nobody is admitted, without a
+//! proposal approved by a strict majority of the members. A player is in at
+//! most one alliance at a time, and for deciding whether someone dominates the
+//! game an alliance counts as a single entity.
+use crate::entity::EntityID;
+use crate::game::CallContext;
+use crate::game::GameBoard;
+use crate::game::LogEvent;
+use crate::nfts::NftPtr;
+use crate::util::Currency;
+use crate::util::Price;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+
+/// What the treasury does if a proposal passes
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone, Eq, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum AllianceSpend {
+    Tokens {
+        to: EntityID,
+        currency: Currency,
+        amount: Price,
+    },
+    PowerPlant {
+        to: EntityID,
+        plant: NftPtr,
+    },
+}
+
+#[derive(Serialize, Clone, Debug, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum AllianceAction {
+    /// Made by a player asking to join
+    Admit {
+        player: EntityID,
+    },
+    Spend(AllianceSpend),
+}
+
+#[derive(Serialize, Clone, Debug, JsonSchema)]
+pub struct AllianceProposal {
+    pub proposer: EntityID,
+    pub action: AllianceAction,
+    pub approvals: BTreeSet<EntityID>,
+    pub rejections: BTreeSet<EntityID>,
+}
+
+#[derive(Serialize, Clone, Debug, JsonSchema)]
+pub struct Alliance {
+    pub name: String,
+    pub members: BTreeSet<EntityID>,
+    pub proposals: BTreeMap<EntityID, AllianceProposal>,
+}
+
+impl Alliance {
+    /// Whether `votes` is a strict majority of the members
+    fn is_majority(&self, votes: &BTreeSet<EntityID>) -> bool {
+        votes.intersection(&self.members).count() * 2 > self.members.len()
+    }
+
+    /// Whether `votes` means a majority can never be reached
+    fn is_blocking(&self, votes: &BTreeSet<EntityID>) -> bool {
+        votes.intersection(&self.members).count() * 2 >= self.members.len()
+    }
+}
+
+#[derive(Serialize, Clone, Debug, JsonSchema)]
+pub enum AllianceError {
+    NoSuchAlliance,
+    NoSuchProposal,
+    NotMember,
+    AlreadyInAlliance { alliance: EntityID },
+    AlreadyApplied,
+    NotPlantOwner,
+    PlantInAuction,
+}
+
+/// Alliance lifecycle, recorded in the event log
+#[derive(Serialize, Clone, Debug, JsonSchema)]
+pub enum AllianceEvent {
+    Created {
+        id: EntityID,
+    },
+    Proposed {
+        id: EntityID,
+        proposal: EntityID,
+    },
+    Joined {
+        id: EntityID,
+        player: EntityID,
+    },
+    Left {
+        id: EntityID,
+        player: EntityID,
+    },
+    /// The last member left, taking what was left in the treasury
+    Dissolved {
+        id: EntityID,
+    },
+    PlantDeposited {
+        id: EntityID,
+        plant: NftPtr,
+    },
+    Executed {
+        id: EntityID,
+        proposal: EntityID,
+    },
+    /// Voted down, or passed but could no longer be carried out (e.g., the
+    /// treasury has since been spent)
+    Rejected {
+        id: EntityID,
+        proposal: EntityID,
+    },
+}
+
+/// An alliance formatted for UX consumption
+#[derive(Serialize, Clone, Debug, JsonSchema)]
+pub struct UXAlliance {
+    pub id: EntityID,
+    pub alliance: Alliance,
+    /// The treasury's token balances, by token nickname
+    pub treasury: Vec<(String, Price)>,
+    pub power_plants: Vec<NftPtr>,
+}
+
+/// A Registry of all alliances
+#[derive(Serialize, Default, Debug, JsonSchema)]
+pub(crate) struct AllianceRegistry {
+    pub(crate) alliances: BTreeMap<EntityID, Alliance>,
+    /// The alliance each player is a member of
+    pub(crate) member_of: BTreeMap<EntityID, EntityID>,
+}
+
+impl AllianceRegistry {
+    /// The id that `entity`'s holdings count towards: its alliance, if it is
+    /// a player in one, and otherwise itself
+    pub(crate) fn team_of(&self, entity: EntityID) -> EntityID {
+        self.member_of.get(&entity).cloned().unwrap_or(entity)
+    }
+
+    pub(crate) fn create(
+        game: &mut GameBoard,
+        CallContext { ref sender }: &CallContext,
+        name: String,
+    ) -> Result<(), AllianceError> {
+        if let Some(alliance) = game.alliances.member_of.get(sender) {
+            return Err(AllianceError::AlreadyInAlliance {
+                alliance: *alliance,
+            });
+        }
+        let id = game.alloc();
+        game.alliances.alliances.insert(
+            id,
+            Alliance {
+                name,
+                members: [*sender].into(),
+                proposals: Default::default(),
+            },
+        );
+        game.alliances.member_of.insert(*sender, id);
+        game.add_to_event_log(*sender, LogEvent::Alliance(AllianceEvent::Created { id }));
+        Ok(())
+    }
+
+    /// Asks to join an alliance, which its members then vote on
+    pub(crate) fn join(
+        game: &mut GameBoard,
+        context: &CallContext,
+        id: EntityID,
+    ) -> Result<(), AllianceError> {
+        let sender = context.sender;
+        if let Some(alliance) = game.alliances.member_of.get(&sender) {
+            return Err(AllianceError::AlreadyInAlliance {
+                alliance: *alliance,
+            });
+        }
+        let alliance = game
+            .alliances
+            .alliances
+            .get(&id)
+            .ok_or(AllianceError::NoSuchAlliance)?;
+        if alliance
+            .proposals
+            .values()
+            .any(|p| matches!(p.action, AllianceAction::Admit { player } if player == sender))
+        {
+            return Err(AllianceError::AlreadyApplied);
+        }
+        Self::add_proposal(game, context, id, AllianceAction::Admit { player: sender });
+        Ok(())
+    }
+
+    /// Leaves an alliance. The last member to leave dissolves it, and takes
+    /// whatever is left in the treasury.
+    pub(crate) fn leave(
+        game: &mut GameBoard,
+        CallContext { ref sender }: &CallContext,
+        id: EntityID,
+    ) -> Result<(), AllianceError> {
+        let alliance = game
+            .alliances
+            .alliances
+            .get_mut(&id)
+            .ok_or(AllianceError::NoSuchAlliance)?;
+        if !alliance.members.remove(sender) {
+            return Err(AllianceError::NotMember);
+        }
+        let dissolved = alliance.members.is_empty();
+        game.alliances.member_of.remove(sender);
+        game.add_to_event_log(
+            *sender,
+            LogEvent::Alliance(AllianceEvent::Left {
+                id,
+                player: *sender,
+            }),
+        );
+        if dissolved {
+            game.alliances.alliances.remove(&id);
+            for token in game.tokens.tokens.values_mut() {
+                let balance = token.balance_check(&id);
+                token.transaction();
+                let _ = token.transfer(&id, sender, balance);
+                token.end_transaction();
+            }
+            for plant in Self::treasury_plants(game, id) {
+                game.nft_sales.nfts.remove(&plant);
+                game.nfts[plant].transfer(*sender);
+            }
+            game.add_to_event_log(*sender, LogEvent::Alliance(AllianceEvent::Dissolved { id }));
+        } else {
+            // the majority needed has shrunk
+            Self::resolve_all(game, id);
+        }
+        Ok(())
+    }
+
+    /// Hands a power plant over to the treasury
+    pub(crate) fn deposit_plant(
+        game: &mut GameBoard,
+        CallContext { ref sender }: &CallContext,
+        id: EntityID,
+        plant: NftPtr,
+    ) -> Result<(), AllianceError> {
+        Self::check_member(game, id, *sender)?;
+        if game.nfts[plant].owner() != *sender {
+            return Err(AllianceError::NotPlantOwner);
+        }
+        if game.auctions.auctions.contains_key(&plant) {
+            return Err(AllianceError::PlantInAuction);
+        }
+        game.nft_sales.nfts.remove(&plant);
+        game.nfts[plant].transfer(id);
+        game.add_to_event_log(
+            *sender,
+            LogEvent::Alliance(AllianceEvent::PlantDeposited { id, plant }),
+        );
+        Ok(())
+    }
+
+    /// Proposes spending from the treasury. The proposer approves it, so in a
+    /// one member alliance it passes straight away.
+    pub(crate) fn propose(
+        game: &mut GameBoard,
+        context: &CallContext,
+        id: EntityID,
+        spend: AllianceSpend,
+    ) -> Result<(), AllianceError> {
+        Self::check_member(game, id, context.sender)?;
+        let proposal = Self::add_proposal(game, context, id, AllianceAction::Spend(spend));
+        Self::resolve(game, id, proposal);
+        Ok(())
+    }
+
+    pub(crate) fn vote(
+        game: &mut GameBoard,
+        CallContext { ref sender }: &CallContext,
+        id: EntityID,
+        proposal: EntityID,
+        approve: bool,
+    ) -> Result<(), AllianceError> {
+        Self::check_member(game, id, *sender)?;
+        let p = game
+            .alliances
+            .alliances
+            .get_mut(&id)
+            .and_then(|a| a.proposals.get_mut(&proposal))
+            .ok_or(AllianceError::NoSuchProposal)?;
+        if approve {
+            p.rejections.remove(sender);
+            p.approvals.insert(*sender);
+        } else {
+            p.approvals.remove(sender);
+            p.rejections.insert(*sender);
+        }
+        Self::resolve(game, id, proposal);
+        Ok(())
+    }
+
+    /// The power plants the treasury of `id` owns
+    pub(crate) fn treasury_plants(game: &GameBoard, id: EntityID) -> Vec<NftPtr> {
+        game.nfts
+            .power_plants
+            .keys()
+            .filter(|p| game.nfts[**p].owner() == id)
+            .cloned()
+            .collect()
+    }
+
+    fn check_member(game: &GameBoard, id: EntityID, who: EntityID) -> Result<(), AllianceError> {
+        let alliance = game
+            .alliances
+            .alliances
+            .get(&id)
+            .ok_or(AllianceError::NoSuchAlliance)?;
+        if !alliance.members.contains(&who) {
+            return Err(AllianceError::NotMember);
+        }
+        Ok(())
+    }
+
+    fn add_proposal(
+        game: &mut GameBoard,
+        CallContext { ref sender }: &CallContext,
+        id: EntityID,
+        action: AllianceAction,
+    ) -> EntityID {
+        let proposal = game.alloc();
+        if let Some(a) = game.alliances.alliances.get_mut(&id) {
+            a.proposals.insert(
+                proposal,
+                AllianceProposal {
+                    proposer: *sender,
+                    action,
+                    approvals: [*sender].into(),
+                    rejections: Default::default(),
+                },
+            );
+        }
+        game.add_to_event_log(
+            *sender,
+            LogEvent::Alliance(AllianceEvent::Proposed { id, proposal }),
+        );
+        proposal
+    }
+
+    fn resolve_all(game: &mut GameBoard, id: EntityID) {
+        let proposals: Vec<EntityID> = match game.alliances.alliances.get(&id) {
+            Some(a) => a.proposals.keys().cloned().collect(),
+            None => return,
+        };
+        for proposal in proposals {
+            Self::resolve(game, id, proposal);
+        }
+    }
+
+    /// Carries out a proposal once a majority approves it, or drops it once a
+    /// majority can no longer be reached
+    fn resolve(game: &mut GameBoard, id: EntityID, proposal: EntityID) {
+        let alliance = match game.alliances.alliances.get(&id) {
+            Some(a) => a,
+            None => return,
+        };
+        let p = match alliance.proposals.get(&proposal) {
+            Some(p) => p,
+            None => return,
+        };
+        let passed = alliance.is_majority(&p.approvals);
+        if !passed && !alliance.is_blocking(&p.rejections) {
+            return;
+        }
+        let proposer = p.proposer;
+        let action = p.action.clone();
+        if let Some(a) = game.alliances.alliances.get_mut(&id) {
+            a.proposals.remove(&proposal);
+        }
+        let executed = passed && Self::execute(game, id, action);
+        let event = if executed {
+            AllianceEvent::Executed { id, proposal }
+        } else {
+            AllianceEvent::Rejected { id, proposal }
+        };
+        game.add_to_event_log(proposer, LogEvent::Alliance(event));
+    }
+
+    /// Carries out an approved action, if it still can be
+    fn execute(game: &mut GameBoard, id: EntityID, action: AllianceAction) -> bool {
+        match action {
+            AllianceAction::Admit { player } => {
+                if game.alliances.member_of.contains_key(&player) {
+                    return false;
+                }
+                game.alliances.member_of.insert(player, id);
+                if let Some(a) = game.alliances.alliances.get_mut(&id) {
+                    a.members.insert(player);
+                }
+                game.add_to_event_log(
+                    player,
+                    LogEvent::Alliance(AllianceEvent::Joined { id, player }),
+                );
+                true
+            }
+            AllianceAction::Spend(AllianceSpend::Tokens {
+                to,
+                currency,
+                amount,
+            }) => {
+                let token = &mut game.tokens[currency];
+                token.transaction();
+                let ok = token.transfer(&id, &to, amount);
+                token.end_transaction();
+                ok
+            }
+            AllianceAction::Spend(AllianceSpend::PowerPlant { to, plant }) => {
+                if game.nfts[plant].owner() != id || game.auctions.auctions.contains_key(&plant) {
+                    return false;
+                }
+                game.nft_sales.nfts.remove(&plant);
+                game.nfts[plant].transfer(to);
+                true
+            }
+        }
+    }
+}
@@ -67,6 +67,7 @@ impl CallbackRegistry {
             for mut x in v.into_iter() {
                 trace!(purpose = x.purpose(), "Game Board Running Action");
                 x.action(game);
+                game.audit_invariants(|| x.purpose());
             }
         }
     }
@@ -0,0 +1,344 @@
+// Copyright Judica, Inc 2022
+//
+// This Source Code Form is subject to the terms of the Mozilla Public
+//  License, v. 2.0. If a copy of the MPL was not distributed with this
+//  file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+//! Forward contracts between players.
+//!
+//! A player writes an offer to go long (pay a fixed price) or short (deliver
+//! the underlying) at a future expiry. When another player accepts, both legs
+//! are locked up in CoinLockups which release back to whoever posted them at
+//! expiry. A ForwardExpiry callback, scheduled when the offer was written and
+//! so always run just before those lockups release, settles the contract by
+//! paying each side out of the other's lockup.
+use crate::callbacks::Callback;
+use crate::entity::EntityID;
+use crate::game::game_move::WriteForward;
+use crate::game::CallContext;
+use crate::game::GameBoard;
+use crate::game::LogEvent;
+use crate::nfts::instances::lockup::CoinLockup;
+use crate::nfts::NftPtr;
+use crate::util::Currency;
+use crate::util::Price;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// Which side of the contract the writer takes
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone, Copy, Eq, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum ForwardSide {
+    /// Pays the price, receives the underlying
+    Long,
+    /// Delivers the underlying, receives the price
+    Short,
+}
+
+/// What the short side owes the long side at expiry
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone, Eq, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum ForwardUnderlying {
+    /// `quantity` of `asset`, locked up in full on acceptance
+    Delivery { asset: Currency, quantity: Price },
+    /// Whatever `plant` mines between acceptance and expiry, paid in the
+    /// game's bitcoin up to `collateral`, which is locked up on acceptance
+    MiningPayout { plant: NftPtr, collateral: Price },
+}
+
+/// The state of a contract once it has been accepted
+#[derive(Serialize, Debug, Clone, JsonSchema)]
+pub struct AcceptedForward {
+    pub long: EntityID,
+    pub short: EntityID,
+    /// Holds the long side's payment
+    pub payment_lockup: NftPtr,
+    /// Holds the short side's delivery or collateral
+    pub delivery_lockup: NftPtr,
+    /// For a MiningPayout, how much the plant had mined at acceptance
+    pub mined_at_start: Price,
+}
+
+/// A forward contract, open for acceptance or accepted
+#[derive(Serialize, Debug, Clone, JsonSchema)]
+pub struct Forward {
+    pub writer: EntityID,
+    pub side: ForwardSide,
+    pub underlying: ForwardUnderlying,
+    pub price: Price,
+    pub currency: Currency,
+    /// If set, only this player may accept
+    pub counterparty: Option<EntityID>,
+    pub expiry: u64,
+    pub accepted: Option<AcceptedForward>,
+}
+
+#[derive(Serialize, Clone, Debug, JsonSchema)]
+pub enum ForwardError {
+    NoSuchForward,
+    NotCounterparty,
+    CannotAcceptOwnForward,
+    AlreadyAccepted,
+    Expired,
+    NotWriter,
+    InsufficientFunds { asset: Currency, needed: Price },
+}
+
+/// Forward contract lifecycle, recorded in the event log
+#[derive(Serialize, Clone, Debug, JsonSchema)]
+pub enum ForwardEvent {
+    Written {
+        id: EntityID,
+        expiry: u64,
+    },
+    Accepted {
+        id: EntityID,
+        long: EntityID,
+        short: EntityID,
+    },
+    Cancelled {
+        id: EntityID,
+    },
+    /// The offer reached expiry without being accepted
+    Lapsed {
+        id: EntityID,
+    },
+    Settled {
+        id: EntityID,
+        /// Paid by the long side to the short side
+        paid: Price,
+        /// Delivered by the short side to the long side
+        delivered: Price,
+    },
+}
+
+/// A forward contract formatted for UX consumption
+#[derive(Serialize, Clone, Debug, JsonSchema)]
+pub struct UXForward {
+    pub id: EntityID,
+    pub forward: Forward,
+}
+
+/// A Registry of all open and accepted forward contracts
+#[derive(Serialize, Default, Debug, JsonSchema)]
+pub(crate) struct ForwardRegistry {
+    pub(crate) forwards: BTreeMap<EntityID, Forward>,
+    /// Bitcoin mined by each power plant over the whole game, for settling
+    /// MiningPayout forwards
+    pub(crate) mined_by_plant: BTreeMap<NftPtr, Price>,
+}
+
+impl ForwardRegistry {
+    /// Writes a new offer. Nothing is locked up until it is accepted.
+    pub(crate) fn write(
+        game: &mut GameBoard,
+        CallContext { ref sender }: &CallContext,
+        WriteForward {
+            side,
+            underlying,
+            price,
+            currency,
+            counterparty,
+            duration,
+        }: WriteForward,
+    ) -> EntityID {
+        let id = game.alloc();
+        let expiry = game.elapsed_time.saturating_add(duration);
+        game.forwards.forwards.insert(
+            id,
+            Forward {
+                writer: *sender,
+                side,
+                underlying,
+                price,
+                currency,
+                counterparty,
+                expiry,
+                accepted: None,
+            },
+        );
+        // Scheduled before any lockup for this forward, so it runs before they
+        // release
+        game.callbacks
+            .schedule(Box::new(ForwardExpiry { id, expiry }));
+        game.add_to_event_log(
+            *sender,
+            LogEvent::Forward(ForwardEvent::Written { id, expiry }),
+        );
+        id
+    }
+
+    /// Accepts an offer, locking up both legs
+    pub(crate) fn accept(
+        game: &mut GameBoard,
+        CallContext { ref sender }: &CallContext,
+        id: EntityID,
+    ) -> Result<(), ForwardError> {
+        let forward = game
+            .forwards
+            .forwards
+            .get(&id)
+            .cloned()
+            .ok_or(ForwardError::NoSuchForward)?;
+        if forward.accepted.is_some() {
+            return Err(ForwardError::AlreadyAccepted);
+        }
+        if forward.writer == *sender {
+            return Err(ForwardError::CannotAcceptOwnForward);
+        }
+        if matches!(forward.counterparty, Some(c) if c != *sender) {
+            return Err(ForwardError::NotCounterparty);
+        }
+        if game.elapsed_time >= forward.expiry {
+            return Err(ForwardError::Expired);
+        }
+        let (long, short) = match forward.side {
+            ForwardSide::Long => (forward.writer, *sender),
+            ForwardSide::Short => (*sender, forward.writer),
+        };
+        let (delivery_asset, delivery_amount, mined_at_start) = match forward.underlying {
+            ForwardUnderlying::Delivery { asset, quantity } => (asset, quantity, 0),
+            ForwardUnderlying::MiningPayout { plant, collateral } => (
+                game.bitcoin_token_id,
+                collateral,
+                game.forwards.mined(plant),
+            ),
+        };
+        // CoinLockup does not check funds, so both legs are checked up front
+        // (together, in case they are the same asset)
+        let mut needed: BTreeMap<(Currency, EntityID), Price> = BTreeMap::new();
+        *needed.entry((forward.currency, long)).or_default() += forward.price;
+        *needed.entry((delivery_asset, short)).or_default() += delivery_amount;
+        for ((asset, who), amount) in needed {
+            if game.tokens[asset].balance_check(&who) < amount {
+                return Err(ForwardError::InsufficientFunds {
+                    asset,
+                    needed: amount,
+                });
+            }
+        }
+        let payment_lockup = CoinLockup::lockup(
+            game,
+            long,
+            long,
+            forward.currency,
+            forward.price,
+            forward.expiry,
+        );
+        let delivery_lockup = CoinLockup::lockup(
+            game,
+            short,
+            short,
+            delivery_asset,
+            delivery_amount,
+            forward.expiry,
+        );
+        if let Some(f) = game.forwards.forwards.get_mut(&id) {
+            f.accepted = Some(AcceptedForward {
+                long,
+                short,
+                payment_lockup,
+                delivery_lockup,
+                mined_at_start,
+            });
+        }
+        game.add_to_event_log(
+            *sender,
+            LogEvent::Forward(ForwardEvent::Accepted { id, long, short }),
+        );
+        Ok(())
+    }
+
+    /// Withdraws an offer which has not yet been accepted
+    pub(crate) fn cancel(
+        game: &mut GameBoard,
+        CallContext { ref sender }: &CallContext,
+        id: EntityID,
+    ) -> Result<(), ForwardError> {
+        let forward = game
+            .forwards
+            .forwards
+            .get(&id)
+            .ok_or(ForwardError::NoSuchForward)?;
+        if forward.writer != *sender {
+            return Err(ForwardError::NotWriter);
+        }
+        if forward.accepted.is_some() {
+            return Err(ForwardError::AlreadyAccepted);
+        }
+        game.forwards.forwards.remove(&id);
+        game.add_to_event_log(*sender, LogEvent::Forward(ForwardEvent::Cancelled { id }));
+        Ok(())
+    }
+
+    /// Total bitcoin a plant has mined so far
+    pub(crate) fn mined(&self, plant: NftPtr) -> Price {
+        self.mined_by_plant.get(&plant).cloned().unwrap_or_default()
+    }
+
+    /// Pays out an accepted forward from its lockups, or drops an offer which
+    /// was never accepted
+    fn settle(game: &mut GameBoard, id: EntityID) {
+        let forward = match game.forwards.forwards.remove(&id) {
+            Some(f) => f,
+            None => return,
+        };
+        let a = match forward.accepted {
+            Some(a) => a,
+            None => {
+                game.add_to_event_log(
+                    forward.writer,
+                    LogEvent::Forward(ForwardEvent::Lapsed { id }),
+                );
+                return;
+            }
+        };
+        let (delivery_asset, delivered) = match forward.underlying {
+            ForwardUnderlying::Delivery { asset, quantity } => (asset, quantity),
+            ForwardUnderlying::MiningPayout { plant, collateral } => {
+                let mined = game.forwards.mined(plant).saturating_sub(a.mined_at_start);
+                (game.bitcoin_token_id, mined.min(collateral))
+            }
+        };
+        // whatever is left in the lockups (e.g., unused collateral) is
+        // returned to its poster when they release
+        let token = &mut game.tokens[forward.currency];
+        token.transaction();
+        let _ = token.transfer(&a.payment_lockup.inner(), &a.short, forward.price);
+        token.end_transaction();
+        let token = &mut game.tokens[delivery_asset];
+        token.transaction();
+        let _ = token.transfer(&a.delivery_lockup.inner(), &a.long, delivered);
+        token.end_transaction();
+        game.add_to_event_log(
+            a.long,
+            LogEvent::Forward(ForwardEvent::Settled {
+                id,
+                paid: forward.price,
+                delivered,
+            }),
+        );
+    }
+}
+
+/// Settles a forward at its expiry
+#[derive(Debug)]
+struct ForwardExpiry {
+    id: EntityID,
+    expiry: u64,
+}
+
+impl Callback for ForwardExpiry {
+    fn time(&self) -> u64 {
+        self.expiry
+    }
+
+    fn action(&mut self, game: &mut GameBoard) {
+        ForwardRegistry::settle(game, self.id)
+    }
+
+    fn purpose(&self) -> String {
+        "Forward Contract Expiry".into()
+    }
+}
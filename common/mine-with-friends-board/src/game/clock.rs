@@ -0,0 +1,133 @@
+// Copyright Judica, Inc 2022
+//
+// This Source Code Form is subject to the terms of the Mozilla Public
+//  License, v. 2.0. If a copy of the MPL was not distributed with this
+//  file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+//! Sources of the game clock.
+//!
+//! By default the game's elapsed time is the median of the time each player
+//! reports in their moves, which a majority of players can fast-forward. With
+//! a sequencer clock, elapsed time only advances when the driver replaying the
+//! sequencer's log attests to the time of a host envelope, and a player's
+//! self-reported time is merely checked to be near the attested time.
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone, Copy, Eq, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum ClockSource {
+    /// Median of each player's self-reported elapsed time
+    PlayerMedian,
+    /// Time attested by the sequencer's Heartbeat and Sequence envelopes
+    Sequencer,
+}
+
+/// Configurable parameters for the game clock, set via GameSetup
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone, Eq, PartialEq)]
+pub struct ClockParams {
+    pub source: ClockSource,
+    /// With a Sequencer clock, how far (ms) a move's time_millis may be from
+    /// the latest attested time before the move is rejected
+    pub max_skew: u64,
+}
+
+impl Default for ClockParams {
+    fn default() -> Self {
+        Self {
+            source: ClockSource::PlayerMedian,
+            max_skew: 30 * 1000,
+        }
+    }
+}
+
+/// A time attested by the sequencer, taken from the header of one of its
+/// envelopes
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone, Copy, Eq, PartialEq)]
+pub struct SequencerTime {
+    /// The envelope's sent time, in ms since the epoch
+    pub time_millis: u64,
+    /// Height of the most recent Bitcoin block checkpointed by the envelope,
+    /// if any
+    pub bitcoin_height: Option<u64>,
+}
+
+/// The live state of the sequencer clock
+#[derive(Serialize, JsonSchema, Debug, Clone, Default)]
+pub struct SequencerClock {
+    /// The first attested time, which the game's elapsed time counts from
+    pub first: Option<SequencerTime>,
+    /// The latest attested time
+    pub latest: Option<SequencerTime>,
+}
+
+#[derive(Serialize, Clone, Debug, JsonSchema)]
+pub enum ClockError {
+    /// The sequencer attested to a time earlier than one it already attested
+    TimeWentBackwards { latest: u64, attested: u64 },
+    /// The sequencer checkpointed an older Bitcoin block than it already had
+    BitcoinHeightWentBackwards { latest: u64, attested: u64 },
+    /// No time has been attested yet, so no move can be checked against it
+    NoAttestedTime,
+    /// A move's time_millis was too far from the attested time
+    TimestampOutOfWindow {
+        time_millis: u64,
+        attested: u64,
+        max_skew: u64,
+    },
+}
+
+impl SequencerClock {
+    /// Record a newly attested time, refusing any that would move the clock
+    /// (or the Bitcoin chain it is checkpointed to) backwards
+    pub(crate) fn attest(&mut self, time: SequencerTime) -> Result<(), ClockError> {
+        if let Some(latest) = self.latest {
+            if time.time_millis < latest.time_millis {
+                return Err(ClockError::TimeWentBackwards {
+                    latest: latest.time_millis,
+                    attested: time.time_millis,
+                });
+            }
+            if let (Some(latest), Some(attested)) = (latest.bitcoin_height, time.bitcoin_height) {
+                if attested < latest {
+                    return Err(ClockError::BitcoinHeightWentBackwards { latest, attested });
+                }
+            }
+        }
+        let bitcoin_height = time
+            .bitcoin_height
+            .or_else(|| self.latest.and_then(|l| l.bitcoin_height));
+        self.first.get_or_insert(time);
+        self.latest = Some(SequencerTime {
+            time_millis: time.time_millis,
+            bitcoin_height,
+        });
+        Ok(())
+    }
+
+    /// Elapsed time since the first attestation
+    pub fn elapsed(&self) -> u64 {
+        match (self.first, self.latest) {
+            (Some(first), Some(latest)) => latest.time_millis.saturating_sub(first.time_millis),
+            _ => 0,
+        }
+    }
+
+    /// Checks that a move's self-reported time is within `max_skew` of the
+    /// latest attested time
+    pub(crate) fn check_move_time(
+        &self,
+        time_millis: u64,
+        max_skew: u64,
+    ) -> Result<(), ClockError> {
+        let attested = self.latest.ok_or(ClockError::NoAttestedTime)?.time_millis;
+        if time_millis.max(attested) - time_millis.min(attested) > max_skew {
+            return Err(ClockError::TimestampOutOfWindow {
+                time_millis,
+                attested,
+                max_skew,
+            });
+        }
+        Ok(())
+    }
+}
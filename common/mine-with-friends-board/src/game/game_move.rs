@@ -4,6 +4,10 @@
 //  License, v. 2.0. If a copy of the MPL was not distributed with this
 //  file, You can obtain one at https://mozilla.org/MPL/2.0/.
 
+use crate::alliances::AllianceSpend;
+use crate::derivatives::{ForwardSide, ForwardUnderlying};
+use crate::lending::LoanCollateral;
+use crate::nfts::auction::AuctionKind;
 use crate::nfts::instances::powerplant::PlantType;
 use crate::nfts::NftPtr;
 use crate::tokens::token_swap::TradingPairID;
@@ -12,6 +16,7 @@ use crate::{entity::EntityID, util::Price};
 use crate::{sanitize, MoveEnvelope};
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 
 /// Grab-Bag Enum of all moves
 ///
@@ -27,12 +32,48 @@ pub enum GameMove {
     PurchaseNFT(PurchaseNFT),
     /// # Sell NFTs
     ListNFTForSale(ListNFTForSale),
+    /// # Auction NFTs
+    StartAuction(StartAuction),
+    /// # Bid in an Auction
+    BidOnAuction(BidOnAuction),
+    /// # Offer a Forward Contract
+    WriteForward(WriteForward),
+    /// # Take the Other Side of a Forward Contract
+    AcceptForward(AcceptForward),
+    /// # Withdraw a Forward Contract Offer
+    CancelForward(CancelForward),
+    /// # Offer Coins for Players to Borrow
+    OfferLoan(OfferLoan),
+    /// # Withdraw a Loan Offer
+    CancelLoanOffer(CancelLoanOffer),
+    /// # Borrow Against Collateral
+    Borrow(Borrow),
+    /// # Pay Down a Loan
+    RepayLoan(RepayLoan),
+    /// # Found an Alliance
+    CreateAlliance(CreateAlliance),
+    /// # Ask to Join an Alliance
+    JoinAlliance(JoinAlliance),
+    /// # Leave an Alliance
+    LeaveAlliance(LeaveAlliance),
+    /// # Give a Power Plant to an Alliance's Treasury
+    DepositPlantWithAlliance(DepositPlantWithAlliance),
+    /// # Propose Spending from an Alliance's Treasury
+    ProposeAllianceSpend(ProposeAllianceSpend),
+    /// # Vote on an Alliance Proposal
+    VoteOnAllianceProposal(VoteOnAllianceProposal),
     /// # Send Coins
     SendTokens(SendTokens),
     /// # Remove Tokens
     RemoveTokens(RemoveTokens),
+    /// # Ship Miners to a Power Plant
+    ColocateMiners(ColocateMiners),
+    /// # Ship Miners back from a Power Plant
+    ShipMiners(ShipMiners),
     /// # Send a logged Chat Message to All Players
     Chat(Chat),
+    /// # Send an Encrypted Chat Message to Some Players
+    PrivateChat(PrivateChat),
     /// # Mint Power Plant NFT
     MintPowerPlant(MintPowerPlant),
     /// # Purchase Materials, then Mint Power Plant NFT
@@ -54,9 +95,27 @@ derive_from!(Trade);
 derive_from!(MintPowerPlant);
 derive_from!(PurchaseNFT);
 derive_from!(ListNFTForSale);
+derive_from!(StartAuction);
+derive_from!(BidOnAuction);
+derive_from!(WriteForward);
+derive_from!(AcceptForward);
+derive_from!(CancelForward);
+derive_from!(OfferLoan);
+derive_from!(CancelLoanOffer);
+derive_from!(Borrow);
+derive_from!(RepayLoan);
+derive_from!(CreateAlliance);
+derive_from!(JoinAlliance);
+derive_from!(LeaveAlliance);
+derive_from!(DepositPlantWithAlliance);
+derive_from!(ProposeAllianceSpend);
+derive_from!(VoteOnAllianceProposal);
 derive_from!(SendTokens);
 derive_from!(RemoveTokens);
+derive_from!(ColocateMiners);
+derive_from!(ShipMiners);
 derive_from!(Chat);
+derive_from!(PrivateChat);
 
 #[derive(Debug, Eq, PartialEq, Serialize, Deserialize, JsonSchema, Clone)]
 pub struct Heartbeat();
@@ -93,6 +152,114 @@ pub struct ListNFTForSale {
     pub currency: Currency,
 }
 
+#[derive(Debug, Eq, PartialEq, Serialize, Deserialize, JsonSchema, Clone)]
+pub struct StartAuction {
+    pub nft_id: NftPtr,
+    pub currency: Currency,
+    pub kind: AuctionKind,
+    /// How long the auction runs for, in elapsed game time (ms)
+    pub duration: u64,
+}
+
+#[derive(Debug, Eq, PartialEq, Serialize, Deserialize, JsonSchema, Clone)]
+pub struct BidOnAuction {
+    pub nft_id: NftPtr,
+    pub amount: Price,
+    pub currency: Currency,
+}
+
+#[derive(Debug, Eq, PartialEq, Serialize, Deserialize, JsonSchema, Clone)]
+pub struct WriteForward {
+    /// The side the writer takes
+    pub side: ForwardSide,
+    pub underlying: ForwardUnderlying,
+    /// What the long side pays at expiry
+    pub price: Price,
+    pub currency: Currency,
+    /// If set, only this player may accept
+    pub counterparty: Option<EntityID>,
+    /// Time until expiry, in elapsed game time (ms)
+    pub duration: u64,
+}
+
+#[derive(Debug, Eq, PartialEq, Serialize, Deserialize, JsonSchema, Clone)]
+pub struct AcceptForward {
+    pub id: EntityID,
+}
+
+#[derive(Debug, Eq, PartialEq, Serialize, Deserialize, JsonSchema, Clone)]
+pub struct CancelForward {
+    pub id: EntityID,
+}
+
+#[derive(Debug, Eq, PartialEq, Serialize, Deserialize, JsonSchema, Clone)]
+pub struct OfferLoan {
+    pub currency: Currency,
+    /// How much is on offer in total, across all borrowers
+    pub amount: Price,
+    /// Interest charged per interest period, in basis points of the debt
+    pub interest_bps: u128,
+    /// How long each loan runs for, in elapsed game time (ms)
+    pub duration: u64,
+    /// The most that can be borrowed, as a percentage of the collateral value
+    pub max_ltv_percent: u128,
+    /// Loans are liquidated once the debt exceeds this percentage of the
+    /// collateral value
+    pub liquidation_ltv_percent: u128,
+}
+
+#[derive(Debug, Eq, PartialEq, Serialize, Deserialize, JsonSchema, Clone)]
+pub struct CancelLoanOffer {
+    pub id: EntityID,
+}
+
+#[derive(Debug, Eq, PartialEq, Serialize, Deserialize, JsonSchema, Clone)]
+pub struct Borrow {
+    pub offer: EntityID,
+    pub amount: Price,
+    pub collateral: LoanCollateral,
+}
+
+#[derive(Debug, Eq, PartialEq, Serialize, Deserialize, JsonSchema, Clone)]
+pub struct RepayLoan {
+    pub id: EntityID,
+    pub amount: Price,
+}
+
+#[derive(Debug, Eq, PartialEq, Serialize, Deserialize, JsonSchema, Clone)]
+pub struct CreateAlliance {
+    pub name: String,
+}
+
+#[derive(Debug, Eq, PartialEq, Serialize, Deserialize, JsonSchema, Clone)]
+pub struct JoinAlliance {
+    pub alliance: EntityID,
+}
+
+#[derive(Debug, Eq, PartialEq, Serialize, Deserialize, JsonSchema, Clone)]
+pub struct LeaveAlliance {
+    pub alliance: EntityID,
+}
+
+#[derive(Debug, Eq, PartialEq, Serialize, Deserialize, JsonSchema, Clone)]
+pub struct DepositPlantWithAlliance {
+    pub alliance: EntityID,
+    pub nft_id: NftPtr,
+}
+
+#[derive(Debug, Eq, PartialEq, Serialize, Deserialize, JsonSchema, Clone)]
+pub struct ProposeAllianceSpend {
+    pub alliance: EntityID,
+    pub spend: AllianceSpend,
+}
+
+#[derive(Debug, Eq, PartialEq, Serialize, Deserialize, JsonSchema, Clone)]
+pub struct VoteOnAllianceProposal {
+    pub alliance: EntityID,
+    pub proposal: EntityID,
+    pub approve: bool,
+}
+
 #[derive(Debug, Eq, PartialEq, Serialize, Deserialize, JsonSchema, Clone)]
 pub struct SendTokens {
     pub to: EntityID,
@@ -108,9 +275,36 @@ pub struct RemoveTokens {
     // do we need time here?
 }
 
+#[derive(Debug, Eq, PartialEq, Serialize, Deserialize, JsonSchema, Clone)]
+pub struct ColocateMiners {
+    pub nft_id: NftPtr,
+    /// Which kind of hashboard to ship
+    pub miners: Currency,
+    pub amount: Price,
+}
+
+#[derive(Debug, Eq, PartialEq, Serialize, Deserialize, JsonSchema, Clone)]
+pub struct ShipMiners {
+    pub nft_id: NftPtr,
+    /// Which kind of hashboard to ship
+    pub miners: Currency,
+    pub amount: Price,
+}
+
 #[derive(Debug, Eq, PartialEq, Serialize, Deserialize, JsonSchema, Clone)]
 pub struct Chat(pub String);
 
+/// A chat message sealed separately to each recipient, so that it is ordered by
+/// the sequencer like any other move but can only be read by them. A direct
+/// message has a single recipient, a group chat several. Senders should seal a
+/// copy to themselves too, to be able to read back their own messages.
+#[derive(Debug, Eq, PartialEq, Serialize, Deserialize, JsonSchema, Clone)]
+pub struct PrivateChat {
+    /// The hex encoded, sealed message for each recipient. The board never
+    /// looks inside, sealing and opening happen in the client.
+    pub sealed: BTreeMap<EntityID, String>,
+}
+
 impl MoveEnvelope {
     pub fn create<G: Into<GameMove>>(
         g: G,
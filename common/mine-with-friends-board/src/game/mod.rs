@@ -4,19 +4,64 @@
 //  License, v. 2.0. If a copy of the MPL was not distributed with this
 //  file, You can obtain one at https://mozilla.org/MPL/2.0/.
 
+use self::clock::ClockError;
+use self::clock::ClockParams;
+use self::clock::ClockSource;
+use self::clock::SequencerClock;
+use self::clock::SequencerTime;
+use self::game_move::AcceptForward;
+use self::game_move::BidOnAuction;
+use self::game_move::Borrow;
+use self::game_move::CancelForward;
+use self::game_move::CancelLoanOffer;
 use self::game_move::Chat;
+use self::game_move::ColocateMiners;
+use self::game_move::CreateAlliance;
+use self::game_move::DepositPlantWithAlliance;
 use self::game_move::GameMove;
 use self::game_move::Heartbeat;
+use self::game_move::JoinAlliance;
+use self::game_move::LeaveAlliance;
 use self::game_move::ListNFTForSale;
 use self::game_move::MintPowerPlant;
+use self::game_move::PrivateChat;
+use self::game_move::ProposeAllianceSpend;
 use self::game_move::PurchaseNFT;
 use self::game_move::RemoveTokens;
+use self::game_move::RepayLoan;
 use self::game_move::SendTokens;
+use self::game_move::ShipMiners;
+use self::game_move::StartAuction;
 use self::game_move::Trade;
+use self::game_move::VoteOnAllianceProposal;
+use crate::alliances::AllianceError;
+use crate::alliances::AllianceEvent;
+use crate::alliances::AllianceRegistry;
+use crate::alliances::UXAlliance;
 use crate::callbacks::CallbackRegistry;
+use crate::derivatives::ForwardError;
+use crate::derivatives::ForwardEvent;
+use crate::derivatives::ForwardRegistry;
+use crate::derivatives::UXForward;
 use crate::entity::EntityID;
 use crate::entity::EntityIDAllocator;
+use crate::invariants::InvariantChecker;
+use crate::invariants::InvariantViolation;
+use crate::lending::LoanError;
+use crate::lending::LoanEvent;
+use crate::lending::LoanRegistry;
+use crate::lending::UXLoan;
+use crate::lending::UXLoanOffer;
+use crate::nfts::auction::AuctionError;
+use crate::nfts::auction::AuctionEvent;
+use crate::nfts::auction::AuctionRegistry;
+use crate::nfts::auction::UXAuction;
 use crate::nfts::instances::powerplant::events::PowerPlantEvent;
+use crate::nfts::instances::powerplant::shipping::Shipment;
+use crate::nfts::instances::powerplant::shipping::ShippingError;
+use crate::nfts::instances::powerplant::subsidy::SubsidyParams;
+use crate::nfts::instances::powerplant::subsidy::SubsidySchedule;
+use crate::nfts::instances::powerplant::subsidy::UXMiningSchedule;
 use crate::nfts::instances::powerplant::PlantType;
 use crate::nfts::instances::powerplant::PowerPlant;
 use crate::nfts::instances::powerplant::PowerPlantPrices;
@@ -32,7 +77,8 @@ use crate::nfts::UXPlantData;
 use crate::sanitize::Sanitizable;
 use crate::tokens;
 use crate::tokens::instances::asics::ASICProducer;
-use crate::tokens::instances::asics::HashBoardData;
+use crate::tokens::instances::asics::HardwareFailureEvent;
+use crate::tokens::instances::asics::ASIC_GENERATIONS;
 use crate::tokens::instances::concrete::ConcreteMiller;
 use crate::tokens::instances::silicon::Silicon;
 use crate::tokens::instances::silicon::SiliconRefinery;
@@ -45,6 +91,11 @@ use crate::tokens::token_swap::TradeError;
 use crate::tokens::token_swap::TradeOutcome;
 use crate::tokens::token_swap::TradingPairID;
 use crate::tokens::token_swap::UXMaterialsPriceData;
+use crate::util::fold_bytes;
+use crate::util::splitmix64;
+use crate::util::Price;
+use crate::world::UXWorldMap;
+use crate::world::WorldMap;
 use crate::MoveEnvelope;
 use schemars::JsonSchema;
 use serde::Deserialize;
@@ -57,14 +108,23 @@ use std::time::Duration;
 use tokens::TokenBase;
 use tokens::TokenPointer;
 use tokens::TokenRegistry;
+use tracing::debug;
 use tracing::info;
 use tracing::trace;
 
+/// The most moves held per player while waiting for a gap to be filled. Moves
+/// past it are not held, so they are only played if the player sends them again.
+pub(crate) const MAX_PENDING_MOVES: usize = 64;
+
 #[derive(Debug, Serialize, Clone, JsonSchema)]
 #[serde(tag = "event_type")]
 pub enum LogEvent {
     GameMove(GameMove),
     MoveRejectReason(MoveRejectReason),
+    Auction(AuctionEvent),
+    Forward(ForwardEvent),
+    Loan(LoanEvent),
+    Alliance(AllianceEvent),
     Other(serde_json::Value),
 }
 
@@ -72,7 +132,29 @@ pub enum LogEvent {
 pub struct UXUserInventory {
     user_power_plants: BTreeMap<NftPtr, UXPlantData>,
     user_token_balances: Vec<(String, u128)>,
+    /// Miners being shipped to or from the user's plants
+    user_shipments: Vec<Shipment>,
+}
+/// A private chat message, as sealed for one of its recipients
+#[derive(Serialize, Clone, Debug, JsonSchema)]
+pub struct UXPrivateChat {
+    pub id: u64,
+    pub from: EntityID,
+    /// Everyone the message was sealed to
+    pub recipients: Vec<EntityID>,
+    pub sealed: String,
+}
+/// A player's standing, as shown to players and spectators alike
+#[derive(Serialize, Clone, Debug, JsonSchema)]
+pub struct UXLeaderboardEntry {
+    pub player: EntityID,
+    pub key: String,
+    pub nick: Option<String>,
+    pub btc: u128,
+    /// The player's own share of the hashrate, as (numerator, denominator)
+    pub hashrate_share: (u128, u128),
 }
+
 #[derive(Serialize, JsonSchema, Debug)]
 pub struct UserData {
     pub key: String,
@@ -95,7 +177,15 @@ pub struct GameBoard {
     pub(crate) users_by_key: BTreeMap<String, EntityID>,
     pub(crate) nfts: NFTRegistry,
     pub(crate) nft_sales: NFTSaleRegistry,
-    pub(crate) player_move_sequence: BTreeMap<EntityID, u64>,
+    pub(crate) auctions: AuctionRegistry,
+    pub(crate) forwards: ForwardRegistry,
+    pub(crate) loans: LoanRegistry,
+    pub(crate) alliances: AllianceRegistry,
+    /// The sequence number of the next move expected from each player
+    pub(crate) next_sequence: BTreeMap<EntityID, u64>,
+    /// Moves which arrived ahead of their turn, held until the gap before them
+    /// is filled
+    pub(crate) pending_moves: BTreeMap<EntityID, BTreeMap<u64, MoveEnvelope>>,
     /// If init = true, must be Some
     pub(crate) bitcoin_token_id: TokenPointer,
     /// If init = true, must be Some
@@ -111,14 +201,27 @@ pub struct GameBoard {
     pub(crate) callbacks: CallbackRegistry,
     pub(crate) elapsed_time: u64,
     pub(crate) finish_time: u64,
-    pub(crate) mining_subsidy: u128,
+    pub(crate) mining: SubsidySchedule,
+    pub(crate) world: WorldMap,
+    /// Miners in transit, keyed by the CoinLockup holding them
+    pub(crate) shipments: BTreeMap<NftPtr, Shipment>,
+    /// Deterministic randomness, see [`GameBoard::next_random`]
+    pub(crate) entropy: u64,
     pub ticks: BTreeMap<EntityID, Tick>,
+    pub clock: ClockParams,
+    pub sequencer_clock: SequencerClock,
     pub chat: VecDeque<(u64, EntityID, String)>,
+    /// Sealed messages, numbered along with the public chat, with the copy for
+    /// each recipient
+    pub private_chat: VecDeque<(u64, EntityID, BTreeMap<EntityID, String>)>,
     pub nicks: BTreeMap<EntityID, String>,
     pub chat_counter: u64,
     pub event_log: VecDeque<(u64, EntityID, LogEvent)>,
     pub event_log_counter: u64,
     pub(crate) plant_prices: PowerPlantPrices,
+    /// Audits the board after every move and callback, if enabled
+    #[serde(skip)]
+    pub(crate) invariants: Option<InvariantChecker>,
 }
 
 pub struct CallContext {
@@ -133,12 +236,53 @@ pub struct GameSetup {
     // TODO: maybe remove no_finish_time default, but helps with existing chains...
     #[serde(default = "no_finish_time")]
     pub finish_time: u64,
+    /// Halving and difficulty parameters for the mining payout
+    #[serde(default)]
+    pub subsidy: SubsidyParams,
+    /// Where elapsed game time comes from
+    #[serde(default)]
+    pub clock: ClockParams,
+    /// Backup sequencers that can take over if the host goes quiet
+    #[serde(default)]
+    pub failover: FailoverParams,
+}
+/// Who may take over sequencing the game, and when.
+///
+/// Custody starts with the host. A backup may take over from the current
+/// custodian once the custodian has gone `handoff_after` ms without an
+/// envelope, doubled for each backup it skips over in `backups`.
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone, Eq, PartialEq)]
+pub struct FailoverParams {
+    /// Keys of the backup sequencers, in the order they take over
+    pub backups: Vec<String>,
+    /// How long (ms) the custodian must be quiet before the next backup may
+    /// take over
+    pub handoff_after: u64,
+}
+
+impl Default for FailoverParams {
+    fn default() -> Self {
+        Self {
+            backups: vec![],
+            handoff_after: 10 * 60 * 1000,
+        }
+    }
 }
 fn no_finish_time() -> u64 {
     // otherwise breaks json
     9_007_199_254_740_991u64
 }
 impl GameSetup {
+    /// Seed for the world map, derived from the set of players so that every
+    /// participant generates the same world
+    fn world_seed(&self) -> u64 {
+        let mut p = self.players.clone();
+        p.sort();
+        p.dedup();
+        p.iter()
+            .flat_map(|player| player.bytes().chain(std::iter::once(0)))
+            .fold(0, |acc, b| splitmix64(acc ^ b as u64))
+    }
     fn setup_game(&self, g: &mut GameBoard) {
         g.finish_time = self.finish_time;
         let mut p = self.players.clone();
@@ -169,6 +313,8 @@ impl GameSetup {
 pub enum FinishReason {
     TimeExpired,
     DominatingPlayer(EntityID),
+    /// An alliance's members and treasury together dominate
+    DominatingAlliance(EntityID),
 }
 
 #[derive(Serialize, Clone, Debug, JsonSchema)]
@@ -177,6 +323,23 @@ pub enum MoveRejectReason {
     GameIsFinished(FinishReason),
     MoveSanitizationError(<GameMove as Sanitizable>::Error),
     TradeRejected(TradeError),
+    ShippingRejected(ShippingError),
+    AuctionRejected(AuctionError),
+    ForwardRejected(ForwardError),
+    LoanRejected(LoanError),
+    AllianceRejected(AllianceError),
+    ClockRejected(ClockError),
+    /// The move skipped ahead of the next expected sequence number. It is held
+    /// until the missing moves arrive.
+    SequenceGap {
+        expected: u64,
+        received: u64,
+    },
+    /// The move's sequence number was already played or is already held
+    Duplicate {
+        expected: u64,
+        received: u64,
+    },
 }
 
 impl From<TradeError> for MoveRejectReason {
@@ -185,6 +348,42 @@ impl From<TradeError> for MoveRejectReason {
     }
 }
 
+impl From<ShippingError> for MoveRejectReason {
+    fn from(v: ShippingError) -> Self {
+        Self::ShippingRejected(v)
+    }
+}
+
+impl From<AuctionError> for MoveRejectReason {
+    fn from(v: AuctionError) -> Self {
+        Self::AuctionRejected(v)
+    }
+}
+
+impl From<ForwardError> for MoveRejectReason {
+    fn from(v: ForwardError) -> Self {
+        Self::ForwardRejected(v)
+    }
+}
+
+impl From<LoanError> for MoveRejectReason {
+    fn from(v: LoanError) -> Self {
+        Self::LoanRejected(v)
+    }
+}
+
+impl From<AllianceError> for MoveRejectReason {
+    fn from(v: AllianceError) -> Self {
+        Self::AllianceRejected(v)
+    }
+}
+
+impl From<ClockError> for MoveRejectReason {
+    fn from(v: ClockError) -> Self {
+        Self::ClockRejected(v)
+    }
+}
+
 impl From<FinishReason> for MoveRejectReason {
     fn from(v: FinishReason) -> Self {
         Self::GameIsFinished(v)
@@ -214,7 +413,10 @@ impl GameBoard {
             &mut alloc,
             "Concrete (1mt)".into(),
         ));
-        let asic = Box::new(TokenBase::new_from_alloc(&mut alloc, "ASIC Gen 1".into()));
+        let asic = Box::new(TokenBase::new_from_alloc(
+            &mut alloc,
+            ASIC_GENERATIONS[0].name.into(),
+        ));
         let steel = Box::new(TokenBase::new_from_alloc(&mut alloc, "Steel (1mt)".into()));
         let silicon = Box::new(TokenBase::new_from_alloc(
             &mut alloc,
@@ -229,11 +431,19 @@ impl GameBoard {
         let asic_token_id = tokens.new_token(asic);
         tokens.hashboards.insert(
             asic_token_id,
-            HashBoardData {
-                hash_per_watt: 3 * 10e10 as u128,
-                reliability: 100,
-            },
+            ASIC_GENERATIONS[0].hashboard_data(1, setup.finish_time),
         );
+        // Later generations of the manufacturing tree
+        for (i, generation) in ASIC_GENERATIONS.iter().enumerate().skip(1) {
+            let token = tokens.new_token(Box::new(TokenBase::new_from_alloc(
+                &mut alloc,
+                generation.name.into(),
+            )));
+            tokens.hashboards.insert(
+                token,
+                generation.hashboard_data(i as u8 + 1, setup.finish_time),
+            );
+        }
         tokens.steel.insert(
             steel_token_id,
             Steel {
@@ -288,18 +498,30 @@ impl GameBoard {
             users_by_key: Default::default(),
             nfts: Default::default(),
             nft_sales: Default::default(),
-            player_move_sequence: Default::default(),
+            auctions: Default::default(),
+            forwards: Default::default(),
+            loans: Default::default(),
+            alliances: Default::default(),
+            next_sequence: Default::default(),
+            pending_moves: Default::default(),
             callbacks: Default::default(),
             elapsed_time: 0,
             finish_time: 0,
-            mining_subsidy: 100_000_000 * 50,
+            mining: SubsidySchedule::new(setup.subsidy.clone()),
+            world: WorldMap::new(setup.world_seed()),
+            shipments: Default::default(),
+            entropy: setup.world_seed(),
             ticks: Default::default(),
+            clock: setup.clock.clone(),
+            sequencer_clock: Default::default(),
             chat: VecDeque::with_capacity(1000),
+            private_chat: VecDeque::with_capacity(1000),
             chat_counter: 0,
             event_log: VecDeque::with_capacity(1000),
             event_log_counter: 0,
             plant_prices,
             nicks: Default::default(),
+            invariants: None,
         };
         setup.setup_game(&mut g);
         g.post_init();
@@ -334,14 +556,14 @@ impl GameBoard {
         self.callbacks.schedule(Box::new(PowerPlantEvent {
             // Next Move
             time: 0,
-            period: 11_003, // 11 seconds,
+            period: self.mining.params.payout_period,
         }));
         self.callbacks.schedule(Box::new(ASICProducer {
             id,
-            total_units: 100_000,
-            base_price: 100_000,
+            total_units: ASIC_GENERATIONS[0].total_units,
+            base_price: ASIC_GENERATIONS[0].base_price,
             price_asset: self.bitcoin_token_id,
-            hash_asset: *self.tokens.hashboards.iter().next().unwrap().0,
+            hash_asset: self.asic_token_id,
             adjusts_every: 10_007, // 10 seconds -- prime rounded for chaos
             elapsed_time: 0,
             first: true,
@@ -379,6 +601,32 @@ impl GameBoard {
             elapsed_time: 0,
             first: true,
         }));
+        // Newer ASIC generations enter production part way through the game
+        let later_generations: Vec<_> = self
+            .tokens
+            .hashboards
+            .iter()
+            .filter(|(_, d)| d.generation > 1)
+            .map(|(token, d)| (*token, d.generation, d.unlocks_at))
+            .collect();
+        for (token, generation, unlocks_at) in later_generations {
+            let params = &ASIC_GENERATIONS[generation as usize - 1];
+            let id = self.alloc();
+            self.callbacks.schedule(Box::new(ASICProducer {
+                id,
+                total_units: params.total_units,
+                base_price: params.base_price,
+                price_asset: self.bitcoin_token_id,
+                hash_asset: token,
+                adjusts_every: 10_007,
+                elapsed_time: unlocks_at,
+                first: true,
+            }));
+        }
+        self.callbacks.schedule(Box::new(HardwareFailureEvent {
+            time: 0,
+            period: 300_007, // 5 minutes
+        }));
 
         #[cfg(not(test))]
         {
@@ -443,6 +691,13 @@ impl GameBoard {
     pub fn current_time(&self) -> u64 {
         self.elapsed_time
     }
+    pub fn bitcoin_token_id(&self) -> TokenPointer {
+        self.bitcoin_token_id
+    }
+    /// How much of a token a user (or NFT) holds
+    pub fn get_balance(&self, token: TokenPointer, user: EntityID) -> Price {
+        self.tokens[token].balance_check(&user)
+    }
     /// Check if a given user is the root_user
     pub fn user_is_admin(&self, user: EntityID) -> bool {
         user == self.root_user
@@ -458,14 +713,20 @@ impl GameBoard {
         let mut v = vec![];
         match self.game_is_finished().ok_or(CloseError::GameNotFinished)? {
             FinishReason::TimeExpired => {
+                // an alliance's treasury is split evenly between its members
+                let btc = &self.tokens[self.bitcoin_token_id];
                 let balances = self
                     .users_by_key
                     .iter()
                     .map(|(k, v)| {
-                        (
-                            k.clone(),
-                            self.tokens[self.bitcoin_token_id].balance_check(v),
-                        )
+                        let treasury_share = self.alliances.member_of.get(v).map_or(0, |a| {
+                            let members = self.alliances.alliances[a].members.len() as u128;
+                            #[allow(clippy::integer_division)]
+                            {
+                                btc.balance_check(a) / members
+                            }
+                        });
+                        (k.clone(), btc.balance_check(v) + treasury_share)
                     })
                     .collect::<Vec<_>>();
                 let total = balances.iter().map(|(_, v)| v).sum::<u128>();
@@ -503,19 +764,31 @@ impl GameBoard {
                 v.push((host_key, twentyfivepercent));
                 Ok(v)
             }
+            FinishReason::DominatingAlliance(id) => {
+                // 75%, split evenly between the members
+                #[allow(clippy::integer_division)]
+                let twentyfivepercent = bounty / 4;
+                let members = &self.alliances.alliances[&id].members;
+                #[allow(clippy::integer_division)]
+                let each = (bounty - twentyfivepercent) / members.len() as u64;
+                v.extend(members.iter().map(|m| (self.users[m].key.clone(), each)));
+                let excess = bounty - twentyfivepercent - each * members.len() as u64;
+                if let Some(m) = v.first_mut() {
+                    m.1 += excess;
+                }
+                // 25%
+                v.push((host_key, twentyfivepercent));
+                Ok(v)
+            }
         }
     }
     /// Processes a GameMove against the board after verifying it's integrity
     /// and sanitizing it.
-    pub fn play(
-        &mut self,
-        MoveEnvelope {
-            d,
-            sequence,
-            time_millis,
-        }: MoveEnvelope,
-        signed_by: String,
-    ) -> Result<(), MoveRejectReason> {
+    ///
+    /// Each player's moves are played strictly in sequence order. A move that
+    /// arrives early is held (and rejected with SequenceGap for now), and is
+    /// played as soon as the moves before it arrive.
+    pub fn play(&mut self, mv: MoveEnvelope, signed_by: String) -> Result<(), MoveRejectReason> {
         let from = *self
             .users_by_key
             .get(&signed_by)
@@ -529,13 +802,76 @@ impl GameBoard {
             return Err(MoveRejectReason::GameIsFinished(finish_reason));
         }
 
-        info!(key = signed_by, ?from, "Got Move {} From Player", sequence);
-        // TODO: check that sequence is the next sequence for that particular user
-        let current_move = self.player_move_sequence.entry(from).or_default();
-        if (*current_move + 1) != sequence && !(*current_move == 0 && sequence == 0) {
-            return Ok(());
+        info!(
+            key = signed_by,
+            ?from,
+            "Got Move {} From Player",
+            mv.sequence
+        );
+        let expected = self.next_sequence.get(&from).cloned().unwrap_or_default();
+        let pending = self.pending_moves.entry(from).or_default();
+        let rejection = if mv.sequence < expected || pending.contains_key(&mv.sequence) {
+            Some(MoveRejectReason::Duplicate {
+                expected,
+                received: mv.sequence,
+            })
+        } else if mv.sequence > expected {
+            // past the limit the move is not held. Every replica drops it
+            // alike, and a copy sent later is taken like any other move.
+            if pending.len() < MAX_PENDING_MOVES {
+                pending.insert(mv.sequence, mv.clone());
+            }
+            Some(MoveRejectReason::SequenceGap {
+                expected,
+                received: mv.sequence,
+            })
         } else {
-            *current_move = sequence;
+            None
+        };
+        if let Some(e) = rejection {
+            self.add_to_event_log(from, LogEvent::MoveRejectReason(e.clone()));
+            return Err(e);
+        }
+
+        let sequence = mv.sequence;
+        let res = self.play_in_sequence(mv, from);
+        // play any held moves which are now next in line
+        loop {
+            let next = self.next_sequence.get(&from).cloned().unwrap_or_default();
+            match self
+                .pending_moves
+                .get_mut(&from)
+                .and_then(|p| p.remove(&next))
+            {
+                Some(mv) => {
+                    if let Err(e) = self.play_in_sequence(mv, from) {
+                        debug!(error=?e, "Held Move Rejected");
+                    }
+                }
+                None => break,
+            }
+        }
+        self.audit_invariants(|| format!("move {} by {}", sequence, signed_by));
+        res
+    }
+
+    /// Plays a move which is known to be the next in sequence for `from`
+    fn play_in_sequence(
+        &mut self,
+        MoveEnvelope {
+            d,
+            sequence,
+            time_millis,
+        }: MoveEnvelope,
+        from: EntityID,
+    ) -> Result<(), MoveRejectReason> {
+        self.next_sequence.insert(from, sequence + 1);
+        if let Some(finish_reason) = self.game_is_finished() {
+            return Err(MoveRejectReason::GameIsFinished(finish_reason));
+        }
+        if self.clock.source == ClockSource::Sequencer {
+            self.sequencer_clock
+                .check_move_time(time_millis, self.clock.max_skew)?;
         }
         let mv = d.sanitize(self)?;
         self.update_current_time((from, time_millis));
@@ -554,10 +890,61 @@ impl GameBoard {
         Ok(())
     }
 
+    /// The sequence number of the next move expected from each player, so that
+    /// clients can resend any moves which were lost
+    pub fn get_ux_next_sequence(&self) -> BTreeMap<EntityID, u64> {
+        self.users
+            .keys()
+            .map(|id| (*id, self.next_sequence.get(id).cloned().unwrap_or_default()))
+            .collect()
+    }
+
     pub fn process_ticks(&mut self) {
         CallbackRegistry::run(self);
     }
 
+    /// Start auditing the board after every move and callback, see
+    /// [`crate::invariants`]
+    pub fn enable_invariant_checks(&mut self) {
+        self.invariants.get_or_insert_with(Default::default);
+        self.audit_invariants(|| "enabling invariant checks".into());
+    }
+
+    /// Every invariant violation found so far, with what was run just before
+    /// it was found
+    pub fn get_invariant_violations(&self) -> Vec<(String, InvariantViolation)> {
+        self.invariants
+            .as_ref()
+            .map(|i| i.violations.clone())
+            .unwrap_or_default()
+    }
+
+    /// Audits the board if invariant checks are enabled. `after` describes
+    /// what was just run, and is only called if needed.
+    pub(crate) fn audit_invariants<F: FnOnce() -> String>(&mut self, after: F) {
+        if let Some(mut checker) = self.invariants.take() {
+            checker.audit(self, &after());
+            self.invariants = Some(checker);
+        }
+    }
+
+    /// Record a time attested by the sequencer, from the header of one of its
+    /// Heartbeat or Sequence envelopes, along with that envelope's hash.
+    /// Drivers should attest each envelope before playing the moves it
+    /// sequences. Only advances the game when it uses a Sequencer clock.
+    ///
+    /// The hash is folded into the game's entropy: players can't choose it,
+    /// and every replica attests the same envelopes in the same order.
+    pub fn attest_time(&mut self, time: SequencerTime, batch: &[u8]) -> Result<(), ClockError> {
+        self.sequencer_clock.attest(time)?;
+        self.entropy = fold_bytes(self.entropy, batch);
+        if self.clock.source == ClockSource::Sequencer {
+            self.elapsed_time = self.elapsed_time.max(self.sequencer_clock.elapsed());
+            self.process_ticks();
+        }
+        Ok(())
+    }
+
     fn update_current_time(&mut self, (from, time): (EntityID, u64)) {
         trace!(?from, time, "updating time for player");
         let tick = self.ticks.entry(from).or_insert(Tick {
@@ -569,6 +956,10 @@ impl GameBoard {
             time.checked_sub(tick.first_time).unwrap_or_default(),
         );
         trace!(elapsed = ?Duration::from_millis(tick.elapsed), player=?from);
+        if self.clock.source == ClockSource::Sequencer {
+            // players' clocks are only checked against the sequencer's
+            return;
+        }
         let mut elapsed: Vec<u64> = self.ticks.values().map(|t| t.elapsed).collect();
         elapsed.sort_unstable();
         trace!(?elapsed, "elapsed times");
@@ -592,10 +983,16 @@ impl GameBoard {
             Some(FinishReason::TimeExpired)
         } else if self.elapsed_time >= (3 * self.finish_time / 4) {
             // After 75 % of the game is finished...
-            self.get_user_hashrate_share()
+            self.get_team_hashrate_share()
                 .iter()
                 .find_map(|(k, v)| if v.0 * 2 >= v.1 { Some(*k) } else { None })
-                .map(FinishReason::DominatingPlayer)
+                .map(|k| {
+                    if self.alliances.alliances.contains_key(&k) {
+                        FinishReason::DominatingAlliance(k)
+                    } else {
+                        FinishReason::DominatingPlayer(k)
+                    }
+                })
         } else {
             None
         }
@@ -662,6 +1059,58 @@ impl GameBoard {
             }) => self
                 .nft_sales
                 .list_nft(&context, nft_id, price, currency, &self.nfts),
+            GameMove::StartAuction(StartAuction {
+                nft_id,
+                currency,
+                kind,
+                duration,
+            }) => AuctionRegistry::start(self, &context, nft_id, currency, kind, duration)?,
+            GameMove::BidOnAuction(BidOnAuction {
+                nft_id,
+                amount,
+                currency,
+            }) => AuctionRegistry::bid(self, &context, nft_id, amount, currency)?,
+            GameMove::WriteForward(offer) => {
+                ForwardRegistry::write(self, &context, offer);
+            }
+            GameMove::AcceptForward(AcceptForward { id }) => {
+                ForwardRegistry::accept(self, &context, id)?
+            }
+            GameMove::CancelForward(CancelForward { id }) => {
+                ForwardRegistry::cancel(self, &context, id)?
+            }
+            GameMove::OfferLoan(offer) => LoanRegistry::offer(self, &context, offer)?,
+            GameMove::CancelLoanOffer(CancelLoanOffer { id }) => {
+                LoanRegistry::cancel_offer(self, &context, id)?
+            }
+            GameMove::Borrow(Borrow {
+                offer,
+                amount,
+                collateral,
+            }) => LoanRegistry::borrow(self, &context, offer, amount, collateral)?,
+            GameMove::RepayLoan(RepayLoan { id, amount }) => {
+                LoanRegistry::repay(self, &context, id, amount)?
+            }
+            GameMove::CreateAlliance(CreateAlliance { name }) => {
+                AllianceRegistry::create(self, &context, name)?
+            }
+            GameMove::JoinAlliance(JoinAlliance { alliance }) => {
+                AllianceRegistry::join(self, &context, alliance)?
+            }
+            GameMove::LeaveAlliance(LeaveAlliance { alliance }) => {
+                AllianceRegistry::leave(self, &context, alliance)?
+            }
+            GameMove::DepositPlantWithAlliance(DepositPlantWithAlliance { alliance, nft_id }) => {
+                AllianceRegistry::deposit_plant(self, &context, alliance, nft_id)?
+            }
+            GameMove::ProposeAllianceSpend(ProposeAllianceSpend { alliance, spend }) => {
+                AllianceRegistry::propose(self, &context, alliance, spend)?
+            }
+            GameMove::VoteOnAllianceProposal(VoteOnAllianceProposal {
+                alliance,
+                proposal,
+                approve,
+            }) => AllianceRegistry::vote(self, &context, alliance, proposal, approve)?,
             GameMove::SendTokens(SendTokens {
                 to,
                 amount,
@@ -687,6 +1136,22 @@ impl GameBoard {
                     info!("Remove Tokens: NFT owner mismatch");
                 }
             }
+            GameMove::ColocateMiners(ColocateMiners {
+                nft_id,
+                miners,
+                amount,
+            }) => {
+                let plant = self.nfts.power_plants[&nft_id].clone();
+                plant.colocate_miners(self, from, miners, amount)?;
+            }
+            GameMove::ShipMiners(ShipMiners {
+                nft_id,
+                miners,
+                amount,
+            }) => {
+                let plant = self.nfts.power_plants[&nft_id].clone();
+                plant.ship_miners(self, from, miners, amount)?;
+            }
             GameMove::Chat(Chat(mut s)) => {
                 if s.starts_with("/nick") && s.is_ascii() && s.len() < 32 {
                     let nick = s.split_at(s.find(' ').unwrap_or(s.len()));
@@ -701,6 +1166,14 @@ impl GameBoard {
                 }
                 self.chat.push_back((self.chat_counter, from, s));
             }
+            GameMove::PrivateChat(PrivateChat { sealed }) => {
+                self.chat_counter += 1;
+                if self.private_chat.len() >= 1000 {
+                    self.private_chat.pop_front();
+                }
+                self.private_chat
+                    .push_back((self.chat_counter, from, sealed));
+            }
         }
         Ok(())
     }
@@ -709,10 +1182,33 @@ impl GameBoard {
         self.chat.clone()
     }
 
+    /// The private chat messages sent to (or by) `user`, with the copy sealed
+    /// for them
+    pub fn get_ux_private_chat_log(&self, user: EntityID) -> Vec<UXPrivateChat> {
+        self.private_chat
+            .iter()
+            .filter_map(|(id, from, sealed)| {
+                Some(UXPrivateChat {
+                    id: *id,
+                    from: *from,
+                    recipients: sealed.keys().cloned().collect(),
+                    sealed: sealed.get(&user)?.clone(),
+                })
+            })
+            .collect()
+    }
+
     pub fn get_ux_event_log(&self) -> VecDeque<(u64, EntityID, LogEvent)> {
         self.event_log.clone()
     }
 
+    /// Draws from the game's entropy, which is seeded from the set of players
+    /// and mixed with the hash of each batch in [`GameBoard::attest_time`]
+    pub(crate) fn next_random(&mut self) -> u64 {
+        self.entropy = splitmix64(self.entropy);
+        self.entropy
+    }
+
     pub(crate) fn add_to_event_log(&mut self, from: EntityID, e: LogEvent) {
         self.event_log_counter += 1;
         if self.event_log.len() >= 1000 {
@@ -766,8 +1262,12 @@ impl GameBoard {
                 let nft = &self.nfts[*pointer];
                 let owner = nft.owner();
                 let nft_entity_id = nft.id();
-                let asic_token_id = self.asic_token_id;
-                let miners = self.tokens[asic_token_id].balance_check(&nft_entity_id);
+                let miners: u128 = self
+                    .tokens
+                    .hashboards
+                    .keys()
+                    .map(|token| self.tokens[*token].balance_check(&nft_entity_id))
+                    .sum();
 
                 UXPlantData {
                     id: *pointer,
@@ -777,6 +1277,7 @@ impl GameBoard {
                     owner,
                     plant_type: power_plant.plant_type,
                     watts: power_plant.watts,
+                    effective_watts: power_plant.effective_watts(self),
                     hashrate: power_plant.compute_hashrate(self),
                 }
             })
@@ -797,26 +1298,128 @@ impl GameBoard {
     }
 
     pub fn get_ux_user_inventory(&self, user_key: String) -> Result<UXUserInventory, ()> {
-        let user_id = self.users_by_key.get(&user_key).unwrap().to_owned();
+        let user_id = *self.users_by_key.get(&user_key).ok_or(())?;
         let user_power_plants = self
             .get_user_power_plants(user_id)
             .unwrap()
             .power_plant_data;
-        let user_token_balances = {
-            let mut balances = Vec::new();
-            for token in self.tokens.tokens.values() {
-                let balance = token.balance_check(&user_id);
-                let nickname = token.nickname().unwrap();
-                balances.push((nickname, balance))
-            }
-            balances
-        };
+        let user_token_balances = self.get_user_token_balances(user_id);
+        let user_shipments = self
+            .shipments
+            .values()
+            .filter(|s| s.owner == user_id)
+            .cloned()
+            .collect();
         Ok(UXUserInventory {
             user_power_plants,
             user_token_balances,
+            user_shipments,
         })
     }
 
+    /// A player's balance of every token, by token nickname
+    pub fn get_user_token_balances(&self, user_id: EntityID) -> Vec<(String, u128)> {
+        let mut balances = Vec::new();
+        for token in self.tokens.tokens.values() {
+            let balance = token.balance_check(&user_id);
+            let nickname = token.nickname().unwrap();
+            balances.push((nickname, balance))
+        }
+        balances
+    }
+
+    pub fn get_ux_auctions(&self) -> Vec<UXAuction> {
+        self.auctions
+            .auctions
+            .iter()
+            .map(|(nft_id, auction)| UXAuction {
+                nft_id: *nft_id,
+                auction: auction.clone(),
+                minimum_bid: auction.minimum_bid(self.elapsed_time),
+            })
+            .collect()
+    }
+
+    /// All open offers and accepted forward contracts
+    pub fn get_ux_forwards(&self) -> Vec<UXForward> {
+        self.forwards
+            .forwards
+            .iter()
+            .map(|(id, forward)| UXForward {
+                id: *id,
+                forward: forward.clone(),
+            })
+            .collect()
+    }
+
+    /// All loan offers with coins left to borrow
+    pub fn get_ux_loan_offers(&self) -> Vec<UXLoanOffer> {
+        self.loans
+            .offers
+            .iter()
+            .filter(|(_, offer)| offer.available > 0)
+            .map(|(id, offer)| UXLoanOffer {
+                id: *id,
+                offer: offer.clone(),
+            })
+            .collect()
+    }
+
+    /// All outstanding loans
+    pub fn get_ux_loans(&self) -> Vec<UXLoan> {
+        self.loans
+            .loans
+            .iter()
+            .map(|(id, loan)| UXLoan {
+                id: *id,
+                loan: loan.clone(),
+                collateral_value: loan.collateral.value(self, loan.currency),
+            })
+            .collect()
+    }
+
+    pub fn get_ux_alliances(&self) -> Vec<UXAlliance> {
+        self.alliances
+            .alliances
+            .iter()
+            .map(|(id, alliance)| UXAlliance {
+                id: *id,
+                alliance: alliance.clone(),
+                treasury: self
+                    .tokens
+                    .tokens
+                    .values()
+                    .map(|t| (t.nickname().unwrap_or_default(), t.balance_check(id)))
+                    .collect(),
+                power_plants: AllianceRegistry::treasury_plants(self, *id),
+            })
+            .collect()
+    }
+
+    /// Every player, by hashrate share and then by bitcoin balance, highest
+    /// first
+    pub fn get_ux_leaderboard(&self) -> Vec<UXLeaderboardEntry> {
+        let shares = self.get_user_hashrate_share();
+        let mut leaderboard: Vec<_> = self
+            .users
+            .iter()
+            .map(|(id, UserData { key })| UXLeaderboardEntry {
+                player: *id,
+                key: key.clone(),
+                nick: self.nicks.get(id).cloned(),
+                btc: self.tokens[self.bitcoin_token_id].balance_check(id),
+                hashrate_share: shares.get(id).cloned().unwrap_or((0, 1)),
+            })
+            .collect();
+        // compare shares by cross multiplying, as denominators may differ
+        leaderboard.sort_by(|a, b| {
+            let (an, ad) = a.hashrate_share;
+            let (bn, bd) = b.hashrate_share;
+            (bn * ad).cmp(&(an * bd)).then(b.btc.cmp(&a.btc))
+        });
+        leaderboard
+    }
+
     pub fn get_ux_energy_market(&self) -> Result<UXForSaleList, ()> {
         let mut listings = Vec::new();
         self.nft_sales.nfts.iter().for_each(|(pointer, listing)| {
@@ -830,6 +1433,14 @@ impl GameBoard {
         });
         Ok(UXForSaleList { listings })
     }
+    pub fn get_ux_mining_schedule(&self) -> UXMiningSchedule {
+        self.mining.to_ux(self.elapsed_time, self.finish_time)
+    }
+
+    pub fn get_ux_world_map(&self) -> UXWorldMap {
+        self.world.to_ux(self.elapsed_time)
+    }
+
     pub fn get_user_hashrate_share(&self) -> BTreeMap<EntityID, (u128, u128)> {
         let denominator = 100000u128;
         let reg = &self.nfts;
@@ -856,6 +1467,19 @@ impl GameBoard {
         res
     }
 
+    /// Like get_user_hashrate_share, but with each alliance's members and
+    /// treasury counted together under the alliance's id
+    pub fn get_team_hashrate_share(&self) -> BTreeMap<EntityID, (u128, u128)> {
+        let mut res: BTreeMap<EntityID, (u128, u128)> = BTreeMap::new();
+        for (entity, (share, denominator)) in self.get_user_hashrate_share() {
+            let v = res
+                .entry(self.alliances.team_of(entity))
+                .or_insert((0, denominator));
+            v.0 += share;
+        }
+        res
+    }
+
     pub fn simulate_buy_trade(
         &mut self,
         pair: TradingPairID,
@@ -901,6 +1525,11 @@ impl GameBoard {
         self.users_by_key.get(signing_key).cloned()
     }
 
+    /// The signing key of a player, the inverse of get_user_id
+    pub fn get_user_key(&self, user: EntityID) -> Option<&str> {
+        self.users.get(&user).map(|u| u.key.as_str())
+    }
+
     pub fn get_power_plant_cost(
         &mut self,
         scale: u64,
@@ -913,4 +1542,5 @@ impl GameBoard {
     }
 }
 
+pub mod clock;
 pub mod game_move;
@@ -0,0 +1,191 @@
+// Copyright Judica, Inc 2022
+//
+// This Source Code Form is subject to the terms of the Mozilla Public
+//  License, v. 2.0. If a copy of the MPL was not distributed with this
+//  file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+//! An opt-in auditor for the GameBoard's internal consistency.
+//!
+//! Once enabled with [`GameBoard::enable_invariant_checks`], the board is
+//! audited after every move and every callback. Violations do not stop the
+//! game, they are logged and kept for inspection, since any violation is a bug
+//! in the board rather than in the move that exposed it.
+use crate::entity::EntityID;
+use crate::game::GameBoard;
+use crate::nfts::NftPtr;
+use crate::tokens::token_swap::TradingPairID;
+use crate::tokens::TokenPointer;
+use crate::util::Price;
+use schemars::JsonSchema;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use tracing::warn;
+
+#[derive(Serialize, Clone, Debug, JsonSchema, Eq, PartialEq)]
+pub enum InvariantViolation {
+    /// A token's supply changed by something other than a mint or burn
+    UnsanctionedSupplyChange {
+        token: TokenPointer,
+        expected: Price,
+        actual: Price,
+    },
+    /// A token's balances do not add up to its supply. `balances` is None if
+    /// they overflow, i.e. some balance underflowed.
+    BalancesDoNotMatchSupply {
+        token: TokenPointer,
+        supply: Price,
+        balances: Option<Price>,
+    },
+    /// A sale or auction is open for an NFT which does not exist
+    MissingNFT { nft_id: NftPtr },
+    /// A sale or auction claims an NFT which has not moved since it was opened
+    /// was owned by someone other than its owner
+    SellerIsNotOwner {
+        nft_id: NftPtr,
+        seller: EntityID,
+        owner: EntityID,
+    },
+    /// A sale or auction was opened at a transfer_count the NFT never reached
+    TransferCountFromTheFuture {
+        nft_id: NftPtr,
+        opened_at: u128,
+        transfer_count: u128,
+    },
+    /// A power plant has no entry in the NFT registry
+    OrphanedPowerPlant { nft_id: NftPtr },
+    /// A market's recorded reserves differ from what its pool holds
+    ReservesDoNotMatchPool {
+        pair: TradingPairID,
+        reserves: (Price, Price),
+        balances: (Price, Price),
+    },
+    /// An auction's escrow does not hold exactly its best bid
+    EscrowDoesNotMatchBid {
+        nft_id: NftPtr,
+        escrowed: Price,
+        bid: Price,
+    },
+}
+
+/// Tracks supply across audits, and the violations found so far
+#[derive(Default, Debug)]
+pub(crate) struct InvariantChecker {
+    /// Each token's (supply, minted, burned) as of the last audit
+    supply: BTreeMap<TokenPointer, (Price, Price, Price)>,
+    /// Violations, with a description of what was just run when found
+    pub(crate) violations: Vec<(String, InvariantViolation)>,
+}
+
+impl InvariantChecker {
+    /// Audits the board, recording any violations against `after`
+    pub(crate) fn audit(&mut self, game: &GameBoard, after: &str) {
+        let mut found = self.check_supply(game);
+        found.extend(check_state(game));
+        for v in found {
+            warn!(after, violation=?v, "GameBoard Invariant Violated");
+            self.violations.push((after.to_owned(), v));
+        }
+    }
+
+    /// Checks that every change in supply since the last audit was made by a
+    /// mint or a burn
+    fn check_supply(&mut self, game: &GameBoard) -> Vec<InvariantViolation> {
+        let mut found = vec![];
+        for token in game.tokens.tokens.values() {
+            let now = (
+                token.total_coins(),
+                token.total_minted(),
+                token.total_burned(),
+            );
+            // tokens created since the last audit start from nothing
+            let (supply, minted, burned) = self.supply.insert(token.ptr(), now).unwrap_or_default();
+            let expected = (supply + (now.1 - minted)).checked_sub(now.2 - burned);
+            if expected != Some(now.0) {
+                found.push(InvariantViolation::UnsanctionedSupplyChange {
+                    token: token.ptr(),
+                    expected: expected.unwrap_or_default(),
+                    actual: now.0,
+                });
+            }
+            let balances = token.sum_of_balances();
+            if balances != Some(now.0) {
+                found.push(InvariantViolation::BalancesDoNotMatchSupply {
+                    token: token.ptr(),
+                    supply: now.0,
+                    balances,
+                });
+            }
+        }
+        found
+    }
+}
+
+/// Checks the invariants which hold between any two moves, independent of
+/// history
+pub(crate) fn check_state(game: &GameBoard) -> Vec<InvariantViolation> {
+    let mut found = vec![];
+    let listings = game
+        .nft_sales
+        .nfts
+        .iter()
+        .map(|(nft_id, s)| (*nft_id, s.seller, s.transfer_count));
+    let auctions = game
+        .auctions
+        .auctions
+        .iter()
+        .map(|(nft_id, a)| (*nft_id, a.seller, a.transfer_count));
+    for (nft_id, seller, opened_at) in listings.chain(auctions) {
+        let nft = match game.nfts.nfts.get(&nft_id) {
+            Some(nft) => nft,
+            None => {
+                found.push(InvariantViolation::MissingNFT { nft_id });
+                continue;
+            }
+        };
+        // A stale sale is fine (it is checked when used), but one made at the
+        // current transfer_count must be by the current owner
+        if opened_at > nft.transfer_count() {
+            found.push(InvariantViolation::TransferCountFromTheFuture {
+                nft_id,
+                opened_at,
+                transfer_count: nft.transfer_count(),
+            });
+        } else if opened_at == nft.transfer_count() && seller != nft.owner() {
+            found.push(InvariantViolation::SellerIsNotOwner {
+                nft_id,
+                seller,
+                owner: nft.owner(),
+            });
+        }
+    }
+    for nft_id in game.nfts.power_plants.keys() {
+        if !game.nfts.nfts.contains_key(nft_id) {
+            found.push(InvariantViolation::OrphanedPowerPlant { nft_id: *nft_id });
+        }
+    }
+    for mkt in game.swap.markets.values() {
+        let balances = (
+            game.tokens[mkt.pair.asset_a].balance_check(&mkt.id),
+            game.tokens[mkt.pair.asset_b].balance_check(&mkt.id),
+        );
+        if balances != (mkt.reserve_a, mkt.reserve_b) {
+            found.push(InvariantViolation::ReservesDoNotMatchPool {
+                pair: mkt.pair,
+                reserves: (mkt.reserve_a, mkt.reserve_b),
+                balances,
+            });
+        }
+    }
+    for (nft_id, auction) in game.auctions.auctions.iter() {
+        let escrowed = game.tokens[auction.currency].balance_check(&auction.escrow);
+        let bid = auction.best_bid.map(|(_, bid)| bid).unwrap_or_default();
+        if escrowed != bid {
+            found.push(InvariantViolation::EscrowDoesNotMatchBid {
+                nft_id: *nft_id,
+                escrowed,
+                bid,
+            });
+        }
+    }
+    found
+}
@@ -0,0 +1,540 @@
+// Copyright Judica, Inc 2022
+//
+// This Source Code Form is subject to the terms of the Mozilla Public
+//  License, v. 2.0. If a copy of the MPL was not distributed with this
+//  file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+//! Player to player loans, collateralized by power plants or tokens.
+//!
+//! A lender offers a pool of coins on fixed terms, which is held in escrow
+//! under the offer's id. Borrowers draw from it by posting collateral, which is
+//! held under the loan's id until the loan is repaid. A LoanTick callback
+//! charges interest every [`INTEREST_PERIOD`], and liquidates the loan once it
+//! is past due or the collateral, valued at the current market prices, no
+//! longer covers the debt.
+use crate::callbacks::Callback;
+use crate::entity::EntityID;
+use crate::game::game_move::OfferLoan;
+use crate::game::CallContext;
+use crate::game::GameBoard;
+use crate::game::LogEvent;
+use crate::nfts::NftPtr;
+use crate::tokens::token_swap::TradingPairID;
+use crate::util::Currency;
+use crate::util::Price;
+use crate::world::DAY_LENGTH;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// How often interest is charged and collateral is revalued
+pub const INTEREST_PERIOD: u64 = DAY_LENGTH / 10;
+
+/// What a borrower puts up against a loan
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone, Eq, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum LoanCollateral {
+    Tokens {
+        asset: Currency,
+        amount: Price,
+    },
+    /// Valued at what it would cost to build today. The plant keeps mining
+    /// while held, and its payouts go to the borrower when it is released.
+    PowerPlant {
+        plant: NftPtr,
+    },
+}
+
+impl LoanCollateral {
+    /// The collateral's value in `currency` at the current market prices
+    pub fn value(&self, game: &GameBoard, currency: Currency) -> Price {
+        match self {
+            LoanCollateral::Tokens { asset, amount } => value_in(game, *asset, *amount, currency),
+            LoanCollateral::PowerPlant { plant } => match game.nfts.power_plants.get(plant) {
+                Some(p) => p
+                    .plant_type
+                    .raw_materials_bill(game, (p.watts / 100_000) as u64)
+                    .into_iter()
+                    .map(|(material, qty)| value_in(game, material, qty, currency))
+                    .fold(0, Price::saturating_add),
+                None => 0,
+            },
+        }
+    }
+}
+
+/// The value of `amount` of `asset` in `currency`, at the spot price of the
+/// market between them or else through the game's bitcoin. Zero if neither
+/// has a price.
+pub(crate) fn value_in(
+    game: &GameBoard,
+    asset: Currency,
+    amount: Price,
+    currency: Currency,
+) -> Price {
+    let btc = game.bitcoin_token_id;
+    spot_value(game, asset, amount, currency)
+        .or_else(|| {
+            spot_value(game, asset, amount, btc).and_then(|v| spot_value(game, btc, v, currency))
+        })
+        .unwrap_or_default()
+}
+
+fn spot_value(
+    game: &GameBoard,
+    asset: Currency,
+    amount: Price,
+    currency: Currency,
+) -> Option<Price> {
+    if asset == currency {
+        return Some(amount);
+    }
+    let mut pair = TradingPairID {
+        asset_a: asset,
+        asset_b: currency,
+    };
+    pair.normalize();
+    let mkt = game.swap.markets.get(&pair)?;
+    let (held, price) = if mkt.pair.asset_a == asset {
+        (mkt.reserve_a, mkt.reserve_b)
+    } else {
+        (mkt.reserve_b, mkt.reserve_a)
+    };
+    if held == 0 {
+        return None;
+    }
+    amount.checked_mul(price).map(|v| v / held)
+}
+
+/// A lender's standing offer, with the unborrowed remainder held under its id
+#[derive(Serialize, Debug, Clone, JsonSchema)]
+pub struct LoanOffer {
+    pub lender: EntityID,
+    pub currency: Currency,
+    /// How much is left to borrow
+    pub available: Price,
+    /// Interest charged per INTEREST_PERIOD, in basis points of the debt
+    pub interest_bps: u128,
+    /// How long each loan runs for, in elapsed game time (ms)
+    pub duration: u64,
+    /// The most that can be borrowed, as a percentage of the collateral value
+    pub max_ltv_percent: u128,
+    /// Loans are liquidated once the debt exceeds this percentage of the
+    /// collateral value
+    pub liquidation_ltv_percent: u128,
+}
+
+/// An outstanding loan, with the collateral held under its id
+#[derive(Serialize, Debug, Clone, JsonSchema)]
+pub struct Loan {
+    pub offer: EntityID,
+    pub lender: EntityID,
+    pub borrower: EntityID,
+    pub currency: Currency,
+    pub principal: Price,
+    /// Principal plus interest, less repayments
+    pub debt: Price,
+    pub interest_bps: u128,
+    pub liquidation_ltv_percent: u128,
+    pub collateral: LoanCollateral,
+    pub due: u64,
+}
+
+#[derive(Serialize, Clone, Debug, JsonSchema)]
+pub enum LoanError {
+    NoSuchOffer,
+    NoSuchLoan,
+    NotLender,
+    NotBorrower,
+    CannotBorrowFromSelf,
+    InsufficientFunds,
+    NotEnoughOffered { available: Price },
+    NotCollateralOwner,
+    CollateralInAuction,
+    InsufficientCollateral { max_borrow: Price },
+}
+
+#[derive(Serialize, Clone, Copy, Debug, JsonSchema)]
+pub enum LiquidationReason {
+    PastDue,
+    Undercollateralized,
+}
+
+/// Loan lifecycle, recorded in the event log
+#[derive(Serialize, Clone, Debug, JsonSchema)]
+pub enum LoanEvent {
+    Offered {
+        id: EntityID,
+        amount: Price,
+    },
+    OfferCancelled {
+        id: EntityID,
+        refunded: Price,
+    },
+    Borrowed {
+        id: EntityID,
+        offer: EntityID,
+        amount: Price,
+    },
+    Repaid {
+        id: EntityID,
+        amount: Price,
+        remaining: Price,
+    },
+    /// Repaid in full, and the collateral returned
+    Closed {
+        id: EntityID,
+    },
+    /// The collateral was seized by the lender
+    Liquidated {
+        id: EntityID,
+        debt: Price,
+        collateral_value: Price,
+        reason: LiquidationReason,
+    },
+}
+
+/// A loan offer formatted for UX consumption
+#[derive(Serialize, Clone, Debug, JsonSchema)]
+pub struct UXLoanOffer {
+    pub id: EntityID,
+    pub offer: LoanOffer,
+}
+
+/// A loan formatted for UX consumption
+#[derive(Serialize, Clone, Debug, JsonSchema)]
+pub struct UXLoan {
+    pub id: EntityID,
+    pub loan: Loan,
+    /// The collateral's value at the current market prices
+    pub collateral_value: Price,
+}
+
+/// A Registry of all loan offers and outstanding loans
+#[derive(Serialize, Default, Debug, JsonSchema)]
+pub(crate) struct LoanRegistry {
+    pub(crate) offers: BTreeMap<EntityID, LoanOffer>,
+    pub(crate) loans: BTreeMap<EntityID, Loan>,
+}
+
+impl LoanRegistry {
+    /// Puts up a new offer, moving the coins on offer into escrow
+    pub(crate) fn offer(
+        game: &mut GameBoard,
+        CallContext { ref sender }: &CallContext,
+        OfferLoan {
+            currency,
+            amount,
+            interest_bps,
+            duration,
+            max_ltv_percent,
+            liquidation_ltv_percent,
+        }: OfferLoan,
+    ) -> Result<(), LoanError> {
+        let id = game.alloc();
+        let token = &mut game.tokens[currency];
+        token.transaction();
+        let funded = token.transfer(sender, &id, amount);
+        token.end_transaction();
+        if !funded {
+            return Err(LoanError::InsufficientFunds);
+        }
+        game.loans.offers.insert(
+            id,
+            LoanOffer {
+                lender: *sender,
+                currency,
+                available: amount,
+                interest_bps,
+                duration,
+                max_ltv_percent,
+                liquidation_ltv_percent,
+            },
+        );
+        game.add_to_event_log(*sender, LogEvent::Loan(LoanEvent::Offered { id, amount }));
+        Ok(())
+    }
+
+    /// Withdraws whatever is left of an offer. Loans already made from it are
+    /// unaffected.
+    pub(crate) fn cancel_offer(
+        game: &mut GameBoard,
+        CallContext { ref sender }: &CallContext,
+        id: EntityID,
+    ) -> Result<(), LoanError> {
+        let offer = game.loans.offers.get(&id).ok_or(LoanError::NoSuchOffer)?;
+        if offer.lender != *sender {
+            return Err(LoanError::NotLender);
+        }
+        let (currency, refunded) = (offer.currency, offer.available);
+        game.loans.offers.remove(&id);
+        let token = &mut game.tokens[currency];
+        token.transaction();
+        let _ = token.transfer(&id, sender, refunded);
+        token.end_transaction();
+        game.add_to_event_log(
+            *sender,
+            LogEvent::Loan(LoanEvent::OfferCancelled { id, refunded }),
+        );
+        Ok(())
+    }
+
+    /// Borrows from an offer against some collateral
+    pub(crate) fn borrow(
+        game: &mut GameBoard,
+        CallContext { ref sender }: &CallContext,
+        offer_id: EntityID,
+        amount: Price,
+        collateral: LoanCollateral,
+    ) -> Result<(), LoanError> {
+        let offer = game
+            .loans
+            .offers
+            .get(&offer_id)
+            .cloned()
+            .ok_or(LoanError::NoSuchOffer)?;
+        if offer.lender == *sender {
+            return Err(LoanError::CannotBorrowFromSelf);
+        }
+        if amount > offer.available {
+            return Err(LoanError::NotEnoughOffered {
+                available: offer.available,
+            });
+        }
+        match collateral {
+            LoanCollateral::Tokens {
+                asset,
+                amount: pledged,
+            } => {
+                if game.tokens[asset].balance_check(sender) < pledged {
+                    return Err(LoanError::InsufficientFunds);
+                }
+            }
+            LoanCollateral::PowerPlant { plant } => {
+                if game.nfts[plant].owner() != *sender {
+                    return Err(LoanError::NotCollateralOwner);
+                }
+                if game.auctions.auctions.contains_key(&plant) {
+                    return Err(LoanError::CollateralInAuction);
+                }
+            }
+        }
+        let max_borrow = collateral
+            .value(game, offer.currency)
+            .saturating_mul(offer.max_ltv_percent)
+            / 100;
+        if amount > max_borrow {
+            return Err(LoanError::InsufficientCollateral { max_borrow });
+        }
+
+        let id = game.alloc();
+        match collateral {
+            LoanCollateral::Tokens {
+                asset,
+                amount: pledged,
+            } => {
+                let token = &mut game.tokens[asset];
+                token.transaction();
+                let _ = token.transfer(sender, &id, pledged);
+                token.end_transaction();
+            }
+            LoanCollateral::PowerPlant { plant } => {
+                game.nft_sales.nfts.remove(&plant);
+                game.nfts[plant].transfer(id);
+            }
+        }
+        let token = &mut game.tokens[offer.currency];
+        token.transaction();
+        let _ = token.transfer(&offer_id, sender, amount);
+        token.end_transaction();
+        if let Some(o) = game.loans.offers.get_mut(&offer_id) {
+            o.available -= amount;
+        }
+        let due = game.elapsed_time.saturating_add(offer.duration);
+        game.loans.loans.insert(
+            id,
+            Loan {
+                offer: offer_id,
+                lender: offer.lender,
+                borrower: *sender,
+                currency: offer.currency,
+                principal: amount,
+                debt: amount,
+                interest_bps: offer.interest_bps,
+                liquidation_ltv_percent: offer.liquidation_ltv_percent,
+                collateral,
+                due,
+            },
+        );
+        game.callbacks.schedule(Box::new(LoanTick {
+            id,
+            time: (game.elapsed_time + INTEREST_PERIOD).min(due),
+        }));
+        game.add_to_event_log(
+            *sender,
+            LogEvent::Loan(LoanEvent::Borrowed {
+                id,
+                offer: offer_id,
+                amount,
+            }),
+        );
+        Ok(())
+    }
+
+    /// Pays down a loan, returning the collateral once it is paid off. Paying
+    /// more than is owed only pays off the debt.
+    pub(crate) fn repay(
+        game: &mut GameBoard,
+        CallContext { ref sender }: &CallContext,
+        id: EntityID,
+        amount: Price,
+    ) -> Result<(), LoanError> {
+        let loan = game.loans.loans.get(&id).ok_or(LoanError::NoSuchLoan)?;
+        if loan.borrower != *sender {
+            return Err(LoanError::NotBorrower);
+        }
+        let (currency, lender, paid) = (loan.currency, loan.lender, amount.min(loan.debt));
+        let token = &mut game.tokens[currency];
+        token.transaction();
+        let ok = token.transfer(sender, &lender, paid);
+        token.end_transaction();
+        if !ok {
+            return Err(LoanError::InsufficientFunds);
+        }
+        let remaining = match game.loans.loans.get_mut(&id) {
+            Some(l) => {
+                l.debt -= paid;
+                l.debt
+            }
+            None => return Err(LoanError::NoSuchLoan),
+        };
+        game.add_to_event_log(
+            *sender,
+            LogEvent::Loan(LoanEvent::Repaid {
+                id,
+                amount: paid,
+                remaining,
+            }),
+        );
+        if remaining == 0 {
+            if let Some(loan) = game.loans.loans.remove(&id) {
+                Self::release(game, id, &loan, loan.borrower);
+                game.add_to_event_log(*sender, LogEvent::Loan(LoanEvent::Closed { id }));
+            }
+        }
+        Ok(())
+    }
+
+    /// Hands the collateral of a closed loan to `to`. Anything a pledged plant
+    /// mined while held goes to the borrower either way.
+    fn release(game: &mut GameBoard, id: EntityID, loan: &Loan, to: EntityID) {
+        match loan.collateral {
+            LoanCollateral::Tokens { asset, amount } => {
+                let token = &mut game.tokens[asset];
+                token.transaction();
+                let _ = token.transfer(&id, &to, amount);
+                token.end_transaction();
+            }
+            LoanCollateral::PowerPlant { plant } => {
+                game.nfts[plant].transfer(to);
+                let token = &mut game.tokens[game.bitcoin_token_id];
+                let mined = token.balance_check(&id);
+                token.transaction();
+                let _ = token.transfer(&id, &loan.borrower, mined);
+                token.end_transaction();
+            }
+        }
+    }
+
+    /// Seizes the collateral for the lender. A seized power plant is listed
+    /// for sale at the outstanding debt, which the lender is free to withdraw.
+    fn liquidate(game: &mut GameBoard, id: EntityID, reason: LiquidationReason) {
+        let loan = match game.loans.loans.remove(&id) {
+            Some(l) => l,
+            None => return,
+        };
+        let collateral_value = loan.collateral.value(game, loan.currency);
+        Self::release(game, id, &loan, loan.lender);
+        if let LoanCollateral::PowerPlant { plant } = loan.collateral {
+            game.nft_sales.list_nft(
+                &CallContext {
+                    sender: loan.lender,
+                },
+                plant,
+                loan.debt.max(1),
+                loan.currency,
+                &game.nfts,
+            );
+        }
+        game.add_to_event_log(
+            loan.borrower,
+            LogEvent::Loan(LoanEvent::Liquidated {
+                id,
+                debt: loan.debt,
+                collateral_value,
+                reason,
+            }),
+        );
+    }
+
+    /// Charges a period of interest, then liquidates the loan if it is due or
+    /// undercollateralized. Returns whether the loan is still outstanding.
+    fn tick(game: &mut GameBoard, id: EntityID) -> bool {
+        let loan = match game.loans.loans.get_mut(&id) {
+            Some(l) => l,
+            None => return false,
+        };
+        // rounded up, so that small debts still accrue interest
+        let owed = loan.debt.saturating_mul(loan.interest_bps);
+        #[allow(clippy::integer_division)]
+        let interest = owed / 10_000 + u128::from(owed % 10_000 != 0);
+        loan.debt = loan.debt.saturating_add(interest);
+        let loan = loan.clone();
+        let reason = if game.elapsed_time >= loan.due {
+            Some(LiquidationReason::PastDue)
+        } else if loan.debt.saturating_mul(100)
+            > loan
+                .collateral
+                .value(game, loan.currency)
+                .saturating_mul(loan.liquidation_ltv_percent)
+        {
+            Some(LiquidationReason::Undercollateralized)
+        } else {
+            None
+        };
+        match reason {
+            Some(reason) => {
+                Self::liquidate(game, id, reason);
+                false
+            }
+            None => true,
+        }
+    }
+}
+
+/// Drives interest and liquidation for a single loan
+#[derive(Debug)]
+struct LoanTick {
+    id: EntityID,
+    time: u64,
+}
+
+impl Callback for LoanTick {
+    fn time(&self) -> u64 {
+        self.time
+    }
+
+    fn action(&mut self, game: &mut GameBoard) {
+        if LoanRegistry::tick(game, self.id) {
+            let due = game.loans.loans[&self.id].due;
+            game.callbacks.schedule(Box::new(LoanTick {
+                id: self.id,
+                time: (game.elapsed_time + INTEREST_PERIOD).min(due),
+            }));
+        }
+    }
+
+    fn purpose(&self) -> String {
+        "Loan Interest and Liquidation".into()
+    }
+}
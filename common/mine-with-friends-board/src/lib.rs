@@ -10,13 +10,18 @@ use sanitize::Unsanitized;
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
+pub mod alliances;
 mod callbacks;
+pub mod derivatives;
 pub mod entity;
 pub mod game;
+pub mod invariants;
+pub mod lending;
 pub mod nfts;
 pub mod sanitize;
 pub mod tokens;
 pub mod util;
+pub mod world;
 
 #[derive(Serialize, Deserialize, Eq, PartialEq, Debug, JsonSchema, Clone)]
 pub struct MoveEnvelope {
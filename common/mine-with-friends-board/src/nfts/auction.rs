@@ -0,0 +1,340 @@
+// Copyright Judica, Inc 2022
+//
+// This Source Code Form is subject to the terms of the Mozilla Public
+//  License, v. 2.0. If a copy of the MPL was not distributed with this
+//  file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+//! English and Dutch auctions for NFTs.
+//!
+//! Bids are escrowed with an entity belonging to the auction, deadlines are
+//! enforced by an AuctionDeadline callback, and every outcome is written to
+//! the event log.
+use super::NFTRegistry;
+use super::NftPtr;
+use crate::callbacks::Callback;
+use crate::entity::EntityID;
+use crate::game::CallContext;
+use crate::game::GameBoard;
+use crate::game::LogEvent;
+use crate::util::Currency;
+use crate::util::Price;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone, Eq, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum AuctionKind {
+    /// Bids must beat the best bid so far by `min_increment`, and the best bid
+    /// at the deadline wins
+    English {
+        reserve: Price,
+        min_increment: Price,
+    },
+    /// The asking price falls from `start_price` to `floor_price` at the
+    /// deadline, and the first bid at or above it wins immediately
+    Dutch {
+        start_price: Price,
+        floor_price: Price,
+    },
+}
+
+/// Represents an open auction of an NFT
+#[derive(Serialize, Debug, Clone, JsonSchema)]
+pub struct Auction {
+    pub kind: AuctionKind,
+    /// The Currency bids are made in
+    pub currency: Currency,
+    /// The seller's ID _at the time the auction was opened_, for replay protection
+    pub seller: EntityID,
+    /// The transfer_count of the NFT _at the time the auction was opened_, for replay protection
+    pub transfer_count: u128,
+    pub started: u64,
+    pub deadline: u64,
+    /// Holds the escrowed best bid, and uniquely identifies this auction
+    pub escrow: EntityID,
+    pub best_bid: Option<(EntityID, Price)>,
+}
+
+#[derive(Serialize, Clone, Debug, JsonSchema)]
+pub enum AuctionError {
+    NotOwner,
+    AlreadyAuctioned,
+    NoSuchAuction,
+    WrongCurrency,
+    SellerCannotBid,
+    BidTooLow { minimum: Price },
+    InsufficientFunds,
+}
+
+/// Auction outcomes, recorded in the event log
+#[derive(Serialize, Clone, Debug, JsonSchema)]
+pub enum AuctionEvent {
+    Started {
+        nft_id: NftPtr,
+        kind: AuctionKind,
+        deadline: u64,
+    },
+    Bid {
+        nft_id: NftPtr,
+        bidder: EntityID,
+        amount: Price,
+    },
+    Sold {
+        nft_id: NftPtr,
+        seller: EntityID,
+        buyer: EntityID,
+        price: Price,
+        currency: Currency,
+    },
+    /// The deadline passed without any acceptable bid
+    Expired { nft_id: NftPtr },
+    /// The NFT changed hands outside of the auction, so any bid was refunded
+    CalledOff { nft_id: NftPtr },
+}
+
+/// An open auction formatted for UX consumption
+#[derive(Serialize, Clone, Debug, JsonSchema)]
+pub struct UXAuction {
+    pub nft_id: NftPtr,
+    pub auction: Auction,
+    /// The least a bid placed now must be
+    pub minimum_bid: Price,
+}
+
+/// A Registry of all open auctions
+#[derive(Serialize, Default, Debug, JsonSchema)]
+pub(crate) struct AuctionRegistry {
+    pub(crate) auctions: BTreeMap<NftPtr, Auction>,
+}
+
+impl Auction {
+    /// For an English auction, the least the next bid may be. For a Dutch
+    /// auction, the current asking price.
+    pub fn minimum_bid(&self, now: u64) -> Price {
+        match self.kind {
+            AuctionKind::English {
+                reserve,
+                min_increment,
+            } => match self.best_bid {
+                Some((_, best)) => best.saturating_add(min_increment.max(1)),
+                None => reserve,
+            },
+            AuctionKind::Dutch {
+                start_price,
+                floor_price,
+            } => {
+                let duration = self.deadline - self.started;
+                let elapsed = now.saturating_sub(self.started).min(duration);
+                let spread = start_price.saturating_sub(floor_price);
+                let discount = spread.saturating_mul(elapsed as u128) / duration.max(1) as u128;
+                start_price.saturating_sub(discount.min(spread))
+            }
+        }
+    }
+
+    /// Checks the seller still owns the NFT, and that it has not moved since
+    /// the auction was opened
+    fn is_valid(&self, nft_id: NftPtr, nfts: &NFTRegistry) -> bool {
+        self.seller == nfts[nft_id].owner() && self.transfer_count == nfts[nft_id].transfer_count()
+    }
+}
+
+impl AuctionRegistry {
+    /// Open an auction of an NFT if the user is the owner. Replaces any fixed
+    /// price listing.
+    pub(crate) fn start(
+        game: &mut GameBoard,
+        CallContext { ref sender }: &CallContext,
+        nft_id: NftPtr,
+        currency: Currency,
+        kind: AuctionKind,
+        duration: u64,
+    ) -> Result<(), AuctionError> {
+        if *sender != game.nfts[nft_id].owner() {
+            return Err(AuctionError::NotOwner);
+        }
+        if let Some(stale) = game.auctions.auctions.get(&nft_id) {
+            if stale.is_valid(nft_id, &game.nfts) {
+                return Err(AuctionError::AlreadyAuctioned);
+            }
+            // the NFT changed hands since, so refund any bid before reopening
+            let escrow = stale.escrow;
+            Self::settle(game, nft_id, escrow);
+        }
+        game.nft_sales.nfts.remove(&nft_id);
+        let escrow = game.alloc();
+        let deadline = game.elapsed_time.saturating_add(duration);
+        game.auctions.auctions.insert(
+            nft_id,
+            Auction {
+                kind: kind.clone(),
+                currency,
+                seller: *sender,
+                transfer_count: game.nfts[nft_id].transfer_count(),
+                started: game.elapsed_time,
+                deadline,
+                escrow,
+                best_bid: None,
+            },
+        );
+        game.callbacks.schedule(Box::new(AuctionDeadline {
+            nft_id,
+            escrow,
+            deadline,
+        }));
+        game.add_to_event_log(
+            *sender,
+            LogEvent::Auction(AuctionEvent::Started {
+                nft_id,
+                kind,
+                deadline,
+            }),
+        );
+        Ok(())
+    }
+
+    /// Bid in an open auction. English bids are escrowed until outbid or the
+    /// deadline, while an acceptable Dutch bid settles immediately.
+    pub(crate) fn bid(
+        game: &mut GameBoard,
+        CallContext { ref sender }: &CallContext,
+        nft_id: NftPtr,
+        amount: Price,
+        currency: Currency,
+    ) -> Result<(), AuctionError> {
+        let auction = game
+            .auctions
+            .auctions
+            .get(&nft_id)
+            .cloned()
+            .ok_or(AuctionError::NoSuchAuction)?;
+        // A rejected bid must leave the board untouched, so a stale auction is
+        // left for its deadline (or a new auction of the NFT) to call off
+        if !auction.is_valid(nft_id, &game.nfts) {
+            return Err(AuctionError::NoSuchAuction);
+        }
+        if currency != auction.currency {
+            return Err(AuctionError::WrongCurrency);
+        }
+        if *sender == auction.seller {
+            return Err(AuctionError::SellerCannotBid);
+        }
+        let minimum = auction.minimum_bid(game.elapsed_time);
+        if amount < minimum {
+            return Err(AuctionError::BidTooLow { minimum });
+        }
+        match auction.kind {
+            AuctionKind::English { .. } => {
+                let token = &mut game.tokens[currency];
+                token.transaction();
+                if !token.transfer(sender, &auction.escrow, amount) {
+                    token.end_transaction();
+                    return Err(AuctionError::InsufficientFunds);
+                }
+                // refund whoever was outbid
+                if let Some((bidder, bid)) = auction.best_bid {
+                    let _ = token.transfer(&auction.escrow, &bidder, bid);
+                }
+                token.end_transaction();
+                if let Some(a) = game.auctions.auctions.get_mut(&nft_id) {
+                    a.best_bid = Some((*sender, amount));
+                }
+                game.add_to_event_log(
+                    *sender,
+                    LogEvent::Auction(AuctionEvent::Bid {
+                        nft_id,
+                        bidder: *sender,
+                        amount,
+                    }),
+                );
+            }
+            AuctionKind::Dutch { .. } => {
+                // the winner pays the asking price, not their bid
+                let token = &mut game.tokens[currency];
+                token.transaction();
+                let paid = token.transfer(sender, &auction.seller, minimum);
+                token.end_transaction();
+                if !paid {
+                    return Err(AuctionError::InsufficientFunds);
+                }
+                game.nfts[nft_id].transfer(*sender);
+                game.auctions.auctions.remove(&nft_id);
+                game.add_to_event_log(
+                    *sender,
+                    LogEvent::Auction(AuctionEvent::Sold {
+                        nft_id,
+                        seller: auction.seller,
+                        buyer: *sender,
+                        price: minimum,
+                        currency,
+                    }),
+                );
+            }
+        }
+        Ok(())
+    }
+
+    /// Close an auction, paying out the best bid if the NFT can still be
+    /// delivered and refunding it otherwise.
+    fn settle(game: &mut GameBoard, nft_id: NftPtr, escrow: EntityID) {
+        let auction = match game.auctions.auctions.get(&nft_id) {
+            Some(a) if a.escrow == escrow => a.clone(),
+            // already settled, or a newer auction of the same NFT
+            _ => return,
+        };
+        game.auctions.auctions.remove(&nft_id);
+        let valid = auction.is_valid(nft_id, &game.nfts);
+        let (from, event) = match auction.best_bid {
+            Some((buyer, price)) if valid => {
+                let token = &mut game.tokens[auction.currency];
+                token.transaction();
+                let _ = token.transfer(&escrow, &auction.seller, price);
+                token.end_transaction();
+                game.nfts[nft_id].transfer(buyer);
+                (
+                    buyer,
+                    AuctionEvent::Sold {
+                        nft_id,
+                        seller: auction.seller,
+                        buyer,
+                        price,
+                        currency: auction.currency,
+                    },
+                )
+            }
+            Some((bidder, bid)) => {
+                let token = &mut game.tokens[auction.currency];
+                token.transaction();
+                let _ = token.transfer(&escrow, &bidder, bid);
+                token.end_transaction();
+                (auction.seller, AuctionEvent::CalledOff { nft_id })
+            }
+            None if valid => (auction.seller, AuctionEvent::Expired { nft_id }),
+            None => (auction.seller, AuctionEvent::CalledOff { nft_id }),
+        };
+        game.add_to_event_log(from, LogEvent::Auction(event));
+    }
+}
+
+/// Closes an auction at its deadline
+#[derive(Debug)]
+struct AuctionDeadline {
+    nft_id: NftPtr,
+    escrow: EntityID,
+    deadline: u64,
+}
+
+impl Callback for AuctionDeadline {
+    fn time(&self) -> u64 {
+        self.deadline
+    }
+
+    fn action(&mut self, game: &mut GameBoard) {
+        AuctionRegistry::settle(game, self.nft_id, self.escrow)
+    }
+
+    fn purpose(&self) -> String {
+        "Auction Deadline".into()
+    }
+}
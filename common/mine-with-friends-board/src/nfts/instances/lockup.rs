@@ -32,7 +32,7 @@ impl CoinLockup {
         asset: TokenPointer,
         amount: Price,
         time_when_free: u64,
-    ) {
+    ) -> NftPtr {
         let lockup_base = BaseNFT {
             owner,
             nft_id: game.alloc(),
@@ -49,6 +49,7 @@ impl CoinLockup {
         game.tokens[asset].transaction();
         let _ = game.tokens[asset].transfer(&sender, &lockup_id.0, amount);
         game.tokens[asset].end_transaction();
+        lockup_id
     }
 }
 
@@ -69,6 +70,8 @@ impl Callback for CoinLockup {
         let balance = token.balance_check(&self.id.0);
         let _ = token.transfer(&self.id.0, &owner, balance);
         token.end_transaction();
+        // if this lockup was shipping miners, they've now arrived
+        game.shipments.remove(&self.id);
     }
 
     fn purpose(&self) -> String {
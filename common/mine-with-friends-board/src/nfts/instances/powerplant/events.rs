@@ -24,16 +24,24 @@ impl Callback for PowerPlantEvent {
         let plants = game.nfts.power_plants.clone();
         let mut total = 0;
         let mut shares: BTreeMap<EntityID, u128> = BTreeMap::new();
+        let mut plant_shares = vec![];
         for (id, plant) in plants {
             let share = plant.compute_hashrate(game);
             total += share;
             let owner = game.nfts[id].owner();
             *shares.entry(owner).or_default() += share;
+            plant_shares.push((id, share));
         }
+        let reward = game.mining.payout(game.elapsed_time, total);
         if let Some(total) = NonZeroU128::new(total) {
+            // tracked per plant for settling forwards on mining payouts
+            for (id, share) in plant_shares {
+                *game.forwards.mined_by_plant.entry(id).or_default() +=
+                    ((share * 1024 * reward).div(total)) / 1024;
+            }
             shares
                 .values_mut()
-                .for_each(|v| *v = ((*v * 1024 * game.mining_subsidy).div(total)) / 1024);
+                .for_each(|v| *v = ((*v * 1024 * reward).div(total)) / 1024);
 
             let btc = &mut game.tokens[game.bitcoin_token_id];
             btc.transaction();
@@ -7,6 +7,8 @@
 use std::cmp::min;
 use std::collections::HashMap;
 pub mod events;
+pub mod shipping;
+pub mod subsidy;
 use super::lockup::CoinLockup;
 use crate::entity::EntityID;
 use crate::game::CallContext;
@@ -31,7 +33,11 @@ pub enum PlantType {
 }
 
 impl PlantType {
-    fn raw_materials_bill(&self, game: &GameBoard, scale: u64) -> Vec<(Currency, Price)> {
+    pub(crate) fn raw_materials_bill(
+        &self,
+        game: &GameBoard,
+        scale: u64,
+    ) -> Vec<(Currency, Price)> {
         let base_prices = game.plant_prices.get(self).unwrap().to_owned();
         let total_prices = base_prices
             .iter()
@@ -68,10 +74,19 @@ impl PowerPlant {
         }
     }
 
+    /// The watts this powerplant actually produces at this game state, given
+    /// its type, location, and the time of day and year
+    pub(crate) fn effective_watts(&self, game: &GameBoard) -> u128 {
+        game.world.effective_watts(
+            self.watts,
+            self.plant_type,
+            self.coordinates,
+            game.elapsed_time,
+        )
+    }
+
     /// Compute the total hashes per second of this powerplant at this game state
     pub(crate) fn compute_hashrate(&self, game: &GameBoard) -> u128 {
-        // TODO: Some algo that uses watts / coordinates / plant_type to compute a scalar?
-        let _scale = 1000;
         let len = game.tokens.hashboards.len();
         let mut hash = Vec::with_capacity(len);
         let hashers: Vec<_> = game.tokens.hashboards.keys().cloned().collect();
@@ -83,7 +98,7 @@ impl PowerPlant {
             }
         }
         hash.sort_unstable();
-        let mut watts = self.watts;
+        let mut watts = self.effective_watts(game);
         let mut hashrate = 0;
         while let Some((hpw, units)) = hash.pop() {
             let available = min(units, watts);
@@ -96,12 +111,18 @@ impl PowerPlant {
         hashrate
     }
 
-    /// Send some of a the owner's hash boxes to this powerplant
-    fn colocate_hashrate(&self, game: &mut GameBoard, miners: TokenPointer, amount: Price) {
+    /// Send some of a the owner's hash boxes to this powerplant, via a
+    /// CoinLockup which emulates shipping
+    fn colocate_hashrate(
+        &self,
+        miners: TokenPointer,
+        amount: Price,
+        shipping_time: u64,
+        game: &mut GameBoard,
+    ) -> NftPtr {
         let owner = game.nfts[self.id].owner();
-        game.tokens[miners].transaction();
-        let _ = game.tokens[miners].transfer(&owner, &self.id.0, amount);
-        game.tokens[miners].end_transaction();
+        let receiver = self.id.0;
+        CoinLockup::lockup(game, owner, receiver, miners, amount, shipping_time)
     }
     /// Withdrawals are processed via a CoinLockup which emulates shipping
     pub(crate) fn ship_hashrate(
@@ -110,7 +131,7 @@ impl PowerPlant {
         amount: Price,
         shipping_time: u64,
         game: &mut GameBoard,
-    ) {
+    ) -> NftPtr {
         let owner = game.nfts[self.id].owner();
         let sender = self.id.0;
         CoinLockup::lockup(game, sender, owner, miners, amount, shipping_time)
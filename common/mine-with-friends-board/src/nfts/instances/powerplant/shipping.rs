@@ -0,0 +1,159 @@
+// Copyright Judica, Inc 2022
+//
+// This Source Code Form is subject to the terms of the Mozilla Public
+//  License, v. 2.0. If a copy of the MPL was not distributed with this
+//  file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+//! Moving miners between a player and their power plants.
+//!
+//! Miners are never moved instantly: they sit in a CoinLockup while in
+//! transit, and each shipment is tracked here until the lockup releases so that
+//! players can see what is on the way and capacity checks can count it.
+use super::PowerPlant;
+use crate::{entity::EntityID, game::GameBoard, nfts::NftPtr, tokens::TokenPointer, util::Price};
+use schemars::JsonSchema;
+use serde::Serialize;
+
+/// Every shipment takes at least this long (ms of elapsed game time)
+const SHIPPING_BASE_TIME: u64 = 60 * 1000;
+/// Additional time per full container of miners
+const SHIPPING_TIME_PER_CONTAINER: u64 = 10 * 1000;
+/// Miners per container
+const CONTAINER_SIZE: Price = 10_000;
+/// No shipment takes longer than this
+const SHIPPING_MAX_TIME: u64 = 5 * 60 * 1000;
+
+#[derive(Serialize, Clone, Copy, Debug, JsonSchema, Eq, PartialEq)]
+pub enum ShippingDirection {
+    ToPlant,
+    FromPlant,
+}
+
+/// A batch of miners in transit between a player and a power plant
+#[derive(Serialize, Clone, Debug, JsonSchema)]
+pub struct Shipment {
+    /// The CoinLockup holding the miners while in transit
+    pub lockup: NftPtr,
+    pub plant: NftPtr,
+    /// The player who sent (or will receive) the miners
+    pub owner: EntityID,
+    pub miners: TokenPointer,
+    pub amount: Price,
+    pub direction: ShippingDirection,
+    /// Elapsed game time at which the shipment is delivered
+    pub arrives: u64,
+}
+
+#[derive(Serialize, Clone, Debug, JsonSchema)]
+pub enum ShippingError {
+    NotPlantOwner,
+    InsufficientMiners { available: Price, requested: Price },
+    OverCapacity { capacity: Price, requested: Price },
+}
+
+/// How long it takes to ship `amount` miners, in either direction
+pub fn shipping_time(amount: Price) -> u64 {
+    #[allow(clippy::integer_division)]
+    let containers = amount.saturating_add(CONTAINER_SIZE - 1) / CONTAINER_SIZE;
+    let time = containers
+        .saturating_mul(SHIPPING_TIME_PER_CONTAINER as u128)
+        .saturating_add(SHIPPING_BASE_TIME as u128);
+    time.min(SHIPPING_MAX_TIME as u128) as u64
+}
+
+impl PowerPlant {
+    /// Total miners of any kind at this plant or on their way to it
+    pub(crate) fn committed_miners(&self, game: &GameBoard) -> Price {
+        let installed: Price = game
+            .tokens
+            .hashboards
+            .keys()
+            .map(|token| game.tokens[*token].balance_check(&self.id.0))
+            .sum();
+        let incoming: Price = game
+            .shipments
+            .values()
+            .filter(|s| s.plant == self.id && s.direction == ShippingDirection::ToPlant)
+            .map(|s| s.amount)
+            .sum();
+        installed + incoming
+    }
+
+    /// Ship some of the owner's miners to this plant, subject to its capacity
+    pub(crate) fn colocate_miners(
+        &self,
+        game: &mut GameBoard,
+        sender: EntityID,
+        miners: TokenPointer,
+        amount: Price,
+    ) -> Result<(), ShippingError> {
+        if game.nfts[self.id].owner() != sender {
+            return Err(ShippingError::NotPlantOwner);
+        }
+        let available = game.tokens[miners].balance_check(&sender);
+        if available < amount {
+            return Err(ShippingError::InsufficientMiners {
+                available,
+                requested: amount,
+            });
+        }
+        // Each miner draws one unit of the plant's rated watts
+        let capacity = self.watts.saturating_sub(self.committed_miners(game));
+        if capacity < amount {
+            return Err(ShippingError::OverCapacity {
+                capacity,
+                requested: amount,
+            });
+        }
+        let arrives = game.elapsed_time + shipping_time(amount);
+        let lockup = self.colocate_hashrate(miners, amount, arrives, game);
+        game.shipments.insert(
+            lockup,
+            Shipment {
+                lockup,
+                plant: self.id,
+                owner: sender,
+                miners,
+                amount,
+                direction: ShippingDirection::ToPlant,
+                arrives,
+            },
+        );
+        Ok(())
+    }
+
+    /// Ship some of this plant's miners back to its owner
+    pub(crate) fn ship_miners(
+        &self,
+        game: &mut GameBoard,
+        sender: EntityID,
+        miners: TokenPointer,
+        amount: Price,
+    ) -> Result<(), ShippingError> {
+        if game.nfts[self.id].owner() != sender {
+            return Err(ShippingError::NotPlantOwner);
+        }
+        let available = game.tokens[miners].balance_check(&self.id.0);
+        if available < amount {
+            return Err(ShippingError::InsufficientMiners {
+                available,
+                requested: amount,
+            });
+        }
+        let arrives = game.elapsed_time + shipping_time(amount);
+        let lockup = self.ship_hashrate(miners, amount, arrives, game);
+        game.shipments.insert(
+            lockup,
+            Shipment {
+                lockup,
+                plant: self.id,
+                owner: sender,
+                miners,
+                amount,
+                direction: ShippingDirection::FromPlant,
+                arrives,
+            },
+        );
+        Ok(())
+    }
+}
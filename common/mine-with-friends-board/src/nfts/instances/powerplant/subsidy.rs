@@ -0,0 +1,154 @@
+// Copyright Judica, Inc 2022
+//
+// This Source Code Form is subject to the terms of the Mozilla Public
+//  License, v. 2.0. If a copy of the MPL was not distributed with this
+//  file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+//! Bitcoin-style issuance for the periodic mining payout.
+//!
+//! The subsidy halves every `halving_interval` of elapsed game time. On top of
+//! that, a difficulty target retargets toward the observed network hashrate
+//! every `retarget_every` payouts, so adding hashrate lowers the reward earned
+//! per hash once the network catches up.
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::cmp::{max, min};
+
+/// Like Bitcoin, a single retarget may move difficulty by at most 4x, and a
+/// period may pay out at most 4x the subsidy before difficulty catches up.
+const MAX_ADJUSTMENT: u128 = 4;
+/// Upper bound on the number of future halvings reported to the UX
+const MAX_UX_HALVINGS: usize = 64;
+
+/// Configurable parameters for the mining subsidy, set via GameSetup
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone, Eq, PartialEq)]
+pub struct SubsidyParams {
+    /// Virtual Sats minted per payout period before any halving
+    pub initial_subsidy: u128,
+    /// Elapsed game time (ms) between halvings. 0 disables halvings.
+    pub halving_interval: u64,
+    /// Elapsed game time (ms) between mining payouts
+    pub payout_period: u64,
+    /// Number of payouts between difficulty retargets. 0 disables retargets.
+    pub retarget_every: u64,
+}
+
+impl Default for SubsidyParams {
+    fn default() -> Self {
+        Self {
+            initial_subsidy: 100_000_000 * 50,
+            halving_interval: 20 * 60 * 1000, // 20 minutes
+            payout_period: 11_003,            // 11 seconds
+            retarget_every: 10,
+        }
+    }
+}
+
+/// The live state of the subsidy schedule
+#[derive(Serialize, JsonSchema, Debug, Clone)]
+pub struct SubsidySchedule {
+    pub params: SubsidyParams,
+    /// The hashrate which earns exactly one subsidy per payout. 0 until the
+    /// first hashrate is observed.
+    pub difficulty: u128,
+    /// Sum of the network hashrate over each payout since the last retarget
+    pub window_hashrate: u128,
+    /// Number of payouts since the last retarget
+    pub window_payouts: u64,
+}
+
+/// A schedule summary formatted for UX consumption
+#[derive(Serialize, Clone, Debug, JsonSchema)]
+pub struct UXMiningSchedule {
+    pub current_subsidy: u128,
+    pub difficulty: u128,
+    pub payout_period: u64,
+    pub payouts_until_retarget: Option<u64>,
+    /// (elapsed time, subsidy from that time on) for each upcoming halving
+    /// before the game finishes
+    pub halvings: Vec<(u64, u128)>,
+}
+
+impl SubsidySchedule {
+    pub fn new(params: SubsidyParams) -> Self {
+        Self {
+            params,
+            difficulty: 0,
+            window_hashrate: 0,
+            window_payouts: 0,
+        }
+    }
+
+    /// The subsidy paid for a payout at `elapsed` game time
+    pub fn subsidy_at(&self, elapsed: u64) -> u128 {
+        if self.params.halving_interval == 0 {
+            return self.params.initial_subsidy;
+        }
+        let halvings = elapsed / self.params.halving_interval;
+        if halvings >= 128 {
+            0
+        } else {
+            self.params.initial_subsidy >> halvings
+        }
+    }
+
+    /// The elapsed game time of the first halving after `elapsed`, if any
+    pub fn next_halving(&self, elapsed: u64) -> Option<u64> {
+        let interval = self.params.halving_interval;
+        if interval == 0 || self.subsidy_at(elapsed) == 0 {
+            return None;
+        }
+        (elapsed / interval + 1).checked_mul(interval)
+    }
+
+    /// Computes the total payout for a period in which the network produced
+    /// `total_hashrate`, and advances the difficulty retarget window.
+    pub(crate) fn payout(&mut self, elapsed: u64, total_hashrate: u128) -> u128 {
+        // bootstrap difficulty from the first hashrate seen
+        if self.difficulty == 0 {
+            self.difficulty = total_hashrate;
+        }
+        let paid = if self.difficulty == 0 {
+            0
+        } else {
+            let found = min(total_hashrate, self.difficulty * MAX_ADJUSTMENT);
+            self.subsidy_at(elapsed) * found / self.difficulty
+        };
+        self.window_hashrate += total_hashrate;
+        self.window_payouts += 1;
+        if self.params.retarget_every != 0 && self.window_payouts >= self.params.retarget_every {
+            self.retarget();
+        }
+        paid
+    }
+
+    /// Move difficulty toward the average hashrate of the last window
+    fn retarget(&mut self) {
+        let average = self.window_hashrate / self.window_payouts as u128;
+        let floor = self.difficulty / MAX_ADJUSTMENT;
+        let ceiling = self.difficulty * MAX_ADJUSTMENT;
+        self.difficulty = min(max(average, floor), ceiling);
+        self.window_hashrate = 0;
+        self.window_payouts = 0;
+    }
+
+    pub fn to_ux(&self, elapsed: u64, finish_time: u64) -> UXMiningSchedule {
+        let mut halvings = vec![];
+        let mut t = elapsed;
+        while let Some(next) = self.next_halving(t) {
+            if next >= finish_time || halvings.len() >= MAX_UX_HALVINGS {
+                break;
+            }
+            halvings.push((next, self.subsidy_at(next)));
+            t = next;
+        }
+        UXMiningSchedule {
+            current_subsidy: self.subsidy_at(elapsed),
+            difficulty: self.difficulty,
+            payout_period: self.params.payout_period,
+            payouts_until_retarget: (self.params.retarget_every != 0)
+                .then(|| self.params.retarget_every - self.window_payouts),
+            halvings,
+        }
+    }
+}
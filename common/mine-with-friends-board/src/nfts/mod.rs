@@ -15,6 +15,7 @@ use std::borrow::Borrow;
 use std::collections::BTreeMap;
 use std::ops::Index;
 use std::ops::IndexMut;
+pub mod auction;
 pub mod instances;
 pub mod sale;
 /// All NFTs must implement these behaviors
@@ -138,6 +139,8 @@ pub struct UXPlantData {
     pub owner: EntityID,
     pub plant_type: PlantType,
     pub watts: Watts,
+    /// Watts currently produced, given location and time of day and year
+    pub effective_watts: Watts,
     pub hashrate: u128,
 }
 #[derive(Serialize, Clone)]
@@ -12,17 +12,25 @@ use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
 use crate::{
+    alliances::AllianceSpend,
+    derivatives::ForwardUnderlying,
     entity::EntityID,
     game::{
         game_move::{
-            Chat, GameMove, Heartbeat, ListNFTForSale, MintPowerPlant, PurchaseNFT, RemoveTokens,
-            SendTokens, Trade,
+            AcceptForward, BidOnAuction, Borrow, CancelForward, CancelLoanOffer, Chat,
+            ColocateMiners, CreateAlliance, DepositPlantWithAlliance, GameMove, Heartbeat,
+            JoinAlliance, LeaveAlliance, ListNFTForSale, MintPowerPlant, OfferLoan, PrivateChat,
+            ProposeAllianceSpend, PurchaseNFT, RemoveTokens, RepayLoan, SendTokens, ShipMiners,
+            StartAuction, Trade, VoteOnAllianceProposal, WriteForward,
         },
         GameBoard,
     },
-    nfts::NftPtr,
+    lending::LoanCollateral,
+    nfts::{auction::AuctionKind, NftPtr},
     tokens::token_swap::TradingPairID,
     tokens::TokenPointer,
+    util::Location,
+    world::WorldMap,
 };
 
 #[derive(Serialize, Clone, Debug)]
@@ -31,8 +39,29 @@ pub enum SanitizationError {
     InvalidNFTPtr(NftPtr),
     InvalidUser(EntityID),
     MintScaleIsZero,
+    InvalidLocation(Location),
+    NotAPowerPlant(NftPtr),
+    NotAHashboard(TokenPointer),
+    ShipmentIsEmpty,
+    AuctionDurationIsZero,
+    DutchAuctionFloorAboveStart,
+    ForwardDurationIsZero,
+    ForwardUnderlyingIsEmpty,
+    LoanDurationIsZero,
+    LoanAmountIsZero,
+    /// Loan-to-value limits must be nonzero, and allow borrowing before
+    /// liquidation
+    InvalidLoanTerms,
+    PrivateChatHasNoRecipients,
+    /// Each sealed copy of a private chat message may be at most
+    /// MAX_SEALED_CHAT_LEN characters
+    PrivateChatTooLong,
+    AllianceNameIsEmpty,
 }
 
+/// The longest sealed private chat message, in hex characters
+pub const MAX_SEALED_CHAT_LEN: usize = 4096;
+
 impl JsonSchema for SanitizationError {
     fn schema_name() -> String {
         "SanitizationError".into()
@@ -117,8 +146,26 @@ impl Sanitizable for GameMove {
             GameMove::SuperMintPowerPlant(x) => GameMove::SuperMintPowerPlant(x.sanitize(context)?),
             GameMove::PurchaseNFT(x) => x.sanitize(context)?.into(),
             GameMove::ListNFTForSale(x) => x.sanitize(context)?.into(),
+            GameMove::StartAuction(x) => x.sanitize(context)?.into(),
+            GameMove::BidOnAuction(x) => x.sanitize(context)?.into(),
+            GameMove::WriteForward(x) => x.sanitize(context)?.into(),
+            GameMove::AcceptForward(x) => x.sanitize(context)?.into(),
+            GameMove::CancelForward(x) => x.sanitize(context)?.into(),
+            GameMove::OfferLoan(x) => x.sanitize(context)?.into(),
+            GameMove::CancelLoanOffer(x) => x.sanitize(context)?.into(),
+            GameMove::Borrow(x) => x.sanitize(context)?.into(),
+            GameMove::RepayLoan(x) => x.sanitize(context)?.into(),
+            GameMove::CreateAlliance(x) => x.sanitize(context)?.into(),
+            GameMove::JoinAlliance(x) => x.sanitize(context)?.into(),
+            GameMove::LeaveAlliance(x) => x.sanitize(context)?.into(),
+            GameMove::DepositPlantWithAlliance(x) => x.sanitize(context)?.into(),
+            GameMove::ProposeAllianceSpend(x) => x.sanitize(context)?.into(),
+            GameMove::VoteOnAllianceProposal(x) => x.sanitize(context)?.into(),
             GameMove::SendTokens(x) => x.sanitize(context)?.into(),
+            GameMove::ColocateMiners(x) => x.sanitize(context)?.into(),
+            GameMove::ShipMiners(x) => x.sanitize(context)?.into(),
             GameMove::Chat(x) => x.sanitize(context)?.into(),
+            GameMove::PrivateChat(x) => x.sanitize(context)?.into(),
         })
     }
 }
@@ -165,6 +212,27 @@ impl Sanitizable for Chat {
     }
 }
 
+impl Sanitizable for PrivateChat {
+    type Output = Self;
+    type Context = GameBoard;
+    type Error = SanitizationError;
+
+    fn sanitize(self, context: &Self::Context) -> Result<Self::Output, Self::Error> {
+        if self.sealed.is_empty() {
+            return Err(SanitizationError::PrivateChatHasNoRecipients);
+        }
+        for (to, sealed) in self.sealed.iter() {
+            if !context.users.contains_key(to) {
+                return Err(SanitizationError::InvalidUser(*to));
+            }
+            if sealed.len() > MAX_SEALED_CHAT_LEN {
+                return Err(SanitizationError::PrivateChatTooLong);
+            }
+        }
+        Ok(self)
+    }
+}
+
 // because resources is Vec<(TokenPointer, u128)> may need make Resources a type and sanitize.
 impl Sanitizable for MintPowerPlant {
     type Output = Self;
@@ -174,7 +242,9 @@ impl Sanitizable for MintPowerPlant {
         if self.scale == 0 {
             return Err(SanitizationError::MintScaleIsZero);
         }
-        // TODO: CHeck that location is a valid coordinate
+        if !WorldMap::is_valid_location(self.location) {
+            return Err(SanitizationError::InvalidLocation(self.location));
+        }
         Ok(self)
     }
 }
@@ -214,6 +284,302 @@ impl Sanitizable for ListNFTForSale {
     }
 }
 
+impl Sanitizable for StartAuction {
+    type Output = Self;
+    type Context = GameBoard;
+    type Error = SanitizationError;
+    fn sanitize(self, context: &Self::Context) -> Result<Self::Output, Self::Error> {
+        let Self {
+            nft_id,
+            currency,
+            kind,
+            duration,
+        } = self;
+        let nft_id = nft_id.sanitize(context)?;
+        if !context.nfts.power_plants.contains_key(&nft_id) {
+            return Err(SanitizationError::NotAPowerPlant(nft_id));
+        }
+        if duration == 0 {
+            return Err(SanitizationError::AuctionDurationIsZero);
+        }
+        if let AuctionKind::Dutch {
+            start_price,
+            floor_price,
+        } = kind
+        {
+            if floor_price > start_price {
+                return Err(SanitizationError::DutchAuctionFloorAboveStart);
+            }
+        }
+        Ok(Self {
+            nft_id,
+            currency: currency.sanitize(context)?,
+            kind,
+            duration,
+        })
+    }
+}
+
+impl Sanitizable for BidOnAuction {
+    type Output = Self;
+    type Context = GameBoard;
+    type Error = SanitizationError;
+    fn sanitize(self, context: &Self::Context) -> Result<Self::Output, Self::Error> {
+        let Self {
+            nft_id,
+            amount,
+            currency,
+        } = self;
+        Ok(Self {
+            nft_id: nft_id.sanitize(context)?,
+            amount,
+            currency: currency.sanitize(context)?,
+        })
+    }
+}
+
+impl Sanitizable for WriteForward {
+    type Output = Self;
+    type Context = GameBoard;
+    type Error = SanitizationError;
+    fn sanitize(self, context: &Self::Context) -> Result<Self::Output, Self::Error> {
+        let Self {
+            side,
+            underlying,
+            price,
+            currency,
+            counterparty,
+            duration,
+        } = self;
+        if duration == 0 {
+            return Err(SanitizationError::ForwardDurationIsZero);
+        }
+        let underlying = match underlying {
+            ForwardUnderlying::Delivery { asset, quantity } => {
+                if quantity == 0 {
+                    return Err(SanitizationError::ForwardUnderlyingIsEmpty);
+                }
+                ForwardUnderlying::Delivery {
+                    asset: asset.sanitize(context)?,
+                    quantity,
+                }
+            }
+            ForwardUnderlying::MiningPayout { plant, collateral } => {
+                let plant = plant.sanitize(context)?;
+                if !context.nfts.power_plants.contains_key(&plant) {
+                    return Err(SanitizationError::NotAPowerPlant(plant));
+                }
+                if collateral == 0 {
+                    return Err(SanitizationError::ForwardUnderlyingIsEmpty);
+                }
+                ForwardUnderlying::MiningPayout { plant, collateral }
+            }
+        };
+        if let Some(c) = counterparty {
+            if !context.users.contains_key(&c) {
+                return Err(SanitizationError::InvalidUser(c));
+            }
+        }
+        Ok(Self {
+            side,
+            underlying,
+            price,
+            currency: currency.sanitize(context)?,
+            counterparty,
+            duration,
+        })
+    }
+}
+
+impl Sanitizable for AcceptForward {
+    type Output = Self;
+    type Context = GameBoard;
+    type Error = SanitizationError;
+    fn sanitize(self, _context: &Self::Context) -> Result<Self::Output, Self::Error> {
+        // checked against the ForwardRegistry when played
+        Ok(self)
+    }
+}
+
+impl Sanitizable for CancelForward {
+    type Output = Self;
+    type Context = GameBoard;
+    type Error = SanitizationError;
+    fn sanitize(self, _context: &Self::Context) -> Result<Self::Output, Self::Error> {
+        Ok(self)
+    }
+}
+
+impl Sanitizable for OfferLoan {
+    type Output = Self;
+    type Context = GameBoard;
+    type Error = SanitizationError;
+    fn sanitize(self, context: &Self::Context) -> Result<Self::Output, Self::Error> {
+        if self.duration == 0 {
+            return Err(SanitizationError::LoanDurationIsZero);
+        }
+        if self.amount == 0 {
+            return Err(SanitizationError::LoanAmountIsZero);
+        }
+        if self.max_ltv_percent == 0 || self.max_ltv_percent > self.liquidation_ltv_percent {
+            return Err(SanitizationError::InvalidLoanTerms);
+        }
+        Ok(Self {
+            currency: self.currency.sanitize(context)?,
+            ..self
+        })
+    }
+}
+
+impl Sanitizable for CancelLoanOffer {
+    type Output = Self;
+    type Context = GameBoard;
+    type Error = SanitizationError;
+    fn sanitize(self, _context: &Self::Context) -> Result<Self::Output, Self::Error> {
+        // checked against the LoanRegistry when played
+        Ok(self)
+    }
+}
+
+impl Sanitizable for Borrow {
+    type Output = Self;
+    type Context = GameBoard;
+    type Error = SanitizationError;
+    fn sanitize(self, context: &Self::Context) -> Result<Self::Output, Self::Error> {
+        let Self {
+            offer,
+            amount,
+            collateral,
+        } = self;
+        if amount == 0 {
+            return Err(SanitizationError::LoanAmountIsZero);
+        }
+        let collateral = match collateral {
+            LoanCollateral::Tokens { asset, amount } => LoanCollateral::Tokens {
+                asset: asset.sanitize(context)?,
+                amount,
+            },
+            LoanCollateral::PowerPlant { plant } => {
+                let plant = plant.sanitize(context)?;
+                if !context.nfts.power_plants.contains_key(&plant) {
+                    return Err(SanitizationError::NotAPowerPlant(plant));
+                }
+                LoanCollateral::PowerPlant { plant }
+            }
+        };
+        Ok(Self {
+            offer,
+            amount,
+            collateral,
+        })
+    }
+}
+
+impl Sanitizable for RepayLoan {
+    type Output = Self;
+    type Context = GameBoard;
+    type Error = SanitizationError;
+    fn sanitize(self, _context: &Self::Context) -> Result<Self::Output, Self::Error> {
+        if self.amount == 0 {
+            return Err(SanitizationError::LoanAmountIsZero);
+        }
+        Ok(self)
+    }
+}
+
+impl Sanitizable for CreateAlliance {
+    type Output = Self;
+    type Context = GameBoard;
+    type Error = SanitizationError;
+    fn sanitize(self, _context: &Self::Context) -> Result<Self::Output, Self::Error> {
+        if self.name.trim().is_empty() {
+            return Err(SanitizationError::AllianceNameIsEmpty);
+        }
+        Ok(self)
+    }
+}
+
+impl Sanitizable for JoinAlliance {
+    type Output = Self;
+    type Context = GameBoard;
+    type Error = SanitizationError;
+    fn sanitize(self, _context: &Self::Context) -> Result<Self::Output, Self::Error> {
+        // checked against the AllianceRegistry when played
+        Ok(self)
+    }
+}
+
+impl Sanitizable for LeaveAlliance {
+    type Output = Self;
+    type Context = GameBoard;
+    type Error = SanitizationError;
+    fn sanitize(self, _context: &Self::Context) -> Result<Self::Output, Self::Error> {
+        // checked against the AllianceRegistry when played
+        Ok(self)
+    }
+}
+
+impl Sanitizable for DepositPlantWithAlliance {
+    type Output = Self;
+    type Context = GameBoard;
+    type Error = SanitizationError;
+    fn sanitize(self, context: &Self::Context) -> Result<Self::Output, Self::Error> {
+        let nft_id = self.nft_id.sanitize(context)?;
+        if !context.nfts.power_plants.contains_key(&nft_id) {
+            return Err(SanitizationError::NotAPowerPlant(nft_id));
+        }
+        Ok(Self { nft_id, ..self })
+    }
+}
+
+impl Sanitizable for ProposeAllianceSpend {
+    type Output = Self;
+    type Context = GameBoard;
+    type Error = SanitizationError;
+    fn sanitize(self, context: &Self::Context) -> Result<Self::Output, Self::Error> {
+        let is_recipient = |to: &EntityID| {
+            context.users.contains_key(to) || context.alliances.alliances.contains_key(to)
+        };
+        let spend = match self.spend {
+            AllianceSpend::Tokens {
+                to,
+                currency,
+                amount,
+            } => {
+                if !is_recipient(&to) {
+                    return Err(SanitizationError::InvalidUser(to));
+                }
+                AllianceSpend::Tokens {
+                    to,
+                    currency: currency.sanitize(context)?,
+                    amount,
+                }
+            }
+            AllianceSpend::PowerPlant { to, plant } => {
+                if !is_recipient(&to) {
+                    return Err(SanitizationError::InvalidUser(to));
+                }
+                let plant = plant.sanitize(context)?;
+                if !context.nfts.power_plants.contains_key(&plant) {
+                    return Err(SanitizationError::NotAPowerPlant(plant));
+                }
+                AllianceSpend::PowerPlant { to, plant }
+            }
+        };
+        Ok(Self { spend, ..self })
+    }
+}
+
+impl Sanitizable for VoteOnAllianceProposal {
+    type Output = Self;
+    type Context = GameBoard;
+    type Error = SanitizationError;
+    fn sanitize(self, _context: &Self::Context) -> Result<Self::Output, Self::Error> {
+        // checked against the AllianceRegistry when played
+        Ok(self)
+    }
+}
+
 impl Sanitizable for SendTokens {
     type Output = Self;
     type Context = GameBoard;
@@ -224,7 +590,10 @@ impl Sanitizable for SendTokens {
             amount,
             currency,
         } = self;
-        if context.users.contains_key(&to) || context.nfts.nfts.contains_key(&to) {
+        if context.users.contains_key(&to)
+            || context.nfts.nfts.contains_key(&to)
+            || context.alliances.alliances.contains_key(&to)
+        {
             Ok(Self {
                 to,
                 amount,
@@ -253,3 +622,62 @@ impl Sanitizable for RemoveTokens {
         })
     }
 }
+
+/// Checks the parts of a shipment common to both directions
+fn sanitize_shipment(
+    nft_id: NftPtr,
+    miners: TokenPointer,
+    amount: u128,
+    context: &GameBoard,
+) -> Result<(NftPtr, TokenPointer), SanitizationError> {
+    if amount == 0 {
+        return Err(SanitizationError::ShipmentIsEmpty);
+    }
+    let nft_id = nft_id.sanitize(context)?;
+    if !context.nfts.power_plants.contains_key(&nft_id) {
+        return Err(SanitizationError::NotAPowerPlant(nft_id));
+    }
+    let miners = miners.sanitize(context)?;
+    if !context.tokens.hashboards.contains_key(&miners) {
+        return Err(SanitizationError::NotAHashboard(miners));
+    }
+    Ok((nft_id, miners))
+}
+
+impl Sanitizable for ColocateMiners {
+    type Output = Self;
+    type Context = GameBoard;
+    type Error = SanitizationError;
+    fn sanitize(self, context: &Self::Context) -> Result<Self::Output, Self::Error> {
+        let Self {
+            nft_id,
+            miners,
+            amount,
+        } = self;
+        let (nft_id, miners) = sanitize_shipment(nft_id, miners, amount, context)?;
+        Ok(Self {
+            nft_id,
+            miners,
+            amount,
+        })
+    }
+}
+
+impl Sanitizable for ShipMiners {
+    type Output = Self;
+    type Context = GameBoard;
+    type Error = SanitizationError;
+    fn sanitize(self, context: &Self::Context) -> Result<Self::Output, Self::Error> {
+        let Self {
+            nft_id,
+            miners,
+            amount,
+        } = self;
+        let (nft_id, miners) = sanitize_shipment(nft_id, miners, amount, context)?;
+        Ok(Self {
+            nft_id,
+            miners,
+            amount,
+        })
+    }
+}
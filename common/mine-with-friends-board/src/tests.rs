@@ -5,17 +5,38 @@
 //  file, You can obtain one at https://mozilla.org/MPL/2.0/.
 
 use crate::{
+    alliances::{AllianceError, AllianceEvent, AllianceSpend},
+    callbacks::Callback,
+    derivatives::{ForwardError, ForwardSide, ForwardUnderlying},
+    entity::EntityID,
     game::{
+        clock::{ClockError, ClockParams, ClockSource, SequencerTime},
         game_move::{
-            GameMove, Heartbeat, ListNFTForSale, MintPowerPlant, PurchaseNFT, RemoveTokens,
-            SendTokens, Trade,
+            AcceptForward, BidOnAuction, Borrow, CancelForward, CancelLoanOffer, Chat,
+            ColocateMiners, CreateAlliance, GameMove, Heartbeat, JoinAlliance, LeaveAlliance,
+            ListNFTForSale, MintPowerPlant, OfferLoan, PrivateChat, ProposeAllianceSpend,
+            PurchaseNFT, RemoveTokens, RepayLoan, SendTokens, ShipMiners, StartAuction, Trade,
+            VoteOnAllianceProposal, WriteForward,
         },
-        FinishReason, GameBoard, GameSetup, MoveRejectReason,
+        FinishReason, GameBoard, GameSetup, LogEvent, MoveRejectReason, MAX_PENDING_MOVES,
     },
-    sanitize::Unsanitized,
-    tokens::token_swap::TradingPairID,
+    lending::{LiquidationReason, LoanCollateral, LoanError, LoanEvent, INTEREST_PERIOD},
+    nfts::auction::{AuctionError, AuctionKind},
+    nfts::instances::powerplant::{
+        shipping::{ShippingDirection, ShippingError},
+        subsidy::{SubsidyParams, SubsidySchedule},
+        PlantType,
+    },
+    sanitize::{SanitizationError, Unsanitized, MAX_SEALED_CHAT_LEN},
+    tokens::{
+        instances::asics::HardwareFailureEvent,
+        token_swap::{TradeError, TradingPairID},
+    },
+    util::splitmix64,
+    world::{WorldMap, DAY_LENGTH, MAX_LATITUDE, MAX_LONGITUDE},
     MoveEnvelope,
 };
+use std::collections::BTreeMap;
 use tracing::{debug, info, trace};
 
 const ALICE: &str = "alice";
@@ -235,6 +256,57 @@ fn test_game_swaps() {
                 assert_eq!(game.tokens[game.asic_token_id].balance_check(&id), 15);
             },
         ),
+        (
+            ALICE,
+            MoveEnvelope {
+                d: Unsanitized(GameMove::Trade(Trade {
+                    pair: TradingPairID {
+                        asset_a: game.steel_token_id,
+                        asset_b: game.silicon_token_id,
+                    },
+                    amount_a: 1,
+                    amount_b: 0,
+                    sell: true,
+                    cap: None,
+                })),
+                sequence: 7,
+                time_millis: 7000,
+            },
+            &|_game, r| {
+                // a market with no liquidity rejects trades in either direction
+                assert!(matches!(
+                    r,
+                    Err(MoveRejectReason::TradeRejected(
+                        TradeError::InsufficientTokens(_)
+                    ))
+                ));
+            },
+        ),
+        (
+            ALICE,
+            MoveEnvelope {
+                d: Unsanitized(GameMove::Trade(Trade {
+                    pair: TradingPairID {
+                        asset_a: game.steel_token_id,
+                        asset_b: game.silicon_token_id,
+                    },
+                    amount_a: 1,
+                    amount_b: 0,
+                    sell: false,
+                    cap: None,
+                })),
+                sequence: 8,
+                time_millis: 8000,
+            },
+            &|_game, r| {
+                assert!(matches!(
+                    r,
+                    Err(MoveRejectReason::TradeRejected(
+                        TradeError::InsufficientTokens(_)
+                    ))
+                ));
+            },
+        ),
     ];
     run_game(moves, &mut game);
 }
@@ -573,8 +645,7 @@ fn test_sell_plant() {
 }
 
 #[test]
-fn test_remove_tokens_from_plant() {
-    // setup, build plant, tx tokens (assert it happened), tx tokens back
+fn test_auctions() {
     let _ = tracing_subscriber::fmt::try_init();
     let mut game = setup_game();
 
@@ -589,7 +660,6 @@ fn test_remove_tokens_from_plant() {
         bob_seq += 1;
         bob_seq - 1
     };
-    // moves to make a plant
     let moves = [
         (
             ALICE,
@@ -605,7 +675,7 @@ fn test_remove_tokens_from_plant() {
             MoveEnvelope {
                 d: Unsanitized(GameMove::Heartbeat(Heartbeat())),
                 sequence: bob_seq_next(),
-                time_millis: 1232,
+                time_millis: 900,
             },
             NO_POST,
         ),
@@ -614,7 +684,7 @@ fn test_remove_tokens_from_plant() {
             MoveEnvelope {
                 d: Unsanitized(GameMove::SuperMintPowerPlant(MintPowerPlant {
                     scale: 1,
-                    plant_type: crate::nfts::instances::powerplant::PlantType::Solar,
+                    plant_type: PlantType::Solar,
                     location: (15, 15),
                 })),
                 sequence: alice_seq_next(),
@@ -625,24 +695,55 @@ fn test_remove_tokens_from_plant() {
     ];
     run_game(moves, &mut game);
 
-    let id = game.get_user_id(ALICE).unwrap();
-    let plants = game.get_user_power_plants(id).unwrap();
-    let plant_id = *plants.power_plant_data.iter().next().unwrap().0;
-    let _btc_balance_before_mining = game.tokens[game.bitcoin_token_id].balance_check(&id);
+    let a_id = game.get_user_id(ALICE).unwrap();
+    let b_id = game.get_user_id(BOB).unwrap();
+    let plant_id = *game
+        .get_user_power_plants(a_id)
+        .unwrap()
+        .power_plant_data
+        .iter()
+        .next()
+        .unwrap()
+        .0;
+    // mining payouts are in bitcoin, so bid in real sats to keep balances exact
+    let sats = game.real_sats_token_id;
+    let bob_start = game.tokens[sats].balance_check(&b_id);
 
-    let moves2 = [
+    // English auction: bids are escrowed and the best one wins at the deadline
+    let moves = [
+        (
+            BOB,
+            MoveEnvelope {
+                d: Unsanitized(GameMove::StartAuction(StartAuction {
+                    nft_id: plant_id,
+                    currency: sats,
+                    kind: AuctionKind::English {
+                        reserve: 1000,
+                        min_increment: 100,
+                    },
+                    duration: 10_000,
+                })),
+                sequence: bob_seq_next(),
+                time_millis: 1500,
+            },
+            (&|_g: &GameBoard, r: Result<(), MoveRejectReason>| {
+                assert!(matches!(
+                    r,
+                    Err(MoveRejectReason::AuctionRejected(AuctionError::NotOwner))
+                ))
+            }) as PostCondition,
+        ),
         (
             ALICE,
             MoveEnvelope {
-                d: Unsanitized(GameMove::Trade(Trade {
-                    pair: TradingPairID {
-                        asset_a: game.bitcoin_token_id,
-                        asset_b: game.asic_token_id,
+                d: Unsanitized(GameMove::StartAuction(StartAuction {
+                    nft_id: plant_id,
+                    currency: sats,
+                    kind: AuctionKind::English {
+                        reserve: 1000,
+                        min_increment: 100,
                     },
-                    amount_a: 0,
-                    amount_b: 1,
-                    sell: false,
-                    cap: None,
+                    duration: 10_000,
                 })),
                 sequence: alice_seq_next(),
                 time_millis: 2000,
@@ -650,60 +751,289 @@ fn test_remove_tokens_from_plant() {
             NO_POST,
         ),
         (
-            ALICE,
+            BOB,
             MoveEnvelope {
-                d: Unsanitized(GameMove::SendTokens(SendTokens {
-                    to: plant_id.inner(),
-                    amount: 1,
-                    currency: game.asic_token_id,
+                d: Unsanitized(GameMove::BidOnAuction(BidOnAuction {
+                    nft_id: plant_id,
+                    amount: 500,
+                    currency: sats,
                 })),
-                sequence: alice_seq_next(),
+                sequence: bob_seq_next(),
+                time_millis: 2500,
+            },
+            &|_g, r| {
+                assert!(matches!(
+                    r,
+                    Err(MoveRejectReason::AuctionRejected(AuctionError::BidTooLow {
+                        minimum: 1000
+                    }))
+                ))
+            },
+        ),
+        (
+            BOB,
+            MoveEnvelope {
+                d: Unsanitized(GameMove::BidOnAuction(BidOnAuction {
+                    nft_id: plant_id,
+                    amount: 1000,
+                    currency: sats,
+                })),
+                sequence: bob_seq_next(),
                 time_millis: 3000,
             },
-            &|g, v| {
-                let id = g.get_user_id(ALICE).unwrap();
-                let plants = &g.get_user_power_plants(id).unwrap();
-                let plant_id = plants.power_plant_data.iter().next().unwrap().0;
-                assert!(v.is_ok());
-                let t = &g.tokens[g.asic_token_id];
-                assert_eq!(1, t.balance_check(&plant_id.inner()));
+            NO_POST,
+        ),
+        (
+            BOB,
+            MoveEnvelope {
+                d: Unsanitized(GameMove::BidOnAuction(BidOnAuction {
+                    nft_id: plant_id,
+                    amount: 1050,
+                    currency: sats,
+                })),
+                sequence: bob_seq_next(),
+                time_millis: 3500,
+            },
+            &|_g, r| {
+                assert!(matches!(
+                    r,
+                    Err(MoveRejectReason::AuctionRejected(AuctionError::BidTooLow {
+                        minimum: 1100
+                    }))
+                ))
+            },
+        ),
+        (
+            BOB,
+            MoveEnvelope {
+                d: Unsanitized(GameMove::BidOnAuction(BidOnAuction {
+                    nft_id: plant_id,
+                    amount: 1100,
+                    currency: sats,
+                })),
+                sequence: bob_seq_next(),
+                time_millis: 4000,
             },
+            NO_POST,
         ),
+    ];
+    run_game(moves, &mut game);
+    // the outbid 1000 was refunded, only the best bid is held
+    assert_eq!(game.tokens[sats].balance_check(&b_id), bob_start - 1100);
+    assert_eq!(game.nfts[plant_id].owner(), a_id);
+
+    let moves = [
         (
             ALICE,
             MoveEnvelope {
                 d: Unsanitized(GameMove::Heartbeat(Heartbeat())),
                 sequence: alice_seq_next(),
-                time_millis: 10000,
+                time_millis: 30_000,
+            },
+            NO_POST,
+        ),
+        (
+            BOB,
+            MoveEnvelope {
+                d: Unsanitized(GameMove::Heartbeat(Heartbeat())),
+                sequence: bob_seq_next(),
+                time_millis: 30_000,
             },
             NO_POST,
         ),
     ];
+    run_game(moves, &mut game);
+    assert_eq!(game.nfts[plant_id].owner(), b_id);
+    assert!(game.auctions.auctions.is_empty());
+    assert_eq!(game.tokens[sats].balance_check(&b_id), bob_start - 1100);
 
-    run_game(moves2, &mut game);
-
-    let asics_before_removal = game.tokens[game.asic_token_id].balance_check(&id);
-    // transfer tokens back to A
-    let moves3 = [
+    // Dutch auction: the first acceptable bid pays the asking price at once
+    let bob_before = game.tokens[sats].balance_check(&b_id);
+    let moves = [
+        (
+            BOB,
+            MoveEnvelope {
+                d: Unsanitized(GameMove::StartAuction(StartAuction {
+                    nft_id: plant_id,
+                    currency: sats,
+                    kind: AuctionKind::Dutch {
+                        start_price: 10_000,
+                        floor_price: 0,
+                    },
+                    duration: 10_000,
+                })),
+                sequence: bob_seq_next(),
+                time_millis: 30_000,
+            },
+            NO_POST,
+        ),
         (
             ALICE,
             MoveEnvelope {
-                d: Unsanitized(GameMove::RemoveTokens(RemoveTokens {
+                d: Unsanitized(GameMove::BidOnAuction(BidOnAuction {
                     nft_id: plant_id,
                     amount: 1,
-                    currency: game.asic_token_id,
+                    currency: sats,
                 })),
                 sequence: alice_seq_next(),
-                time_millis: 12000,
+                time_millis: 30_000,
+            },
+            (&|_g: &GameBoard, r: Result<(), MoveRejectReason>| {
+                assert!(matches!(
+                    r,
+                    Err(MoveRejectReason::AuctionRejected(
+                        AuctionError::BidTooLow { .. }
+                    ))
+                ))
+            }) as PostCondition,
+        ),
+        (
+            ALICE,
+            MoveEnvelope {
+                d: Unsanitized(GameMove::BidOnAuction(BidOnAuction {
+                    nft_id: plant_id,
+                    amount: 10_000,
+                    currency: sats,
+                })),
+                sequence: alice_seq_next(),
+                time_millis: 34_000,
+            },
+            NO_POST,
+        ),
+    ];
+    run_game(moves, &mut game);
+    assert_eq!(game.nfts[plant_id].owner(), a_id);
+    assert!(game.auctions.auctions.is_empty());
+    let paid = game.tokens[sats].balance_check(&b_id) - bob_before;
+    assert!(paid > 0 && paid < 10_000);
+
+    // A bid on an auction gone stale is rejected without touching the board
+    let moves = [
+        (
+            ALICE,
+            MoveEnvelope {
+                d: Unsanitized(GameMove::StartAuction(StartAuction {
+                    nft_id: plant_id,
+                    currency: sats,
+                    kind: AuctionKind::English {
+                        reserve: 1000,
+                        min_increment: 100,
+                    },
+                    duration: 10_000,
+                })),
+                sequence: alice_seq_next(),
+                time_millis: 35_000,
+            },
+            NO_POST,
+        ),
+        (
+            BOB,
+            MoveEnvelope {
+                d: Unsanitized(GameMove::BidOnAuction(BidOnAuction {
+                    nft_id: plant_id,
+                    amount: 1000,
+                    currency: sats,
+                })),
+                sequence: bob_seq_next(),
+                time_millis: 35_000,
             },
             NO_POST,
         ),
+    ];
+    run_game(moves, &mut game);
+    // the plant changes hands outside of the auction
+    game.nfts[plant_id].transfer(b_id);
+    let bob_before = game.tokens[sats].balance_check(&b_id);
+    let moves = [(
+        BOB,
+        MoveEnvelope {
+            d: Unsanitized(GameMove::BidOnAuction(BidOnAuction {
+                nft_id: plant_id,
+                amount: 1100,
+                currency: sats,
+            })),
+            sequence: bob_seq_next(),
+            time_millis: 35_500,
+        },
+        (&|_g: &GameBoard, r: Result<(), MoveRejectReason>| {
+            assert!(matches!(
+                r,
+                Err(MoveRejectReason::AuctionRejected(
+                    AuctionError::NoSuchAuction
+                ))
+            ))
+        }) as PostCondition,
+    )];
+    run_game(moves, &mut game);
+    assert_eq!(game.tokens[sats].balance_check(&b_id), bob_before);
+    assert_eq!(
+        game.auctions.auctions[&plant_id].best_bid,
+        Some((b_id, 1000))
+    );
+
+    // reopening the NFT calls off the stale auction and refunds its bid
+    let moves = [(
+        BOB,
+        MoveEnvelope {
+            d: Unsanitized(GameMove::StartAuction(StartAuction {
+                nft_id: plant_id,
+                currency: sats,
+                kind: AuctionKind::English {
+                    reserve: 1000,
+                    min_increment: 100,
+                },
+                duration: 10_000,
+            })),
+            sequence: bob_seq_next(),
+            time_millis: 36_000,
+        },
+        NO_POST,
+    )];
+    run_game(moves, &mut game);
+    assert_eq!(game.tokens[sats].balance_check(&b_id), bob_before + 1000);
+    assert_eq!(game.auctions.auctions[&plant_id].seller, b_id);
+}
+
+#[test]
+fn test_forwards() {
+    let _ = tracing_subscriber::fmt::try_init();
+    let mut game = setup_game();
+    game.enable_invariant_checks();
+
+    let mut alice_seq = 0;
+    let mut alice_seq_next = || {
+        alice_seq += 1;
+        alice_seq - 1
+    };
+
+    let mut bob_seq = 0;
+    let mut bob_seq_next = || {
+        bob_seq += 1;
+        bob_seq - 1
+    };
+    let a_id = game.get_user_id(ALICE).unwrap();
+    let b_id = game.get_user_id(BOB).unwrap();
+    let sats = game.real_sats_token_id;
+    let btc = game.bitcoin_token_id;
+    // neither player owns a plant yet, so bitcoin balances only move by
+    // forwards
+    let alice_start = (
+        game.tokens[sats].balance_check(&a_id),
+        game.tokens[btc].balance_check(&a_id),
+    );
+    let bob_start = (
+        game.tokens[sats].balance_check(&b_id),
+        game.tokens[btc].balance_check(&b_id),
+    );
+
+    // Alice sells Bob 5000 bitcoin for 3000 sats, for delivery at 11_000
+    let moves = [
         (
             ALICE,
             MoveEnvelope {
                 d: Unsanitized(GameMove::Heartbeat(Heartbeat())),
                 sequence: alice_seq_next(),
-                time_millis: 13000,
+                time_millis: 123,
             },
             NO_POST,
         ),
@@ -712,16 +1042,1977 @@ fn test_remove_tokens_from_plant() {
             MoveEnvelope {
                 d: Unsanitized(GameMove::Heartbeat(Heartbeat())),
                 sequence: bob_seq_next(),
-                time_millis: 100_000,
+                time_millis: 900,
+            },
+            NO_POST,
+        ),
+        (
+            ALICE,
+            MoveEnvelope {
+                d: Unsanitized(GameMove::WriteForward(WriteForward {
+                    side: ForwardSide::Short,
+                    underlying: ForwardUnderlying::Delivery {
+                        asset: btc,
+                        quantity: 5000,
+                    },
+                    price: 3000,
+                    currency: sats,
+                    counterparty: None,
+                    duration: 10_000,
+                })),
+                sequence: alice_seq_next(),
+                time_millis: 1000,
             },
             NO_POST,
         ),
     ];
+    run_game(moves, &mut game);
+    let id = *game.forwards.forwards.keys().next_back().unwrap();
 
-    run_game(moves3, &mut game);
-    let asics_after_removal = game.tokens[game.asic_token_id].balance_check(&id);
-    trace!(asics_after_removal, asics_before_removal);
-    assert!(asics_after_removal > asics_before_removal);
+    let moves = [
+        (
+            ALICE,
+            MoveEnvelope {
+                d: Unsanitized(GameMove::AcceptForward(AcceptForward { id })),
+                sequence: alice_seq_next(),
+                time_millis: 1500,
+            },
+            (&|_g: &GameBoard, r: Result<(), MoveRejectReason>| {
+                assert!(matches!(
+                    r,
+                    Err(MoveRejectReason::ForwardRejected(
+                        ForwardError::CannotAcceptOwnForward
+                    ))
+                ))
+            }) as PostCondition,
+        ),
+        (
+            BOB,
+            MoveEnvelope {
+                d: Unsanitized(GameMove::CancelForward(CancelForward { id })),
+                sequence: bob_seq_next(),
+                time_millis: 1500,
+            },
+            &|_g, r| {
+                assert!(matches!(
+                    r,
+                    Err(MoveRejectReason::ForwardRejected(ForwardError::NotWriter))
+                ))
+            },
+        ),
+        (
+            BOB,
+            MoveEnvelope {
+                d: Unsanitized(GameMove::AcceptForward(AcceptForward { id })),
+                sequence: bob_seq_next(),
+                time_millis: 2000,
+            },
+            NO_POST,
+        ),
+        (
+            ALICE,
+            MoveEnvelope {
+                d: Unsanitized(GameMove::CancelForward(CancelForward { id })),
+                sequence: alice_seq_next(),
+                time_millis: 2500,
+            },
+            &|_g, r| {
+                assert!(matches!(
+                    r,
+                    Err(MoveRejectReason::ForwardRejected(
+                        ForwardError::AlreadyAccepted
+                    ))
+                ))
+            },
+        ),
+    ];
+    run_game(moves, &mut game);
+    // both legs are locked up until expiry
+    assert_eq!(game.tokens[sats].balance_check(&b_id), bob_start.0 - 3000);
+    assert_eq!(game.tokens[btc].balance_check(&a_id), alice_start.1 - 5000);
+    assert_eq!(game.tokens[btc].balance_check(&b_id), bob_start.1);
+
+    let moves = [
+        (
+            ALICE,
+            MoveEnvelope {
+                d: Unsanitized(GameMove::Heartbeat(Heartbeat())),
+                sequence: alice_seq_next(),
+                time_millis: 20_000,
+            },
+            NO_POST,
+        ),
+        (
+            BOB,
+            MoveEnvelope {
+                d: Unsanitized(GameMove::Heartbeat(Heartbeat())),
+                sequence: bob_seq_next(),
+                time_millis: 20_000,
+            },
+            NO_POST,
+        ),
+    ];
+    run_game(moves, &mut game);
+    assert!(game.forwards.forwards.is_empty());
+    assert_eq!(game.tokens[sats].balance_check(&a_id), alice_start.0 + 3000);
+    assert_eq!(game.tokens[sats].balance_check(&b_id), bob_start.0 - 3000);
+    assert_eq!(game.tokens[btc].balance_check(&a_id), alice_start.1 - 5000);
+    assert_eq!(game.tokens[btc].balance_check(&b_id), bob_start.1 + 5000);
+
+    // Bob offers Alice more than he has, then buys whatever Alice's plant
+    // mines, up to the collateral
+    let moves = [
+        (
+            ALICE,
+            MoveEnvelope {
+                d: Unsanitized(GameMove::SuperMintPowerPlant(MintPowerPlant {
+                    scale: 1,
+                    plant_type: PlantType::Solar,
+                    location: (15, 15),
+                })),
+                sequence: alice_seq_next(),
+                time_millis: 21_000,
+            },
+            NO_POST,
+        ),
+        (
+            BOB,
+            MoveEnvelope {
+                d: Unsanitized(GameMove::WriteForward(WriteForward {
+                    side: ForwardSide::Long,
+                    underlying: ForwardUnderlying::Delivery {
+                        asset: btc,
+                        quantity: 1,
+                    },
+                    price: u64::MAX as u128,
+                    currency: sats,
+                    counterparty: Some(a_id),
+                    duration: 10_000,
+                })),
+                sequence: bob_seq_next(),
+                time_millis: 21_000,
+            },
+            NO_POST,
+        ),
+    ];
+    run_game(moves, &mut game);
+    let plant_id = *game
+        .get_user_power_plants(a_id)
+        .unwrap()
+        .power_plant_data
+        .iter()
+        .next()
+        .unwrap()
+        .0;
+    let unfunded = *game.forwards.forwards.keys().next_back().unwrap();
+    let collateral = 1_000_000;
+    let moves = [
+        (
+            ALICE,
+            MoveEnvelope {
+                d: Unsanitized(GameMove::AcceptForward(AcceptForward { id: unfunded })),
+                sequence: alice_seq_next(),
+                time_millis: 21_500,
+            },
+            (&|_g: &GameBoard, r: Result<(), MoveRejectReason>| {
+                assert!(matches!(
+                    r,
+                    Err(MoveRejectReason::ForwardRejected(
+                        ForwardError::InsufficientFunds { .. }
+                    ))
+                ))
+            }) as PostCondition,
+        ),
+        (
+            ALICE,
+            MoveEnvelope {
+                d: Unsanitized(GameMove::WriteForward(WriteForward {
+                    side: ForwardSide::Short,
+                    underlying: ForwardUnderlying::MiningPayout {
+                        plant: plant_id,
+                        collateral,
+                    },
+                    price: 1000,
+                    currency: sats,
+                    counterparty: Some(b_id),
+                    duration: 100_000,
+                })),
+                sequence: alice_seq_next(),
+                time_millis: 22_000,
+            },
+            NO_POST,
+        ),
+    ];
+    run_game(moves, &mut game);
+    let id = *game.forwards.forwards.keys().next_back().unwrap();
+    let moves = [
+        (
+            BOB,
+            MoveEnvelope {
+                d: Unsanitized(GameMove::CancelForward(CancelForward { id: unfunded })),
+                sequence: bob_seq_next(),
+                time_millis: 22_500,
+            },
+            NO_POST,
+        ),
+        (
+            BOB,
+            MoveEnvelope {
+                d: Unsanitized(GameMove::AcceptForward(AcceptForward { id: unfunded })),
+                sequence: bob_seq_next(),
+                time_millis: 22_500,
+            },
+            &|_g, r| {
+                assert!(matches!(
+                    r,
+                    Err(MoveRejectReason::ForwardRejected(
+                        ForwardError::NoSuchForward
+                    ))
+                ))
+            },
+        ),
+        (
+            BOB,
+            MoveEnvelope {
+                d: Unsanitized(GameMove::AcceptForward(AcceptForward { id })),
+                sequence: bob_seq_next(),
+                time_millis: 23_000,
+            },
+            NO_POST,
+        ),
+    ];
+    run_game(moves, &mut game);
+    let accepted = game.forwards.forwards[&id].accepted.clone().unwrap();
+    let bob_before = game.tokens[btc].balance_check(&b_id);
+
+    let moves = [
+        (
+            ALICE,
+            MoveEnvelope {
+                d: Unsanitized(GameMove::Heartbeat(Heartbeat())),
+                sequence: alice_seq_next(),
+                time_millis: 200_000,
+            },
+            NO_POST,
+        ),
+        (
+            BOB,
+            MoveEnvelope {
+                d: Unsanitized(GameMove::Heartbeat(Heartbeat())),
+                sequence: bob_seq_next(),
+                time_millis: 200_000,
+            },
+            NO_POST,
+        ),
+    ];
+    run_game(moves, &mut game);
+    assert!(game.forwards.forwards.is_empty());
+    let mined = game.forwards.mined(plant_id) - accepted.mined_at_start;
+    assert_eq!(
+        game.tokens[btc].balance_check(&b_id) - bob_before,
+        mined.min(collateral)
+    );
+    // unused collateral went back to Alice when the lockup released
+    assert_eq!(
+        game.tokens[btc].balance_check(&accepted.delivery_lockup.inner()),
+        0
+    );
+    assert_eq!(
+        game.tokens[sats].balance_check(&accepted.payment_lockup.inner()),
+        0
+    );
+    assert!(game.get_invariant_violations().is_empty());
+}
+
+#[test]
+fn test_loans() {
+    let _ = tracing_subscriber::fmt::try_init();
+    let mut game = setup_game();
+    game.enable_invariant_checks();
+
+    let mut alice_seq = 0;
+    let mut alice_seq_next = || {
+        alice_seq += 1;
+        alice_seq - 1
+    };
+
+    let mut bob_seq = 0;
+    let mut bob_seq_next = || {
+        bob_seq += 1;
+        bob_seq - 1
+    };
+    let a_id = game.get_user_id(ALICE).unwrap();
+    let b_id = game.get_user_id(BOB).unwrap();
+    let sats = game.real_sats_token_id;
+    let btc = game.bitcoin_token_id;
+    // nobody owns a plant yet, so bitcoin balances only move by loans
+    let alice_start = game.tokens[btc].balance_check(&a_id);
+    let bob_start = game.tokens[btc].balance_check(&b_id);
+    let offer = |amount, interest_bps, duration| {
+        GameMove::OfferLoan(OfferLoan {
+            currency: btc,
+            amount,
+            interest_bps,
+            duration,
+            max_ltv_percent: 50,
+            liquidation_ltv_percent: 80,
+        })
+    };
+    let borrow_against_btc = |offer, amount| {
+        GameMove::Borrow(Borrow {
+            offer,
+            amount,
+            collateral: LoanCollateral::Tokens {
+                asset: btc,
+                amount: 1_000_000,
+            },
+        })
+    };
+
+    let moves = [
+        (
+            ALICE,
+            MoveEnvelope {
+                d: Unsanitized(GameMove::Heartbeat(Heartbeat())),
+                sequence: alice_seq_next(),
+                time_millis: 123,
+            },
+            NO_POST,
+        ),
+        (
+            BOB,
+            MoveEnvelope {
+                d: Unsanitized(GameMove::Heartbeat(Heartbeat())),
+                sequence: bob_seq_next(),
+                time_millis: 900,
+            },
+            NO_POST,
+        ),
+        (
+            ALICE,
+            MoveEnvelope {
+                d: Unsanitized(offer(2_000_000, 100, 5 * INTEREST_PERIOD)),
+                sequence: alice_seq_next(),
+                time_millis: 1000,
+            },
+            NO_POST,
+        ),
+    ];
+    run_game(moves, &mut game);
+    assert_eq!(
+        game.tokens[btc].balance_check(&a_id),
+        alice_start - 2_000_000
+    );
+    let offer_id = *game.loans.offers.keys().next_back().unwrap();
+
+    let moves = [
+        (
+            ALICE,
+            MoveEnvelope {
+                d: Unsanitized(borrow_against_btc(offer_id, 1)),
+                sequence: alice_seq_next(),
+                time_millis: 1000,
+            },
+            (&|_g: &GameBoard, r: Result<(), MoveRejectReason>| {
+                assert!(matches!(
+                    r,
+                    Err(MoveRejectReason::LoanRejected(
+                        LoanError::CannotBorrowFromSelf
+                    ))
+                ))
+            }) as PostCondition,
+        ),
+        (
+            BOB,
+            MoveEnvelope {
+                // real sats have no market, so are worth nothing as collateral
+                d: Unsanitized(GameMove::Borrow(Borrow {
+                    offer: offer_id,
+                    amount: 1,
+                    collateral: LoanCollateral::Tokens {
+                        asset: sats,
+                        amount: 1_000_000,
+                    },
+                })),
+                sequence: bob_seq_next(),
+                time_millis: 2000,
+            },
+            &|_g, r| {
+                assert!(matches!(
+                    r,
+                    Err(MoveRejectReason::LoanRejected(
+                        LoanError::InsufficientCollateral { max_borrow: 0 }
+                    ))
+                ))
+            },
+        ),
+        (
+            BOB,
+            MoveEnvelope {
+                d: Unsanitized(borrow_against_btc(offer_id, 600_000)),
+                sequence: bob_seq_next(),
+                time_millis: 2000,
+            },
+            &|_g, r| {
+                assert!(matches!(
+                    r,
+                    Err(MoveRejectReason::LoanRejected(
+                        LoanError::InsufficientCollateral {
+                            max_borrow: 500_000
+                        }
+                    ))
+                ))
+            },
+        ),
+        (
+            BOB,
+            MoveEnvelope {
+                d: Unsanitized(borrow_against_btc(offer_id, 400_000)),
+                sequence: bob_seq_next(),
+                time_millis: 2000,
+            },
+            NO_POST,
+        ),
+    ];
+    run_game(moves, &mut game);
+    assert_eq!(
+        game.tokens[btc].balance_check(&b_id),
+        bob_start - 1_000_000 + 400_000
+    );
+    let loan_id = *game.loans.loans.keys().next_back().unwrap();
+
+    // one period of interest at 1%
+    let moves = [
+        (
+            ALICE,
+            MoveEnvelope {
+                d: Unsanitized(GameMove::Heartbeat(Heartbeat())),
+                sequence: alice_seq_next(),
+                time_millis: 63_000,
+            },
+            NO_POST,
+        ),
+        (
+            BOB,
+            MoveEnvelope {
+                d: Unsanitized(GameMove::Heartbeat(Heartbeat())),
+                sequence: bob_seq_next(),
+                time_millis: 63_000,
+            },
+            NO_POST,
+        ),
+    ];
+    run_game(moves, &mut game);
+    assert_eq!(game.loans.loans[&loan_id].debt, 404_000);
+
+    // overpaying only pays off the debt, and returns the collateral
+    let moves = [
+        (
+            BOB,
+            MoveEnvelope {
+                d: Unsanitized(GameMove::RepayLoan(RepayLoan {
+                    id: loan_id,
+                    amount: 1_000_000,
+                })),
+                sequence: bob_seq_next(),
+                time_millis: 70_000,
+            },
+            NO_POST,
+        ),
+        (
+            BOB,
+            MoveEnvelope {
+                d: Unsanitized(borrow_against_btc(offer_id, 100_000)),
+                sequence: bob_seq_next(),
+                time_millis: 70_000,
+            },
+            NO_POST,
+        ),
+        (
+            BOB,
+            MoveEnvelope {
+                d: Unsanitized(GameMove::CancelLoanOffer(CancelLoanOffer { id: offer_id })),
+                sequence: bob_seq_next(),
+                time_millis: 70_000,
+            },
+            &|_g, r| {
+                assert!(matches!(
+                    r,
+                    Err(MoveRejectReason::LoanRejected(LoanError::NotLender))
+                ))
+            },
+        ),
+        (
+            ALICE,
+            MoveEnvelope {
+                d: Unsanitized(GameMove::CancelLoanOffer(CancelLoanOffer { id: offer_id })),
+                sequence: alice_seq_next(),
+                time_millis: 71_000,
+            },
+            NO_POST,
+        ),
+    ];
+    run_game(moves, &mut game);
+    assert!(game.loans.offers.is_empty());
+    assert_eq!(game.loans.loans.len(), 1);
+    assert_eq!(
+        game.tokens[btc].balance_check(&a_id),
+        alice_start - 2_000_000 + 404_000 + 1_500_000
+    );
+
+    // the second loan is never repaid, so the collateral goes to Alice
+    let moves = [
+        (
+            ALICE,
+            MoveEnvelope {
+                d: Unsanitized(GameMove::Heartbeat(Heartbeat())),
+                sequence: alice_seq_next(),
+                time_millis: 400_000,
+            },
+            NO_POST,
+        ),
+        (
+            BOB,
+            MoveEnvelope {
+                d: Unsanitized(GameMove::Heartbeat(Heartbeat())),
+                sequence: bob_seq_next(),
+                time_millis: 400_000,
+            },
+            NO_POST,
+        ),
+        (
+            ALICE,
+            MoveEnvelope {
+                d: Unsanitized(GameMove::Heartbeat(Heartbeat())),
+                sequence: alice_seq_next(),
+                time_millis: 400_500,
+            },
+            NO_POST,
+        ),
+    ];
+    run_game(moves, &mut game);
+    assert!(game.loans.loans.is_empty());
+    assert_eq!(
+        game.tokens[btc].balance_check(&a_id),
+        alice_start - 2_000_000 + 404_000 + 1_500_000 + 1_000_000
+    );
+    assert_eq!(
+        game.tokens[btc].balance_check(&b_id),
+        bob_start + 400_000 - 404_000 - 1_000_000 + 100_000
+    );
+    assert!(game.event_log.iter().any(|(_, _, e)| matches!(
+        e,
+        LogEvent::Loan(LoanEvent::Liquidated {
+            reason: LiquidationReason::PastDue,
+            ..
+        })
+    )));
+
+    // Bob borrows as much as he can against a plant at 20% a period, and
+    // loses it
+    let moves = [
+        (
+            BOB,
+            MoveEnvelope {
+                d: Unsanitized(GameMove::SuperMintPowerPlant(MintPowerPlant {
+                    scale: 1,
+                    plant_type: PlantType::Solar,
+                    location: (15, 15),
+                })),
+                sequence: bob_seq_next(),
+                time_millis: 401_000,
+            },
+            NO_POST,
+        ),
+        (
+            ALICE,
+            MoveEnvelope {
+                d: Unsanitized(offer(5_000_000, 2000, 4 * INTEREST_PERIOD)),
+                sequence: alice_seq_next(),
+                time_millis: 401_000,
+            },
+            NO_POST,
+        ),
+    ];
+    run_game(moves, &mut game);
+    let offer_id = *game.loans.offers.keys().next_back().unwrap();
+    let plant_id = *game
+        .get_user_power_plants(b_id)
+        .unwrap()
+        .power_plant_data
+        .iter()
+        .next()
+        .unwrap()
+        .0;
+    let collateral = LoanCollateral::PowerPlant { plant: plant_id };
+    let amount = (collateral.value(&game, btc) / 2).min(5_000_000);
+    assert!(amount > 0);
+    let moves = [(
+        BOB,
+        MoveEnvelope {
+            d: Unsanitized(GameMove::Borrow(Borrow {
+                offer: offer_id,
+                amount,
+                collateral,
+            })),
+            sequence: bob_seq_next(),
+            time_millis: 402_000,
+        },
+        NO_POST,
+    )];
+    run_game(moves, &mut game);
+    let loan_id = *game.loans.loans.keys().next_back().unwrap();
+    assert_eq!(game.nfts[plant_id].owner(), loan_id);
+
+    let moves = (0..4).map(|i| {
+        let (who, seq) = if i % 2 == 0 {
+            (ALICE, alice_seq_next())
+        } else {
+            (BOB, bob_seq_next())
+        };
+        (
+            who,
+            MoveEnvelope {
+                d: Unsanitized(GameMove::Heartbeat(Heartbeat())),
+                sequence: seq,
+                time_millis: 700_000 + i,
+            },
+            NO_POST,
+        )
+    });
+    run_game(moves.collect::<Vec<_>>(), &mut game);
+    assert!(game.loans.loans.is_empty());
+    assert_eq!(game.nfts[plant_id].owner(), a_id);
+    let debt = game
+        .event_log
+        .iter()
+        .filter_map(|(_, _, e)| match e {
+            LogEvent::Loan(LoanEvent::Liquidated { id, debt, .. }) if *id == loan_id => Some(*debt),
+            _ => None,
+        })
+        .next()
+        .unwrap();
+    assert!(debt > amount);
+    // the seized plant is put up for sale at the debt
+    let listing = &game.nft_sales.nfts[&plant_id];
+    assert_eq!(listing.seller, a_id);
+    assert_eq!(listing.price, debt);
+    assert_eq!(listing.currency, btc);
+    assert!(game.get_invariant_violations().is_empty());
+}
+
+#[test]
+fn test_remove_tokens_from_plant() {
+    // setup, build plant, tx tokens (assert it happened), tx tokens back
+    let _ = tracing_subscriber::fmt::try_init();
+    let mut game = setup_game();
+
+    let mut alice_seq = 0;
+    let mut alice_seq_next = || {
+        alice_seq += 1;
+        alice_seq - 1
+    };
+
+    let mut bob_seq = 0;
+    let mut bob_seq_next = || {
+        bob_seq += 1;
+        bob_seq - 1
+    };
+    // moves to make a plant
+    let moves = [
+        (
+            ALICE,
+            MoveEnvelope {
+                d: Unsanitized(GameMove::Heartbeat(Heartbeat())),
+                sequence: alice_seq_next(),
+                time_millis: 123,
+            },
+            NO_POST,
+        ),
+        (
+            BOB,
+            MoveEnvelope {
+                d: Unsanitized(GameMove::Heartbeat(Heartbeat())),
+                sequence: bob_seq_next(),
+                time_millis: 1232,
+            },
+            NO_POST,
+        ),
+        (
+            ALICE,
+            MoveEnvelope {
+                d: Unsanitized(GameMove::SuperMintPowerPlant(MintPowerPlant {
+                    scale: 1,
+                    plant_type: crate::nfts::instances::powerplant::PlantType::Solar,
+                    location: (15, 15),
+                })),
+                sequence: alice_seq_next(),
+                time_millis: 1000,
+            },
+            NO_POST,
+        ),
+    ];
+    run_game(moves, &mut game);
+
+    let id = game.get_user_id(ALICE).unwrap();
+    let plants = game.get_user_power_plants(id).unwrap();
+    let plant_id = *plants.power_plant_data.iter().next().unwrap().0;
+    let _btc_balance_before_mining = game.tokens[game.bitcoin_token_id].balance_check(&id);
+
+    let moves2 = [
+        (
+            ALICE,
+            MoveEnvelope {
+                d: Unsanitized(GameMove::Trade(Trade {
+                    pair: TradingPairID {
+                        asset_a: game.bitcoin_token_id,
+                        asset_b: game.asic_token_id,
+                    },
+                    amount_a: 0,
+                    amount_b: 1,
+                    sell: false,
+                    cap: None,
+                })),
+                sequence: alice_seq_next(),
+                time_millis: 2000,
+            },
+            NO_POST,
+        ),
+        (
+            ALICE,
+            MoveEnvelope {
+                d: Unsanitized(GameMove::SendTokens(SendTokens {
+                    to: plant_id.inner(),
+                    amount: 1,
+                    currency: game.asic_token_id,
+                })),
+                sequence: alice_seq_next(),
+                time_millis: 3000,
+            },
+            &|g, v| {
+                let id = g.get_user_id(ALICE).unwrap();
+                let plants = &g.get_user_power_plants(id).unwrap();
+                let plant_id = plants.power_plant_data.iter().next().unwrap().0;
+                assert!(v.is_ok());
+                let t = &g.tokens[g.asic_token_id];
+                assert_eq!(1, t.balance_check(&plant_id.inner()));
+            },
+        ),
+        (
+            ALICE,
+            MoveEnvelope {
+                d: Unsanitized(GameMove::Heartbeat(Heartbeat())),
+                sequence: alice_seq_next(),
+                time_millis: 10000,
+            },
+            NO_POST,
+        ),
+    ];
+
+    run_game(moves2, &mut game);
+
+    let asics_before_removal = game.tokens[game.asic_token_id].balance_check(&id);
+    // transfer tokens back to A
+    let moves3 = [
+        (
+            ALICE,
+            MoveEnvelope {
+                d: Unsanitized(GameMove::RemoveTokens(RemoveTokens {
+                    nft_id: plant_id,
+                    amount: 1,
+                    currency: game.asic_token_id,
+                })),
+                sequence: alice_seq_next(),
+                time_millis: 12000,
+            },
+            NO_POST,
+        ),
+        (
+            ALICE,
+            MoveEnvelope {
+                d: Unsanitized(GameMove::Heartbeat(Heartbeat())),
+                sequence: alice_seq_next(),
+                time_millis: 13000,
+            },
+            NO_POST,
+        ),
+        (
+            BOB,
+            MoveEnvelope {
+                d: Unsanitized(GameMove::Heartbeat(Heartbeat())),
+                sequence: bob_seq_next(),
+                time_millis: 100_000,
+            },
+            NO_POST,
+        ),
+    ];
+
+    run_game(moves3, &mut game);
+    let asics_after_removal = game.tokens[game.asic_token_id].balance_check(&id);
+    trace!(asics_after_removal, asics_before_removal);
+    assert!(asics_after_removal > asics_before_removal);
+}
+
+#[test]
+fn test_colocate_and_ship_miners() {
+    let _ = tracing_subscriber::fmt::try_init();
+    let mut game = setup_game();
+
+    let mut alice_seq = 0;
+    let mut alice_seq_next = || {
+        alice_seq += 1;
+        alice_seq - 1
+    };
+    let moves = [
+        (
+            ALICE,
+            MoveEnvelope {
+                d: Unsanitized(GameMove::Heartbeat(Heartbeat())),
+                sequence: alice_seq_next(),
+                time_millis: 123,
+            },
+            NO_POST,
+        ),
+        (
+            BOB,
+            MoveEnvelope {
+                d: Unsanitized(GameMove::Heartbeat(Heartbeat())),
+                sequence: 0,
+                time_millis: 1232,
+            },
+            NO_POST,
+        ),
+        (
+            BOB,
+            MoveEnvelope {
+                d: Unsanitized(GameMove::Heartbeat(Heartbeat())),
+                sequence: 1,
+                time_millis: 30000,
+            },
+            NO_POST,
+        ),
+        (
+            ALICE,
+            MoveEnvelope {
+                d: Unsanitized(GameMove::SuperMintPowerPlant(MintPowerPlant {
+                    scale: 1,
+                    plant_type: PlantType::Solar,
+                    location: (15, 15),
+                })),
+                sequence: alice_seq_next(),
+                time_millis: 1000,
+            },
+            NO_POST,
+        ),
+    ];
+    run_game(moves, &mut game);
+
+    let id = game.get_user_id(ALICE).unwrap();
+    let plant_id = *game
+        .get_user_power_plants(id)
+        .unwrap()
+        .power_plant_data
+        .keys()
+        .next()
+        .unwrap();
+    let asic = game.asic_token_id;
+    game.tokens[asic].transaction();
+    game.tokens[asic].mint(&id, 200_000);
+    game.tokens[asic].end_transaction();
+
+    let colocate = |amount, sequence, time_millis| MoveEnvelope {
+        d: Unsanitized(GameMove::ColocateMiners(ColocateMiners {
+            nft_id: plant_id,
+            miners: asic,
+            amount,
+        })),
+        sequence,
+        time_millis,
+    };
+    let ship = |amount, sequence, time_millis| MoveEnvelope {
+        d: Unsanitized(GameMove::ShipMiners(ShipMiners {
+            nft_id: plant_id,
+            miners: asic,
+            amount,
+        })),
+        sequence,
+        time_millis,
+    };
+
+    let moves2 = [
+        (
+            ALICE,
+            colocate(60_000, alice_seq_next(), 2000),
+            (&|g: &GameBoard, r: Result<(), MoveRejectReason>| {
+                assert!(r.is_ok());
+                // in transit, not yet at the plant
+                let shipment = g.shipments.values().next().unwrap();
+                assert_eq!(shipment.direction, ShippingDirection::ToPlant);
+                assert_eq!(shipment.amount, 60_000);
+                assert_eq!(
+                    g.tokens[g.asic_token_id].balance_check(&shipment.plant.inner()),
+                    0
+                );
+            }) as PostCondition,
+        ),
+        (
+            ALICE,
+            // plant is rated at 100kW, and 60k miners are on the way
+            colocate(50_000, alice_seq_next(), 2500),
+            &|_g, r| {
+                assert!(matches!(
+                    r,
+                    Err(MoveRejectReason::ShippingRejected(
+                        ShippingError::OverCapacity {
+                            capacity: 40_000,
+                            requested: 50_000
+                        }
+                    ))
+                ))
+            },
+        ),
+        (BOB, colocate(1, 2, 31000), &|_g, r| {
+            assert!(matches!(
+                r,
+                Err(MoveRejectReason::ShippingRejected(
+                    ShippingError::NotPlantOwner
+                ))
+            ))
+        }),
+        (
+            ALICE,
+            MoveEnvelope {
+                d: Unsanitized(GameMove::Heartbeat(Heartbeat())),
+                sequence: alice_seq_next(),
+                time_millis: 300_000,
+            },
+            &|g, r| {
+                assert!(r.is_ok());
+                // delivered
+                assert!(g.shipments.is_empty());
+                let id = g.get_user_id(ALICE).unwrap();
+                let plants = g.get_user_power_plants(id).unwrap();
+                let plant_id = plants.power_plant_data.keys().next().unwrap();
+                let t = &g.tokens[g.asic_token_id];
+                assert_eq!(t.balance_check(&plant_id.inner()), 60_000);
+            },
+        ),
+        (ALICE, ship(70_000, alice_seq_next(), 301_000), &|_g, r| {
+            assert!(matches!(
+                r,
+                Err(MoveRejectReason::ShippingRejected(
+                    ShippingError::InsufficientMiners {
+                        available: 60_000,
+                        requested: 70_000
+                    }
+                ))
+            ))
+        }),
+        (ALICE, ship(10_000, alice_seq_next(), 302_000), &|g, r| {
+            assert!(r.is_ok());
+            let shipment = g.shipments.values().next().unwrap();
+            assert_eq!(shipment.direction, ShippingDirection::FromPlant);
+            let t = &g.tokens[g.asic_token_id];
+            assert_eq!(t.balance_check(&shipment.plant.inner()), 50_000);
+        }),
+        (ALICE, ship(0, alice_seq_next(), 303_000), &|_g, r| {
+            assert!(matches!(
+                r,
+                Err(MoveRejectReason::MoveSanitizationError(
+                    SanitizationError::ShipmentIsEmpty
+                ))
+            ))
+        }),
+    ];
+    run_game(moves2, &mut game);
+}
+
+#[test]
+fn test_asic_generations() {
+    let _ = tracing_subscriber::fmt::try_init();
+    let mut game = setup_game();
+    let generations: Vec<_> = game
+        .tokens
+        .hashboards
+        .iter()
+        .map(|(t, d)| (*t, d.generation, d.unlocks_at, d.reliability))
+        .collect();
+    assert_eq!(generations.len(), 3);
+    assert_eq!(generations[0].0, game.asic_token_id);
+    assert_eq!(
+        generations
+            .iter()
+            .map(|g| (g.1, g.2, g.3))
+            .collect::<Vec<_>>(),
+        vec![(1, 0, 100), (2, 250_000, 98), (3, 500_000, 95)]
+    );
+    let (gen2, gen3) = (generations[1].0, generations[2].0);
+
+    let entropy_before = game.entropy;
+    let moves = [
+        (
+            ALICE,
+            MoveEnvelope {
+                d: Unsanitized(GameMove::Heartbeat(Heartbeat())),
+                sequence: 0,
+                time_millis: 0,
+            },
+            NO_POST,
+        ),
+        (
+            BOB,
+            MoveEnvelope {
+                d: Unsanitized(GameMove::Heartbeat(Heartbeat())),
+                sequence: 0,
+                time_millis: 0,
+            },
+            NO_POST,
+        ),
+        (
+            ALICE,
+            MoveEnvelope {
+                d: Unsanitized(GameMove::SuperMintPowerPlant(MintPowerPlant {
+                    scale: 1,
+                    plant_type: PlantType::Solar,
+                    location: (15, 15),
+                })),
+                sequence: 1,
+                time_millis: 1000,
+            },
+            NO_POST,
+        ),
+    ];
+    run_game(moves, &mut game);
+    // players can't steer the randomness with what they move...
+    assert_eq!(game.entropy, entropy_before);
+    // ...only the sequencer's envelopes feed it
+    game.attest_time(
+        SequencerTime {
+            time_millis: 1000,
+            bitcoin_height: None,
+        },
+        &[7u8; 32],
+    )
+    .unwrap();
+    assert_ne!(game.entropy, entropy_before);
+    // newer generations are not yet in production
+    assert_eq!(game.tokens[gen2].total_coins(), 0);
+
+    let id = game.get_user_id(ALICE).unwrap();
+    let plant = game
+        .get_user_power_plants(id)
+        .unwrap()
+        .power_plant_data
+        .keys()
+        .next()
+        .unwrap()
+        .inner();
+    game.tokens[gen3].transaction();
+    game.tokens[gen3].mint(&plant, 10_000);
+    game.tokens[gen3].end_transaction();
+    game.tokens[game.asic_token_id].transaction();
+    game.tokens[game.asic_token_id].mint(&plant, 10_000);
+    game.tokens[game.asic_token_id].end_transaction();
+
+    // 95% reliable, so exactly 5% of the 10k units burn out
+    HardwareFailureEvent {
+        time: 0,
+        period: 1_000_000_000,
+    }
+    .action(&mut game);
+    assert_eq!(game.tokens[gen3].balance_check(&plant), 9_500);
+    assert_eq!(
+        game.tokens[game.asic_token_id].balance_check(&plant),
+        10_000
+    );
+
+    run_game(
+        [
+            (
+                ALICE,
+                MoveEnvelope {
+                    d: Unsanitized(GameMove::Heartbeat(Heartbeat())),
+                    sequence: 2,
+                    time_millis: 300_000,
+                },
+                NO_POST,
+            ),
+            (
+                BOB,
+                MoveEnvelope {
+                    d: Unsanitized(GameMove::Heartbeat(Heartbeat())),
+                    sequence: 1,
+                    time_millis: 300_000,
+                },
+                &|g, r| {
+                    assert!(r.is_ok());
+                    // Gen 2 unlocked at 250s
+                    let gen2 = *g.tokens.hashboards.keys().nth(1).unwrap();
+                    assert!(g.tokens[gen2].total_coins() > 0);
+                },
+            ),
+        ],
+        &mut game,
+    );
+}
+
+#[test]
+fn test_subsidy_schedule() {
+    let mut schedule = SubsidySchedule::new(SubsidyParams {
+        initial_subsidy: 1000,
+        halving_interval: 100,
+        payout_period: 10,
+        retarget_every: 2,
+    });
+    assert_eq!(schedule.subsidy_at(0), 1000);
+    assert_eq!(schedule.subsidy_at(99), 1000);
+    assert_eq!(schedule.subsidy_at(100), 500);
+    assert_eq!(schedule.subsidy_at(250), 250);
+    assert_eq!(schedule.next_halving(250), Some(300));
+    assert_eq!(schedule.subsidy_at(100 * 128), 0);
+    assert_eq!(schedule.next_halving(100 * 128), None);
+
+    // first hashrate seen sets the difficulty
+    assert_eq!(schedule.payout(0, 10), 1000);
+    // hashrate doubling pays out double until the retarget
+    assert_eq!(schedule.payout(10, 20), 2000);
+    assert_eq!(schedule.difficulty, 15);
+    // after the retarget each hash earns less
+    assert_eq!(schedule.payout(20, 20), 1333);
+    // growth is clamped to 4x per period
+    assert_eq!(schedule.payout(30, 1000), 4000);
+    assert_eq!(schedule.difficulty, 60);
+
+    let ux = schedule.to_ux(250, 1000);
+    assert_eq!(ux.current_subsidy, 250);
+    assert_eq!(
+        ux.halvings,
+        vec![
+            (300, 125),
+            (400, 62),
+            (500, 31),
+            (600, 15),
+            (700, 7),
+            (800, 3),
+            (900, 1)
+        ]
+    );
+}
+
+#[test]
+fn test_sequencer_clock() {
+    let _ = tracing_subscriber::fmt::try_init();
+    let mut game = GameBoard::new(&GameSetup {
+        players: vec![ALICE.into(), BOB.into()],
+        start_amount: 10_000_000,
+        finish_time: 1_000_000,
+        subsidy: Default::default(),
+        clock: ClockParams {
+            source: ClockSource::Sequencer,
+            max_skew: 1000,
+        },
+        failover: Default::default(),
+    });
+    let heartbeat = |sequence, time_millis| MoveEnvelope {
+        d: Unsanitized(GameMove::Heartbeat(Heartbeat())),
+        sequence,
+        time_millis,
+    };
+    let at = |time_millis, bitcoin_height| SequencerTime {
+        time_millis,
+        bitcoin_height,
+    };
+
+    // nothing to check moves against yet
+    assert!(matches!(
+        game.play(heartbeat(0, 5000), ALICE.into()),
+        Err(MoveRejectReason::ClockRejected(ClockError::NoAttestedTime))
+    ));
+
+    game.attest_time(at(10_000, Some(100)), &[0u8; 32]).unwrap();
+    assert!(game.play(heartbeat(0, 10_500), BOB.into()).is_ok());
+    assert_eq!(game.elapsed_time, 0);
+
+    // players can no longer fast forward the game
+    assert!(matches!(
+        game.play(heartbeat(1, 900_000), ALICE.into()),
+        Err(MoveRejectReason::ClockRejected(
+            ClockError::TimestampOutOfWindow { .. }
+        ))
+    ));
+    assert_eq!(game.elapsed_time, 0);
+
+    game.attest_time(at(20_000, None), &[0u8; 32]).unwrap();
+    assert_eq!(game.elapsed_time, 10_000);
+    assert!(game.play(heartbeat(2, 19_500), ALICE.into()).is_ok());
+    assert_eq!(game.elapsed_time, 10_000);
+
+    // the sequencer cannot rewind time or the chain
+    assert!(matches!(
+        game.attest_time(at(15_000, None), &[0u8; 32]),
+        Err(ClockError::TimeWentBackwards { .. })
+    ));
+    assert!(matches!(
+        game.attest_time(at(25_000, Some(99)), &[0u8; 32]),
+        Err(ClockError::BitcoinHeightWentBackwards {
+            latest: 100,
+            attested: 99
+        })
+    ));
+    assert_eq!(game.elapsed_time, 10_000);
+
+    game.attest_time(at(10_000 + 1_000_000, Some(101)), &[0u8; 32])
+        .unwrap();
+    assert!(matches!(
+        game.play(heartbeat(1, 1_010_000), BOB.into()),
+        Err(MoveRejectReason::GameIsFinished(FinishReason::TimeExpired))
+    ));
+}
+
+#[test]
+fn test_move_sequencing() {
+    let _ = tracing_subscriber::fmt::try_init();
+    let mut game = setup_game();
+    let chat = |sequence, msg: &str| MoveEnvelope {
+        d: Unsanitized(GameMove::Chat(Chat(msg.into()))),
+        sequence,
+        time_millis: 1000 * sequence,
+    };
+    let a_id = game.get_user_id(ALICE).unwrap();
+
+    assert!(game.play(chat(0, "first"), ALICE.into()).is_ok());
+    assert!(matches!(
+        game.play(chat(0, "first"), ALICE.into()),
+        Err(MoveRejectReason::Duplicate {
+            expected: 1,
+            received: 0
+        })
+    ));
+
+    // an early move is held rather than lost...
+    assert!(matches!(
+        game.play(chat(2, "third"), ALICE.into()),
+        Err(MoveRejectReason::SequenceGap {
+            expected: 1,
+            received: 2
+        })
+    ));
+    assert!(matches!(
+        game.event_log.back(),
+        Some((
+            _,
+            _,
+            LogEvent::MoveRejectReason(MoveRejectReason::SequenceGap { .. })
+        ))
+    ));
+    assert!(matches!(
+        game.play(chat(2, "third"), ALICE.into()),
+        Err(MoveRejectReason::Duplicate {
+            expected: 1,
+            received: 2
+        })
+    ));
+    assert_eq!(game.get_ux_next_sequence()[&a_id], 1);
+    assert_eq!(game.chat.len(), 1);
+
+    // ...and played, in order, once the gap is filled
+    assert!(game.play(chat(1, "second"), ALICE.into()).is_ok());
+    assert_eq!(game.get_ux_next_sequence()[&a_id], 3);
+    assert_eq!(
+        game.chat.iter().map(|c| c.2.as_str()).collect::<Vec<_>>(),
+        vec!["first", "second", "third"]
+    );
+    assert!(game.pending_moves[&a_id].is_empty());
+
+    // only so many early moves are held, the rest are dropped...
+    let dropped = 4 + MAX_PENDING_MOVES as u64;
+    for sequence in 4..=dropped {
+        assert!(matches!(
+            game.play(chat(sequence, "early"), ALICE.into()),
+            Err(MoveRejectReason::SequenceGap { .. })
+        ));
+    }
+    assert_eq!(game.pending_moves[&a_id].len(), MAX_PENDING_MOVES);
+    assert!(game.play(chat(3, "fourth"), ALICE.into()).is_ok());
+    assert_eq!(game.get_ux_next_sequence()[&a_id], dropped);
+    // ...and played if sent again
+    assert!(game.play(chat(dropped, "resent"), ALICE.into()).is_ok());
+    assert_eq!(game.get_ux_next_sequence()[&a_id], dropped + 1);
+    assert_eq!(game.chat.back().map(|c| c.2.as_str()), Some("resent"));
+
+    // other players are sequenced independently
+    let b_id = game.get_user_id(BOB).unwrap();
+    assert_eq!(game.get_ux_next_sequence()[&b_id], 0);
+    assert!(game.play(chat(0, "hi"), BOB.into()).is_ok());
+}
+
+#[test]
+fn test_private_chat() {
+    let _ = tracing_subscriber::fmt::try_init();
+    let mut game = setup_game();
+    let a_id = game.get_user_id(ALICE).unwrap();
+    let b_id = game.get_user_id(BOB).unwrap();
+    let mut seq = 0;
+    let mut private_chat = |sealed: Vec<(EntityID, String)>| {
+        seq += 1;
+        MoveEnvelope {
+            d: Unsanitized(GameMove::PrivateChat(PrivateChat {
+                sealed: sealed.into_iter().collect(),
+            })),
+            sequence: seq - 1,
+            time_millis: 1000 * seq,
+        }
+    };
+
+    assert!(game
+        .play(
+            private_chat(vec![(b_id, "for bob".into()), (a_id, "for alice".into())]),
+            ALICE.into()
+        )
+        .is_ok());
+    let log = game.get_ux_private_chat_log(b_id);
+    assert_eq!(log.len(), 1);
+    assert_eq!(log[0].from, a_id);
+    assert_eq!(log[0].recipients, vec![a_id, b_id]);
+    assert_eq!(log[0].sealed, "for bob");
+    assert_eq!(game.get_ux_private_chat_log(a_id)[0].sealed, "for alice");
+    // private messages never show up in the public chat
+    assert!(game.chat.is_empty());
+
+    assert!(matches!(
+        game.play(private_chat(vec![]), ALICE.into()),
+        Err(MoveRejectReason::MoveSanitizationError(
+            SanitizationError::PrivateChatHasNoRecipients
+        ))
+    ));
+    let nobody = EntityID(u64::MAX);
+    assert!(matches!(
+        game.play(private_chat(vec![(nobody, "hello?".into())]), ALICE.into()),
+        Err(MoveRejectReason::MoveSanitizationError(
+            SanitizationError::InvalidUser(e)
+        )) if e == nobody
+    ));
+    assert!(matches!(
+        game.play(
+            private_chat(vec![(b_id, "0".repeat(MAX_SEALED_CHAT_LEN + 1))]),
+            ALICE.into()
+        ),
+        Err(MoveRejectReason::MoveSanitizationError(
+            SanitizationError::PrivateChatTooLong
+        ))
+    ));
+    assert_eq!(game.private_chat.len(), 1);
+}
+
+#[test]
+fn test_alliances() {
+    let _ = tracing_subscriber::fmt::try_init();
+    let mut game = setup_game();
+    game.enable_invariant_checks();
+    let a_id = game.get_user_id(ALICE).unwrap();
+    let b_id = game.get_user_id(BOB).unwrap();
+    let btc = game.bitcoin_token_id;
+    let alice_start = game.tokens[btc].balance_check(&a_id);
+    let bob_start = game.tokens[btc].balance_check(&b_id);
+    let mut seqs: BTreeMap<&str, u64> = BTreeMap::new();
+    let mut play = |game: &mut GameBoard, who: &'static str, m: GameMove, time_millis| {
+        let seq = seqs.entry(who).or_default();
+        *seq += 1;
+        game.play(
+            MoveEnvelope {
+                d: Unsanitized(m),
+                sequence: *seq - 1,
+                time_millis,
+            },
+            who.into(),
+        )
+    };
+    let last_event = |game: &GameBoard| match game.event_log.back() {
+        Some((_, _, LogEvent::Alliance(e))) => e.clone(),
+        e => panic!("expected an alliance event, got {:?}", e),
+    };
+    let pay_bob = |alliance, amount| {
+        GameMove::ProposeAllianceSpend(ProposeAllianceSpend {
+            alliance,
+            spend: AllianceSpend::Tokens {
+                to: b_id,
+                currency: btc,
+                amount,
+            },
+        })
+    };
+    let vote = |alliance, proposal, approve| {
+        GameMove::VoteOnAllianceProposal(VoteOnAllianceProposal {
+            alliance,
+            proposal,
+            approve,
+        })
+    };
+
+    assert!(matches!(
+        play(
+            &mut game,
+            ALICE,
+            GameMove::CreateAlliance(CreateAlliance { name: " ".into() }),
+            100
+        ),
+        Err(MoveRejectReason::MoveSanitizationError(
+            SanitizationError::AllianceNameIsEmpty
+        ))
+    ));
+    play(
+        &mut game,
+        ALICE,
+        GameMove::CreateAlliance(CreateAlliance {
+            name: "Hashers".into(),
+        }),
+        200,
+    )
+    .unwrap();
+    let id = *game.alliances.alliances.keys().next_back().unwrap();
+
+    // joining takes a vote of the members
+    play(
+        &mut game,
+        BOB,
+        GameMove::JoinAlliance(JoinAlliance { alliance: id }),
+        300,
+    )
+    .unwrap();
+    assert!(matches!(
+        play(
+            &mut game,
+            BOB,
+            GameMove::JoinAlliance(JoinAlliance { alliance: id }),
+            400
+        ),
+        Err(MoveRejectReason::AllianceRejected(
+            AllianceError::AlreadyApplied
+        ))
+    ));
+    let application = *game.alliances.alliances[&id]
+        .proposals
+        .keys()
+        .next()
+        .unwrap();
+    play(&mut game, ALICE, vote(id, application, true), 500).unwrap();
+    assert!(game.alliances.alliances[&id].members.contains(&b_id));
+    assert_eq!(game.alliances.team_of(b_id), id);
+    assert!(matches!(
+        play(
+            &mut game,
+            BOB,
+            GameMove::CreateAlliance(CreateAlliance {
+                name: "Splinter".into()
+            }),
+            600
+        ),
+        Err(MoveRejectReason::AllianceRejected(
+            AllianceError::AlreadyInAlliance { alliance }
+        )) if alliance == id
+    ));
+
+    // anyone can pay into the treasury, but spending takes a majority
+    play(
+        &mut game,
+        BOB,
+        GameMove::SendTokens(SendTokens {
+            to: id,
+            amount: 1000,
+            currency: btc,
+        }),
+        700,
+    )
+    .unwrap();
+    play(&mut game, BOB, pay_bob(id, 600), 800).unwrap();
+    let proposal = *game.alliances.alliances[&id]
+        .proposals
+        .keys()
+        .next()
+        .unwrap();
+    // one of two is not a majority...
+    assert_eq!(game.tokens[btc].balance_check(&id), 1000);
+    // ...and with the other against, one can never be reached
+    play(&mut game, ALICE, vote(id, proposal, false), 900).unwrap();
+    assert!(matches!(
+        last_event(&game),
+        AllianceEvent::Rejected { proposal: p, .. } if p == proposal
+    ));
+    assert!(game.alliances.alliances[&id].proposals.is_empty());
+    assert!(matches!(
+        play(&mut game, ALICE, vote(id, proposal, true), 1000),
+        Err(MoveRejectReason::AllianceRejected(
+            AllianceError::NoSuchProposal
+        ))
+    ));
+
+    play(&mut game, BOB, pay_bob(id, 600), 1100).unwrap();
+    let proposal = *game.alliances.alliances[&id]
+        .proposals
+        .keys()
+        .next()
+        .unwrap();
+    play(&mut game, ALICE, vote(id, proposal, true), 1200).unwrap();
+    assert!(matches!(
+        last_event(&game),
+        AllianceEvent::Executed { proposal: p, .. } if p == proposal
+    ));
+    assert_eq!(game.tokens[btc].balance_check(&id), 400);
+    assert_eq!(game.tokens[btc].balance_check(&b_id), bob_start - 400);
+
+    // the last member to leave takes what is left
+    play(
+        &mut game,
+        ALICE,
+        GameMove::LeaveAlliance(LeaveAlliance { alliance: id }),
+        1300,
+    )
+    .unwrap();
+    assert_eq!(game.alliances.team_of(a_id), a_id);
+    play(
+        &mut game,
+        ALICE,
+        GameMove::CreateAlliance(CreateAlliance {
+            name: "Solo".into(),
+        }),
+        1400,
+    )
+    .unwrap();
+    let solo = *game.alliances.alliances.keys().next_back().unwrap();
+    play(
+        &mut game,
+        ALICE,
+        GameMove::SendTokens(SendTokens {
+            to: solo,
+            amount: 50,
+            currency: btc,
+        }),
+        1500,
+    )
+    .unwrap();
+    // a lone member passes their own proposals
+    play(&mut game, ALICE, pay_bob(solo, 10), 1600).unwrap();
+    play(
+        &mut game,
+        ALICE,
+        GameMove::LeaveAlliance(LeaveAlliance { alliance: solo }),
+        1700,
+    )
+    .unwrap();
+    assert!(matches!(last_event(&game), AllianceEvent::Dissolved { id } if id == solo));
+    assert!(!game.alliances.alliances.contains_key(&solo));
+    assert_eq!(game.tokens[btc].balance_check(&a_id), alice_start - 10);
+
+    // at the end, Bob is credited with the treasury of his alliance
+    play(
+        &mut game,
+        ALICE,
+        GameMove::Heartbeat(Heartbeat()),
+        2_000_000,
+    )
+    .unwrap();
+    let _ = play(&mut game, BOB, GameMove::Heartbeat(Heartbeat()), 2_000_000);
+    assert!(matches!(
+        game.game_is_finished(),
+        Some(FinishReason::TimeExpired)
+    ));
+    let bounty = 1_000_000u64;
+    let rewards = game.get_close_distribution(bounty, "host".into()).unwrap();
+    let alice_btc = game.tokens[btc].balance_check(&a_id);
+    let bob_btc = game.tokens[btc].balance_check(&b_id) + 400;
+    let bob_reward = rewards.iter().find(|(k, _)| k == BOB).unwrap().1;
+    assert_eq!(
+        bob_reward as u128,
+        (bob_btc * bounty as u128) / (alice_btc + bob_btc)
+    );
+    assert!(game.get_invariant_violations().is_empty());
+}
+
+#[test]
+fn test_dominating_alliance() {
+    let _ = tracing_subscriber::fmt::try_init();
+    let mut game = setup_game();
+    let a_id = game.get_user_id(ALICE).unwrap();
+    let b_id = game.get_user_id(BOB).unwrap();
+    let mut seqs: BTreeMap<&str, u64> = BTreeMap::new();
+    let mut play = |game: &mut GameBoard, who: &'static str, m: GameMove, time_millis| {
+        let seq = seqs.entry(who).or_default();
+        *seq += 1;
+        game.play(
+            MoveEnvelope {
+                d: Unsanitized(m),
+                sequence: *seq - 1,
+                time_millis,
+            },
+            who.into(),
+        )
+    };
+
+    play(&mut game, ALICE, GameMove::Heartbeat(Heartbeat()), 123).unwrap();
+    play(&mut game, BOB, GameMove::Heartbeat(Heartbeat()), 500).unwrap();
+    play(
+        &mut game,
+        ALICE,
+        GameMove::SuperMintPowerPlant(MintPowerPlant {
+            scale: 1,
+            plant_type: PlantType::Solar,
+            location: (15, 15),
+        }),
+        1000,
+    )
+    .unwrap();
+    let plant_id = *game
+        .get_user_power_plants(a_id)
+        .unwrap()
+        .power_plant_data
+        .keys()
+        .next()
+        .unwrap();
+    let asic = game.asic_token_id;
+    let pair = TradingPairID {
+        asset_a: game.bitcoin_token_id,
+        asset_b: asic,
+    };
+    play(
+        &mut game,
+        ALICE,
+        GameMove::Trade(Trade {
+            pair,
+            amount_a: 0,
+            amount_b: 1,
+            sell: false,
+            cap: None,
+        }),
+        2000,
+    )
+    .unwrap();
+    play(
+        &mut game,
+        ALICE,
+        GameMove::SendTokens(SendTokens {
+            to: plant_id.inner(),
+            amount: 1,
+            currency: asic,
+        }),
+        3000,
+    )
+    .unwrap();
+
+    // only Alice mines, but Bob shares in her alliance's win
+    play(
+        &mut game,
+        ALICE,
+        GameMove::CreateAlliance(CreateAlliance {
+            name: "Hashers".into(),
+        }),
+        4000,
+    )
+    .unwrap();
+    let id = *game.alliances.alliances.keys().next_back().unwrap();
+    play(
+        &mut game,
+        BOB,
+        GameMove::JoinAlliance(JoinAlliance { alliance: id }),
+        5000,
+    )
+    .unwrap();
+    let application = *game.alliances.alliances[&id]
+        .proposals
+        .keys()
+        .next()
+        .unwrap();
+    play(
+        &mut game,
+        ALICE,
+        GameMove::VoteOnAllianceProposal(VoteOnAllianceProposal {
+            alliance: id,
+            proposal: application,
+            approve: true,
+        }),
+        6000,
+    )
+    .unwrap();
+    assert!(game.alliances.alliances[&id].members.contains(&b_id));
+
+    // median time should be averaged to >= 750k
+    play(
+        &mut game,
+        ALICE,
+        GameMove::Heartbeat(Heartbeat()),
+        1_500_200,
+    )
+    .unwrap();
+    assert!(matches!(
+        game.game_is_finished(),
+        Some(FinishReason::DominatingAlliance(a)) if a == id
+    ));
+
+    // the members split 75% evenly, with the odd sat to the first of them
+    let bounty = 1_000_001u64;
+    let rewards = game.get_close_distribution(bounty, "host".into()).unwrap();
+    let (first, second) = if a_id < b_id {
+        (ALICE, BOB)
+    } else {
+        (BOB, ALICE)
+    };
+    assert_eq!(
+        rewards,
+        vec![
+            (first.to_string(), 375_001),
+            (second.to_string(), 375_000),
+            ("host".to_string(), 250_000),
+        ]
+    );
+}
+
+#[test]
+fn test_leaderboard() {
+    let _ = tracing_subscriber::fmt::try_init();
+    let mut game = setup_game();
+    let a_id = game.get_user_id(ALICE).unwrap();
+    let b_id = game.get_user_id(BOB).unwrap();
+    // ties stay in player order
+    let leaderboard = game.get_ux_leaderboard();
+    assert_eq!(
+        leaderboard.iter().map(|e| e.player).collect::<Vec<_>>(),
+        vec![a_id, b_id]
+    );
+    assert!(leaderboard
+        .iter()
+        .all(|e| e.btc == 10_000_000 && e.hashrate_share.0 == 0));
+    assert_eq!(leaderboard[0].key, ALICE);
+
+    let btc = game.bitcoin_token_id;
+    assert!(game
+        .play(
+            MoveEnvelope {
+                d: Unsanitized(GameMove::SendTokens(SendTokens {
+                    to: b_id,
+                    amount: 1_000,
+                    currency: btc,
+                })),
+                sequence: 0,
+                time_millis: 1000,
+            },
+            ALICE.into()
+        )
+        .is_ok());
+    let leaderboard = game.get_ux_leaderboard();
+    assert_eq!(leaderboard[0].player, b_id);
+    assert_eq!(leaderboard[0].btc, 10_001_000);
+    assert_eq!(leaderboard[1].player, a_id);
+    assert_eq!(leaderboard[1].btc, 9_999_000);
+}
+
+#[test]
+fn test_world_map() {
+    let world = WorldMap::new(7);
+    assert_eq!(world.flare_sites, WorldMap::new(7).flare_sites);
+    assert_ne!(world.flare_sites, WorldMap::new(8).flare_sites);
+
+    // at longitude 0, elapsed 0 is midnight and 3/4 of a day is noon
+    let equator = (0, 0);
+    let irradiance = world.solar_irradiance(equator);
+    assert!(irradiance >= 60);
+    assert_eq!(world.output_percent(PlantType::Solar, equator, 0), 10);
+    assert_eq!(
+        world.output_percent(PlantType::Solar, equator, 3 * DAY_LENGTH / 4),
+        irradiance
+    );
+    assert_eq!(world.solar_irradiance((MAX_LATITUDE, 0)), 0);
+    assert_eq!(
+        world.effective_watts(1000, PlantType::Solar, equator, 3 * DAY_LENGTH / 4),
+        10 * irradiance
+    );
+
+    let site = world.flare_sites[0];
+    assert_eq!(world.output_percent(PlantType::Flare, site, 0), 100);
+    // flare sites are never placed near the poles
+    assert_eq!(
+        world.output_percent(PlantType::Flare, (MAX_LATITUDE, 0), 0),
+        10
+    );
+
+    let ux = world.to_ux(0);
+    assert_eq!(ux.cells.len(), 18 * 36);
+    assert!(ux.cells.iter().any(|c| c.river_flow == 0));
+
+    let mut game = setup_game();
+    let moves = [(
+        ALICE,
+        MoveEnvelope {
+            d: Unsanitized(GameMove::MintPowerPlant(MintPowerPlant {
+                scale: 1,
+                plant_type: PlantType::Hydro,
+                location: (MAX_LATITUDE + 1, 0),
+            })),
+            sequence: 0,
+            time_millis: 0,
+        },
+        (&|_g: &GameBoard, r: Result<(), MoveRejectReason>| {
+            assert!(matches!(
+                r,
+                Err(MoveRejectReason::MoveSanitizationError(
+                    SanitizationError::InvalidLocation(_)
+                ))
+            ))
+        }) as PostCondition,
+    )];
+    run_game(moves, &mut game);
+}
+
+#[test]
+fn test_invariants_hold_for_random_moves() {
+    let _ = tracing_subscriber::fmt::try_init();
+    for seed in 0..16 {
+        let mut game = setup_game();
+        game.enable_invariant_checks();
+        let mut rng = seed;
+        let mut next = move |bound: u64| {
+            rng = splitmix64(rng);
+            rng % bound.max(1)
+        };
+        let players = [ALICE, BOB];
+        let mut sequences = [0u64; 2];
+        for step in 0..200u64 {
+            let who = next(2) as usize;
+            let d = random_move(&game, &mut next);
+            let mv = MoveEnvelope {
+                d: Unsanitized(d),
+                sequence: sequences[who],
+                time_millis: step * 1000,
+            };
+            sequences[who] += 1;
+            let r = game.play(mv.clone(), players[who].into());
+            trace!(move_=?mv, ?r, "Random Move");
+            let violations = game.get_invariant_violations();
+            assert!(
+                violations.is_empty(),
+                "seed {} step {}: {:?}",
+                seed,
+                step,
+                violations
+            );
+        }
+    }
+}
+
+/// Generates a random move. Pointers are drawn from what exists
+/// on the board so that most moves get past sanitization, and amounts span
+/// many orders of magnitude so that many moves are also rejected.
+fn random_move(game: &GameBoard, next: &mut impl FnMut(u64) -> u64) -> GameMove {
+    let plants: Vec<_> = game.nfts.power_plants.keys().cloned().collect();
+    let plant = plants.get(next(plants.len() as u64 + 1) as usize).cloned();
+    let tokens: Vec<_> = game.tokens.tokens.values().map(|t| t.ptr()).collect();
+    let token = tokens[next(tokens.len() as u64) as usize];
+    let asics: Vec<_> = game.tokens.hashboards.keys().cloned().collect();
+    let miners = asics[next(asics.len() as u64) as usize];
+    let markets: Vec<_> = game.swap.markets.values().cloned().collect();
+    let btc = game.bitcoin_token_id;
+    match (next(10), plant) {
+        (1, _) if !markets.is_empty() => {
+            let mkt = markets[next(markets.len() as u64) as usize];
+            let sell = next(2) == 0;
+            let (amount_a, amount_b) = if next(2) == 0 {
+                let a = random_amount(next);
+                (
+                    if sell {
+                        a
+                    } else {
+                        a.checked_rem(mkt.reserve_a / 2).unwrap_or(a)
+                    },
+                    0,
+                )
+            } else {
+                let b = random_amount(next);
+                (
+                    0,
+                    if sell {
+                        b
+                    } else {
+                        b.checked_rem(mkt.reserve_b / 2).unwrap_or(b)
+                    },
+                )
+            };
+            GameMove::Trade(Trade {
+                pair: mkt.pair,
+                amount_a,
+                amount_b,
+                sell,
+                cap: None,
+            })
+        }
+        (2, _) => GameMove::SuperMintPowerPlant(MintPowerPlant {
+            scale: 1 + next(3),
+            location: (
+                next(2 * MAX_LATITUDE as u64) as i64 - MAX_LATITUDE,
+                next(2 * MAX_LONGITUDE as u64) as i64 - MAX_LONGITUDE,
+            ),
+            plant_type: [PlantType::Solar, PlantType::Hydro, PlantType::Flare][next(3) as usize],
+        }),
+        (3, Some(nft_id)) => GameMove::ListNFTForSale(ListNFTForSale {
+            nft_id,
+            price: random_amount(next),
+            currency: btc,
+        }),
+        (4, Some(nft_id)) => GameMove::PurchaseNFT(PurchaseNFT {
+            nft_id,
+            limit_price: random_amount(next),
+            currency: btc,
+        }),
+        (5, Some(nft_id)) => {
+            let (a, b) = (random_amount(next), random_amount(next));
+            GameMove::StartAuction(StartAuction {
+                nft_id,
+                currency: btc,
+                kind: if next(2) == 0 {
+                    AuctionKind::English {
+                        reserve: a,
+                        min_increment: b % 1000,
+                    }
+                } else {
+                    AuctionKind::Dutch {
+                        start_price: a.max(b),
+                        floor_price: a.min(b),
+                    }
+                },
+                duration: 1 + next(20_000),
+            })
+        }
+        (6, Some(nft_id)) => GameMove::BidOnAuction(BidOnAuction {
+            nft_id,
+            amount: random_amount(next),
+            currency: btc,
+        }),
+        (7, plant) => {
+            let other = game.get_user_id([ALICE, BOB][next(2) as usize]).unwrap();
+            GameMove::SendTokens(SendTokens {
+                to: match plant {
+                    Some(p) if next(2) == 0 => p.inner(),
+                    _ => other,
+                },
+                amount: random_amount(next),
+                currency: token,
+            })
+        }
+        (8, Some(nft_id)) => GameMove::ColocateMiners(ColocateMiners {
+            nft_id,
+            miners,
+            amount: random_amount(next) % 1000,
+        }),
+        (9, Some(nft_id)) => GameMove::ShipMiners(ShipMiners {
+            nft_id,
+            miners,
+            amount: random_amount(next) % 1000,
+        }),
+        _ => GameMove::Heartbeat(Heartbeat()),
+    }
+}
+
+/// An amount anywhere from 0 to 2^32
+fn random_amount(next: &mut impl FnMut(u64) -> u64) -> u128 {
+    let bits = 1 + next(32);
+    next(1 << bits) as u128
 }
 
 fn run_game<I>(moves: I, game: &mut GameBoard)
@@ -753,6 +3044,9 @@ fn setup_game() -> GameBoard {
         players: vec![ALICE.into(), BOB.into()],
         start_amount: 10_000_000,
         finish_time: 1_000_000,
+        subsidy: Default::default(),
+        clock: Default::default(),
+        failover: Default::default(),
     };
 
     GameBoard::new(&setup)
@@ -14,6 +14,8 @@ use tracing::{debug, trace};
 use crate::callbacks::Callback;
 use crate::entity::EntityID;
 use crate::game::CallContext;
+use crate::game::GameBoard;
+use crate::game::LogEvent;
 use crate::tokens::TokenPointer;
 
 use crate::tokens::token_swap::{ConstantFunctionMarketMaker, TradingPairID};
@@ -23,15 +25,73 @@ use crate::util::Price;
 #[derive(Serialize, JsonSchema, Debug)]
 pub struct HashBoardData {
     pub hash_per_watt: u128,
-    // out of 100, currently not used for anything.
-    // Could be used to determine "burn out"
+    /// Out of 100, the percent of running units which survive each
+    /// HardwareFailureEvent
     pub reliability: u8,
+    /// Which generation of the manufacturing tree this is, starting at 1
+    pub generation: u8,
+    /// Elapsed game time at which this generation enters production
+    pub unlocks_at: u64,
+}
+
+/// One step of the manufacturing tree
+pub(crate) struct ASICGeneration {
+    pub name: &'static str,
+    pub hash_per_watt: u128,
+    pub reliability: u8,
+    /// Production starts after this many quarters of the game have elapsed
+    pub unlocks_after_quarters: u64,
+    pub total_units: u128,
+    pub base_price: Price,
+}
+
+/// Each generation is more efficient than the last but, being less proven,
+/// less reliable.
+pub(crate) const ASIC_GENERATIONS: [ASICGeneration; 3] = [
+    ASICGeneration {
+        name: "ASIC Gen 1",
+        hash_per_watt: 3 * 100_000_000_000,
+        reliability: 100,
+        unlocks_after_quarters: 0,
+        total_units: 100_000,
+        base_price: 100_000,
+    },
+    ASICGeneration {
+        name: "ASIC Gen 2",
+        hash_per_watt: 6 * 100_000_000_000,
+        reliability: 98,
+        unlocks_after_quarters: 1,
+        total_units: 100_000,
+        base_price: 150_000,
+    },
+    ASICGeneration {
+        name: "ASIC Gen 3",
+        hash_per_watt: 12 * 100_000_000_000,
+        reliability: 95,
+        unlocks_after_quarters: 2,
+        total_units: 100_000,
+        base_price: 225_000,
+    },
+];
+
+impl ASICGeneration {
+    pub(crate) fn hashboard_data(&self, generation: u8, finish_time: u64) -> HashBoardData {
+        HashBoardData {
+            hash_per_watt: self.hash_per_watt,
+            reliability: self.reliability,
+            generation,
+            unlocks_at: finish_time / 4 * self.unlocks_after_quarters,
+        }
+    }
 }
 
 /// A ASICProducer is a kind of CFMM bot that deploys a basic strategy to
 /// periodically sell 1% of it's holdings in ASICs, after setting up an initial
 /// market condition with 10% of the hashrate available at a set price.
 ///
+/// Once newer generations are in production, the producer clears out its
+/// remaining stock faster, depreciating the older units.
+///
 /// If it were more clever, the algorithm could do some fancier things.
 #[derive(Serialize, Clone, Debug)]
 pub struct ASICProducer {
@@ -51,7 +111,7 @@ impl Callback for ASICProducer {
         self.elapsed_time
     }
 
-    fn action(&mut self, game: &mut crate::game::GameBoard) {
+    fn action(&mut self, game: &mut GameBoard) {
         let pair = TradingPairID {
             asset_a: self.hash_asset,
             asset_b: self.price_asset,
@@ -83,6 +143,7 @@ impl Callback for ASICProducer {
             self.total_units -= start;
         }
         let balance = game.tokens[self.hash_asset].balance_check(&self.id);
+        let superseded_by = generations_superseded_by(game, self.hash_asset);
         // TODO: Something more clever here?
         //
         // Ideas:
@@ -93,7 +154,7 @@ impl Callback for ASICProducer {
         match ConstantFunctionMarketMaker::do_sell_trade(
             game,
             pair,
-            min(balance / 100, balance),
+            min(balance * (1 + 4 * superseded_by) / 100, balance),
             0,
             None,
             false,
@@ -116,3 +177,78 @@ impl Callback for ASICProducer {
         "Adjusting the market for ASICs".to_string()
     }
 }
+
+/// How many newer generations than `hash_asset` are in production
+fn generations_superseded_by(game: &GameBoard, hash_asset: TokenPointer) -> u128 {
+    let generation = match game.tokens.hashboards.get(&hash_asset) {
+        Some(d) => d.generation,
+        None => return 0,
+    };
+    game.tokens
+        .hashboards
+        .values()
+        .filter(|d| d.generation > generation && d.unlocks_at <= game.elapsed_time)
+        .count() as u128
+}
+
+/// HardwareFailureEvent periodically burns out some of the hashboards running
+/// at each power plant, according to each generation's reliability.
+#[derive(Clone, Debug)]
+pub struct HardwareFailureEvent {
+    pub time: u64,
+    pub period: u64,
+}
+
+impl Callback for HardwareFailureEvent {
+    fn time(&self) -> u64 {
+        self.time
+    }
+
+    fn action(&mut self, game: &mut GameBoard) {
+        let plants: Vec<_> = game.nfts.power_plants.keys().cloned().collect();
+        let boards: Vec<_> = game
+            .tokens
+            .hashboards
+            .iter()
+            .filter(|(_, d)| d.reliability < 100)
+            .map(|(token, d)| (*token, d.reliability as u128))
+            .collect();
+        for plant in plants {
+            for (token, reliability) in &boards {
+                let units = game.tokens[*token].balance_check(&plant.inner());
+                let expected = units * (100 - reliability);
+                // whole failures are certain, a fractional one is a weighted
+                // coin flip
+                let mut failed = expected / 100;
+                if expected % 100 != 0 && (game.next_random() % 100) < (expected % 100) as u64 {
+                    failed += 1;
+                }
+                if failed == 0 {
+                    continue;
+                }
+                let coin = &mut game.tokens[*token];
+                coin.transaction();
+                coin.burn(&plant.inner(), failed);
+                coin.end_transaction();
+                trace!(?plant, ?token, failed, "Hashboards Burned Out");
+                let owner = game.nfts[plant].owner();
+                game.add_to_event_log(
+                    owner,
+                    LogEvent::Other(serde_json::json!({
+                        "hardware_failure": {
+                            "plant": plant,
+                            "miners": token,
+                            "failed": failed,
+                        }
+                    })),
+                );
+            }
+        }
+        self.time = game.elapsed_time + self.period;
+        game.callbacks.schedule(Box::new(self.clone()));
+    }
+
+    fn purpose(&self) -> String {
+        "Periodic Hashboard Failures".into()
+    }
+}
@@ -44,6 +44,13 @@ pub(crate) trait Token: Send + Sync + Debug {
     fn balance_check(&self, to: &EntityID) -> u128;
     /// Checks the total amount of coins
     fn total_coins(&self) -> u128;
+    /// All coins ever created by `mint` (transfers are not counted)
+    fn total_minted(&self) -> u128;
+    /// All coins ever destroyed by `burn` (transfers are not counted)
+    fn total_burned(&self) -> u128;
+    /// Sums every balance, or None if the sum overflows (which can only
+    /// happen if some balance underflowed)
+    fn sum_of_balances(&self) -> Option<u128>;
     /// Transfer coins from the `sender` to the `receiver`.
     /// postcondition: if returns false, no effect. if true, transfer success.
     /// attr: transaction_required
@@ -66,6 +73,10 @@ pub(crate) struct TokenBase {
     pub(crate) balances: BTreeMap<EntityID, Price>,
     /// Cached from the sum(balances.values())
     pub(crate) total: Price,
+    /// Running count of coins minted, for auditing the supply
+    pub(crate) minted: Price,
+    /// Running count of coins burned, for auditing the supply
+    pub(crate) burned: Price,
     #[cfg(test)]
     /// Test Only: if in a transaction, record the total amount before/after
     pub(crate) in_transaction: Option<Price>,
@@ -88,6 +99,8 @@ impl TokenBase {
         Self {
             balances: Default::default(),
             total: Default::default(),
+            minted: Default::default(),
+            burned: Default::default(),
             this: allocator.make(),
             #[cfg(test)]
             in_transaction: None,
@@ -99,6 +112,8 @@ impl TokenBase {
         Self {
             balances: Default::default(),
             total: Default::default(),
+            minted: Default::default(),
+            burned: Default::default(),
             this: game.alloc(),
             #[cfg(test)]
             in_transaction: None,
@@ -116,6 +131,7 @@ impl Token for TokenBase {
         let amt = self.balances.entry(*to).or_default();
         *amt += amount;
         self.total += amount;
+        self.minted += amount;
     }
     fn burn(&mut self, to: &EntityID, amount: u128) {
         self.check_in_transaction();
@@ -126,6 +142,7 @@ impl Token for TokenBase {
         let amt = self.balances.entry(*to).or_default();
         *amt -= amount;
         self.total -= amount;
+        self.burned += amount;
     }
 
     fn balance_check(&self, to: &EntityID) -> u128 {
@@ -134,6 +151,17 @@ impl Token for TokenBase {
     fn total_coins(&self) -> u128 {
         self.total
     }
+    fn total_minted(&self) -> u128 {
+        self.minted
+    }
+    fn total_burned(&self) -> u128 {
+        self.burned
+    }
+    fn sum_of_balances(&self) -> Option<u128> {
+        self.balances
+            .values()
+            .try_fold(0u128, |acc, b| acc.checked_add(*b))
+    }
     fn to_json(&self) -> serde_json::Value {
         serde_json::to_value(self).unwrap()
     }
@@ -170,8 +198,11 @@ impl Token for TokenBase {
             return false;
         }
         trace!(?sender, ?receiver, nickname=?self.nickname(), amount, "Transferring Tokens");
-        self.burn(sender, amount);
-        self.mint(receiver, amount);
+        self.check_in_transaction();
+        // moves balances directly, so that only real mints and burns are
+        // counted towards the supply
+        *self.balances.entry(*sender).or_default() -= amount;
+        *self.balances.entry(*receiver).or_default() += amount;
         true
     }
 
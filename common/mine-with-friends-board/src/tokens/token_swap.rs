@@ -65,6 +65,8 @@ impl ConstantFunctionMarketMakerPair {
                     lp: game.tokens.new_token(Box::new(TokenBase {
                         balances: Default::default(),
                         total: Default::default(),
+                        minted: Default::default(),
+                        burned: Default::default(),
                         this: base_id,
                         #[cfg(test)]
                         in_transaction: None,
@@ -335,7 +337,13 @@ impl ConstantFunctionMarketMaker {
             // (mkt_qty_selling*mkt_qty_buying)/(mkt_qty_buying-buy_amt) - mkt_qty_selling = sell_amt
             let mkt_qty_selling = tokens[selling].balance_check(&mkt.id);
             let mkt_qty_buying = tokens[buying].balance_check(&mkt.id);
-            if buy_amt > mkt_qty_buying {
+            if mkt_qty_selling == 0 {
+                return Err(TradeError::InsufficientTokens(
+                    "Market has no liquidity".into(),
+                ));
+            }
+            // buying out the whole reserve would take an unbounded sell_amt
+            if buy_amt >= mkt_qty_buying {
                 return Err(TradeError::InsufficientTokens(
                     "Market has insufficient tokens".into(),
                 ));
@@ -528,6 +536,11 @@ impl ConstantFunctionMarketMaker {
 
             let mkt_qty_selling = tokens[selling].balance_check(&mkt.id);
             let mkt_qty_buying = tokens[buying].balance_check(&mkt.id);
+            if mkt_qty_selling == 0 || mkt_qty_buying == 0 {
+                return Err(TradeError::InsufficientTokens(
+                    "Market has no liquidity".into(),
+                ));
+            }
 
             if let Some(min) = buy_min {
                 if mkt_qty_buying < min {
@@ -15,3 +15,21 @@ pub type Watts = u128;
 pub type Location = (i64, i64);
 pub type ForSale = bool;
 pub type HasMiners = bool;
+
+/// SplitMix64 finalizer, a well-mixed and portable integer hash
+pub(crate) fn splitmix64(x: u64) -> u64 {
+    let mut z = x.wrapping_add(0x9e37_79b9_7f4a_7c15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^ (z >> 31)
+}
+
+/// Folds some bytes into a running SplitMix64 hash
+pub(crate) fn fold_bytes(seed: u64, bytes: &[u8]) -> u64 {
+    let seed = splitmix64(seed ^ bytes.len() as u64);
+    bytes.chunks(8).fold(seed, |acc, chunk| {
+        let mut word = [0u8; 8];
+        word[..chunk.len()].copy_from_slice(chunk);
+        splitmix64(acc ^ u64::from_le_bytes(word))
+    })
+}
@@ -0,0 +1,235 @@
+// Copyright Judica, Inc 2022
+//
+// This Source Code Form is subject to the terms of the Mozilla Public
+//  License, v. 2.0. If a copy of the MPL was not distributed with this
+//  file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+//! A deterministic model of the world that power plants are built in.
+//!
+//! The map is generated from a seed shared by all players, and varies over
+//! elapsed game time with a day/night and a seasonal cycle. All math here is
+//! integer-only so that every client (and the contract, when replaying a game)
+//! computes identical plant outputs.
+//!
+//! Locations are (latitude, longitude) in millionths of a degree.
+use crate::nfts::instances::powerplant::PlantType;
+use crate::util::{splitmix64, Location, Watts};
+use schemars::JsonSchema;
+use serde::Serialize;
+
+/// Locations are measured in millionths of a degree
+pub const MICRO_DEGREES: i64 = 1_000_000;
+pub const MAX_LATITUDE: i64 = 90 * MICRO_DEGREES;
+pub const MAX_LONGITUDE: i64 = 180 * MICRO_DEGREES;
+/// Size of a map cell, in degrees
+pub const CELL_DEGREES: i64 = 10;
+const CELL: i64 = CELL_DEGREES * MICRO_DEGREES;
+/// Length of a day in elapsed game time (ms)
+pub const DAY_LENGTH: u64 = 10 * 60 * 1000;
+/// Length of a year in elapsed game time (ms)
+pub const YEAR_LENGTH: u64 = 8 * DAY_LENGTH;
+/// Number of gas flare sites on the map
+const FLARE_SITES: u64 = 24;
+/// Flare sites are placed no further than this from the equator
+const FLARE_MAX_LATITUDE: i64 = 60 * MICRO_DEGREES;
+/// Within this distance of a flare site a plant gets all the gas it can burn
+const FLARE_RADIUS: i64 = 5 * MICRO_DEGREES;
+/// Beyond this distance of a flare site a plant gets no gas
+const FLARE_REACH: i64 = 20 * MICRO_DEGREES;
+/// Every plant produces at least this percent of its rating (e.g., from
+/// storage or grid backup), so no plant is ever entirely idle.
+const OUTPUT_FLOOR: u128 = 10;
+
+/// The static features of the world, generated from a seed
+#[derive(Serialize, JsonSchema, Debug, Clone)]
+pub struct WorldMap {
+    pub seed: u64,
+    pub flare_sites: Vec<Location>,
+}
+
+/// Per-cell resource data formatted for UX consumption
+#[derive(Serialize, Clone, Debug, JsonSchema)]
+pub struct UXMapCell {
+    /// The south-west corner of the cell
+    pub corner: Location,
+    /// Percent of peak sunlight at noon on an equinox
+    pub solar_irradiance: u128,
+    /// Percent of rated river flow, before seasonal effects
+    pub river_flow: u128,
+}
+
+/// The world map formatted for UX consumption
+#[derive(Serialize, Clone, Debug, JsonSchema)]
+pub struct UXWorldMap {
+    pub cell_degrees: i64,
+    pub cells: Vec<UXMapCell>,
+    pub flare_sites: Vec<Location>,
+    pub day_length: u64,
+    pub year_length: u64,
+    pub elapsed_time: u64,
+}
+
+impl WorldMap {
+    pub fn new(seed: u64) -> Self {
+        let flare_sites = (0..FLARE_SITES)
+            .map(|i| {
+                let lat = noise(seed, 1, i as i64, 0) % (2 * FLARE_MAX_LATITUDE as u64 + 1);
+                let lon = noise(seed, 2, i as i64, 0) % (2 * MAX_LONGITUDE as u64);
+                (lat as i64 - FLARE_MAX_LATITUDE, lon as i64 - MAX_LONGITUDE)
+            })
+            .collect();
+        Self { seed, flare_sites }
+    }
+
+    /// Checks that a location is on the map
+    pub fn is_valid_location((lat, lon): Location) -> bool {
+        (-MAX_LATITUDE..=MAX_LATITUDE).contains(&lat)
+            && (-MAX_LONGITUDE..=MAX_LONGITUDE).contains(&lon)
+    }
+
+    /// Percent of peak sunlight a location gets at noon on an equinox, from
+    /// its latitude and (seeded) typical cloud cover
+    pub fn solar_irradiance(&self, location: Location) -> u128 {
+        // roughly cos(latitude), without any floating point
+        let lat = location.0 as i128;
+        let max = MAX_LATITUDE as i128;
+        let by_latitude = (100 - (100 * lat * lat) / (max * max)) as u128;
+        let (x, y) = cell_of(location);
+        let clear_sky = 60 + (noise(self.seed, 3, x, y) % 41) as u128;
+        by_latitude * clear_sky / 100
+    }
+
+    /// Percent of rated river flow available at a location, before seasonal
+    /// effects. Roughly a quarter of the map is dry.
+    pub fn river_flow(&self, location: Location) -> u128 {
+        let (x, y) = cell_of(location);
+        let n = noise(self.seed, 4, x, y);
+        if n % 4 == 0 {
+            0
+        } else {
+            40 + ((n >> 2) % 111) as u128
+        }
+    }
+
+    /// Percent of a flare plant's gas needs met at a location, from its
+    /// distance to the nearest flare site
+    pub fn flare_gas(&self, location: Location) -> u128 {
+        let d = self
+            .flare_sites
+            .iter()
+            .map(|site| distance(*site, location))
+            .min()
+            .unwrap_or(i64::MAX);
+        if d <= FLARE_RADIUS {
+            100
+        } else if d >= FLARE_REACH {
+            0
+        } else {
+            (100 * (FLARE_REACH - d) / (FLARE_REACH - FLARE_RADIUS)) as u128
+        }
+    }
+
+    /// Percent of a plant's rated watts it produces at `elapsed` game time
+    pub fn output_percent(&self, plant_type: PlantType, location: Location, elapsed: u64) -> u128 {
+        let pct = match plant_type {
+            PlantType::Solar => {
+                self.solar_irradiance(location)
+                    * daylight(location, elapsed)
+                    * season(location, elapsed)
+                    / (100 * 100)
+            }
+            // snowmelt makes rivers peak in spring rather than summer
+            PlantType::Hydro => {
+                self.river_flow(location) * season(location, elapsed + YEAR_LENGTH / 8) / 100
+            }
+            PlantType::Flare => self.flare_gas(location),
+        };
+        pct.max(OUTPUT_FLOOR)
+    }
+
+    /// Scale a plant's rated watts by its type and location at `elapsed` game
+    /// time
+    pub fn effective_watts(
+        &self,
+        watts: Watts,
+        plant_type: PlantType,
+        location: Location,
+        elapsed: u64,
+    ) -> Watts {
+        watts * self.output_percent(plant_type, location, elapsed) / 100
+    }
+
+    pub fn to_ux(&self, elapsed_time: u64) -> UXWorldMap {
+        let mut cells = vec![];
+        for x in -(MAX_LATITUDE / CELL)..(MAX_LATITUDE / CELL) {
+            for y in -(MAX_LONGITUDE / CELL)..(MAX_LONGITUDE / CELL) {
+                let corner = (x * CELL, y * CELL);
+                let center = (corner.0 + CELL / 2, corner.1 + CELL / 2);
+                cells.push(UXMapCell {
+                    corner,
+                    solar_irradiance: self.solar_irradiance(center),
+                    river_flow: self.river_flow(center),
+                })
+            }
+        }
+        UXWorldMap {
+            cell_degrees: CELL_DEGREES,
+            cells,
+            flare_sites: self.flare_sites.clone(),
+            day_length: DAY_LENGTH,
+            year_length: YEAR_LENGTH,
+            elapsed_time,
+        }
+    }
+}
+
+/// Percent of noon sunlight at a location's local time of day
+fn daylight((_lat, lon): Location, elapsed: u64) -> u128 {
+    let offset = (lon + MAX_LONGITUDE) as u64 * DAY_LENGTH / (2 * MAX_LONGITUDE) as u64;
+    let local = (elapsed + offset) % DAY_LENGTH;
+    let quarter = DAY_LENGTH / 4;
+    // sunrise at 0, noon at a quarter day, sunset at half a day
+    if local >= 2 * quarter {
+        0
+    } else {
+        let from_noon = local.abs_diff(quarter);
+        (100 * (quarter - from_noon) / quarter) as u128
+    }
+}
+
+/// Percent of average output for the season at a location, between 50 and
+/// 150. The swing grows with distance from the equator, and the hemispheres
+/// are opposite.
+fn season((lat, _lon): Location, elapsed: u64) -> u128 {
+    let phase = (elapsed % YEAR_LENGTH) as i64;
+    let quarter = (YEAR_LENGTH / 4) as i64;
+    // triangle wave between -100 and 100, peaking at midsummer in the north
+    let wave = if phase < quarter {
+        100 * phase / quarter
+    } else if phase < 3 * quarter {
+        100 - 100 * (phase - quarter) / quarter
+    } else {
+        -100 + 100 * (phase - 3 * quarter) / quarter
+    };
+    let swing = wave * 50 * lat.abs() / MAX_LATITUDE / 100;
+    let swing = if lat < 0 { -swing } else { swing };
+    (100 + swing) as u128
+}
+
+/// Approximate distance in micro-degrees, wrapping around the date line
+fn distance((lat_a, lon_a): Location, (lat_b, lon_b): Location) -> i64 {
+    let d_lat = (lat_a - lat_b).abs();
+    let d_lon = (lon_a - lon_b).abs();
+    let d_lon = d_lon.min(2 * MAX_LONGITUDE - d_lon);
+    d_lat.max(d_lon)
+}
+
+/// The map cell containing a location
+fn cell_of((lat, lon): Location) -> (i64, i64) {
+    (lat.div_euclid(CELL), lon.div_euclid(CELL))
+}
+
+/// Deterministic noise for a map feature (`salt`) at a cell
+fn noise(seed: u64, salt: u64, x: i64, y: i64) -> u64 {
+    splitmix64(seed ^ splitmix64(salt ^ splitmix64(((x as u64) << 32) ^ (y as u32 as u64))))
+}
@@ -41,6 +41,9 @@ pub enum Event {
     NewRecompileTriggeringObservation(Value, SArc<EventKey>),
     // strictly speaking we don't need this to be an event with any information.
     EmittedPSBTVia(PsbtString, XOnlyPublicKey),
+    // the sequencer has been quiet since the given time; kept as a record,
+    // nothing is recompiled from it.
+    HostUnresponsive(XOnlyPublicKey, i64),
 }
 
 #[derive(Serialize, Deserialize, Clone)]
@@ -89,6 +89,10 @@ pub(crate) async fn event_loop_inner(
                 trace!(?instance, emitter = b.to_hex(), ?psbt, "EmittedPSBTVia");
                 // Nothing to do -- this action is mostly here for de-deuplication
             }
+            Some(events::Event::HostUnresponsive(key, since)) => {
+                info!(?instance, host = key.to_hex(), since, "HostUnresponsive");
+                // Nothing to do -- no continuation depends on host liveness
+            }
             Some(events::Event::TransactionFinalized(s, tx)) => {
                 info!(
                     ?instance,
@@ -9,7 +9,7 @@ use crate::{
     TaskSet, OK_T,
 };
 use attest_database::connection::MsgDB;
-use attest_messages::{Envelope, GenericEnvelope};
+use attest_messages::Envelope;
 use bitcoin::{psbt::PartiallySignedTransaction, XOnlyPublicKey};
 use event_log::{
     connection::EventLog,
@@ -19,25 +19,29 @@ use event_log::{
 };
 use game_host_messages::{BroadcastByHost, Channelized};
 use game_player_messages::ParticipantAction;
-use game_sequencer::{OnlineDBFetcher, SequencerError, UnauthenticatedRawSequencer};
+use game_sequencer::{
+    attest_envelope, censorship::find_censorship, custody, liveness::HostLiveness, OnlineDBFetcher,
+    Sequenced, SequencerError, UnauthenticatedRawSequencer,
+};
 use mine_with_friends_board::{
     game::{FinishReason, GameBoard, MoveRejectReason},
     MoveEnvelope,
 };
 use sapio_base::serialization_helpers::SArc;
-use simps::{EventKey, EK_GAME_ACTION_LOSE, EK_GAME_ACTION_WIN};
+use simps::{EventKey, EK_GAME_ACTION_CENSORSHIP, EK_GAME_ACTION_LOSE, EK_GAME_ACTION_WIN};
 use std::{
     collections::BTreeMap,
     error::Error,
+    str::FromStr,
     sync::{atomic::AtomicBool, Arc},
     time::Duration,
 };
 use tokio::{
     spawn,
-    sync::{mpsc::UnboundedReceiver, Notify, OwnedMutexGuard},
+    sync::{mpsc::UnboundedReceiver, watch, Notify, OwnedMutexGuard},
     task::{spawn_blocking, JoinHandle},
 };
-use tracing::{debug, info};
+use tracing::{debug, info, warn};
 pub async fn sequencer_extractor(
     oracle_key: XOnlyPublicKey,
     msg_db: MsgDB,
@@ -76,11 +80,20 @@ pub async fn sequencer_extractor_inner(
     );
 
     let game_setup = get_game_setup(&msg_db, oracle_key).await?;
+    let players = game_setup
+        .players
+        .iter()
+        .map(|p| XOnlyPublicKey::from_str(p))
+        .collect::<Result<Vec<_>, _>>()?;
 
     let new_game = GameBoard::new(&game_setup);
+    let liveness = db_fetcher.subscribe_liveness();
 
-    let game_sequencer =
-        game_sequencer::DemuxedSequencer::new(shutdown.clone(), db_fetcher.clone());
+    let game_sequencer = game_sequencer::DemuxedSequencer::new(
+        shutdown.clone(),
+        db_fetcher.clone(),
+        Some(players.iter().cloned().collect()),
+    );
     tasks.push(spawn(async move {
         db_fetcher.run().await;
         OK_T
@@ -105,6 +118,20 @@ pub async fn sequencer_extractor_inner(
             new_synthetic_event.clone(),
         )
     });
+    tasks.push(watch_for_censorship(
+        evlog.clone(),
+        msg_db.clone(),
+        oracle_key,
+        players,
+        evlog_group_id,
+        new_synthetic_event.clone(),
+    ));
+    tasks.push(watch_host_liveness(
+        evlog.clone(),
+        liveness,
+        evlog_group_id,
+        new_synthetic_event.clone(),
+    ));
     tasks.push({
         let recieved_psbt = game_sequencer.recieve_psbt.lock_owned().await;
         handle_psbts(
@@ -204,13 +231,22 @@ pub fn start_game(
     oracle_key: XOnlyPublicKey,
     evlog_group_id: OccurrenceGroupID,
     mut game: GameBoard,
-    mut moves: OwnedMutexGuard<UnboundedReceiver<(MoveEnvelope, XOnlyPublicKey)>>,
+    mut moves: OwnedMutexGuard<UnboundedReceiver<Sequenced<(MoveEnvelope, XOnlyPublicKey)>>>,
     new_synthetic_event: Arc<Notify>,
 ) -> JoinHandle<Result<(), Box<dyn std::error::Error + Send + Sync>>> {
     spawn(async move {
         // TODO: Check which game the move is for?
         let mut move_count = 0;
-        while let Some((next_move, signed_by)) = moves.recv().await {
+        while let Some(next) = moves.recv().await {
+            let (next_move, signed_by) = match next {
+                Sequenced::Attested(hash, header) => {
+                    if let Err(e) = attest_envelope(&mut game, &hash, &header) {
+                        warn!(error=?e, "Sequencer Attested to a Bad Time");
+                    }
+                    continue;
+                }
+                Sequenced::Move(m) => m,
+            };
             info!(move_ = ?next_move, "New Move Recieved");
 
             move_count += 1;
@@ -227,7 +263,9 @@ pub fn start_game(
                     evlog_group_id,
                     match r {
                         FinishReason::TimeExpired => EK_GAME_ACTION_WIN.clone(),
-                        FinishReason::DominatingPlayer(_) => EK_GAME_ACTION_LOSE.clone(),
+                        FinishReason::DominatingPlayer(_) | FinishReason::DominatingAlliance(_) => {
+                            EK_GAME_ACTION_LOSE.clone()
+                        }
                     },
                     Some(events::Tag::ScopedCounter("game_move".into(), move_count)),
                     new_synthetic_event,
@@ -241,8 +279,90 @@ pub fn start_game(
     })
 }
 
-type HostEnvelope = GenericEnvelope<Channelized<BroadcastByHost>>;
-type ParticipantEnvelope = GenericEnvelope<ParticipantAction>;
+/// Periodically checks whether the host has censored any player, and if so
+/// hands the proof to the contract
+pub fn watch_for_censorship(
+    evlog: EventLog,
+    msg_db: MsgDB,
+    oracle_key: XOnlyPublicKey,
+    players: Vec<XOnlyPublicKey>,
+    evlog_group_id: OccurrenceGroupID,
+    new_synthetic_event: Arc<Notify>,
+) -> JoinHandle<Result<(), Box<dyn std::error::Error + Send + Sync>>> {
+    spawn(async move {
+        let proof = loop {
+            tokio::time::sleep(Duration::from_secs(30)).await;
+            let handle = msg_db.get_handle_read().await;
+            let players = players.clone();
+            let found = spawn_blocking(move || find_censorship(&handle, oracle_key, &players))
+                .await?
+                .map_err(|e| format!("Censorship Check Failed: {}", e))?;
+            if let Some(proof) = found {
+                break proof;
+            }
+        };
+        info!(player = %proof.censored[0].header().key(), "Host Censored a Player");
+        let accessor = evlog.get_accessor().await;
+        // one proof is enough, so the tag never changes
+        match accessor.insert_new_occurrence_now_from(
+            evlog_group_id,
+            &events::TaggedEvent(
+                events::Event::NewRecompileTriggeringObservation(
+                    serde_json::to_value(proof)?,
+                    EK_GAME_ACTION_CENSORSHIP.clone(),
+                ),
+                Some(events::Tag::ScopedCounter("censorship".into(), 1)),
+            ),
+        )? {
+            Ok(_) => new_synthetic_event.notify_one(),
+            Err(Idempotent::AlreadyExists) => {}
+        }
+        OK_T
+    })
+}
+
+/// Records an event each time the host goes quiet for longer than
+/// [`game_sequencer::liveness::HOST_UNRESPONSIVE_AFTER_MS`]
+pub fn watch_host_liveness(
+    evlog: EventLog,
+    liveness: watch::Receiver<Option<HostLiveness>>,
+    evlog_group_id: OccurrenceGroupID,
+    new_synthetic_event: Arc<Notify>,
+) -> JoinHandle<Result<(), Box<dyn std::error::Error + Send + Sync>>> {
+    spawn(async move {
+        loop {
+            tokio::time::sleep(Duration::from_secs(30)).await;
+            let heard = liveness.borrow().clone();
+            let heard = match heard {
+                Some(heard) => heard,
+                None => continue,
+            };
+            let since = match heard.unresponsive_since(attest_util::now()) {
+                Some(since) => since,
+                None => continue,
+            };
+            let accessor = evlog.get_accessor().await;
+            // one observation per silence, so the tag is when it began
+            match accessor.insert_new_occurrence_now_from(
+                evlog_group_id,
+                &events::TaggedEvent(
+                    events::Event::HostUnresponsive(heard.key, since),
+                    Some(events::Tag::ScopedValue(
+                        "host_unresponsive".into(),
+                        since.to_string(),
+                    )),
+                ),
+            )? {
+                Ok(_) => {
+                    warn!(key = %heard.key, since, "Host Unresponsive");
+                    new_synthetic_event.notify_one()
+                }
+                Err(Idempotent::AlreadyExists) => {}
+            }
+        }
+    })
+}
+
 fn make_snapshot(
     move_count: u64,
     evlog: EventLog,
@@ -255,34 +375,14 @@ fn make_snapshot(
 ) -> JoinHandle<Result<(), Box<dyn std::error::Error + Send + Sync>>> {
     spawn(async move {
         let handle = msg_db.get_handle_read().await;
-        let (sequencer_envelopes, msg_cache) = spawn_blocking(move || {
-            let sequencer_envelopes = handle
-                .load_all_messages_for_user_by_key_connected::<_, HostEnvelope>(&oracle_key)
-                .map_err(|_| "Database Fetch Error")?;
-            let def = Default::default();
-            let m: Vec<ParticipantEnvelope> =
-                handle
-                    .messages_by_hash(sequencer_envelopes.iter().flat_map(
-                        |m| match &m.msg().data {
-                            BroadcastByHost::Sequence(d) => d,
-                            _ => &def,
-                        },
-                    ))
-                    .map_err(|_| "Database Fetch Error")?;
-            Ok::<_, &'static str>((
-                sequencer_envelopes,
-                m.into_iter()
-                    .map(|v| (v.canonicalized_hash_ref(), v))
-                    .collect(),
-            ))
+        let sequenced = spawn_blocking(move || {
+            custody::load_sequenced::<_, ParticipantAction>(&handle, oracle_key)
+                .map_err(|_| "Database Fetch Error")
         })
         .await??;
 
         // takes only the first move_count moves, and whittles down the messages to just the ones mentioned.
-        let v = UnauthenticatedRawSequencer::<ParticipantAction> {
-            sequencer_envelopes,
-            msg_cache,
-        };
+        let v = UnauthenticatedRawSequencer::<ParticipantAction>::from(sequenced);
         if let Ok(v) = serde_json::to_value(v) {
             let accessor = evlog.get_accessor().await;
             // don't care if this fails
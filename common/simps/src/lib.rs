@@ -232,6 +232,8 @@ lazy_static! {
         SArc(Arc::new(EventKey("game_action_players_win".into())));
     pub static ref EK_GAME_ACTION_LOSE: SArc<EventKey> =
         SArc(Arc::new(EventKey("game_action_players_lose".into())));
+    pub static ref EK_GAME_ACTION_CENSORSHIP: SArc<EventKey> =
+        SArc(Arc::new(EventKey("game_action_host_censored".into())));
     // Bitcoin Related
     pub static ref SOURCE_BITCOIN_RPC: SArc<EventSource> =
         SArc(Arc::new(EventSource("bitcoin-rpc".into())));
@@ -6,17 +6,17 @@
 //! Payment Pool Contract for Sapio Studio Advent Calendar Entry
 use crate::sapio_base::Clause;
 use bitcoin::hashes::sha256;
+use bitcoin::secp256k1::Secp256k1;
 use bitcoin::secp256k1::SecretKey;
 use bitcoin::util::amount::Amount;
 use bitcoin::BlockHash;
 use bitcoin::BlockHeader;
 use bitcoin::TxMerkleNode;
 use bitcoin::XOnlyPublicKey;
+use game_sequencer::censorship::CensorshipProof;
 use game_sequencer::ExtractedMoveEnvelopes;
 use mine_with_friends_board::game::FinishReason;
 use mine_with_friends_board::game::GameBoard;
-use mine_with_friends_board::game::GameSetup;
-use mine_with_friends_board::game::MoveRejectReason;
 use sapio::contract::error::CompilationError;
 use sapio::contract::object::ObjectMetadata;
 use sapio::contract::*;
@@ -158,16 +158,42 @@ impl GameStarted {
     #[continuation(
         web_api,
         coerce_args = "coerce_censorship_proof",
-        guarded_by = "[Self::all_players_signed]"
+        guarded_by = "[Self::all_players_signed]",
+        simps = "Some(Self::subscribe_host_censored)"
     )]
-    fn host_cheat_censor(self, _ctx: Context, proof: Option<CensorshipProof>) {
-        if let Some(proof) = proof {
-            Err(CompilationError::TerminateWith("Not Yet Supported".into()))
-        } else {
-            empty()
+    fn host_cheat_censor(self, ctx: Context, proof: Option<CensorshipProof>) {
+        match proof {
+            Some(proof) => {
+                let players: Vec<_> = self.kernel.players.keys().map(|PK(k)| *k).collect();
+                proof
+                    .verify(
+                        &Secp256k1::verification_only(),
+                        self.kernel.game_host.0,
+                        &players,
+                    )
+                    .map_err(|e| {
+                        CompilationError::TerminateWith(format!("Invalid Censorship Proof: {}", e))
+                    })?;
+                let mut tmpl = ctx.template();
+                for (player, balance) in &self.kernel.players {
+                    tmpl = tmpl.add_output((*balance).into(), &player.0, None)?
+                }
+                tmpl.into()
+            }
+            None => empty(),
         }
     }
 
+    fn subscribe_host_censored(
+        &self,
+        ctx: Context,
+    ) -> Result<Vec<Box<dyn SIMPAttachableAt<ContinuationPointLT>>>, CompilationError> {
+        Ok(vec![Box::new(EventRecompiler {
+            source: EventSource("*".into()),
+            filter: (*simps::EK_GAME_ACTION_CENSORSHIP.0).clone(),
+        })])
+    }
+
     fn subscribe_players_win(
         &self,
         ctx: Context,
@@ -191,29 +217,26 @@ impl GameStarted {
         &self,
         trace: ExtractedMoveEnvelopes,
     ) -> Result<(FinishReason, GameBoard), GameBoard> {
-        let mut game = GameBoard::new(&GameSetup {
-            players: self
-                .kernel
-                .players
-                .keys()
-                .map(|PK(k)| k.to_string())
-                .collect(),
-            // TODO: Should this be something else?
-            // NOTE: IF YOU CHANGE THIS YOU MUST ALSO CHANGE NewGame.tsx This
-            // should be read dynamically from somewhere, but this is a hack
-            // workaround for now
-            start_amount: 100_000_000,
-            finish_time: self.kernel.timeout,
-        });
+        let mut game = GameBoard::new(
+            &trace.game_setup(
+                self.kernel
+                    .players
+                    .keys()
+                    .map(|PK(k)| k.to_string())
+                    .collect(),
+                // TODO: Should this be something else?
+                // NOTE: IF YOU CHANGE THIS YOU MUST ALSO CHANGE NewGame.tsx This
+                // should be read dynamically from somewhere, but this is a hack
+                // workaround for now
+                100_000_000,
+                self.kernel.timeout,
+            ),
+        );
 
-        for (mv, pk) in trace.0 {
-            match game.play(mv, pk.to_string()) {
-                Ok(()) => {}
-                Err(MoveRejectReason::GameIsFinished(g)) => return Ok((g, game)),
-                _ => continue,
-            }
+        match trace.replay(&mut game) {
+            Some(g) => Ok((g, game)),
+            None => Err(game),
         }
-        Err(game)
     }
     #[continuation(
         web_api,
@@ -270,7 +293,10 @@ impl GameStarted {
             None => empty(),
             Some(trace) => {
                 match self.get_finished_board(trace) {
-                    Ok((FinishReason::DominatingPlayer(id), game)) => {
+                    Ok((
+                        FinishReason::DominatingPlayer(id) | FinishReason::DominatingAlliance(id),
+                        game,
+                    )) => {
                         // TODO: verify that one player possesses over 50% of the hash rate, else abort with empty
 
                         // TODO: if there is a player that possesses that hash rate, allocate assets according to the game
@@ -364,8 +390,6 @@ struct GameStart {
 }
 #[derive(Serialize, Deserialize, JsonSchema)]
 pub struct HostKey(SecretKey);
-#[derive(Serialize, Deserialize, JsonSchema)]
-pub struct CensorshipProof {}
 
 pub enum GameEnd {
     HostCheatEquivocate(DLogDiscovered),
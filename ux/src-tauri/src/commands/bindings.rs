@@ -14,6 +14,7 @@ use mine_with_friends_board::game::game_move::{GameMove, MintPowerPlant};
 use mine_with_friends_board::game::UXUserInventory;
 use mine_with_friends_board::nfts::instances::powerplant::PlantType;
 use mine_with_friends_board::tokens::token_swap::{TradeError, TradeOutcome, TradingPairID};
+use mine_with_friends_board::world::UXWorldMap;
 use sapio_bitcoin::secp256k1::{All, Secp256k1};
 use std::sync::Arc;
 use tauri::async_runtime::Mutex;
@@ -26,11 +27,14 @@ pub const HANDLER: &(dyn Fn(Invoke) + Send + Sync) = &generate_handler![
     get_materials_schema,
     get_purchase_schema,
     get_inventory_by_key,
+    get_world_map,
     make_move_inner,
     switch_to_game,
+    spectate_game,
     switch_to_db,
     set_signing_key,
     send_chat,
+    send_private_chat,
     make_new_chain,
     make_new_game,
     list_my_users,
@@ -89,6 +93,7 @@ pub(crate) async fn super_mint(
         secp.inner().clone(),
         db.inner().clone(),
         signing_key.inner().clone(),
+        s.inner().clone(),
         GameMove::SuperMintPowerPlant(MintPowerPlant {
             scale,
             location,
@@ -203,17 +208,39 @@ pub(crate) async fn send_chat(
     secp: State<'_, Arc<Secp256k1<All>>>,
     db: State<'_, Database>,
     sk: State<'_, SigningKeyInner>,
+    game: GameState<'_>,
     chat: String,
 ) -> Result<(), &'static str> {
     modify::make_move_inner_inner(
         secp.inner().clone(),
         db.inner().clone(),
         sk.inner().clone(),
+        game.inner().clone(),
         GameMove::from(Chat(chat)),
     )
     .await
 }
 
+#[tauri::command]
+pub(crate) async fn send_private_chat(
+    secp: State<'_, Arc<Secp256k1<All>>>,
+    db: State<'_, Database>,
+    sk: State<'_, SigningKeyInner>,
+    game: GameState<'_>,
+    recipients: Vec<EntityID>,
+    chat: String,
+) -> Result<(), &'static str> {
+    modify::send_private_chat_inner(
+        secp.inner().clone(),
+        db.inner().clone(),
+        sk.inner().clone(),
+        game,
+        recipients,
+        chat,
+    )
+    .await
+}
+
 #[tauri::command]
 pub(crate) async fn disconnect_game(
     sk: State<'_, SigningKeyInner>,
@@ -243,6 +270,28 @@ pub(crate) async fn switch_to_game(
         db.inner().clone(),
         game,
         key,
+        false,
+    )
+    .await
+}
+
+/// Follows the game sequenced by `key` read-only, whether or not any of our
+/// keys are playing in it
+#[tauri::command]
+pub(crate) async fn spectate_game(
+    secp: State<'_, Arc<Secp256k1<All>>>,
+    db: State<'_, Database>,
+    sk: State<'_, SigningKeyInner>,
+    game: GameState<'_>,
+    key: XOnlyPublicKey,
+) -> Result<(), ()> {
+    modify::switch_to_game_inner(
+        secp.inner().clone(),
+        sk.inner().clone(),
+        db.inner().clone(),
+        game,
+        key,
+        true,
     )
     .await
 }
@@ -252,12 +301,14 @@ pub(crate) async fn make_move_inner(
     secp: State<'_, Arc<Secp256k1<All>>>,
     db: State<'_, Database>,
     sk: State<'_, SigningKeyInner>,
+    game: GameState<'_>,
     nextMove: GameMove,
 ) -> Result<(), &'static str> {
     modify::make_move_inner_inner(
         secp.inner().clone(),
         db.inner().clone(),
         sk.inner().clone(),
+        game.inner().clone(),
         nextMove,
     )
     .await
@@ -271,3 +322,8 @@ pub(crate) async fn get_inventory_by_key(
     let res = view::get_user_inventory_by_key(game, user_key).await;
     res
 }
+
+#[tauri::command]
+pub(crate) async fn get_world_map(game: GameState<'_>) -> Result<UXWorldMap, SyncError> {
+    view::get_world_map(game).await
+}
@@ -5,6 +5,7 @@
 //  file, You can obtain one at https://mozilla.org/MPL/2.0/.
 
 use crate::config::Globals;
+use crate::private_chat;
 use crate::tasks::GameServer;
 use crate::tor::GameHost;
 use crate::tor::TorClient;
@@ -13,6 +14,7 @@ use crate::DatabaseInner;
 use crate::Game;
 use crate::GameInitState;
 use crate::GameState;
+use crate::GameStateInner;
 use crate::Pending;
 use crate::SigningKeyInner;
 use attest_database::db_handle::create::TipControl;
@@ -26,6 +28,7 @@ use game_player_messages::ParticipantAction;
 use mine_with_friends_board::entity::EntityID;
 use mine_with_friends_board::game::game_move::GameMove;
 use mine_with_friends_board::game::game_move::Heartbeat;
+use mine_with_friends_board::game::game_move::PrivateChat;
 use mine_with_friends_board::game::GameBoard;
 use mine_with_friends_board::sanitize::Unsanitized;
 use mine_with_friends_board::MoveEnvelope;
@@ -34,6 +37,8 @@ use sapio_bitcoin::secp256k1::All;
 use sapio_bitcoin::secp256k1::Secp256k1;
 use sapio_bitcoin::KeyPair;
 use sapio_bitcoin::XOnlyPublicKey;
+use std::collections::BTreeMap;
+use std::str::FromStr;
 use std::sync::Arc;
 use tauri::async_runtime::Mutex;
 use tauri::State;
@@ -160,12 +165,18 @@ pub(crate) async fn make_new_chain_inner(
     Ok(())
 }
 
+/// Signs and inserts `next_move` on our chain. Spectators never sign anything
+/// for the game they are following, so this fails while spectating.
 pub(crate) async fn make_move_inner_inner(
     secp: Arc<Secp256k1<All>>,
     db: Database,
     sk: SigningKeyInner,
+    game: GameStateInner,
     next_move: GameMove,
 ) -> Result<(), &'static str> {
+    if game.lock().await.game_opt().map_or(false, |g| g.spectating) {
+        return Err("Spectating, Cannot Make Moves");
+    }
     let xpubkey = sk.lock().await.ok_or("No Key Selected")?;
     let msgdb = db.get().await.map_err(|_e| "No DB Available")?;
     let mut handle = msgdb.get_handle_all().await;
@@ -227,14 +238,69 @@ pub(crate) async fn make_move_inner_inner(
     .or(Err("DB Error"))?
 }
 
+/// Seals a chat message to each of the recipients and to ourselves, and sends
+/// it as a PrivateChat move
+pub(crate) async fn send_private_chat_inner(
+    secp: Arc<Secp256k1<All>>,
+    db: Database,
+    sk: SigningKeyInner,
+    game: GameState<'_>,
+    recipients: Vec<EntityID>,
+    chat: String,
+) -> Result<(), &'static str> {
+    let xpubkey = sk.lock().await.ok_or("No Key Selected")?;
+    let secret = {
+        let msgdb = db.get().await.map_err(|_e| "No DB Available")?;
+        let handle = msgdb.get_handle_read().await;
+        spawn_blocking(move || handle.get_keymap())
+            .await
+            .or(Err("DB Error"))?
+            .or(Err("Could not get keys"))?
+            .get(&xpubkey)
+            .cloned()
+            .ok_or("Unknown Secret Key for PK")?
+    };
+    let sealed = {
+        let mut game = game.lock().await;
+        let game = game.game_mut().ok_or("No Game")?;
+        if game.spectating {
+            return Err("Spectating, Cannot Make Moves");
+        }
+        let board = &game.board;
+        let me = board
+            .get_user_id(&xpubkey.to_hex())
+            .ok_or("Not a Player in this Game")?;
+        recipients
+            .into_iter()
+            .chain(std::iter::once(me))
+            .map(|to| {
+                let key = board
+                    .get_user_key(to)
+                    .and_then(|k| XOnlyPublicKey::from_str(k).ok())
+                    .ok_or("Unknown Recipient")?;
+                Ok((to, private_chat::seal(&secret, &key, &chat)))
+            })
+            .collect::<Result<BTreeMap<_, _>, &'static str>>()?
+    };
+    make_move_inner_inner(
+        secp,
+        db,
+        sk,
+        game.inner().clone(),
+        GameMove::from(PrivateChat { sealed }),
+    )
+    .await
+}
+
 pub(crate) async fn switch_to_game_inner(
     secp: Arc<Secp256k1<All>>,
     singing_key: SigningKeyInner,
     db: Database,
     game: GameState<'_>,
     key: XOnlyPublicKey,
+    spectating: bool,
 ) -> Result<(), ()> {
-    info!(?key, "Switching to Sequencer Key");
+    info!(?key, spectating, "Switching to Sequencer Key");
     let game = game.inner().clone();
     spawn(async move {
         info!("Spawned Game switching Task");
@@ -260,6 +326,12 @@ pub(crate) async fn switch_to_game_inner(
             }
         };
         tracing::trace!(?game_setup, "Found GameSetup");
+        let players = game_setup
+            .players
+            .iter()
+            .map(|p| XOnlyPublicKey::from_str(p))
+            .collect::<Result<_, _>>()
+            .or(Err("GameSetup has an Invalid Player Key"))?;
 
         let game2 = game.clone();
         let mut g = game2.lock().await;
@@ -273,6 +345,10 @@ pub(crate) async fn switch_to_game_inner(
             board: GameBoard::new(game_setup),
             host_key: key,
             server: None,
+            spectating,
+            players,
+            rejected_envelopes: vec![],
+            host_liveness: None,
         };
         *g = GameInitState::Game(new_game);
         GameServer::start(secp, singing_key, db, g, game).await?;
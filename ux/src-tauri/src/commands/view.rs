@@ -5,26 +5,37 @@
 //  file, You can obtain one at https://mozilla.org/MPL/2.0/.
 
 use crate::{
-    tor::GameHost, Database, Game, GameInitState, GameState, GameStateInner, Pending, PrintOnDrop,
-    SigningKeyInner, TriggerRerender,
+    private_chat, tor::GameHost, Database, Game, GameInitState, GameState, GameStateInner, Pending,
+    PrintOnDrop, SigningKeyInner, TriggerRerender,
 };
 use game_host_messages::{BroadcastByHost, Channelized, JoinCode};
 use game_player_messages::ParticipantAction;
+use game_sequencer::{liveness::HostLiveness, RejectedEnvelope};
 use mine_with_friends_board::{
+    alliances::UXAlliance,
+    derivatives::UXForward,
     entity::EntityID,
-    game::{GameBoard, GameSetup, UXUserInventory},
+    game::{GameBoard, GameSetup, UXLeaderboardEntry, UXUserInventory},
+    lending::{UXLoan, UXLoanOffer},
     nfts::{
-        instances::powerplant::PlantType,
+        auction::UXAuction,
+        instances::powerplant::{subsidy::UXMiningSchedule, PlantType},
         sale::{UXForSaleList, UXNFTSale},
         NftPtr, UXPlantData,
     },
     tokens::token_swap::{TradeError, TradeOutcome, TradingPairID, UXMaterialsPriceData},
+    world::UXWorldMap,
 };
 use sapio_bitcoin::XOnlyPublicKey;
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
-use std::{collections::VecDeque, ops::Deref, sync::Arc};
+use std::{
+    collections::{BTreeMap, VecDeque},
+    ops::Deref,
+    str::FromStr,
+    sync::Arc,
+};
 use std::{path::PathBuf, time::Duration};
 use tauri::{
     async_runtime::{spawn_blocking, Mutex},
@@ -101,14 +112,36 @@ pub struct EmittedAppState {
 #[derive(Serialize, JsonSchema, Debug)]
 struct GameDependentEmitted {
     chat_log: VecDeque<(u64, EntityID, String)>,
+    /// Private messages to or from the signing key, as (id, from, recipients,
+    /// message). The message is None if it could not be opened.
+    private_chat_log: Vec<(u64, EntityID, Vec<EntityID>, Option<String>)>,
     #[schemars(with = "String")]
     host_key: XOnlyPublicKey,
+    /// Following the game read-only, with no player key
+    spectating: bool,
     #[schemars(with = "GameBoard")]
     game_board: Value,
     materials_price_data: Vec<UXMaterialsPriceData>,
     power_plants: Vec<UXPlantData>,
     energy_exchange: Vec<UXNFTSale>,
+    auctions: Vec<UXAuction>,
+    forwards: Vec<UXForward>,
+    loan_offers: Vec<UXLoanOffer>,
+    loans: Vec<UXLoan>,
+    alliances: Vec<UXAlliance>,
+    leaderboard: Vec<UXLeaderboardEntry>,
+    /// Envelopes of the signing key's that the host sequenced but were not
+    /// played, or everyone's when spectating
+    rejected_envelopes: Vec<RejectedEnvelope>,
+    /// What was last heard from the host
+    host_liveness: Option<HostLiveness>,
+    /// When the host went quiet, once it has been quiet long enough to be
+    /// considered unresponsive
+    host_unresponsive_since: Option<i64>,
+    /// The sequence number the game expects next from each player
+    next_sequence: BTreeMap<EntityID, u64>,
     user_inventory: Option<UXUserInventory>,
+    mining_schedule: UXMiningSchedule,
 }
 
 async fn game_synchronizer_inner_loop(
@@ -118,7 +151,7 @@ async fn game_synchronizer_inner_loop(
     d: &Database,
     window: &Window,
 ) -> Result<EmittedAppState, SyncError> {
-    let (db_connection, available_sequencers, user_keys) = {
+    let (db_connection, available_sequencers, user_keys, keymap) = {
         let handle = if let Some(l) = d.state.lock().await.as_ref() {
             let name: String = l.name.clone();
             let path_buf: Option<PathBuf> = l.prefix.clone();
@@ -137,7 +170,9 @@ async fn game_synchronizer_inner_loop(
                         BroadcastByHost::GameSetup(g) => Some((e.header().key(), g.clone())),
                         BroadcastByHost::Sequence(_)
                         | BroadcastByHost::NewPeer(_)
-                        | BroadcastByHost::Heartbeat => None,
+                        | BroadcastByHost::Heartbeat(_)
+                        | BroadcastByHost::TakeOver { .. }
+                        | BroadcastByHost::Quarantine(_) => None,
                     })
                     .collect();
                 let v = handle.get_keymap().map_err(|_| SyncError::DatabaseError)?;
@@ -148,16 +183,15 @@ async fn game_synchronizer_inner_loop(
                     .map(|e| e.header().key())
                     .filter(|k| v.contains_key(k))
                     .collect();
-                Ok::<_, SyncError>((Some((name, prefix)), sequencer_keys, user_keys))
+                Ok::<_, SyncError>((Some((name, prefix)), sequencer_keys, user_keys, v))
             })
             .await
             .map_err(|_| SyncError::DatabaseError)??
         } else {
-            (None, vec![], vec![])
+            (None, vec![], vec![], BTreeMap::new())
         }
     };
     let signing_key_opt = *signing_key.lock().await;
-    let signing_key = signing_key_opt.as_ref().ok_or(SyncError::NoSigningKey);
     let game_host_service = game_host.lock().await.clone();
 
     let pending = s.lock().await.pending_opt().cloned();
@@ -181,16 +215,43 @@ async fn game_synchronizer_inner_loop(
             serde_json::Value::Null
         });
         let chat_log = game.board.get_ux_chat_log();
-        let user_inventory = signing_key
-            .as_ref()
+        // A spectator may still have a key selected, but it isn't a player's
+        let player_key = signing_key_opt.filter(|_| !game.spectating);
+        let private_chat_log = player_key
+            .and_then(|key| {
+                let secret = keymap.get(&key)?;
+                let me = game.board.get_user_id(&key.to_string())?;
+                Some(
+                    game.board
+                        .get_ux_private_chat_log(me)
+                        .into_iter()
+                        .map(|m| {
+                            let opened = game
+                                .board
+                                .get_user_key(m.from)
+                                .and_then(|k| XOnlyPublicKey::from_str(k).ok())
+                                .and_then(|from| private_chat::open(secret, &from, &m.sealed));
+                            (m.id, m.from, m.recipients, opened)
+                        })
+                        .collect(),
+                )
+            })
+            .unwrap_or_default();
+        let user_inventory = player_key
+            .ok_or(SyncError::NoSigningKey)
             .map(|key| {
                 game.board
                     .get_ux_user_inventory(key.to_string())
                     .map_err(|()| SyncError::KeyUnknownByGame)
             })
             .flip()?
-            .map_err(|e| e.clone())
             .ok();
+        let rejected_envelopes = game
+            .rejected_envelopes
+            .iter()
+            .filter(|r| game.spectating || Some(r.key) == player_key)
+            .cloned()
+            .collect();
         // Attempt to get data to show prices
         let raw_price_data = game.board.get_ux_materials_prices();
         let plants: Vec<UXPlantData> = game.board.get_ux_power_plant_data();
@@ -201,12 +262,28 @@ async fn game_synchronizer_inner_loop(
 
         to_emit.game = Some(GameDependentEmitted {
             chat_log,
+            private_chat_log,
             host_key: game.host_key,
+            spectating: game.spectating,
             game_board: game_value,
             materials_price_data: raw_price_data,
             power_plants: plants,
             energy_exchange: listings.listings,
+            auctions: game.board.get_ux_auctions(),
+            forwards: game.board.get_ux_forwards(),
+            loan_offers: game.board.get_ux_loan_offers(),
+            loans: game.board.get_ux_loans(),
+            alliances: game.board.get_ux_alliances(),
+            leaderboard: game.board.get_ux_leaderboard(),
+            rejected_envelopes,
+            host_liveness: game.host_liveness.clone(),
+            host_unresponsive_since: game
+                .host_liveness
+                .as_ref()
+                .and_then(|l| l.unresponsive_since(attest_util::now())),
+            next_sequence: game.board.get_ux_next_sequence(),
             user_inventory,
+            mining_schedule: game.board.get_ux_mining_schedule(),
         });
         Ok::<(), SyncError>(())
     }
@@ -330,3 +407,9 @@ pub(crate) async fn get_user_inventory_by_key(
         .map_err(|()| SyncError::KeyUnknownByGame)?;
     Ok(inventory)
 }
+
+pub(crate) async fn get_world_map(s: GameState<'_>) -> Result<UXWorldMap, SyncError> {
+    let mut game = s.lock().await;
+    let game = game.game_mut().ok_or(SyncError::NoGame)?;
+    Ok(game.board.get_ux_world_map())
+}
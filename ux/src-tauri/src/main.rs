@@ -13,6 +13,7 @@ use attest_database::{connection::MsgDB, setup_db};
 use commands::bindings::HANDLER;
 use config::Config;
 use game_host_messages::JoinCode;
+use game_sequencer::{liveness::HostLiveness, RejectedEnvelope};
 use mine_with_friends_board::game::GameBoard;
 use sapio_bitcoin::{secp256k1::Secp256k1, XOnlyPublicKey};
 use schemars::JsonSchema;
@@ -20,6 +21,7 @@ use serde::Deserialize;
 use serde::Serialize;
 use tokio::sync::mpsc::Receiver;
 use tokio::sync::mpsc::error::TrySendError;
+use std::collections::BTreeSet;
 use std::{error::Error, path::PathBuf, sync::Arc};
 use tasks::GameServer;
 use tauri::{async_runtime::Mutex, window, Manager, State};
@@ -33,6 +35,7 @@ use tracing::warn;
 
 mod commands;
 mod config;
+mod private_chat;
 mod tasks;
 mod tor;
 
@@ -47,6 +50,13 @@ pub struct Game {
     board: GameBoard,
     host_key: XOnlyPublicKey,
     server: Option<Arc<GameServer>>,
+    /// Following the game without playing: nothing is ever signed for it
+    spectating: bool,
+    players: BTreeSet<XOnlyPublicKey>,
+    /// Envelopes the host sequenced that were not played
+    rejected_envelopes: Vec<RejectedEnvelope>,
+    /// What was last heard from the host
+    host_liveness: Option<HostLiveness>,
 }
 
 #[derive(Serialize, Debug, Deserialize, JsonSchema,Clone)]
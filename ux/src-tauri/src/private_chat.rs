@@ -0,0 +1,117 @@
+// Copyright Judica, Inc 2022
+//
+// This Source Code Form is subject to the terms of the Mozilla Public
+//  License, v. 2.0. If a copy of the MPL was not distributed with this
+//  file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+//! Sealing and opening of private chat messages.
+//!
+//! Each copy of a message is encrypted under a key derived by ECDH between the
+//! sender's chain key and the recipient's, so either of them can open it. The
+//! message is sealed with XChaCha20-Poly1305 under a random nonce and encoded
+//! as hex of `nonce || ciphertext`.
+use chacha20poly1305::aead::{Aead, KeyInit};
+use chacha20poly1305::{Key, XChaCha20Poly1305, XNonce};
+use sapio_bitcoin::hashes::hex::{FromHex, ToHex};
+use sapio_bitcoin::hashes::{sha256, Hash, HashEngine};
+use sapio_bitcoin::secp256k1::ecdh::shared_secret_point;
+use sapio_bitcoin::secp256k1::rand::{thread_rng, RngCore};
+use sapio_bitcoin::secp256k1::{Parity, PublicKey, SecretKey};
+use sapio_bitcoin::XOnlyPublicKey;
+
+const NONCE_LEN: usize = 24;
+
+fn cipher(secret: &SecretKey, theirs: &XOnlyPublicKey) -> XChaCha20Poly1305 {
+    let point = shared_secret_point(
+        &PublicKey::from_x_only_public_key(*theirs, Parity::Even),
+        secret,
+    );
+    // Only the x coordinate is used: an x-only key stands for the point
+    // with even y, which may be the negation of the point of the secret
+    // key, so the two sides may compute negated points.
+    let mut engine = sha256::Hash::engine();
+    engine.input(b"mine-with-friends/private-chat/xchacha20poly1305");
+    engine.input(&point[..32]);
+    let key = sha256::Hash::from_engine(engine).into_inner();
+    XChaCha20Poly1305::new(Key::from_slice(&key))
+}
+
+/// Seals `message` from the holder of `secret` to the holder of `theirs`
+pub fn seal(secret: &SecretKey, theirs: &XOnlyPublicKey, message: &str) -> String {
+    let mut nonce = [0u8; NONCE_LEN];
+    thread_rng().fill_bytes(&mut nonce);
+    let data = cipher(secret, theirs)
+        .encrypt(XNonce::from_slice(&nonce), message.as_bytes())
+        .expect("Chat messages are far below the cipher's length limit");
+    let mut sealed = nonce.to_vec();
+    sealed.extend(data);
+    sealed.to_hex()
+}
+
+/// Opens a message sealed between the holder of `secret` and the holder of
+/// `theirs`, in either direction. None if it was not sealed for this pair of
+/// keys or was tampered with.
+pub fn open(secret: &SecretKey, theirs: &XOnlyPublicKey, sealed: &str) -> Option<String> {
+    let bytes = Vec::<u8>::from_hex(sealed).ok()?;
+    if bytes.len() < NONCE_LEN {
+        return None;
+    }
+    let (nonce, data) = bytes.split_at(NONCE_LEN);
+    let data = cipher(secret, theirs)
+        .decrypt(XNonce::from_slice(nonce), data)
+        .ok()?;
+    String::from_utf8(data).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sapio_bitcoin::secp256k1::Secp256k1;
+
+    fn keys() -> (SecretKey, XOnlyPublicKey) {
+        let secret = SecretKey::new(&mut thread_rng());
+        let public = PublicKey::from_secret_key(&Secp256k1::new(), &secret);
+        (secret, public.x_only_public_key().0)
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let (alice, alice_pk) = keys();
+        let (bob, bob_pk) = keys();
+        let sealed = seal(&alice, &bob_pk, "meet at the flare site");
+        assert_eq!(
+            open(&bob, &alice_pk, &sealed).as_deref(),
+            Some("meet at the flare site")
+        );
+        // the sender can read back their own copy
+        assert_eq!(
+            open(&alice, &bob_pk, &sealed).as_deref(),
+            Some("meet at the flare site")
+        );
+        // a fresh nonce each time
+        assert_ne!(sealed, seal(&alice, &bob_pk, "meet at the flare site"));
+    }
+
+    #[test]
+    fn test_wrong_recipient() {
+        let (alice, alice_pk) = keys();
+        let (_bob, bob_pk) = keys();
+        let (carol, _carol_pk) = keys();
+        let sealed = seal(&alice, &bob_pk, "hello bob");
+        assert_eq!(open(&carol, &alice_pk, &sealed), None);
+    }
+
+    #[test]
+    fn test_tampered() {
+        let (alice, alice_pk) = keys();
+        let (bob, bob_pk) = keys();
+        let mut bytes = Vec::<u8>::from_hex(&seal(&alice, &bob_pk, "hello bob")).unwrap();
+        let last = bytes.len() - 1;
+        bytes[last] ^= 1;
+        assert_eq!(open(&bob, &alice_pk, &bytes.to_hex()), None);
+        bytes[last] ^= 1;
+        bytes[NONCE_LEN] ^= 1;
+        assert_eq!(open(&bob, &alice_pk, &bytes.to_hex()), None);
+        assert_eq!(open(&bob, &alice_pk, &bytes[..NONCE_LEN].to_hex()), None);
+    }
+}
@@ -10,7 +10,11 @@ use crate::GameInitState;
 use crate::GameStateInner;
 use crate::Pending;
 use crate::SigningKeyInner;
+use game_sequencer::attest_envelope;
+use game_sequencer::liveness::HostLiveness;
 use game_sequencer::OnlineDBFetcher;
+use game_sequencer::RejectedEnvelope;
+use game_sequencer::Sequenced;
 use game_sequencer::Sequencer;
 use mine_with_friends_board::entity::EntityID;
 use mine_with_friends_board::game::game_move::GameMove;
@@ -23,10 +27,13 @@ use std::sync::atomic::Ordering;
 use std::sync::Arc;
 use std::time::Duration;
 use tokio::spawn;
+use tokio::sync::broadcast::{error::RecvError, Receiver};
+use tokio::sync::watch;
 use tokio::sync::MutexGuard;
 use tokio::task::JoinHandle;
 use tracing::debug;
 use tracing::info;
+use tracing::warn;
 
 /// Game Server Handle
 pub struct GameServer {
@@ -74,8 +81,16 @@ impl GameServer {
                     k,
                     db,
                 );
-                let game_sequencer =
-                    game_sequencer::Sequencer::new(shutdown.clone(), db_fetcher.clone());
+                let game_sequencer = game_sequencer::Sequencer::new(
+                    shutdown.clone(),
+                    db_fetcher.clone(),
+                    Some(game.players.clone()),
+                );
+                spawn(record_rejected(
+                    g.clone(),
+                    game_sequencer.0.subscribe_rejected(),
+                ));
+                spawn(record_liveness(g.clone(), db_fetcher.subscribe_liveness()));
                 spawn(db_fetcher.run());
                 spawn({
                     let game_sequencer = game_sequencer.clone();
@@ -92,34 +107,39 @@ impl GameServer {
                         heartbeat_enable.clone(),
                     )
                 };
-                spawn({
-                    let database = database.clone();
-                    let shutdown = shutdown.clone();
-                    let secp = secp;
-                    let signing_key = signing_key.clone();
-                    async move {
-                        let mut t = tokio::time::interval(Duration::from_millis(5000));
-                        loop {
-                            let a = t.tick().await;
-                            if shutdown.load(Ordering::Relaxed) {
-                                break;
-                            }
-                            if heartbeat_enable.load(Ordering::Relaxed) {
-                                tracing::trace!("Game Heartbeat!");
-                                crate::commands::modify::make_move_inner_inner(
-                                    secp.clone(),
-                                    database.clone(),
-                                    signing_key.clone(),
-                                    GameMove::Heartbeat(
-                                        mine_with_friends_board::game::game_move::Heartbeat(),
-                                    ),
-                                )
-                                .await
-                                .map_err(|e| tracing::trace!(err=?e, "Game Heartbeat!"));
+                // Spectators have no place in the game to keep alive
+                if !game.spectating {
+                    spawn({
+                        let database = database.clone();
+                        let shutdown = shutdown.clone();
+                        let secp = secp;
+                        let signing_key = signing_key.clone();
+                        let g = g.clone();
+                        async move {
+                            let mut t = tokio::time::interval(Duration::from_millis(5000));
+                            loop {
+                                let a = t.tick().await;
+                                if shutdown.load(Ordering::Relaxed) {
+                                    break;
+                                }
+                                if heartbeat_enable.load(Ordering::Relaxed) {
+                                    tracing::trace!("Game Heartbeat!");
+                                    crate::commands::modify::make_move_inner_inner(
+                                        secp.clone(),
+                                        database.clone(),
+                                        signing_key.clone(),
+                                        g.clone(),
+                                        GameMove::Heartbeat(
+                                            mine_with_friends_board::game::game_move::Heartbeat(),
+                                        ),
+                                    )
+                                    .await
+                                    .map_err(|e| tracing::trace!(err=?e, "Game Heartbeat!"));
+                                }
                             }
                         }
-                    }
-                });
+                    });
+                }
                 game.server = Some(Arc::new(GameServer { shutdown }));
             }
         }
@@ -127,6 +147,33 @@ impl GameServer {
     }
 }
 
+/// Keeps the envelopes the sequencer rejects, so the UX can say which moves
+/// were not played
+async fn record_rejected(g: GameStateInner, mut rejected: Receiver<RejectedEnvelope>) {
+    loop {
+        match rejected.recv().await {
+            Ok(r) => {
+                if let Some(game) = g.lock().await.game_mut() {
+                    game.rejected_envelopes.push(r);
+                }
+            }
+            Err(RecvError::Lagged(n)) => warn!(n, "Missed Rejected Envelopes"),
+            Err(RecvError::Closed) => break,
+        }
+    }
+}
+
+/// Keeps what was last heard from the host, so the UX can say when it went
+/// quiet
+async fn record_liveness(g: GameStateInner, mut liveness: watch::Receiver<Option<HostLiveness>>) {
+    while liveness.changed().await.is_ok() {
+        let heard = liveness.borrow().clone();
+        if let Some(game) = g.lock().await.game_mut() {
+            game.host_liveness = heard;
+        }
+    }
+}
+
 // Play the moves one by one
 pub(crate) fn start_game(
     _shutdown: Arc<AtomicBool>,
@@ -136,7 +183,18 @@ pub(crate) fn start_game(
 ) -> JoinHandle<()> {
     spawn(async move {
         // TODO: Check which game the move is for?
-        while let Some((game_move, s)) = sequencer.output_move().await {
+        while let Some(next) = sequencer.output_move().await {
+            let (game_move, s) = match next {
+                Sequenced::Attested(hash, header) => {
+                    if let Some(game) = g.lock().await.game_mut() {
+                        if let Err(e) = attest_envelope(&mut game.board, &hash, &header) {
+                            warn!(error=?e, "Sequencer Attested to a Bad Time");
+                        }
+                    }
+                    continue;
+                }
+                Sequenced::Move(m) => m,
+            };
             info!(move_ = ?game_move, "New Move Recieved");
             let mut game = g.lock().await;
 
@@ -149,6 +207,14 @@ pub(crate) fn start_game(
                         }
                         MoveRejectReason::MoveSanitizationError(_) => {}
                         MoveRejectReason::TradeRejected(_) => {}
+                        MoveRejectReason::ShippingRejected(_) => {}
+                        MoveRejectReason::AuctionRejected(_) => {}
+                        MoveRejectReason::ForwardRejected(_) => {}
+                        MoveRejectReason::LoanRejected(_) => {}
+                        MoveRejectReason::AllianceRejected(_) => {}
+                        MoveRejectReason::ClockRejected(_) => {}
+                        MoveRejectReason::SequenceGap { .. } => {}
+                        MoveRejectReason::Duplicate { .. } => {}
                     }
                     debug!(reason=?err, "Rejected Move");
                 } else {
@@ -124,6 +124,8 @@ impl TorClient {
             .post(format!("http://{}:{}/{}", url, port, GAME_NEW))
             .json(&NewGameArgs {
                 duration_minutes: minutes,
+                max_players: None,
+                clock: None,
             })
             .send()
             .await
@@ -42,6 +42,9 @@ export type FinishReason =
    | {
        DominatingPlayer: EntityID
      }
+   | {
+       DominatingAlliance: EntityID
+     }
  export type SanitizationError = string
  export type TradeError =
    | "MarketSlipped"